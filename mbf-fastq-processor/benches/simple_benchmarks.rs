@@ -107,6 +107,17 @@ fn benchmark_key_steps(c: &mut Criterion) {
         .unwrap()
         .join("test_cases/sample_data/fasta/input_kmers.fa");
     let str_kmer_file = kmer_file.to_string_lossy();
+    let mapping_file = std::env::current_dir()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .join("test_cases/single_step/convert/map_tag/basic/input_mapping.tsv");
+    let str_mapping_file = mapping_file.to_string_lossy();
+    let table_file =
+        std::env::current_dir().unwrap().parent().unwrap().join(
+            "test_cases/single_step/extraction/tag_from_table/basic/input_classification.tsv",
+        );
+    let str_table_file = table_file.to_string_lossy();
     let benchmarks = vec![
         BenchmarkConfig::new(
             "Progress",
@@ -241,6 +252,24 @@ fn benchmark_key_steps(c: &mut Criterion) {
             molecule_count,
             thread_count,
         ),
+        BenchmarkConfig::new(
+            "BinNumericTag",
+            r#"[[step]]
+    action = "CalcLength"
+    segment = "read1"
+    out_label = "length"
+
+[[step]]
+    action = "BinNumericTag"
+    in_label = "length"
+    out_label = "length_class"
+    breakpoints = [50, 100]
+
+[[step]]
+    action = "ForgetAllTags""#,
+            molecule_count,
+            thread_count,
+        ),
         BenchmarkConfig::new(
             "CalcBaseContent",
             r#"[[step]]
@@ -296,6 +325,27 @@ fn benchmark_key_steps(c: &mut Criterion) {
             molecule_count,
             thread_count,
         ),
+        BenchmarkConfig::new(
+            "CalcTagHitCount",
+            r#"[[step]]
+    action = "ExtractIUPAC"
+    out_label = "sites"
+    search = "GATC"
+    anchor = "Anywhere"
+    max_mismatches = 0
+    segment = "read1"
+    find_all = true
+
+[[step]]
+    action = "CalcTagHitCount"
+    in_label = "sites"
+    out_label = "site_count"
+
+[[step]]
+    action = "ForgetAllTags""#,
+            molecule_count,
+            thread_count,
+        ),
         BenchmarkConfig::new(
             "CalcNCount",
             r#"[[step]]
@@ -347,6 +397,35 @@ fn benchmark_key_steps(c: &mut Criterion) {
     on_missing="set_missing"
     separator = "_"
 
+[[step]]
+    action = "ForgetAllTags""#,
+            molecule_count,
+            thread_count,
+        ),
+        BenchmarkConfig::new(
+            "CombineLocations",
+            r#"[[step]]
+    action = "ExtractRegion"
+    segment = "read1"
+    start = 0
+    length = 5
+    out_label = "tag1"
+    anchor = "Start"
+
+[[step]]
+    action = "ExtractRegion"
+    segment = "read1"
+    start = 3
+    length = 5
+    out_label = "tag2"
+    anchor = "Start"
+
+[[step]]
+    action = "CombineLocations"
+    in_labels = ["tag1", "tag2"]
+    operation = "subtract"
+    out_label = "combined"
+
 [[step]]
     action = "ForgetAllTags""#,
             molecule_count,
@@ -430,6 +509,17 @@ fn benchmark_key_steps(c: &mut Criterion) {
             molecule_count,
             thread_count,
         ),
+        BenchmarkConfig::new(
+            "ExternalCommand",
+            r#"[[step]]
+    action = "ExternalCommand"
+    command = ["cat"]
+    format = "Fastq"
+    segment = "read1"
+    workers = 2"#,
+            molecule_count,
+            thread_count,
+        ),
         BenchmarkConfig::new(
             "ExtractIUPAC",
             r#"[[step]]
@@ -672,6 +762,31 @@ fn benchmark_key_steps(c: &mut Criterion) {
             molecule_count,
             thread_count,
         ),
+        BenchmarkConfig::new(
+            "MapTag",
+            &format!(
+                r#"[[step]]
+    action = "ExtractRegion"
+    segment = "read1"
+    start = 0
+    length = 4
+    out_label = "barcode"
+    anchor = "Start"
+
+[[step]]
+    action = "MapTag"
+    in_label = "barcode"
+    out_label = "cell"
+    filename = "{str_mapping_file}"
+    default = "unknown"
+    max_hamming_distance = 1
+
+[[step]]
+    action = "ForgetAllTags""#
+            ),
+            molecule_count,
+            thread_count,
+        ),
         BenchmarkConfig::new(
             "Lowercase",
             r#"[[step]]
@@ -753,6 +868,25 @@ fn benchmark_key_steps(c: &mut Criterion) {
             molecule_count,
             thread_count,
         ),
+        BenchmarkConfig::new(
+            "RenameFromTemplate",
+            r#"[[step]]
+    action = "ExtractRegion"
+    segment = "read1"
+    start = 0
+    length = 8
+    out_label = "umi"
+    anchor = "Start"
+
+[[step]]
+    action = "RenameFromTemplate"
+    template = "{umi}:{name_1}/{segment}"
+
+[[step]]
+    action = "ForgetAllTags""#,
+            molecule_count,
+            thread_count,
+        ),
         BenchmarkConfig::new(
             "ReplaceTagWithLetter",
             r#"[[step]]
@@ -918,6 +1052,21 @@ fn benchmark_key_steps(c: &mut Criterion) {
     seed = 43
     false_positive_rate = 0.01
 
+[[step]]
+    action = "ForgetAllTags""#
+            ),
+            molecule_count,
+            thread_count,
+        ),
+        BenchmarkConfig::new(
+            "TagFromTable",
+            &format!(
+                r#"[[step]]
+    action = "TagFromTable"
+    filename = "{str_table_file}"
+    segment = "read1"
+    tags = [{{column = "taxid", out_label = "taxon"}}, {{column = "score", out_label = "score", value_type = "Numeric"}}]
+
 [[step]]
     action = "ForgetAllTags""#
             ),
@@ -940,6 +1089,25 @@ fn benchmark_key_steps(c: &mut Criterion) {
     direction = "Start"
     keep_tag = false
 
+    "#,
+            molecule_count,
+            thread_count,
+        ),
+        BenchmarkConfig::new(
+            "SplitAtTag",
+            r#"[[step]]
+    action = "ExtractIUPAC"
+    segment = "read1"
+    search = "GATC"
+    max_mismatches = 0
+    anchor = "Anywhere"
+    out_label = "site"
+    find_all = true
+
+[[step]]
+    action = "SplitAtTag"
+    in_label = "site"
+    min_length = 10
     "#,
             molecule_count,
             thread_count,
@@ -0,0 +1,284 @@
+//! Embedding the pipeline in your own program.
+//!
+//! Build a [`Pipeline`] from a TOML configuration (or a [`Config`]),
+//! feed it reads from memory, add your own [`Step`]s, and receive the output
+//! blocks and the report through callbacks:
+//!
+//! ```no_run
+//! use mbf_fastq_processor::api::{FastQBlocksCombined, InputInfo, OptDemultiplex, Pipeline, Step};
+//!
+//! /// drop every read that starts with 'N'
+//! struct NoLeadingN;
+//!
+//! impl Step for NoLeadingN {
+//!     fn apply(
+//!         &self,
+//!         mut block: FastQBlocksCombined,
+//!         _input_info: &InputInfo,
+//!         _block_no: usize,
+//!         _demultiplex_info: &OptDemultiplex,
+//!     ) -> anyhow::Result<(FastQBlocksCombined, bool)> {
+//!         let keep: Vec<bool> = block.segments[0].apply(|read| !read.seq().starts_with(b"N"));
+//!         block.apply_bool_filter(&keep);
+//!         Ok((block, true))
+//!     }
+//! }
+//!
+//! let toml = r#"
+//! [input]
+//!     read1 = "in_memory"
+//! [[step]]
+//!     action = "Report"
+//!     name = "report"
+//!     count = true
+//! [output]
+//!     prefix = "output"
+//!     format = "None"
+//!     report_json = true
+//! "#;
+//! let mut read_count = 0;
+//! mbf_fastq_processor::api::Pipeline::from_toml(toml)?
+//!     .with_memory_input("read1", b"@r1\nACGT\n+\nIIII\n".to_vec())
+//!     .add_step(NoLeadingN)
+//!     .on_block(move |block| {
+//!         read_count += block.len();
+//!         Ok(())
+//!     })
+//!     .on_report(|report| println!("{report}"))
+//!     .run()?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! For finer control, drive the stages ([`RunStage0`] to [`RunStage5`]) yourself,
+//! the way [`Pipeline::run`] does.
+
+use anyhow::{Result, bail};
+use indexmap::IndexMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::cli::process::{QcFailure, parse_config, remap_errors};
+use crate::config::{ExpandedConfig, Stage, StageTags};
+use crate::transformations::{_CustomStep, Transformation};
+
+pub use crate::config::{CheckedConfig, Config};
+pub use crate::demultiplex::{DemultiplexBarcodes, OptDemultiplex};
+pub use crate::io::{FastQBlocksCombined, InputFile, InputFiles};
+pub use crate::pipeline::{
+    BlockSink, OutputSinks, ReportSink, RunStage0, RunStage1, RunStage2, RunStage3, RunStage4,
+    RunStage5,
+};
+pub use crate::transformations::{FinalizeReportResult, InputInfo, ModifiedSegments, Step};
+
+/// A configured pipeline, ready to [`run`](Pipeline::run).
+pub struct Pipeline {
+    config: Config,
+    raw_config: String,
+    /// to point errors at the files & lines they came from
+    expansion: Option<ExpandedConfig>,
+    readers: IndexMap<String, Vec<InputFile>>,
+    custom_steps: Vec<Box<dyn Step + Send + Sync>>,
+    sinks: OutputSinks,
+    output_directory: PathBuf,
+    allow_overwrite: bool,
+}
+
+impl Pipeline {
+    /// Parse a TOML configuration.
+    /// `[vars]` and `[[step]] include` are expanded relative to the current directory,
+    /// `[[branch]]` sections are not supported.
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        let expansion = crate::config::expand_config(toml, Path::new(""))?;
+        let raw_config = expansion
+            .as_ref()
+            .map_or_else(|| toml.to_string(), |expansion| expansion.toml.clone());
+        if crate::config::split_branches(&raw_config)?.is_some() {
+            bail!("[[branch]] sections are not supported when embedding the pipeline");
+        }
+        let config = parse_config(&raw_config, "config.toml", expansion.as_ref())?;
+        let mut pipeline = Self::new(config, raw_config);
+        pipeline.expansion = expansion;
+        Ok(pipeline)
+    }
+
+    /// Use an already parsed configuration.
+    /// `raw_config` is the text it was parsed from - it's recorded in the report.
+    #[must_use]
+    pub fn from_config(config: Config, raw_config: &str) -> Self {
+        Self::new(config, raw_config.to_string())
+    }
+
+    fn new(config: Config, raw_config: String) -> Self {
+        Pipeline {
+            config,
+            raw_config,
+            expansion: None,
+            readers: IndexMap::new(),
+            custom_steps: Vec::new(),
+            sinks: OutputSinks::default(),
+            output_directory: PathBuf::from("."),
+            allow_overwrite: false,
+        }
+    }
+
+    /// Read `segment` from `reader` (FASTQ, optionally compressed) instead of the configured files.
+    ///
+    /// Call repeatedly to chain several readers, just like listing several files.
+    /// Once any reader is supplied, the input files in the configuration are never opened,
+    /// and every segment needs a reader. For interleaved input, supply the readers
+    /// for the first segment.
+    #[must_use]
+    pub fn with_input_reader(mut self, segment: &str, reader: impl Read + Send + 'static) -> Self {
+        self.readers
+            .entry(segment.to_string())
+            .or_default()
+            .push(InputFile::Reader(Box::new(reader)));
+        self
+    }
+
+    /// Read `segment` from an in-memory FASTQ (optionally compressed),
+    /// see [`with_input_reader`](Pipeline::with_input_reader).
+    #[must_use]
+    pub fn with_memory_input(self, segment: &str, data: impl Into<Vec<u8>>) -> Self {
+        self.with_input_reader(segment, std::io::Cursor::new(data.into()))
+    }
+
+    /// Append a custom step after the configured steps.
+    ///
+    /// Custom steps see no tags (all tags must have been used by the configured steps),
+    /// and must return `Ok(None)` from `Step::finalize`.
+    #[must_use]
+    pub fn add_step(mut self, step: impl Step + Send + Sync + 'static) -> Self {
+        self.custom_steps.push(Box::new(step));
+        self
+    }
+
+    /// Receive every output block, in input order.
+    ///
+    /// Output files are still written as configured - use `format = 'None'` to write none.
+    /// An error ends the processing.
+    #[must_use]
+    pub fn on_block(
+        mut self,
+        callback: impl FnMut(&FastQBlocksCombined) -> Result<()> + Send + 'static,
+    ) -> Self {
+        self.sinks.blocks = Some(Box::new(callback));
+        self
+    }
+
+    /// Receive the json report once processing is done,
+    /// instead of writing the report files.
+    #[must_use]
+    pub fn on_report(mut self, callback: impl FnOnce(serde_json::Value) + Send + 'static) -> Self {
+        self.sinks.report = Some(Box::new(callback));
+        self
+    }
+
+    /// Where output files go. Default: the current directory
+    #[must_use]
+    pub fn output_directory(mut self, output_directory: impl Into<PathBuf>) -> Self {
+        self.output_directory = output_directory.into();
+        self
+    }
+
+    /// Overwrite existing output files. Default: false
+    #[must_use]
+    pub fn allow_overwrite(mut self, allow_overwrite: bool) -> Self {
+        self.allow_overwrite = allow_overwrite;
+        self
+    }
+
+    /// Process all reads.
+    ///
+    /// Fails with a [`QcFailure`] if `[qc_thresholds]` 'fail' limits were hit
+    /// and `fail_exit_code` is set.
+    pub fn run(self) -> Result<()> {
+        let checked = if self.readers.is_empty() {
+            self.config.check()
+        } else {
+            self.config.check_with_memory_input()
+        };
+        let mut parsed =
+            checked.map_err(|e| remap_errors(e, "config.toml", self.expansion.as_ref()))?;
+        let input_files = if self.readers.is_empty() {
+            None
+        } else {
+            Some(assign_readers(&parsed, self.readers)?)
+        };
+        for step in self.custom_steps {
+            parsed.stages.push(Stage {
+                transformation: Transformation::_CustomStep(Box::new(_CustomStep::new(step))),
+                allowed_tags: Vec::new(),
+                step_no: None,
+                tags: StageTags::default(),
+            });
+        }
+        let fail_exit_code = parsed
+            .qc_thresholds
+            .as_ref()
+            .and_then(|qc_thresholds| qc_thresholds.fail_exit_code);
+
+        let run = RunStage0::new(&parsed).with_output_sinks(self.sinks);
+        let run = run.configure_demultiplex_and_init_stages(
+            &mut parsed,
+            &self.output_directory,
+            self.allow_overwrite,
+        )?;
+        let run = match input_files {
+            Some(input_files) => run.create_input_threads_from(&parsed, input_files)?,
+            None => run.create_input_threads(&parsed)?,
+        };
+        let run = run.create_stage_threads(&mut parsed);
+        let run = run.create_output_threads(&parsed, self.raw_config)?;
+        let run = run.join_threads();
+        if !run.errors.is_empty() {
+            bail!(run.errors.join("\n"));
+        }
+        if let Some(exit_code) = fail_exit_code
+            && !run.qc_failed_metrics.is_empty()
+        {
+            return Err(QcFailure {
+                exit_code,
+                failed_metrics: run.qc_failed_metrics,
+            }
+            .into());
+        }
+        Ok(())
+    }
+}
+
+/// Order the supplied readers by segment
+fn assign_readers(
+    parsed: &CheckedConfig,
+    mut readers: IndexMap<String, Vec<InputFile>>,
+) -> Result<InputFiles> {
+    let segment_order = parsed.input.get_segment_order();
+    let wanted: &[String] = if parsed.input.structured.is_interleaved() {
+        &segment_order[..1]
+    } else {
+        segment_order
+    };
+    let mut segments = Vec::new();
+    for segment in wanted {
+        match readers.shift_remove(segment) {
+            Some(segment_readers) => segments.push(segment_readers),
+            None => bail!("No input reader supplied for segment '{segment}'"),
+        }
+    }
+    if let Some(segment) = readers.keys().next() {
+        bail!(
+            "Input reader supplied for segment '{segment}', which is not {}. Available: {}",
+            if parsed.input.structured.is_interleaved() {
+                "the first interleaved segment"
+            } else {
+                "a segment"
+            },
+            wanted.join(", ")
+        );
+    }
+    Ok(InputFiles {
+        segment_files: crate::io::reads::SegmentsCombined { segments },
+        total_size_of_largest_segment: None,
+        largest_segment_idx: 0,
+    })
+}
@@ -0,0 +1,175 @@
+/// Checkpoints (output.checkpoint_interval) & `process --resume`
+///
+/// Every `checkpoint_interval` seconds, the coordinator stops feeding new blocks,
+/// waits until every block in flight has been written, collects the state of all steps
+/// and hands it to the output thread. That one finishes the current compression frame
+/// of every output file, and writes '{prefix}.checkpoint.json' with the file lengths.
+///
+/// On resume, the output files are truncated to those lengths and appended to,
+/// and the steps get their state back. The inputs continue at the recorded byte offsets
+/// if all of them are uncompressed or zstd FASTQ files - otherwise the reads already processed
+/// are skipped (they are parsed, but not processed).
+use anyhow::{Context, Result, bail};
+use sha2::Digest;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::config::CheckedConfig;
+use crate::io::parsers::InputPosition;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct Checkpoint {
+    /// sha256 of the configuration - resuming with a changed one is refused
+    pub config_hash: String,
+    /// molecules read from the input, processed and written
+    pub reads_done: usize,
+    /// output file -> length in bytes
+    pub output_lengths: BTreeMap<String, u64>,
+    /// where each segment's input continues, None if it can not be seeked to
+    #[serde(default)]
+    pub input_positions: Vec<Option<InputPosition>>,
+    /// `Step::checkpoint_state` of every stage, in order
+    pub step_states: Vec<Option<serde_json::Value>>,
+}
+
+/// What the coordinator hands to the output thread once block `block_no`
+/// has passed all stages
+pub struct PendingCheckpoint {
+    pub block_no: usize,
+    pub reads_done: usize,
+    pub input_positions: Vec<Option<InputPosition>>,
+    pub step_states: Vec<Option<serde_json::Value>>,
+}
+
+/// Where each segment's input continues after a block - filled in by the input threads
+/// before they send the block on, taken by the coordinator once it arrives.
+#[derive(Clone, Default)]
+pub struct InputPositions(Arc<Mutex<BTreeMap<usize, Vec<Option<InputPosition>>>>>);
+
+impl InputPositions {
+    pub fn insert(&self, block_no: usize, positions: Vec<Option<InputPosition>>) {
+        self.0
+            .lock()
+            .expect("input positions mutex poisoned")
+            .insert(block_no, positions);
+    }
+
+    /// Empty if the input threads did not record any (e.g. benchmark mode)
+    #[must_use]
+    pub fn take(&self, block_no: usize) -> Vec<Option<InputPosition>> {
+        self.0
+            .lock()
+            .expect("input positions mutex poisoned")
+            .remove(&block_no)
+            .unwrap_or_default()
+    }
+}
+
+pub struct CheckpointSettings {
+    /// None: don't write (new) checkpoints
+    pub interval: Option<Duration>,
+    pub path: PathBuf,
+    pub config_hash: String,
+    pub resume_from: Option<Checkpoint>,
+    pub input_positions: InputPositions,
+    /// resuming: the inputs continue at the checkpoint's positions, no reads are skipped
+    pub seek_inputs: bool,
+}
+
+#[must_use]
+pub fn checkpoint_path(output_directory: &Path, prefix: &str) -> PathBuf {
+    output_directory.join(format!("{prefix}.checkpoint.json"))
+}
+
+impl CheckpointSettings {
+    /// None if the configuration does not ask for checkpoints and we're not resuming
+    pub fn new(
+        parsed: &CheckedConfig,
+        output_directory: &Path,
+        raw_config: &str,
+        resume: bool,
+    ) -> Result<Option<Self>> {
+        let Some(output) = parsed.output.as_ref() else {
+            return Ok(None);
+        };
+        let interval = output.checkpoint_interval.map(Duration::from_secs);
+        if interval.is_none() && !resume {
+            return Ok(None);
+        }
+        let path = checkpoint_path(output_directory, &output.prefix);
+        let config_hash = format!("{:x}", sha2::Sha256::digest(raw_config.as_bytes()));
+        let resume_from = if resume {
+            let checkpoint = Checkpoint::load(&path)?;
+            if checkpoint.config_hash != config_hash {
+                bail!(
+                    "The configuration changed since the checkpoint {} was written - can not resume. Run without --resume to start over.",
+                    path.display()
+                );
+            }
+            Some(checkpoint)
+        } else {
+            None
+        };
+        Ok(Some(CheckpointSettings {
+            interval,
+            path,
+            config_hash,
+            resume_from,
+            input_positions: InputPositions::default(),
+            seek_inputs: false,
+        }))
+    }
+
+    /// Resuming, and every segment's input can continue at a byte offset:
+    /// the positions, in segment order.
+    #[must_use]
+    pub fn resume_positions(&self, segment_count: usize) -> Option<Vec<InputPosition>> {
+        let checkpoint = self.resume_from.as_ref()?;
+        if checkpoint.input_positions.len() != segment_count {
+            return None;
+        }
+        checkpoint.input_positions.iter().cloned().collect()
+    }
+}
+
+impl Checkpoint {
+    pub fn load(path: &Path) -> Result<Self> {
+        let raw = ex::fs::read_to_string(path).with_context(|| {
+            format!(
+                "No checkpoint to resume from: {}. Checkpoints are written when output.checkpoint_interval is set.",
+                path.display()
+            )
+        })?;
+        serde_json::from_str(&raw)
+            .with_context(|| format!("Could not parse checkpoint {}", path.display()))
+    }
+
+    /// write to a temp file & rename, so a kill never leaves a half written checkpoint
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let temp_path = path.with_extension("json.tmp");
+        let raw = serde_json::to_string_pretty(self)?;
+        {
+            let mut file = ex::fs::File::create(&temp_path)
+                .with_context(|| format!("Could not write checkpoint {}", temp_path.display()))?;
+            file.write_all(raw.as_bytes())?;
+            file.sync_all()?;
+        }
+        ex::fs::rename(&temp_path, path)
+            .with_context(|| format!("Could not write checkpoint {}", path.display()))?;
+        Ok(())
+    }
+}
+
+/// the run completed - there's nothing to resume
+pub fn remove_checkpoint(path: &Path) -> Result<()> {
+    match ex::fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => {
+            Err(err).with_context(|| format!("Failed to remove checkpoint {}", path.display()))
+        }
+    }
+}
@@ -0,0 +1,330 @@
+use anyhow::{Context, Result, bail};
+use indexmap::IndexMap;
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::cli::process::read_config;
+use crate::cli::validate::validate_one;
+use crate::config::deser::TagLabel;
+use crate::config::{CheckedConfig, Stage};
+use crate::transformations::{ModifiedSegments, Step};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExplainFormat {
+    /// one row per step
+    Table,
+    /// Graphviz
+    Dot,
+    Mermaid,
+}
+
+impl std::str::FromStr for ExplainFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "table" => Ok(ExplainFormat::Table),
+            "dot" => Ok(ExplainFormat::Dot),
+            "mermaid" => Ok(ExplainFormat::Mermaid),
+            _ => bail!("Unknown explain format '{s}'. Use one of: table, dot, mermaid"),
+        }
+    }
+}
+
+pub struct Explanation {
+    pub text: String,
+    /// tags that are declared, but only ever forgotten
+    pub warnings: Vec<String>,
+}
+
+/// Describe what each step of a configuration does with tags and segments
+pub fn explain(toml_file: &Path, format: ExplainFormat) -> Result<Explanation> {
+    let (raw_config, expansion) = read_config(toml_file)?;
+    match crate::config::split_branches(&raw_config)? {
+        Some(branches) => {
+            if format != ExplainFormat::Table {
+                bail!(
+                    "Graph export is not supported for configurations with [[branch]] sections. Use --format table."
+                );
+            }
+            let mut text = String::new();
+            let mut warnings = Vec::new();
+            for branch in branches {
+                let checked = validate_one(&branch.toml, "config.toml", expansion.as_ref())
+                    .with_context(|| format!("In branch '{}'", branch.name))?;
+                let explanation = explain_checked(&checked, format);
+                writeln!(text, "# Branch '{}'", branch.name).expect("writing to string");
+                text.push_str(&explanation.text);
+                text.push('\n');
+                warnings.extend(
+                    explanation
+                        .warnings
+                        .into_iter()
+                        .map(|warning| format!("Branch '{}': {warning}", branch.name)),
+                );
+            }
+            Ok(Explanation { text, warnings })
+        }
+        None => {
+            let checked = validate_one(&raw_config, "config.toml", expansion.as_ref())?;
+            Ok(explain_checked(&checked, format))
+        }
+    }
+}
+
+#[must_use]
+pub fn explain_checked(checked: &CheckedConfig, format: ExplainFormat) -> Explanation {
+    let segment_order = checked.input.get_segment_order();
+    let rows: Vec<Row> = checked
+        .stages
+        .iter()
+        .map(|stage| Row::new(stage, segment_order))
+        .collect();
+    let text = match format {
+        ExplainFormat::Table => table(&rows),
+        ExplainFormat::Dot => dot(&rows),
+        ExplainFormat::Mermaid => mermaid(&rows),
+    };
+    Explanation {
+        text,
+        warnings: unused_tags(&checked.stages),
+    }
+}
+
+struct Row {
+    step: String,
+    action: String,
+    declared: Vec<String>,
+    /// 'label (type)'
+    declared_with_type: String,
+    used: Vec<String>,
+    removed: Vec<String>,
+    modifies: String,
+    serial: bool,
+}
+
+impl Row {
+    fn new(stage: &Stage, segment_order: &[String]) -> Self {
+        Row {
+            step: stage
+                .step_no
+                .map_or_else(|| "-".to_string(), |step_no| step_no.to_string()),
+            action: stage.transformation.action_name().to_string(),
+            declared: stage
+                .tags
+                .declared
+                .iter()
+                .map(|(label, _tag_type)| label.to_string())
+                .collect(),
+            declared_with_type: stage
+                .tags
+                .declared
+                .iter()
+                .map(|(label, tag_type)| format!("{label} ({tag_type})"))
+                .collect::<Vec<_>>()
+                .join(", "),
+            used: stage.tags.used.iter().map(ToString::to_string).collect(),
+            removed: stage.tags.removed.iter().map(ToString::to_string).collect(),
+            modifies: match stage.transformation.modified_segments() {
+                ModifiedSegments::None => String::new(),
+                ModifiedSegments::All => "all segments".to_string(),
+                ModifiedSegments::Some(segments) => segments
+                    .iter()
+                    .map(|segment| segment_order[segment.get_index()].as_str())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                ModifiedSegments::TagRegions(label) => format!("regions of '{label}'"),
+            },
+            serial: stage.transformation.needs_serial(),
+        }
+    }
+
+    /// 'step: action' - internal stages have no step number
+    fn title(&self) -> String {
+        format!("{}: {}", self.step, self.action)
+    }
+
+    fn graph_label_lines(&self) -> Vec<String> {
+        let mut lines = vec![self.title()];
+        if !self.declared_with_type.is_empty() {
+            lines.push(format!("declares {}", self.declared_with_type));
+        }
+        if !self.modifies.is_empty() {
+            lines.push(format!("modifies {}", self.modifies));
+        }
+        if self.serial {
+            lines.push("serial".to_string());
+        }
+        lines
+    }
+}
+
+fn table(rows: &[Row]) -> String {
+    fn or_dash(value: String) -> String {
+        if value.is_empty() {
+            "-".to_string()
+        } else {
+            value
+        }
+    }
+    let header = [
+        "Step", "Action", "Declares", "Uses", "Removes", "Modifies", "Serial",
+    ];
+    let cells: Vec<[String; 7]> = rows
+        .iter()
+        .map(|row| {
+            [
+                row.step.clone(),
+                row.action.clone(),
+                or_dash(row.declared_with_type.clone()),
+                or_dash(row.used.join(", ")),
+                or_dash(row.removed.join(", ")),
+                or_dash(row.modifies.clone()),
+                if row.serial { "yes" } else { "no" }.to_string(),
+            ]
+        })
+        .collect();
+    let mut widths = header.map(str::len);
+    for row in &cells {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let mut out = String::new();
+    let mut write_line = |cells: &[&str]| {
+        let line = cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        writeln!(out, "{}", line.trim_end()).expect("writing to string");
+    };
+    write_line(&header);
+    for row in &cells {
+        write_line(&row.iter().map(String::as_str).collect::<Vec<_>>());
+    }
+    out
+}
+
+/// An edge from the stage that declared a tag to a stage using or removing it
+struct TagEdge {
+    from: usize,
+    to: usize,
+    label: String,
+    removes: bool,
+}
+
+fn tag_edges(rows: &[Row]) -> Vec<TagEdge> {
+    let mut declared_by: IndexMap<String, usize> = IndexMap::new();
+    let mut edges = Vec::new();
+    for (ii, row) in rows.iter().enumerate() {
+        for label in &row.used {
+            if let Some(from) = declared_by.get(label) {
+                edges.push(TagEdge {
+                    from: *from,
+                    to: ii,
+                    label: label.clone(),
+                    removes: false,
+                });
+            }
+        }
+        for label in &row.removed {
+            if let Some(from) = declared_by.shift_remove(label) {
+                edges.push(TagEdge {
+                    from,
+                    to: ii,
+                    label: label.clone(),
+                    removes: true,
+                });
+            }
+        }
+        for label in &row.declared {
+            declared_by.insert(label.clone(), ii);
+        }
+    }
+    edges
+}
+
+fn dot(rows: &[Row]) -> String {
+    let mut out = String::new();
+    out.push_str("digraph pipeline {\n    node [shape=box];\n");
+    for (ii, row) in rows.iter().enumerate() {
+        let label = row.graph_label_lines().join("\\n");
+        writeln!(out, "    s{ii} [label=\"{label}\"];").expect("writing to string");
+    }
+    for ii in 1..rows.len() {
+        writeln!(out, "    s{} -> s{ii} [weight=10];", ii - 1).expect("writing to string");
+    }
+    for edge in tag_edges(rows) {
+        writeln!(
+            out,
+            "    s{} -> s{} [style={}, color=blue, label=\"{}{}\"];",
+            edge.from,
+            edge.to,
+            if edge.removes { "dotted" } else { "dashed" },
+            edge.label,
+            if edge.removes { " (removed)" } else { "" }
+        )
+        .expect("writing to string");
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn mermaid(rows: &[Row]) -> String {
+    let mut out = String::new();
+    out.push_str("flowchart TD\n");
+    for (ii, row) in rows.iter().enumerate() {
+        let label = row.graph_label_lines().join("<br/>");
+        writeln!(out, "    s{ii}[\"{label}\"]").expect("writing to string");
+    }
+    for ii in 1..rows.len() {
+        writeln!(out, "    s{} --> s{ii}", ii - 1).expect("writing to string");
+    }
+    for edge in tag_edges(rows) {
+        writeln!(
+            out,
+            "    s{} -. \"{}{}\" .-> s{}",
+            edge.from,
+            edge.label,
+            if edge.removes { " (removed)" } else { "" },
+            edge.to
+        )
+        .expect("writing to string");
+    }
+    out
+}
+
+/// Tags no later step reads - they are only forgotten.
+/// (Tags that are neither read nor forgotten are a configuration error)
+fn unused_tags(stages: &[Stage]) -> Vec<String> {
+    let mut pending: IndexMap<TagLabel, (String, bool)> = IndexMap::new();
+    let mut warnings = Vec::new();
+    let mut warn = |label: &TagLabel, declared_in: &str| {
+        warnings.push(format!(
+            "Tag '{label}' (declared in step {declared_in}) is never used, only forgotten."
+        ));
+    };
+    for stage in stages {
+        for label in &stage.tags.used {
+            if let Some((_, used)) = pending.get_mut(label) {
+                *used = true;
+            }
+        }
+        for label in &stage.tags.removed {
+            if let Some((declared_in, used)) = pending.shift_remove(label)
+                && !used
+            {
+                warn(label, &declared_in);
+            }
+        }
+        for (label, _tag_type) in &stage.tags.declared {
+            let declared_in = stage
+                .step_no
+                .map_or_else(|| "-".to_string(), |step_no| step_no.to_string());
+            pending.insert(label.clone(), (declared_in, false));
+        }
+    }
+    warnings
+}
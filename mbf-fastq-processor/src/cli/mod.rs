@@ -1,6 +1,9 @@
 use crate::config::PartialConfig;
 
+pub mod explain;
 pub mod process;
+pub mod report_merge;
+pub mod samples;
 pub mod validate;
 pub mod verify;
 
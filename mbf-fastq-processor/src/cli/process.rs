@@ -1,31 +1,98 @@
 use anyhow::{Context, Result, bail};
 use std::path::Path;
 
+use crate::checkpoint::{CheckpointSettings, remove_checkpoint};
 use crate::cli::improve_error_messages;
 use crate::config::CheckedConfig;
 use crate::config::Config;
+use crate::config::ExpandedConfig;
+use crate::config::Stage;
 use crate::output::OutputRunMarker;
 use crate::pipeline;
 use toml_pretty_deser::prelude::*;
 
+/// Processing completed, but [qc_thresholds] 'fail' limits were hit
+/// and `fail_exit_code` was set.
+#[derive(Debug)]
+pub struct QcFailure {
+    pub exit_code: i32,
+    pub failed_metrics: Vec<String>,
+}
+
+impl std::fmt::Display for QcFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "QC failed. Metrics outside of their fail thresholds: {}",
+            self.failed_metrics.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for QcFailure {}
+
 pub fn run(toml_file: &Path, output_directory: &Path, allow_overwrite: bool) -> Result<()> {
-    let output_directory = output_directory.to_owned();
+    let (raw_config, expansion) = read_config(toml_file)?;
+    run_config(
+        raw_config,
+        output_directory,
+        allow_overwrite,
+        expansion.as_ref(),
+        false,
+    )
+}
+
+/// Continue an interrupted run from its last checkpoint (see output.checkpoint_interval).
+///
+/// The configuration must be unchanged. Output files are truncated to their
+/// checkpointed lengths and appended to.
+pub fn resume(toml_file: &Path, output_directory: &Path) -> Result<()> {
+    let (raw_config, expansion) = read_config(toml_file)?;
+    run_config(raw_config, output_directory, true, expansion.as_ref(), true)
+}
+
+/// Read a configuration file, expanding [vars] and includes.
+///
+/// Returns the configuration to parse, and the expansion (if any) to map errors back.
+pub(crate) fn read_config(toml_file: &Path) -> Result<(String, Option<ExpandedConfig>)> {
     let raw_config = ex::fs::read_to_string(toml_file)
         .with_context(|| format!("Could not read toml file: {}", toml_file.to_string_lossy()))?;
-    let result = Config::tpd_from_toml(&raw_config, FieldMatchMode::AnyCase, VecMode::SingleOk);
-    let parsed = match result {
-        Ok(config) => config,
-        Err(e) => {
-            let pretty = e.pretty("config.toml");
-            if pretty.trim().is_empty() {
-                dbg!(&e);
-                bail!("Failed to parse config.toml and no pretty error available");
-            }
-            let pretty = improve_error_messages("config.toml", e);
-            bail!("{pretty}");
+    let toml_dir = toml_file.parent().unwrap_or_else(|| Path::new(""));
+    match crate::config::expand_config(&raw_config, toml_dir)? {
+        Some(expansion) => Ok((expansion.toml.clone(), Some(expansion))),
+        None => Ok((raw_config, None)),
+    }
+}
+
+/// Process a configuration that's already in memory
+pub(crate) fn run_config(
+    raw_config: String,
+    output_directory: &Path,
+    allow_overwrite: bool,
+    expansion: Option<&ExpandedConfig>,
+    resume: bool,
+) -> Result<()> {
+    let output_directory = output_directory.to_owned();
+    if let Some(branches) = crate::config::split_branches(&raw_config)? {
+        if resume {
+            bail!("--resume is not supported for configurations with [[branch]] sections");
         }
-    };
-    let checked = parsed.check()?;
+        return run_branches(
+            branches,
+            &output_directory,
+            allow_overwrite,
+            &raw_config,
+            expansion,
+        );
+    }
+    let checked = parse_and_check(&raw_config, "config.toml", expansion)?;
+    for warning in checked.memory_limit_warnings() {
+        eprintln!("Warning: {warning}");
+    }
+    let checkpoint = CheckpointSettings::new(&checked, &output_directory, &raw_config, resume)?;
+    let checkpoint_path = checkpoint
+        .as_ref()
+        .map(|checkpoint| checkpoint.path.clone());
     let marker_prefix = checked
         .output
         .as_ref()
@@ -40,32 +107,267 @@ pub fn run(toml_file: &Path, output_directory: &Path, allow_overwrite: bool) ->
         output_directory.as_ref(),
         allow_overwrite,
         raw_config,
+        checkpoint,
     );
 
+    // QC failures happen after all output has been written
+    if let Some(checkpoint_path) = checkpoint_path
+        && (res.is_ok() || res.as_ref().is_err_and(|e| e.is::<QcFailure>()))
+    {
+        remove_checkpoint(&checkpoint_path)?;
+    }
+    finish_markers(res, vec![marker])
+}
+
+fn parse_and_check(
+    raw_config: &str,
+    toml_filename: &str,
+    expansion: Option<&ExpandedConfig>,
+) -> Result<CheckedConfig> {
+    parse_config(raw_config, toml_filename, expansion)?
+        .check()
+        .map_err(|e| remap_errors(e, toml_filename, expansion))
+}
+
+/// Point the line numbers in (pretty) errors of an expanded configuration
+/// at the files & lines they came from.
+pub(crate) fn remap_errors(
+    error: anyhow::Error,
+    toml_filename: &str,
+    expansion: Option<&ExpandedConfig>,
+) -> anyhow::Error {
+    match expansion {
+        Some(expansion) => {
+            anyhow::anyhow!(
+                "{}",
+                expansion.remap_error(&format!("{error:?}"), toml_filename)
+            )
+        }
+        None => error,
+    }
+}
+
+/// Parse (but do not check) a configuration, with pretty errors
+pub(crate) fn parse_config(
+    raw_config: &str,
+    toml_filename: &str,
+    expansion: Option<&ExpandedConfig>,
+) -> Result<Config> {
+    let result = Config::tpd_from_toml(raw_config, FieldMatchMode::AnyCase, VecMode::SingleOk);
+    let parsed = match result {
+        Ok(config) => config,
+        Err(e) => {
+            let pretty = e.pretty(toml_filename);
+            if pretty.trim().is_empty() {
+                dbg!(&e);
+                bail!("Failed to parse {toml_filename} and no pretty error available");
+            }
+            let pretty = improve_error_messages(toml_filename, e);
+            return Err(remap_errors(
+                anyhow::anyhow!("{pretty}"),
+                toml_filename,
+                expansion,
+            ));
+        }
+    };
+    Ok(parsed)
+}
+
+fn finish_markers(res: Result<()>, markers: Vec<OutputRunMarker>) -> Result<()> {
     match res {
         Ok(()) => {
-            marker.mark_complete()?;
+            for marker in markers {
+                marker.mark_complete()?;
+            }
             Ok(())
         }
         Err(e) => {
-            if format!("{e:?}").contains("already exists") {
-                marker.mark_complete()?;
+            // QC failures happen after all output has been written
+            if e.is::<QcFailure>() || format!("{e:?}").contains("already exists") {
+                for marker in markers {
+                    marker.mark_complete()?;
+                }
             }
             Err(e)
         }
     }
 }
 
+/// [[branch]] sections: every branch is checked as a configuration of its own,
+/// at runtime they share the input threads and the common steps.
+fn run_branches(
+    branches: Vec<crate::config::BranchConfig>,
+    output_directory: &Path,
+    allow_overwrite: bool,
+    raw_config: &str,
+    expansion: Option<&ExpandedConfig>,
+) -> Result<()> {
+    let mut checked = Vec::new();
+    for branch in &branches {
+        checked.push(
+            parse_and_check(&branch.toml, "config.toml", expansion)
+                .with_context(|| format!("In branch '{}'", branch.name))?,
+        );
+    }
+    for warning in checked
+        .iter()
+        .flat_map(CheckedConfig::memory_limit_warnings)
+    {
+        eprintln!("Warning: {warning}");
+    }
+    if checked.iter().any(|parsed| {
+        parsed
+            .output
+            .as_ref()
+            .is_some_and(|output| output.checkpoint_interval.is_some())
+    }) {
+        bail!("output.checkpoint_interval is not supported in [[branch]] sections");
+    }
+    let mut markers = Vec::new();
+    let mut allow_overwrite = allow_overwrite;
+    for parsed in &checked {
+        let marker_prefix = &parsed
+            .output
+            .as_ref()
+            .expect("config.check() ensures output is present")
+            .prefix;
+        let marker = OutputRunMarker::create(output_directory, marker_prefix)?;
+        allow_overwrite |= marker.was_preexisting();
+        markers.push(marker);
+    }
+    let common_step_count = branches[0].common_step_count;
+    let names: Vec<String> = branches.into_iter().map(|branch| branch.name).collect();
+    let res = inner_run_branches(
+        checked,
+        &names,
+        common_step_count,
+        output_directory,
+        allow_overwrite,
+        raw_config,
+    );
+    finish_markers(res, markers)
+}
+
+/// [[branch]]: the leading stages that stem from the common [[step]]s
+/// (including the read pairing spot check ahead of them)
+fn common_stage_count(stages: &[Stage], common_step_count: usize) -> usize {
+    stages
+        .iter()
+        .take_while(|stage| {
+            stage
+                .step_no
+                .is_none_or(|step_no| step_no <= common_step_count)
+        })
+        .count()
+}
+
+fn inner_run_branches(
+    mut checked: Vec<CheckedConfig>,
+    names: &[String],
+    common_step_count: usize,
+    output_directory: &Path,
+    allow_overwrite: bool,
+    raw_config: &str,
+) -> Result<()> {
+    let fail_exit_code = checked[0]
+        .qc_thresholds
+        .as_ref()
+        .and_then(|qc_thresholds| qc_thresholds.fail_exit_code);
+
+    // The common steps run once, ahead of all branches.
+    // Every branch has (uninitialized) copies of them - we keep the first one's,
+    // unless another branch has more common stages: the read pairing spot check,
+    // which a branch's own validation step suppresses.
+    let common_stage_counts: Vec<usize> = checked
+        .iter()
+        .map(|parsed| common_stage_count(&parsed.stages, common_step_count))
+        .collect();
+    let common_from = (0..checked.len())
+        .max_by_key(|&ii| (common_stage_counts[ii], std::cmp::Reverse(ii)))
+        .context("[[branch]]: no branches")?;
+    let common_stages: Vec<Stage> = checked[common_from]
+        .stages
+        .drain(..common_stage_counts[common_from])
+        .collect();
+    for (ii, parsed) in checked.iter_mut().enumerate() {
+        if ii != common_from {
+            parsed.stages.drain(..common_stage_counts[ii]);
+        }
+    }
+
+    // the common steps are configured like the first branch (e.g. its output prefix)
+    let first_branch_stages = std::mem::replace(&mut checked[0].stages, common_stages);
+    let common = pipeline::RunStage0::new(&checked[0]).configure_demultiplex_and_init_stages(
+        &mut checked[0],
+        output_directory,
+        allow_overwrite,
+    )?;
+    let common_stages = std::mem::replace(&mut checked[0].stages, first_branch_stages);
+
+    let mut stage1 = Vec::new();
+    for parsed in &mut checked {
+        let run = pipeline::RunStage0::new(parsed).after_common_steps(&common);
+        stage1.push(run.configure_demultiplex_and_init_stages(
+            parsed,
+            output_directory,
+            allow_overwrite,
+        )?);
+    }
+    let first_branch_stages = std::mem::replace(&mut checked[0].stages, common_stages);
+    let stage2 = common.create_branched_input_threads(stage1, &mut checked[0])?;
+    checked[0].stages = first_branch_stages;
+
+    let mut stage4 = Vec::new();
+    for (run, parsed) in stage2.into_iter().zip(checked.iter_mut()) {
+        let run = run.create_stage_threads(parsed);
+        stage4.push(run.create_output_threads(parsed, raw_config.to_string())?);
+    }
+    let mut errors = Vec::new();
+    let mut qc_failed_metrics = Vec::new();
+    for (run, name) in stage4.into_iter().zip(names) {
+        let run = run.join_threads();
+        errors.extend(
+            run.errors
+                .into_iter()
+                .map(|error| format!("Branch '{name}': {error}")),
+        );
+        qc_failed_metrics.extend(
+            run.qc_failed_metrics
+                .into_iter()
+                .map(|metric| format!("{name}: {metric}")),
+        );
+    }
+    if !errors.is_empty() {
+        bail!(errors.join("\n"));
+    }
+    if let Some(exit_code) = fail_exit_code
+        && !qc_failed_metrics.is_empty()
+    {
+        return Err(QcFailure {
+            exit_code,
+            failed_metrics: qc_failed_metrics,
+        }
+        .into());
+    }
+    Ok(())
+}
+
 fn inner_run(
     mut parsed: CheckedConfig,
     output_directory: &Path,
     allow_overwrite: bool,
     raw_config: String,
+    checkpoint: Option<CheckpointSettings>,
 ) -> Result<()> {
     let start_time = std::time::Instant::now();
     let is_benchmark = parsed.benchmark.as_ref().is_some_and(|b| b.enable);
+    let fail_exit_code = parsed
+        .qc_thresholds
+        .as_ref()
+        .and_then(|qc_thresholds| qc_thresholds.fail_exit_code);
+    let qc_failed_metrics;
     {
-        let run = pipeline::RunStage0::new(&parsed);
+        let run = pipeline::RunStage0::new(&parsed).with_checkpoint(checkpoint);
         let run = run.configure_demultiplex_and_init_stages(
             &mut parsed,
             output_directory,
@@ -82,6 +384,7 @@ fn inner_run(
         if !errors.is_empty() {
             bail!(errors.join("\n"));
         }
+        qc_failed_metrics = run.qc_failed_metrics;
 
         drop(parsed);
     }
@@ -92,5 +395,14 @@ fn inner_run(
             elapsed.as_secs_f64()
         );
     }
+    if let Some(exit_code) = fail_exit_code
+        && !qc_failed_metrics.is_empty()
+    {
+        return Err(QcFailure {
+            exit_code,
+            failed_metrics: qc_failed_metrics,
+        }
+        .into());
+    }
     Ok(())
 }
@@ -0,0 +1,296 @@
+use anyhow::{Context, Result, bail};
+use indexmap::IndexMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Keys of a json report that describe the run, not a Report step
+const NON_REPORT_KEYS: [&str; 3] = ["__", "run_info", "report_order"];
+
+/// Combine many mbf-fastq-processor json reports into one
+/// `{output_prefix}.json`, `{output_prefix}.tsv` and `{output_prefix}.html`.
+///
+/// Sample names are derived from the report file names (without `.json`),
+/// using as many parent directories as necessary to make them unique.
+pub fn merge_reports(
+    report_files: &[PathBuf],
+    output_prefix: &Path,
+    allow_overwrite: bool,
+) -> Result<()> {
+    if report_files.is_empty() {
+        bail!("report-merge needs at least one json report");
+    }
+    let sample_names = derive_sample_names(report_files)?;
+    merge_named_reports(
+        sample_names.into_iter().zip(report_files.iter().cloned()),
+        output_prefix,
+        allow_overwrite,
+    )
+}
+
+/// `merge_reports` with given sample names
+pub(crate) fn merge_named_reports(
+    named_report_files: impl IntoIterator<Item = (String, PathBuf)>,
+    output_prefix: &Path,
+    allow_overwrite: bool,
+) -> Result<()> {
+    let mut samples: IndexMap<String, serde_json::Value> = IndexMap::new();
+    for (sample_name, report_file) in named_report_files {
+        let report = read_report(&report_file)?;
+        samples.insert(sample_name, report);
+    }
+
+    let merged = merge_json(&samples);
+    let merged_str = serde_json::to_string_pretty(&merged)?;
+    let tsv = merged_tsv(&samples);
+
+    let json_filename = with_suffix(output_prefix, "json");
+    let tsv_filename = with_suffix(output_prefix, "tsv");
+    let html_filename = with_suffix(output_prefix, "html");
+    if !allow_overwrite {
+        for filename in [&json_filename, &tsv_filename, &html_filename] {
+            if filename.exists() {
+                bail!(
+                    "Output file '{}' already exists. Pass --allow-overwrite to replace it.",
+                    filename.display()
+                );
+            }
+        }
+    }
+    write_file(&json_filename, merged_str.as_bytes())?;
+    write_file(&tsv_filename, tsv.as_bytes())?;
+    write_file(&html_filename, merged_html(&merged_str).as_bytes())?;
+    Ok(())
+}
+
+fn with_suffix(prefix: &Path, suffix: &str) -> PathBuf {
+    let mut name = prefix.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn write_file(filename: &Path, contents: &[u8]) -> Result<()> {
+    let mut fh = ex::fs::File::create(filename)
+        .with_context(|| format!("Could not create output file: {}", filename.display()))?;
+    fh.write_all(contents)
+        .with_context(|| format!("Could not write output file: {}", filename.display()))?;
+    Ok(())
+}
+
+fn read_report(report_file: &Path) -> Result<serde_json::Value> {
+    let raw = ex::fs::read_to_string(report_file)
+        .with_context(|| format!("Could not read json report: {}", report_file.display()))?;
+    let parsed: serde_json::Value = serde_json::from_str(&raw)
+        .with_context(|| format!("Could not parse json report: {}", report_file.display()))?;
+    match parsed.as_object() {
+        Some(obj) if obj.contains_key("__") && obj.contains_key("report_order") => Ok(parsed),
+        _ => bail!(
+            "{} does not look like a mbf-fastq-processor json report (missing '__' or 'report_order')",
+            report_file.display()
+        ),
+    }
+}
+
+/// Name each report by its file stem. If that's ambiguous (e.g. every run used prefix='output'),
+/// prepend parent directories until all names are unique.
+fn derive_sample_names(report_files: &[PathBuf]) -> Result<Vec<String>> {
+    let components: Vec<Vec<String>> = report_files
+        .iter()
+        .map(|path| {
+            let mut parts: Vec<String> = path
+                .components()
+                .filter_map(|c| match c {
+                    std::path::Component::Normal(part) => Some(part.to_string_lossy().to_string()),
+                    _ => None,
+                })
+                .collect();
+            if let Some(last) = parts.last_mut()
+                && let Some(stripped) = last.strip_suffix(".json")
+            {
+                *last = stripped.to_string();
+            }
+            parts
+        })
+        .collect();
+    let max_depth = components.iter().map(Vec::len).max().unwrap_or(1).max(1);
+    for depth in 1..=max_depth {
+        let names: Vec<String> = components
+            .iter()
+            .map(|parts| parts[parts.len().saturating_sub(depth)..].join("/"))
+            .collect();
+        let mut seen = std::collections::HashSet::new();
+        if names.iter().all(|name| seen.insert(name.as_str())) {
+            return Ok(names);
+        }
+    }
+    bail!("Could not derive unique sample names - was the same json report passed twice?")
+}
+
+/// Union of all report_order entries, in first-seen order
+fn report_order_union(samples: &IndexMap<String, serde_json::Value>) -> Vec<String> {
+    let mut order: Vec<String> = Vec::new();
+    for report in samples.values() {
+        if let Some(labels) = report.get("report_order").and_then(|x| x.as_array()) {
+            for label in labels.iter().filter_map(|x| x.as_str()) {
+                if !order.iter().any(|x| x == label) {
+                    order.push(label.to_string());
+                }
+            }
+        }
+    }
+    order
+}
+
+fn merge_json(samples: &IndexMap<String, serde_json::Value>) -> serde_json::Value {
+    let sample_order: Vec<&String> = samples.keys().collect();
+    let mut sample_map = serde_json::Map::new();
+    for (name, report) in samples {
+        sample_map.insert(name.clone(), report.clone());
+    }
+    serde_json::json!({
+        "__": {
+            "version": env!("CARGO_PKG_VERSION"),
+            "repository": env!("CARGO_PKG_HOMEPAGE"),
+        },
+        "sample_order": sample_order,
+        "report_order": report_order_union(samples),
+        "samples": serde_json::Value::Object(sample_map),
+    })
+}
+
+/// Flatten all scalar values below `value` into `out`, keyed by their '.' joined path.
+/// Arrays (curves) are skipped - they don't fit into a table.
+fn flatten_scalars(prefix: &str, value: &serde_json::Value, out: &mut IndexMap<String, String>) {
+    match value {
+        serde_json::Value::Object(obj) => {
+            for (key, sub_value) in obj {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{prefix}.{key}")
+                };
+                flatten_scalars(&path, sub_value, out);
+            }
+        }
+        serde_json::Value::Array(_) => {}
+        serde_json::Value::Null => {
+            out.insert(prefix.to_string(), String::new());
+        }
+        serde_json::Value::String(s) => {
+            out.insert(prefix.to_string(), s.replace(['\t', '\n', '\r'], " "));
+        }
+        serde_json::Value::Bool(_) | serde_json::Value::Number(_) => {
+            out.insert(prefix.to_string(), value.to_string());
+        }
+    }
+}
+
+/// One row per sample, one column per scalar metric (`report_label.path.to.value`)
+fn merged_tsv(samples: &IndexMap<String, serde_json::Value>) -> String {
+    let report_order = report_order_union(samples);
+    let mut columns: IndexMap<String, ()> = IndexMap::new();
+    let mut rows: Vec<(&String, IndexMap<String, String>)> = Vec::new();
+    for (name, report) in samples {
+        let mut flat = IndexMap::new();
+        for label in &report_order {
+            if let Some(section) = report.get(label) {
+                flatten_scalars(label, section, &mut flat);
+            }
+        }
+        if let Some(obj) = report.as_object() {
+            // report steps not listed in report_order (should not happen, but be lenient)
+            for (key, section) in obj {
+                if !NON_REPORT_KEYS.contains(&key.as_str()) && !report_order.contains(key) {
+                    flatten_scalars(key, section, &mut flat);
+                }
+            }
+        }
+        for key in flat.keys() {
+            columns.entry(key.clone()).or_insert(());
+        }
+        rows.push((name, flat));
+    }
+    let mut out = String::new();
+    out.push_str("sample");
+    for column in columns.keys() {
+        out.push('\t');
+        out.push_str(column);
+    }
+    out.push('\n');
+    for (name, flat) in rows {
+        out.push_str(name);
+        for column in columns.keys() {
+            out.push('\t');
+            if let Some(value) = flat.get(column) {
+                out.push_str(value);
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn merged_html(json_report_string: &str) -> String {
+    let template = include_str!("../html/merged_template.html");
+    let chartjs = include_str!("../html/chart/chart.umd.min.js");
+    template
+        .replace("%TITLE%", "mbf-fastq-processor-merged-report")
+        .replace("\"%DATA%\"", json_report_string)
+        .replace("/*%CHART%*/", chartjs)
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derive_sample_names() {
+        let names = derive_sample_names(&[
+            PathBuf::from("a/output.json"),
+            PathBuf::from("b/other.json"),
+        ])
+        .unwrap();
+        assert_eq!(names, vec!["output", "other"]);
+        let names = derive_sample_names(&[
+            PathBuf::from("runs/a/output.json"),
+            PathBuf::from("runs/b/output.json"),
+        ])
+        .unwrap();
+        assert_eq!(names, vec!["a/output", "b/output"]);
+        assert!(
+            derive_sample_names(&[
+                PathBuf::from("a/output.json"),
+                PathBuf::from("a/output.json"),
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_merged_tsv() {
+        let mut samples = IndexMap::new();
+        samples.insert(
+            "s1".to_string(),
+            serde_json::json!({
+                "__": {"version": "1"},
+                "report_order": ["start"],
+                "start": {"molecule_count": 10, "read1": {"length_distribution": [0, 10]}},
+            }),
+        );
+        samples.insert(
+            "s2".to_string(),
+            serde_json::json!({
+                "__": {"version": "1"},
+                "report_order": ["start", "end"],
+                "start": {"molecule_count": 5},
+                "end": {"a": {"molecule_count": 2}},
+            }),
+        );
+        let tsv = merged_tsv(&samples);
+        assert_eq!(
+            tsv,
+            "sample\tstart.molecule_count\tend.a.molecule_count\ns1\t10\t\ns2\t5\t2\n"
+        );
+    }
+}
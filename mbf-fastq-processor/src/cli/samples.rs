@@ -0,0 +1,479 @@
+//! Batch mode: run one configuration over a table of samples (`process --samples samples.tsv`)
+//!
+//! Every `{column}` in the `[input]` and `[output]` sections is replaced
+//! by the sample's value, then the samples are processed one after another
+//! (or `jobs` at a time).
+use anyhow::{Context, Result, bail};
+use indexmap::IndexMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use toml_edit::{DocumentMut, Item, Value};
+
+use crate::cli::process::{QcFailure, read_config, run_config};
+use crate::cli::report_merge::merge_named_reports;
+use crate::config::ExpandedConfig;
+
+/// One sample: column name -> value, always containing 'sample'
+type Sample = IndexMap<String, String>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SampleStatus {
+    Ok,
+    QcFailed,
+    Failed,
+}
+
+impl SampleStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            SampleStatus::Ok => "ok",
+            SampleStatus::QcFailed => "qc_failed",
+            SampleStatus::Failed => "failed",
+        }
+    }
+}
+
+struct SampleResult {
+    status: SampleStatus,
+    seconds: f64,
+    message: String,
+    qc_failure: Option<QcFailure>,
+}
+
+/// Parse the sample table: tab separated, first line is the header,
+/// which must contain a 'sample' column. Empty lines and lines starting with '#' are skipped.
+fn parse_sample_table(raw: &str) -> Result<Vec<Sample>> {
+    let mut lines = raw
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'));
+    let Some((_, header)) = lines.next() else {
+        bail!("Sample table is empty - expected a header line with at least a 'sample' column");
+    };
+    let columns: Vec<String> = header.split('\t').map(|x| x.trim().to_string()).collect();
+    if !columns.iter().any(|column| column == "sample") {
+        bail!(
+            "Sample table header has no 'sample' column. Found: {}",
+            columns.join(", ")
+        );
+    }
+    for (ii, column) in columns.iter().enumerate() {
+        if column.is_empty() || !column.chars().all(is_variable_char) {
+            bail!(
+                "Sample table column '{column}': column names may only contain letters, numbers, '_' and '-'"
+            );
+        }
+        if columns[..ii].contains(column) {
+            bail!("Sample table has a duplicate column '{column}'");
+        }
+    }
+    let mut samples: Vec<Sample> = Vec::new();
+    for (line_no, line) in lines {
+        let values: Vec<&str> = line.split('\t').collect();
+        if values.len() != columns.len() {
+            bail!(
+                "Sample table line {}: expected {} columns, found {}",
+                line_no + 1,
+                columns.len(),
+                values.len()
+            );
+        }
+        let sample: Sample = columns
+            .iter()
+            .cloned()
+            .zip(values.iter().map(|x| x.trim().to_string()))
+            .collect();
+        let name = &sample["sample"];
+        if name.is_empty() {
+            bail!("Sample table line {}: empty sample name", line_no + 1);
+        }
+        if samples.iter().any(|other| &other["sample"] == name) {
+            bail!("Sample table: duplicate sample '{name}'");
+        }
+        samples.push(sample);
+    }
+    if samples.is_empty() {
+        bail!("Sample table contains no samples");
+    }
+    Ok(samples)
+}
+
+fn is_variable_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-'
+}
+
+/// Replace `{column}` with the sample's value.
+/// Braces around anything that's not a possible column name are left alone.
+fn substitute_str(text: &str, sample: &Sample) -> Result<String> {
+    let mut res = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        res.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find('}') {
+            Some(end) if end > 0 && after[..end].chars().all(is_variable_char) => {
+                let name = &after[..end];
+                let Some(value) = sample.get(name) else {
+                    bail!(
+                        "Unknown sample variable '{{{name}}}'. Available (sample table columns): {}",
+                        sample
+                            .keys()
+                            .map(String::as_str)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    );
+                };
+                res.push_str(value);
+                rest = &after[end + 1..];
+            }
+            _ => {
+                res.push('{');
+                rest = after;
+            }
+        }
+    }
+    res.push_str(rest);
+    Ok(res)
+}
+
+fn substitute_value(value: &mut Value, sample: &Sample) -> Result<()> {
+    match value {
+        Value::String(text) => {
+            let replaced = substitute_str(text.value(), sample)?;
+            if &replaced != text.value() {
+                let decor = text.decor().clone();
+                *value = Value::from(replaced);
+                *value.decor_mut() = decor;
+            }
+        }
+        Value::Array(array) => {
+            for entry in array.iter_mut() {
+                substitute_value(entry, sample)?;
+            }
+        }
+        Value::InlineTable(table) => {
+            for (_, entry) in table.iter_mut() {
+                substitute_value(entry, sample)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn substitute_item(item: &mut Item, sample: &Sample) -> Result<()> {
+    match item {
+        Item::Value(value) => substitute_value(value, sample)?,
+        Item::Table(table) => {
+            for (_, entry) in table.iter_mut() {
+                substitute_item(entry, sample)?;
+            }
+        }
+        Item::ArrayOfTables(tables) => {
+            for table in tables.iter_mut() {
+                for (_, entry) in table.iter_mut() {
+                    substitute_item(entry, sample)?;
+                }
+            }
+        }
+        Item::None => {}
+    }
+    Ok(())
+}
+
+/// The configuration for one sample: variables replaced in `[input]`, `[output]`
+/// (and the `[branch.output]`s)
+fn config_for_sample(raw_config: &str, sample: &Sample) -> Result<String> {
+    let mut doc: DocumentMut = raw_config
+        .parse()
+        .context("Could not parse the configuration as TOML")?;
+    for (key, item) in doc.as_table_mut().iter_mut() {
+        if key.eq_ignore_ascii_case("input") || key.eq_ignore_ascii_case("output") {
+            substitute_item(item, sample)?;
+        } else if key.eq_ignore_ascii_case("branch")
+            && let Item::ArrayOfTables(branches) = item
+        {
+            for branch in branches.iter_mut() {
+                for (key, item) in branch.iter_mut() {
+                    if key.eq_ignore_ascii_case("output") {
+                        substitute_item(item, sample)?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(doc.to_string())
+}
+
+/// `{output_directory}/{prefix}.json`, if this configuration writes a json report
+fn json_report_filename(raw_config: &str, output_directory: &Path) -> Option<PathBuf> {
+    let parsed = crate::config::config_from_string(raw_config).ok()?;
+    let output = parsed.output.as_ref()?;
+    output
+        .report_json
+        .then(|| output_directory.join(format!("{}.json", output.prefix)))
+}
+
+fn output_prefix(raw_config: &str) -> Option<String> {
+    crate::config::config_from_string(raw_config)
+        .ok()?
+        .output
+        .map(|output| output.prefix)
+}
+
+fn run_sample(
+    raw_config: String,
+    output_directory: &Path,
+    allow_overwrite: bool,
+    expansion: Option<&ExpandedConfig>,
+) -> SampleResult {
+    let start = std::time::Instant::now();
+    let res = run_config(
+        raw_config,
+        output_directory,
+        allow_overwrite,
+        expansion,
+        false,
+    );
+    let seconds = start.elapsed().as_secs_f64();
+    match res {
+        Ok(()) => SampleResult {
+            status: SampleStatus::Ok,
+            seconds,
+            message: String::new(),
+            qc_failure: None,
+        },
+        Err(e) => match e.downcast::<QcFailure>() {
+            Ok(qc_failure) => SampleResult {
+                status: SampleStatus::QcFailed,
+                seconds,
+                message: qc_failure.failed_metrics.join(", "),
+                qc_failure: Some(qc_failure),
+            },
+            Err(e) => SampleResult {
+                status: SampleStatus::Failed,
+                seconds,
+                message: format!("{e:#}"),
+                qc_failure: None,
+            },
+        },
+    }
+}
+
+fn status_tsv(samples: &[Sample], results: &[SampleResult]) -> String {
+    let mut out = "sample\tstatus\tseconds\tmessage\n".to_string();
+    for (sample, result) in samples.iter().zip(results) {
+        let message: String = result
+            .message
+            .chars()
+            .map(|c| {
+                if c == '\t' || c == '\n' || c == '\r' {
+                    ' '
+                } else {
+                    c
+                }
+            })
+            .collect();
+        out.push_str(&format!(
+            "{}\t{}\t{:.2}\t{}\n",
+            sample["sample"],
+            result.status.as_str(),
+            result.seconds,
+            message.trim()
+        ));
+    }
+    out
+}
+
+/// Process `toml_file` once per row of `samples_file`, up to `jobs` samples at a time.
+///
+/// Writes `{summary_prefix}_status.tsv` (one line per sample) and,
+/// if the samples write json reports, `{summary_prefix}_report.{json,tsv,html}` (see report-merge).
+/// Fails if any sample failed - after all samples have been processed.
+pub fn run_samples(
+    toml_file: &Path,
+    samples_file: &Path,
+    output_directory: &Path,
+    allow_overwrite: bool,
+    jobs: usize,
+    summary_prefix: &str,
+) -> Result<()> {
+    if jobs == 0 {
+        bail!("--jobs must be at least 1");
+    }
+    let (raw_config, expansion) = read_config(toml_file)?;
+    let raw_samples = ex::fs::read_to_string(samples_file).with_context(|| {
+        format!(
+            "Could not read sample table: {}",
+            samples_file.to_string_lossy()
+        )
+    })?;
+    let samples = parse_sample_table(&raw_samples)?;
+
+    // substitute everything up front - fail before spending hours on the first samples
+    let mut configs = Vec::new();
+    let mut seen_prefixes: IndexMap<String, String> = IndexMap::new();
+    for sample in &samples {
+        let name = &sample["sample"];
+        let config =
+            config_for_sample(&raw_config, sample).with_context(|| format!("Sample '{name}'"))?;
+        if let Some(prefix) = output_prefix(&config)
+            && let Some(other) = seen_prefixes.insert(prefix.clone(), name.clone())
+        {
+            bail!(
+                "Samples '{other}' and '{name}' both write to output prefix '{prefix}'. Use a sample variable in [output] prefix, e.g. prefix = '{{sample}}'"
+            );
+        }
+        configs.push(config);
+    }
+
+    let status_filename = output_directory.join(format!("{summary_prefix}_status.tsv"));
+    let report_prefix = output_directory.join(format!("{summary_prefix}_report"));
+    if !allow_overwrite {
+        for suffix in ["json", "tsv", "html"] {
+            let filename = PathBuf::from(format!("{}.{suffix}", report_prefix.display()));
+            if filename.exists() {
+                bail!(
+                    "Output file '{}' already exists. Pass --allow-overwrite to replace it.",
+                    filename.display()
+                );
+            }
+        }
+        if status_filename.exists() {
+            bail!(
+                "Output file '{}' already exists. Pass --allow-overwrite to replace it.",
+                status_filename.display()
+            );
+        }
+    }
+
+    let next_sample = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<SampleResult>>> =
+        Mutex::new((0..samples.len()).map(|_| None).collect());
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.min(samples.len()) {
+            scope.spawn(|| {
+                loop {
+                    let ii = next_sample.fetch_add(1, Ordering::Relaxed);
+                    let Some(config) = configs.get(ii) else {
+                        break;
+                    };
+                    let result = run_sample(
+                        config.clone(),
+                        output_directory,
+                        allow_overwrite,
+                        expansion.as_ref(),
+                    );
+                    if result.status == SampleStatus::Failed {
+                        eprintln!(
+                            "Sample '{}' failed: {}",
+                            samples[ii]["sample"], result.message
+                        );
+                    }
+                    results.lock().expect("results mutex poisoned")[ii] = Some(result);
+                }
+            });
+        }
+    });
+    let results: Vec<SampleResult> = results
+        .into_inner()
+        .expect("results mutex poisoned")
+        .into_iter()
+        .map(|result| result.expect("every sample was processed"))
+        .collect();
+
+    ex::fs::write(&status_filename, status_tsv(&samples, &results)).with_context(|| {
+        format!(
+            "Could not write sample status file: {}",
+            status_filename.display()
+        )
+    })?;
+
+    let reports: Vec<(String, PathBuf)> = samples
+        .iter()
+        .zip(&configs)
+        .zip(&results)
+        .filter(|(_, result)| result.status != SampleStatus::Failed)
+        .filter_map(|((sample, config), _)| {
+            json_report_filename(config, output_directory)
+                .filter(|filename| filename.exists())
+                .map(|filename| (sample["sample"].clone(), filename))
+        })
+        .collect();
+    if !reports.is_empty() {
+        merge_named_reports(reports, &report_prefix, true)?;
+    }
+
+    let failed: Vec<&str> = samples
+        .iter()
+        .zip(&results)
+        .filter(|(_, result)| result.status == SampleStatus::Failed)
+        .map(|(sample, _)| sample["sample"].as_str())
+        .collect();
+    if !failed.is_empty() {
+        bail!(
+            "{} of {} samples failed: {}. See {}",
+            failed.len(),
+            samples.len(),
+            failed.join(", "),
+            status_filename.display()
+        );
+    }
+    let mut qc_failures = samples
+        .iter()
+        .zip(results)
+        .filter_map(|(sample, result)| Some((sample, result.qc_failure?)))
+        .peekable();
+    if let Some((_, first)) = qc_failures.peek() {
+        let exit_code = first.exit_code;
+        let failed_metrics = qc_failures
+            .flat_map(|(sample, qc_failure)| {
+                qc_failure
+                    .failed_metrics
+                    .into_iter()
+                    .map(move |metric| format!("{}: {metric}", sample["sample"]))
+            })
+            .collect();
+        return Err(QcFailure {
+            exit_code,
+            failed_metrics,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sample_table() {
+        let samples =
+            parse_sample_table("# comment\nsample\tr1\nA\ta.fq\n\nB\tb.fq\n").expect("valid table");
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[1]["sample"], "B");
+        assert_eq!(samples[1]["r1"], "b.fq");
+        assert!(parse_sample_table("name\tr1\nA\ta.fq\n").is_err());
+        assert!(parse_sample_table("sample\tr1\nA\n").is_err());
+        assert!(parse_sample_table("sample\nA\nA\n").is_err());
+    }
+
+    #[test]
+    fn test_substitute_str() {
+        let sample: Sample = [("sample", "A"), ("r1", "a.fq")]
+            .iter()
+            .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+            .collect();
+        assert_eq!(
+            substitute_str("data/{r1}", &sample).expect("known"),
+            "data/a.fq"
+        );
+        assert_eq!(
+            substitute_str("{sample}_{ {} x}", &sample).expect("known"),
+            "A_{ {} x}"
+        );
+        assert!(substitute_str("{r2}", &sample).is_err());
+    }
+}
@@ -2,28 +2,36 @@ use anyhow::{Context, Result};
 use ex::fs;
 use std::path::Path;
 
+use crate::cli::process::{read_config, remap_errors};
+use crate::config::ExpandedConfig;
 use crate::{cli::improve_error_messages, config::Config};
 use toml_pretty_deser::prelude::*;
 
-pub fn validate_config(toml_file: &Path) -> Result<Vec<String>> {
-    let raw_config = ex::fs::read_to_string(toml_file)
-        .with_context(|| format!("Could not read toml file: {}", toml_file.to_string_lossy()))?;
-    let result = Config::tpd_from_toml(&raw_config, FieldMatchMode::AnyCase, VecMode::SingleOk);
-    let checked = match result {
-        Ok(config) => config,
-        Err(e) => {
-            //dbg!(&e);
-            return Err(anyhow::anyhow!(
-                "{}",
-                improve_error_messages("config.toml", e)
-            ));
+pub struct Validation {
+    pub warnings: Vec<String>,
+    /// The configuration after [vars] and includes were expanded - if it used them
+    pub expanded_config: Option<String>,
+}
+
+pub fn validate_config(toml_file: &Path) -> Result<Validation> {
+    let (raw_config, expansion) = read_config(toml_file)?;
+    let checked = match crate::config::split_branches(&raw_config)? {
+        Some(branches) => {
+            // all branches share the input, the warnings below are the same for each
+            let mut first = None;
+            for branch in branches {
+                let checked = validate_one(&branch.toml, "config.toml", expansion.as_ref())
+                    .with_context(|| format!("In branch '{}'", branch.name))?;
+                first.get_or_insert(checked);
+            }
+            first.expect("split_branches returns at least one branch")
         }
+        None => validate_one(&raw_config, "config.toml", expansion.as_ref())?,
     };
-    let checked = checked.check_for_validation()?;
 
     let toml_dir = toml_file.parent().unwrap_or_else(|| Path::new("."));
 
-    let mut warnings = Vec::new();
+    let mut warnings = checked.memory_limit_warnings();
 
     match &checked.input.structured {
         crate::config::StructuredInput::Interleaved { files, .. } => {
@@ -52,5 +60,31 @@ pub fn validate_config(toml_file: &Path) -> Result<Vec<String>> {
         }
     }
 
-    Ok(warnings)
+    Ok(Validation {
+        warnings,
+        expanded_config: expansion.map(|expansion| expansion.toml),
+    })
+}
+
+pub(crate) fn validate_one(
+    raw_config: &str,
+    toml_filename: &str,
+    expansion: Option<&ExpandedConfig>,
+) -> Result<crate::config::CheckedConfig> {
+    let result = Config::tpd_from_toml(raw_config, FieldMatchMode::AnyCase, VecMode::SingleOk);
+    let checked = match result {
+        Ok(config) => config,
+        Err(e) => {
+            //dbg!(&e);
+            let pretty = improve_error_messages(toml_filename, e);
+            return Err(remap_errors(
+                anyhow::anyhow!("{pretty}"),
+                toml_filename,
+                expansion,
+            ));
+        }
+    };
+    checked
+        .check_for_validation()
+        .map_err(|e| remap_errors(e, toml_filename, expansion))
 }
@@ -61,7 +61,29 @@ pub fn verify_outputs(
     let raw_config = ex::fs::read_to_string(toml_file)
         .with_context(|| format!("Could not read toml file: {}", toml_file.to_string_lossy()))?;
 
-    let (output_prefix, uses_stdout) = {
+    // [vars] and includes - errors are reported by the actual run
+    let expansion = crate::config::expand_config(&raw_config, &toml_dir)
+        .ok()
+        .flatten();
+    let raw_config = expansion
+        .as_ref()
+        .map_or(raw_config, |expansion| expansion.toml.clone());
+
+    let (output_prefixes, uses_stdout) = if let Ok(Some(branches)) =
+        crate::config::split_branches(&raw_config)
+    {
+        // every branch writes its own outputs
+        let prefixes: Vec<String> = branches
+            .iter()
+            .filter_map(|branch| crate::config::config_from_string(&branch.toml).ok())
+            .filter_map(|parsed| parsed.output.as_ref().map(|o| o.prefix.clone()))
+            .collect();
+        if prefixes.is_empty() {
+            (vec!["missing_output_config".to_string()], false)
+        } else {
+            (prefixes, false)
+        }
+    } else {
         let result = crate::config::config_from_string(&raw_config);
 
         // let parsed = match result {
@@ -82,8 +104,13 @@ pub fn verify_outputs(
 
         result
             .ok()
-            .and_then(|parsed| parsed.output.as_ref().map(|o| (o.prefix.clone(), o.stdout)))
-            .unwrap_or_else(|| ("missing_output_config".to_string(), false))
+            .and_then(|parsed| {
+                parsed
+                    .output
+                    .as_ref()
+                    .map(|o| (vec![o.prefix.clone()], o.stdout))
+            })
+            .unwrap_or_else(|| (vec!["missing_output_config".to_string()], false))
     };
 
     let temp_dir = tempfile::tempdir().context("Failed to create temporary directory")?;
@@ -119,6 +146,16 @@ pub fn verify_outputs(
     // Otherwise, symlinks suffice — the TOML is kept verbatim so relative paths still resolve.
     let toml_value: toml::Value =
         toml::from_str(&raw_config).context("Failed to parse TOML for file setup")?;
+    let included_files: Vec<toml::Value> = expansion
+        .as_ref()
+        .map(|expansion| {
+            expansion
+                .included_files
+                .iter()
+                .map(|path| toml::Value::String(path.to_string_lossy().to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
 
     if do_copy_input_files {
         if let Some(input_table) = toml_value.get("input").and_then(|v| v.as_table()) {
@@ -142,6 +179,9 @@ pub fn verify_outputs(
                 }
             }
         }
+        for value in &included_files {
+            copy_input_file(value, &toml_dir, &temp_path)?;
+        }
         for entry in fs::read_dir(&toml_dir)? {
             let entry = entry?;
             let src_path = entry.path();
@@ -180,6 +220,9 @@ pub fn verify_outputs(
                 }
             }
         }
+        for value in &included_files {
+            create_symlinks_for_files(value, &toml_dir, &temp_path)?;
+        }
         // Also symlink any ancillary input files (e.g. .bai index alongside .bam)
         // that aren't explicitly named in the TOML but live next to the inputs.
         for entry in fs::read_dir(&toml_dir)? {
@@ -258,14 +301,15 @@ pub fn verify_outputs(
     };
 
     if expected_validation_error.is_none() | expected_validation_warning.is_some() {
-        let warnings =
-            crate::cli::validate::validate_config(&temp_toml_path).with_context(|| {
+        let warnings = crate::cli::validate::validate_config(&temp_toml_path)
+            .with_context(|| {
                 if expected_runtime_error.is_some() {
                     "Configuration validation failed, but a runtime error was expected.".to_string()
                 } else {
                     "Configuration validation failed unexpectedly.".to_string()
                 }
-            })?;
+            })?
+            .warnings;
         if let Some(expected_warning) = expected_validation_warning {
             if warnings.is_empty() {
                 bail!("Expected validation warning, but none were produced.");
@@ -404,13 +448,13 @@ pub fn verify_outputs(
 
                 if !uses_stdout {
                     let expected_files =
-                        find_output_files(expected_dir, &output_prefix).unwrap_or_default();
+                        find_output_files(expected_dir, &output_prefixes).unwrap_or_default();
 
                     if expected_files.is_empty() {
                         bail!(
                             "No expected output files found in {} with prefix '{}'",
                             expected_dir.display(),
-                            output_prefix
+                            output_prefixes.join("', '")
                         );
                     }
 
@@ -450,7 +494,7 @@ pub fn verify_outputs(
                 }
 
                 if !uses_stdout {
-                    let actual_files = find_output_files(&actual_dir, &output_prefix)?;
+                    let actual_files = find_output_files(&actual_dir, &output_prefixes)?;
                     for actual_file in &actual_files {
                         let surplus = actual_file
                             .strip_prefix(&actual_dir)
@@ -595,8 +639,11 @@ pub fn normalize_report_content(content: &str, input_dir: Option<&Path>) -> Stri
         })
         .into_owned();
 
-    let normalize_re = Regex::new(r#""(?P<key>threads_per_segment|thread_count)"\s*:\s*[^"]*"#)
-        .expect("invalid normalize regex");
+    // thread counts and output.report_timing measurements differ from run to run
+    let normalize_re = Regex::new(
+        r#""(?P<key>threads_per_segment|thread_count|wall_time_s|cpu_time_s|queue_wait_s|max_queue_wait_s|peak_allocation_bytes)"\s*:\s*[^"]*"#,
+    )
+    .expect("invalid normalize regex");
 
     let content = normalize_re
         .replace_all(&content, |caps: &regex::Captures| {
@@ -636,7 +683,18 @@ pub fn normalize_progress_content(content: &str) -> String {
         .into_owned()
 }
 
-fn find_output_files(dir: &Path, prefix: &str) -> Result<Vec<std::path::PathBuf>> {
+fn find_output_files(dir: &Path, prefixes: &[String]) -> Result<Vec<std::path::PathBuf>> {
+    let mut files = Vec::new();
+    for prefix in prefixes {
+        files.extend(find_output_files_one_prefix(dir, prefix)?);
+    }
+    // prefixes may overlap ('out' and 'out_b')
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+fn find_output_files_one_prefix(dir: &Path, prefix: &str) -> Result<Vec<std::path::PathBuf>> {
     let mut files = Vec::new();
     let full = dir.join(prefix);
     let dir = full
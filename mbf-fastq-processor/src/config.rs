@@ -14,14 +14,19 @@ use std::collections::{BTreeMap, HashSet};
 use std::path::Path;
 use toml_pretty_deser::prelude::*;
 
+mod branches;
 pub mod deser;
+mod expand;
 mod input;
 pub mod options;
 mod output;
+mod qc_thresholds;
 mod segments;
 
 use crate::{dna, get_number_of_cores};
+pub use branches::{BranchConfig, split_branches};
 pub use deser::{offer_alternatives, validate_tag_name};
+pub use expand::{ExpandedConfig, expand_config};
 pub use input::{
     CompressionFormat, FileFormat, Input, InputOptions, PartialInput, PartialInputOptions,
     STDIN_MAGIC_PATH, StructuredInput,
@@ -29,6 +34,7 @@ pub use input::{
 pub use io::fileformats::PhredEncoding;
 pub use options::{Options, PartialOptions};
 pub use output::{Output, PartialOutput, validate_compression_level_u8};
+pub use qc_thresholds::{PartialQcThreshold, PartialQcThresholds, QcThreshold, QcThresholds};
 pub use segments::{
     ResolvedSourceAll, ResolvedSourceNoAll, SegmentIndex, SegmentIndexOrAll, SegmentOrNameIndex,
     ValidateSegment,
@@ -39,6 +45,8 @@ pub struct TagMetadata {
     pub used: bool,
     pub tag_type: TagValueType,
     pub span: std::ops::Range<usize>,
+    /// see `DeclaredTag::categories`
+    pub categories: Option<Vec<String>>,
 }
 
 pub fn config_from_string(toml: &str) -> Result<Config, DeserError<PartialConfig>> {
@@ -108,10 +116,35 @@ struct InputFormatsObserved {
     gzip: bool,
 }
 
+/// How `Config::inner_check` treats the configured input files
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum InputFileCheck {
+    /// open them, detect their formats
+    Files,
+    /// validation mode - they may not exist
+    Skip,
+    /// reads are supplied by the caller (library use), as (optionally compressed) FASTQ
+    InMemory,
+}
+
 #[derive(Debug)]
 pub struct Stage {
     pub transformation: Transformation,
     pub allowed_tags: Vec<TagLabel>,
+    /// 1-based number of the user's [[step]] this stage was expanded from.
+    /// None for stages we added on our own (e.g. spot checks)
+    pub step_no: Option<usize>,
+    pub tags: StageTags,
+}
+
+/// What a stage does with tags - recorded while verifying the tag flow,
+/// used by `explain`
+#[derive(Debug, Clone, Default)]
+pub struct StageTags {
+    pub declared: Vec<(TagLabel, TagValueType)>,
+    /// the tags this stage reads (not just the ones it must keep up to date)
+    pub used: Vec<TagLabel>,
+    pub removed: Vec<TagLabel>,
 }
 
 #[derive(JsonSchema)]
@@ -139,11 +172,23 @@ pub struct Config {
     #[tpd(nested)]
     pub benchmark: Option<Benchmark>,
 
+    /// pass/warn/fail limits on report metrics
+    #[tpd(nested)]
+    pub qc_thresholds: Option<QcThresholds>,
+
     #[tpd(skip)]
     pub report_labels: Vec<String>,
 
     #[tpd(skip)]
     pub allowed_tags_per_transformation: Vec<Vec<TagLabel>>,
+
+    /// `Stage::tags` per expanded transformation
+    #[tpd(skip)]
+    pub tags_per_transformation: Vec<StageTags>,
+
+    /// `Stage::step_no` per expanded transformation
+    #[tpd(skip)]
+    pub step_numbers: Vec<Option<usize>>,
 }
 
 #[derive(Debug)]
@@ -154,6 +199,7 @@ pub struct CheckedConfig {
     pub options: Options,
     pub barcodes: IndexMap<TagLabel, Barcodes>,
     pub benchmark: Option<Benchmark>,
+    pub qc_thresholds: Option<QcThresholds>,
     pub report_labels: Vec<String>,
 }
 
@@ -177,6 +223,7 @@ impl VerifyIn<TPDRoot> for PartialConfig {
                 options::default_spot_check_read_pairing(),
                 0..0,
             ),
+            memory_limit: TomlValue::new_ok(None, 0..0),
             debug_failures: TomlValue::new_ok(
                 options::PartialFailureOptions {
                     fail_output_after_bytes: TomlValue::new_ok(None, 0..0),
@@ -194,6 +241,8 @@ impl VerifyIn<TPDRoot> for PartialConfig {
         self.verify_no_duplicate_files_no_empty_segments();
         self.transform.or_default();
         self.verify_reports();
+        self.verify_report_compare();
+        self.verify_qc_thresholds();
         self.verify_barcodes();
         self.verify_barcodes_and_segment_names_disjoint();
         self.verify_benchmark_molecule_count();
@@ -270,6 +319,13 @@ impl PartialConfig {
             .and_then(|x| x.as_ref())
             .and_then(|x| x.report_json.as_ref())
             .is_some_and(|o| *o);
+        // the funnel is report content of its own
+        let report_funnel = self
+            .output
+            .as_ref()
+            .and_then(|x| x.as_ref())
+            .and_then(|x| x.report_funnel.as_ref())
+            .is_some_and(|o| *o);
         let is_benchmark = self
             .benchmark
             .as_ref()
@@ -308,7 +364,11 @@ impl PartialConfig {
             report_transform.state = TomlValueState::Custom { spans };
             report_transform.help =
                 Some("Either remove the report, or enable it's output.".to_string());
-        } else if (report_html || report_json) && report_transform.is_none() && transforms_ok {
+        } else if (report_html || report_json)
+            && report_transform.is_none()
+            && !report_funnel
+            && transforms_ok
+        {
             let mut spans = Vec::new();
             if let Some(tv_report_html) = self
                 .output
@@ -391,6 +451,84 @@ impl PartialConfig {
         }
     }
 
+    /// Names of the (successfully parsed) Report steps
+    fn report_names(&self) -> Vec<String> {
+        self.transform
+            .as_ref()
+            .map(|transforms| {
+                transforms
+                    .iter()
+                    .filter_map(|t| match t.as_ref() {
+                        Some(PartialTransformation::Report(config)) => config
+                            .toml_value
+                            .as_ref()
+                            .and_then(|x| x.name.as_ref())
+                            .cloned(),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn verify_report_compare(&mut self) {
+        let report_names = self.report_names();
+        if let Some(Some(output)) = self.output.as_mut()
+            && let Some(Some(compare)) = output.report_compare.as_mut()
+        {
+            if compare.len() != 2 {
+                output.report_compare.state = TomlValueState::new_validation_failed(
+                    "report_compare needs exactly two report names",
+                );
+                output.report_compare.help = Some(
+                    "Use report_compare = ['before', 'after'] with the names of two Report steps."
+                        .to_string(),
+                );
+                return;
+            }
+            let mut seen = HashSet::new();
+            for tv_name in compare.iter_mut() {
+                if let Some(name) = tv_name.as_ref() {
+                    if !report_names.contains(name) {
+                        tv_name.help = Some(offer_alternatives(name, &report_names));
+                        tv_name.state =
+                            TomlValueState::new_validation_failed("No Report step with this name");
+                    } else if !seen.insert(name.clone()) {
+                        tv_name.state = TomlValueState::new_validation_failed(
+                            "Can not compare a report to itself",
+                        );
+                        tv_name.help = Some("Name two different Report steps.".to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Every metric must start with the name of a Report step
+    fn verify_qc_thresholds(&mut self) {
+        let report_names = self.report_names();
+        if let Some(Some(qc_thresholds)) = self.qc_thresholds.as_mut()
+            && let Some(checks) = qc_thresholds.check.as_mut()
+        {
+            for check in checks.iter_mut().filter_map(|check| check.as_mut()) {
+                if let Some(metric) = check.metric.as_ref() {
+                    let report_name = metric.split('.').next().unwrap_or_default();
+                    if !metric.contains('.') {
+                        check.metric.state = TomlValueState::new_validation_failed(
+                            "Metric must be 'report_name.path.to.value'",
+                        );
+                        check.metric.help =
+                            Some("For example 'report.read1.q30_fraction'".to_string());
+                    } else if !report_names.iter().any(|name| name == report_name) {
+                        check.metric.help = Some(offer_alternatives(report_name, &report_names));
+                        check.metric.state =
+                            TomlValueState::new_validation_failed("No Report step with this name");
+                    }
+                }
+            }
+        }
+    }
+
     fn verify_benchmark_molecule_count(&mut self) {
         if let Some(Some(benchmark)) = self.benchmark.as_mut() {
             benchmark.molecule_count.verify(|v| {
@@ -573,13 +711,17 @@ impl PartialConfig {
                     report_html: TomlValue::new_ok(false, 0..0),
                     report_json: TomlValue::new_ok(false, 0..0),
                     report_timing: TomlValue::new_ok(false, 0..0),
+                    report_compare: TomlValue::new_ok(None, 0..0),
+                    report_funnel: TomlValue::new_ok(false, 0..0),
                     stdout: TomlValue::new_ok(false, 0..0),
                     interleave: TomlValue::new_ok(None, 0..0),
                     output: TomlValue::new_ok(Some(Vec::new()), 0..0),
+                    rejected_output: TomlValue::new_ok(false, 0..0),
                     output_hash_uncompressed: TomlValue::new_ok(false, 0..0),
                     output_hash_compressed: TomlValue::new_ok(false, 0..0),
                     ix_separator: TomlValue::new_ok(output::default_ix_separator(), 0..0),
                     chunksize: TomlValue::new_ok(None, 0..0),
+                    checkpoint_interval: TomlValue::new_ok(None, 0..0),
                 }),
                 0..0,
             );
@@ -685,8 +827,9 @@ impl PartialConfig {
                 };
 
                 self.expand_spot_checks(&mut push_new, &transforms);
+                let mut step_numbers = vec![None; expanded_transforms.borrow().len()];
 
-                for mut t in transforms.drain(..) {
+                for (step_no, mut t) in transforms.drain(..).enumerate() {
                     match t.as_mut().expect("parent was ok") {
                         PartialTransformation::ExtractRegion(step_config) => {
                             let tag_span = step_config.tag_span.clone();
@@ -714,6 +857,7 @@ impl PartialConfig {
                                             out_label: step_config.out_label,
                                             regions,
                                             // region_separator: None,
+                                            if_tag: step_config.if_tag,
                                             output_tag_type: None,
                                         },
                                     ),
@@ -770,6 +914,7 @@ impl PartialConfig {
                                                 true,
                                                 BString::from("GC"),
                                                 BString::from("N"),
+                                                step_config.if_tag,
                                             ),
                                         ),
                                         tag_span,
@@ -789,6 +934,7 @@ impl PartialConfig {
                                                 false,
                                                 BString::from("N"),
                                                 BString::default(),
+                                                step_config.if_tag,
                                             ),
                                         ),
                                         tag_span,
@@ -811,6 +957,7 @@ impl PartialConfig {
                                                 length_tag_label.clone(),
                                             ),
                                             segment: step_config.segment,
+                                            if_tag: TomlValue::new_ok_unplaced(None),
                                         },
                                     ),
                                     tag_span: tag_span.clone(),
@@ -827,6 +974,7 @@ impl PartialConfig {
                                                 keep_or_remove: TomlValue::new_ok_unplaced(
                                                     crate::transformations::KeepOrRemove::Keep,
                                                 ),
+                                                if_tag: step_config.if_tag,
                                             },
                                         ),
                                         tag_span,
@@ -909,10 +1057,12 @@ impl PartialConfig {
                             push_existing(t);
                         }
                     }
+                    step_numbers.resize(expanded_transforms.borrow().len(), Some(step_no + 1));
                 }
                 self.transform =
                     TomlValue::new_ok(expanded_transforms.into_inner(), transform_span);
                 self.report_labels = Some(res_report_labels);
+                self.step_numbers = Some(step_numbers);
             } else {
                 unreachable!()
             }
@@ -990,7 +1140,7 @@ impl PartialConfig {
             if let Some(true) = config.count.as_ref() {
                 push_new(PartialTransformation::_ReportCount(PartialTaggedVariant {
                     toml_value: TomlValue::new_ok_unplaced(Box::new(
-                        reports::Partial_ReportCount::new(*report_no),
+                        reports::Partial_ReportCount::new(*report_no, config.if_tag.clone()),
                     )),
                     tag_span: 0..0,
                 }));
@@ -999,7 +1149,10 @@ impl PartialConfig {
                 push_new(PartialTransformation::_ReportLengthDistribution(
                     PartialTaggedVariant {
                         toml_value: TomlValue::new_ok_unplaced(Box::new(
-                            reports::Partial_ReportLengthDistribution::new(*report_no),
+                            reports::Partial_ReportLengthDistribution::new(
+                                *report_no,
+                                config.if_tag.clone(),
+                            ),
                         )),
                         tag_span: 0..0,
                     },
@@ -1012,6 +1165,7 @@ impl PartialConfig {
                             reports::Partial_ReportDuplicateCount::new(
                                 *report_no,
                                 config.debug_reproducibility.clone(),
+                                config.if_tag.clone(),
                             ),
                         )),
                         tag_span: 0..0,
@@ -1026,6 +1180,7 @@ impl PartialConfig {
                             reports::Partial_ReportDuplicateFragmentCount::new(
                                 *report_no,
                                 config.debug_reproducibility.clone(),
+                                config.if_tag.clone(),
                             ),
                         )),
                         tag_span: 0..0,
@@ -1036,7 +1191,10 @@ impl PartialConfig {
                 push_new(PartialTransformation::_ReportBaseStatisticsPart1(
                     PartialTaggedVariant {
                         toml_value: TomlValue::new_ok_unplaced(Box::new(
-                            reports::Partial_ReportBaseStatisticsPart1::new(*report_no),
+                            reports::Partial_ReportBaseStatisticsPart1::new(
+                                *report_no,
+                                config.if_tag.clone(),
+                            ),
                         )),
                         tag_span: 0..0,
                     },
@@ -1044,7 +1202,10 @@ impl PartialConfig {
                 push_new(PartialTransformation::_ReportBaseStatisticsPart2(
                     PartialTaggedVariant {
                         toml_value: TomlValue::new_ok_unplaced(Box::new(
-                            reports::Partial_ReportBaseStatisticsPart2::new(*report_no),
+                            reports::Partial_ReportBaseStatisticsPart2::new(
+                                *report_no,
+                                config.if_tag.clone(),
+                            ),
                         )),
                         tag_span: 0..0,
                     },
@@ -1062,6 +1223,26 @@ impl PartialConfig {
                                     .map(|x| x.0)
                                     .collect(),
                                 config.count_oligos_segment.clone(),
+                                config.if_tag.clone(),
+                            ),
+                        )),
+                        tag_span: 0..0,
+                    },
+                ));
+            }
+            if let Some(Some(k)) = config.kmer_enrichment.as_ref() {
+                push_new(PartialTransformation::_ReportKmerEnrichment(
+                    PartialTaggedVariant {
+                        toml_value: TomlValue::new_ok_unplaced(Box::new(
+                            reports::Partial_ReportKmerEnrichment::new(
+                                *report_no,
+                                *k,
+                                *config
+                                    .kmer_enrichment_top_n
+                                    .as_ref()
+                                    .expect("parent was ok"),
+                                config.kmer_enrichment_segment.clone(),
+                                config.if_tag.clone(),
                             ),
                         )),
                         tag_span: 0..0,
@@ -1076,6 +1257,7 @@ impl PartialConfig {
                                 reports::Partial_ReportTagHistogram::new(
                                     *report_no,
                                     tag_name.clone(),
+                                    config.if_tag.clone(),
                                 ),
                             )),
                             tag_span: 0..0,
@@ -1090,6 +1272,7 @@ impl PartialConfig {
     pub fn verify_transformation_labels(&mut self) {
         use crate::transformations::TagUser;
         let mut allowed_tags_per_stage: Vec<Vec<TagLabel>> = Vec::new();
+        let mut tags_per_stage: Vec<StageTags> = Vec::new();
         let mut tags_available: IndexMap<TagLabel, TagMetadata> = IndexMap::new();
         if let Some(transformations) = self.transform.as_mut()
             && let Some(input) = self.input.as_ref()
@@ -1109,12 +1292,14 @@ impl PartialConfig {
                 //         continue; // skip further processing of this transform if validation failed
                 //     }
                 let tag_info = trafo.get_tag_usage(&tags_available, segment_order);
+                let mut stage_tags = StageTags::default();
                 match tag_info.removed_tags {
                     crate::transformations::RemovedTags::None => {}
                     crate::transformations::RemovedTags::All => {
                         for metadata in tags_available.values_mut() {
                             metadata.used = true;
                         }
+                        stage_tags.removed.extend(tags_available.keys().cloned());
                         tags_available.clear();
                     }
                     crate::transformations::RemovedTags::Some(tags) => {
@@ -1134,6 +1319,7 @@ impl PartialConfig {
                                 continue; //no point on doing anything else with this tag
                             }
                             tags_available.shift_remove(&tag_name);
+                            stage_tags.removed.push(tag_name);
                         }
                     }
                 }
@@ -1155,6 +1341,7 @@ impl PartialConfig {
                                 .iter()
                                 .any(|tag_type| tag_type.compatible(metadata.tag_type))
                             {
+                                stage_tags.used.push(tag_name.clone());
                                 if !tag_info.must_see_all_tags {
                                     //otherwise, we already have the tag in the list.
                                     if tags_used_here.contains(tag_name) {
@@ -1207,7 +1394,11 @@ impl PartialConfig {
                 }
                 allowed_tags_per_stage.push(tags_used_here);
 
-                if let Some(dt) = tag_info.declared_tag {
+                for dt in tag_info
+                    .declared_tag
+                    .into_iter()
+                    .chain(tag_info.declared_tags)
+                {
                     if let Some(meta) = tags_available.get(&dt.name) {
                         any_tag_errors = true;
                         let spans = vec![
@@ -1222,6 +1413,7 @@ impl PartialConfig {
                             "Rename either tag, or add a ForgetTag step inbetween.".to_string(),
                         );
                     } else {
+                        stage_tags.declared.push((dt.name.clone(), dt.tag_type));
                         all_tags_ever.insert(dt.name.0.clone(), dt.toml_source_span.clone());
                         tags_available.insert(
                             dt.name.clone(),
@@ -1229,12 +1421,15 @@ impl PartialConfig {
                                 used: false,
                                 tag_type: dt.tag_type,
                                 span: dt.toml_source_span,
+                                categories: dt.categories,
                             },
                         );
                     }
                 }
+                tags_per_stage.push(stage_tags);
             }
             self.allowed_tags_per_transformation = Some(allowed_tags_per_stage);
+            self.tags_per_transformation = Some(tags_per_stage);
 
             //now verify the tags don't overlap barcodes or segments
             if let Some(Some(barcodes)) = self.barcodes.as_mut() {
@@ -1344,10 +1539,16 @@ impl Config {
     /// so we can accurately check the names
     #[allow(clippy::too_many_lines)]
     pub fn check(self) -> Result<CheckedConfig> {
-        self.inner_check(true)
+        self.inner_check(InputFileCheck::Files)
     }
 
-    fn inner_check(mut self, check_input_files_exist: bool) -> Result<CheckedConfig> {
+    /// Check configuration when the reads are supplied in-process
+    /// (see [`crate::api::Pipeline::with_input_reader`]) - the input files are never opened.
+    pub fn check_with_memory_input(self) -> Result<CheckedConfig> {
+        self.inner_check(InputFileCheck::InMemory)
+    }
+
+    fn inner_check(mut self, input_file_check: InputFileCheck) -> Result<CheckedConfig> {
         let mut errors = Vec::new();
         let mut stages = None;
 
@@ -1357,9 +1558,15 @@ impl Config {
             let stages_ = self.check_transformations(&mut errors);
             //self.transfrom is now empty, the trafos have been expanded into stepsk.
             assert!(self.transform.is_empty());
-            if check_input_files_exist {
-                let input_formats_observed = self.check_input_format(&mut errors);
-                self.configure_multithreading(&input_formats_observed);
+            match input_file_check {
+                InputFileCheck::Files => {
+                    let input_formats_observed = self.check_input_format(&mut errors);
+                    self.configure_multithreading(&input_formats_observed);
+                }
+                InputFileCheck::InMemory => {
+                    self.configure_multithreading(&InputFormatsObserved::default());
+                }
+                InputFileCheck::Skip => {}
             }
             stages = Some(stages_);
         }
@@ -1386,6 +1593,7 @@ impl Config {
             options: self.options,
             barcodes: self.barcodes.unwrap_or_default(),
             benchmark: self.benchmark,
+            qc_thresholds: self.qc_thresholds,
             report_labels: self.report_labels,
         })
     }
@@ -1393,7 +1601,7 @@ impl Config {
     /// Check configuration for validation mode (allows missing input files)
     #[allow(clippy::too_many_lines)]
     pub fn check_for_validation(self) -> Result<CheckedConfig> {
-        self.inner_check(false)
+        self.inner_check(InputFileCheck::Skip)
     }
 
     #[allow(clippy::similar_names)]
@@ -1534,15 +1742,21 @@ impl Config {
     #[allow(clippy::too_many_lines)]
     fn check_transformations(&mut self, _errors: &mut Vec<anyhow::Error>) -> Vec<Stage> {
         let allowed_tags_per_stage = self.allowed_tags_per_transformation.clone();
+        let step_numbers = std::mem::take(&mut self.step_numbers);
+        let tags_per_stage = std::mem::take(&mut self.tags_per_transformation);
 
         let stages: Vec<Stage> = self
             .transform
             .drain(..)
             .zip(allowed_tags_per_stage)
-            .filter(|(t, _)| !matches!(t, Transformation::Report { .. }))
-            .map(|(t, tags)| Stage {
+            .zip(step_numbers.into_iter().chain(std::iter::repeat(None)))
+            .zip(tags_per_stage)
+            .filter(|(((t, _), _), _)| !matches!(t, Transformation::Report { .. }))
+            .map(|(((t, allowed_tags), step_no), tags)| Stage {
                 transformation: t,
-                allowed_tags: tags.into_iter().collect(),
+                allowed_tags: allowed_tags.into_iter().collect(),
+                step_no,
+                tags,
             })
             .collect();
 
@@ -1595,6 +1809,34 @@ impl CheckedConfig {
             .as_ref()
             .map_or_else(output::default_ix_separator, |x| x.ix_separator.clone())
     }
+
+    /// Steps whose state `options.memory_limit` does not bound -
+    /// the limit is only shared among the other steps.
+    #[must_use]
+    pub fn memory_limit_warnings(&self) -> Vec<String> {
+        if self.options.memory_limit.is_none() {
+            return Vec::new();
+        }
+        self.stages
+            .iter()
+            .filter(|stage| stage.transformation.ignores_memory_limit())
+            .map(|stage| {
+                let action = stage.transformation.action_name();
+                // the duplicate counts are expanded from a Report step
+                let action = if action.starts_with("_Report") {
+                    "Report"
+                } else {
+                    action
+                };
+                let step = stage
+                    .step_no
+                    .map_or_else(|| action.to_string(), |no| format!("Step {no} ({action})"));
+                format!(
+                    "{step}: keeps its state in memory, which options.memory_limit does not bound. The memory used may exceed the limit (see the 'Memory limit' section of the options documentation)."
+                )
+            })
+            .collect()
+    }
 }
 
 fn calculate_thread_counts(
@@ -0,0 +1,286 @@
+//! `[[branch]]` sections: one input, several independent step chains, each with its own output.
+//!
+//! Every branch becomes a complete configuration of its own - all top level sections,
+//! the common `[[step]]`s, followed by the branch's steps and its `[branch.output]` -
+//! which is then parsed & verified like any other configuration.
+//! It is cut from the configuration's text line by line (other branches blanked,
+//! `[[branch.step]]` turned into `[[step]]`), so error line numbers match the user's file.
+//! At runtime, the input is read and the common steps are run only once,
+//! every block they finish is handed to each branch.
+use anyhow::{Result, bail};
+use std::collections::HashMap;
+use toml_edit::{DocumentMut, Item, Table};
+
+pub struct BranchConfig {
+    pub name: String,
+    /// the complete configuration (TOML) for this branch - line by line
+    /// like the configuration it was split from
+    pub toml: String,
+    /// how many of the steps are the common ones
+    pub common_step_count: usize,
+}
+
+fn find_key(table: &Table, name: &str) -> Option<String> {
+    table
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(key, _)| key.to_string())
+}
+
+/// Split a configuration with `[[branch]]` sections into one configuration per branch.
+///
+/// Returns None if there are no branches (or the TOML doesn't parse -
+/// the regular parser produces the better error message for that).
+pub fn split_branches(raw_config: &str) -> Result<Option<Vec<BranchConfig>>> {
+    let Ok(mut doc) = raw_config.parse::<DocumentMut>() else {
+        return Ok(None);
+    };
+    let Some(branch_key) = find_key(doc.as_table(), "branch") else {
+        return Ok(None);
+    };
+    let branches = doc.remove(&branch_key).expect("key was just found");
+    let Item::ArrayOfTables(branches) = branches else {
+        bail!("'branch' must be a list of tables ([[branch]] sections)");
+    };
+    if branches.is_empty() {
+        bail!("[[branch]]: at least one branch is required");
+    }
+    if let Some(output_key) = find_key(doc.as_table(), "output") {
+        bail!(
+            "[{output_key}] can not be used together with [[branch]] - every branch has its own [branch.output]"
+        );
+    }
+    if let Some(benchmark_key) = find_key(doc.as_table(), "benchmark") {
+        bail!("[{benchmark_key}] can not be used together with [[branch]]");
+    }
+    let step_key = find_key(doc.as_table(), "step")
+        .or_else(|| find_key(doc.as_table(), "transform"))
+        .unwrap_or_else(|| "step".to_string());
+    let common_step_count = match doc.get(&step_key) {
+        None => 0,
+        Some(Item::ArrayOfTables(steps)) => steps.len(),
+        Some(_) => return Ok(None), // let the regular parser complain
+    };
+
+    let mut names: Vec<String> = Vec::new();
+    let mut seen_prefixes: HashMap<String, String> = HashMap::new();
+    for branch in branches.iter() {
+        let mut name = None;
+        let mut output = None;
+        for (key, value) in branch {
+            if key.eq_ignore_ascii_case("name") {
+                match value.as_str() {
+                    Some(value) => name = Some(value.to_string()),
+                    None => bail!("[[branch]]: 'name' must be a string"),
+                }
+            } else if key.eq_ignore_ascii_case("step") || key.eq_ignore_ascii_case("transform") {
+                if !value.is_array_of_tables() {
+                    bail!("[[branch]]: steps must be [[branch.step]] sections");
+                }
+            } else if key.eq_ignore_ascii_case("output") {
+                // we cut the branches from the text by their section headers
+                match value {
+                    Item::Table(table) if !table.is_dotted() => output = Some(table),
+                    _ => bail!("[[branch]]: 'output' must be a [branch.output] section"),
+                }
+            } else {
+                bail!(
+                    "[[branch]]: unknown key '{key}'. Valid are: 'name', [[branch.step]] and [branch.output]"
+                );
+            }
+        }
+        let Some(name) = name else {
+            bail!("[[branch]]: every branch needs a 'name'");
+        };
+        if name.is_empty() || name.contains(['/', '\\']) {
+            bail!("[[branch]]: invalid name '{name}' - must not be empty or contain '/' or '\\'");
+        }
+        if names.contains(&name) {
+            bail!("[[branch]]: duplicate branch name '{name}'");
+        }
+        let Some(output) = output else {
+            bail!("[[branch]] '{name}': needs an [branch.output] section");
+        };
+        if let Some(prefix) = find_key(output, "prefix")
+            .and_then(|prefix_key| output.get(&prefix_key))
+            .and_then(Item::as_str)
+            && let Some(other) = seen_prefixes.insert(prefix.to_string(), name.clone())
+        {
+            bail!(
+                "[[branch]] '{name}': output prefix '{prefix}' is already used by branch '{other}'"
+            );
+        }
+        names.push(name);
+    }
+
+    let lines: Vec<&str> = raw_config.lines().collect();
+    let headers = super::expand::headers(&lines);
+    let mut res = Vec::new();
+    for (branch_no, name) in names.into_iter().enumerate() {
+        res.push(BranchConfig {
+            name,
+            toml: branch_toml(&lines, &headers, branch_no, &step_key)?,
+            common_step_count,
+        });
+    }
+    Ok(Some(res))
+}
+
+/// One branch's configuration: everything outside of `[[branch]]` sections,
+/// and this branch's `[branch.*]` sections without the `branch.`.
+/// Every other line is left empty, so the line numbers stay the same.
+fn branch_toml(
+    lines: &[&str],
+    headers: &[Option<(bool, String)>],
+    branch_no: usize,
+    step_key: &str,
+) -> Result<String> {
+    let mut toml = String::new();
+    let mut current_branch: Option<usize> = None;
+    let mut keep = true;
+    for (line, header) in lines.iter().zip(headers) {
+        let mut line = (*line).to_string();
+        if let Some((is_array, header_name)) = header {
+            let parts: Vec<&str> = header_name
+                .split('.')
+                .map(|part| part.trim().trim_matches(['"', '\'']))
+                .collect();
+            let section = parts[0];
+            if section != "branch" {
+                if current_branch.is_some() && (section == "step" || section == "transform") {
+                    bail!(
+                        "[[{section}]] sections must come before the first [[branch]] - they are shared by all branches"
+                    );
+                }
+                keep = true;
+            } else if parts.len() == 1 {
+                current_branch = Some(current_branch.map_or(0, |no| no + 1));
+                keep = false;
+            } else if current_branch == Some(branch_no) {
+                keep = true;
+                line = if *is_array && matches!(parts[1..], ["step" | "transform"]) {
+                    let indent = &line[..line.len() - line.trim_start().len()];
+                    format!("{indent}[[{step_key}]]")
+                } else {
+                    strip_branch_from_header(&line)
+                        .expect("parse_header saw a branch.* header in this line")
+                };
+            } else {
+                keep = false;
+            }
+        }
+        if keep {
+            toml.push_str(&line);
+        }
+        toml.push('\n');
+    }
+    Ok(toml)
+}
+
+/// `[branch.output]` -> `[output]`, keeping the rest of the line
+fn strip_branch_from_header(line: &str) -> Option<String> {
+    let rest = line.trim_start();
+    let indent = &line[..line.len() - rest.len()];
+    let brackets = if rest.starts_with("[[") { "[[" } else { "[" };
+    let rest = rest.strip_prefix(brackets)?.trim_start();
+    let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'');
+    let rest = match quote {
+        Some(quote) => rest.strip_prefix(quote)?,
+        None => rest,
+    };
+    if !rest.get(.."branch".len())?.eq_ignore_ascii_case("branch") {
+        return None;
+    }
+    let rest = &rest["branch".len()..];
+    let rest = match quote {
+        Some(quote) => rest.strip_prefix(quote)?,
+        None => rest,
+    };
+    let rest = rest.trim_start().strip_prefix('.')?.trim_start();
+    Some(format!("{indent}{brackets}{rest}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_branches() {
+        let raw = r#"
+[input]
+    read1 = 'a.fq'
+
+[[step]]
+    action = 'CutStart'
+    n = 1
+    segment = 'read1'
+
+[[branch]]
+    name = 'short'
+    [[branch.step]]
+        action = 'Head'
+        n = 2
+    [branch.output]
+        prefix = 'short'
+
+[[branch]]
+    name = 'all'
+    [branch.output]
+        prefix = 'all'
+"#;
+        let branches = split_branches(raw)
+            .expect("valid branches")
+            .expect("has branches");
+        assert_eq!(branches.len(), 2);
+        assert_eq!(branches[0].name, "short");
+        let short: DocumentMut = branches[0].toml.parse().expect("valid toml");
+        let steps = short["step"].as_array_of_tables().expect("steps");
+        assert_eq!(steps.len(), 2);
+        assert_eq!(
+            steps.get(0).expect("step")["action"].as_str(),
+            Some("CutStart")
+        );
+        assert_eq!(steps.get(1).expect("step")["action"].as_str(), Some("Head"));
+        assert_eq!(short["output"]["prefix"].as_str(), Some("short"));
+        assert!(short.get("branch").is_none());
+        let all: DocumentMut = branches[1].toml.parse().expect("valid toml");
+        assert_eq!(all["step"].as_array_of_tables().expect("steps").len(), 1);
+        assert_eq!(all["input"]["read1"].as_str(), Some("a.fq"));
+        assert_eq!(branches[0].common_step_count, 1);
+
+        // line numbers match the original
+        let raw_lines: Vec<&str> = raw.lines().collect();
+        for branch in &branches {
+            assert_eq!(branch.toml.lines().count(), raw_lines.len());
+        }
+        let short_lines: Vec<&str> = branches[0].toml.lines().collect();
+        assert_eq!(raw_lines[11], "    [[branch.step]]");
+        assert_eq!(short_lines[11], "    [[step]]");
+        assert_eq!(short_lines[12], "        action = 'Head'");
+        assert_eq!(short_lines[14], "    [output]");
+        assert_eq!(short_lines[18], "");
+        let all_lines: Vec<&str> = branches[1].toml.lines().collect();
+        assert_eq!(all_lines[12], "");
+        assert_eq!(all_lines[19], "    [output]");
+    }
+
+    #[test]
+    fn test_split_branches_errors() {
+        assert!(
+            split_branches("[input]\nread1 = 'a.fq'\n")
+                .expect("no error")
+                .is_none()
+        );
+        let with_output =
+            "[output]\nprefix = 'x'\n[[branch]]\nname = 'a'\n[branch.output]\nprefix = 'a'\n";
+        assert!(split_branches(with_output).is_err());
+        let same_prefix = "[[branch]]\nname = 'a'\n[branch.output]\nprefix = 'x'\n[[branch]]\nname = 'b'\n[branch.output]\nprefix = 'x'\n";
+        assert!(split_branches(same_prefix).is_err());
+        let same_prefix_other_case = "[[branch]]\nname = 'a'\n[branch.output]\nprefix = 'x'\n[[branch]]\nname = 'b'\n[branch.output]\nPrefix = 'x'\n";
+        assert!(split_branches(same_prefix_other_case).is_err());
+        let inline_output = "[[branch]]\nname = 'a'\noutput = {prefix = 'a'}\n";
+        assert!(split_branches(inline_output).is_err());
+        let step_after_branch = "[[branch]]\nname = 'a'\n[branch.output]\nprefix = 'a'\n[[step]]\naction = 'Head'\nn = 1\n";
+        assert!(split_branches(step_after_branch).is_err());
+    }
+}
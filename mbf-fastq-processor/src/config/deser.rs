@@ -138,6 +138,63 @@ pub fn tpd_adapt_extract_base_or_dot(mut input: TomlValue<String>) -> TomlValue<
     })
 }
 
+/// A number of bytes: either an integer, or a string like '512M', '4G' or '1.5GiB'
+/// (binary units - K = 1024)
+#[must_use]
+pub fn tpd_adapt_memory_size(mut input: TomlValue<toml_edit::Item>) -> TomlValue<usize> {
+    let help = "Provide either a number of bytes, or a string with a unit, e.g. '512M' or '4G' (powers of 1024), or '500MB' (powers of 1000)";
+    input.try_map(|input| {
+        let bytes = match input {
+            toml_edit::Item::Value(toml_edit::Value::Integer(v)) => usize::try_from(*v.value())
+                .map_err(|_| ValidationFailure::new("Must be > 0", Some(help)))?,
+            toml_edit::Item::Value(toml_edit::Value::String(v)) => parse_memory_size(v.value())
+                .ok_or_else(|| ValidationFailure::new("Invalid memory size", Some(help)))?,
+            _ => {
+                return Err(ValidationFailure::new(
+                    "Wrong type, expected a memory size",
+                    Some(help),
+                ));
+            }
+        };
+        if bytes == 0 {
+            return Err(ValidationFailure::new("Must be > 0", Some(help)));
+        }
+        Ok(bytes)
+    })
+}
+
+#[allow(clippy::cast_precision_loss)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+fn parse_memory_size(input: &str) -> Option<usize> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let unit = unit.trim().to_ascii_uppercase();
+    // 'MB' is 10^6, 'M' and 'MiB' are 2^20
+    let (unit, base): (&str, u64) = if let Some(unit) = unit.strip_suffix("IB") {
+        (unit, 1 << 10)
+    } else if let Some(unit) = unit.strip_suffix('B') {
+        (unit, 1000)
+    } else {
+        (&unit, 1 << 10)
+    };
+    let exponent = match unit {
+        "" => 0,
+        "K" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        _ => return None,
+    };
+    let factor = base.pow(exponent);
+    let bytes = (number * factor as f64).round();
+    (bytes.is_finite() && bytes < usize::MAX as f64).then_some(bytes as usize)
+}
+
 pub(crate) fn default_comment_insert_char() -> u8 {
     b' '
 }
@@ -619,6 +676,7 @@ impl ToDeclaredTag for TomlValue<TagLabel> {
                 toml_source_state: &mut self.state,
                 toml_source_help: &mut self.help,
                 toml_source_span: span,
+                categories: None,
             })
         } else {
             None
@@ -635,6 +693,7 @@ impl ToDeclaredTag for TomlValue<Option<TagLabel>> {
                 toml_source_state: &mut self.state,
                 toml_source_help: &mut self.help,
                 toml_source_span: span,
+                categories: None,
             })
         } else {
             None
@@ -0,0 +1,586 @@
+//! `[vars]` with `${name}` interpolation, and `[[step]] include = 'file.toml'`.
+//!
+//! Works on the text, line by line: the expanded configuration keeps every line
+//! of the included files verbatim, so parser errors can be mapped back
+//! to the file & line they came from.
+use anyhow::{Context, Result, bail};
+use indexmap::IndexMap;
+use std::path::{Path, PathBuf};
+use toml_edit::{DocumentMut, Item, Value};
+
+/// Included files may include further files - up to this depth (guards against include cycles)
+const MAX_INCLUDE_DEPTH: usize = 10;
+
+pub struct ExpandedConfig {
+    /// The configuration with [vars] removed, includes spliced in and variables replaced
+    pub toml: String,
+    /// Every included file, relative to the main configuration's directory
+    pub included_files: Vec<PathBuf>,
+    /// Per line of `toml`: (index into `included_files` - None for the main configuration,
+    /// line number in that file)
+    source_lines: Vec<(Option<usize>, usize)>,
+}
+
+/// `[name]` / `[[name]]` header lines (name lowercased)
+fn parse_header(line: &str) -> Option<(bool, String)> {
+    let line = line.split('#').next().unwrap_or_default().trim();
+    let (is_array, name) = if let Some(inner) = line
+        .strip_prefix("[[")
+        .and_then(|inner| inner.strip_suffix("]]"))
+    {
+        (true, inner)
+    } else {
+        (
+            false,
+            line.strip_prefix('[')
+                .and_then(|inner| inner.strip_suffix(']'))?,
+        )
+    };
+    let name = name.trim();
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-. '\"".contains(c));
+    valid.then(|| (is_array, name.to_ascii_lowercase()))
+}
+
+/// The kind of TOML string a position is in
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum OpenString {
+    Basic,
+    Literal,
+    MultiLineBasic,
+    MultiLineLiteral,
+}
+
+/// Scan one line that starts inside `open` (a multi-line string or None).
+///
+/// Returns where the line's comment starts (if any), and the multi-line string
+/// the next line starts in.
+fn scan_line(line: &str, mut open: Option<OpenString>) -> (Option<usize>, Option<OpenString>) {
+    let bytes = line.as_bytes();
+    let quotes_at =
+        |pos: usize, quote: u8| bytes[pos..].iter().take_while(|b| **b == quote).count();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let c = bytes[pos];
+        match open {
+            None => match c {
+                b'#' => return (Some(pos), None),
+                b'"' | b'\'' => {
+                    let multi_line = quotes_at(pos, c) >= 3;
+                    open = Some(match (c, multi_line) {
+                        (b'"', false) => OpenString::Basic,
+                        (b'"', true) => OpenString::MultiLineBasic,
+                        (_, false) => OpenString::Literal,
+                        (_, true) => OpenString::MultiLineLiteral,
+                    });
+                    if multi_line {
+                        pos += 3;
+                        continue;
+                    }
+                }
+                _ => {}
+            },
+            Some(OpenString::Basic | OpenString::MultiLineBasic) if c == b'\\' => pos += 1,
+            Some(OpenString::Basic) if c == b'"' => open = None,
+            Some(OpenString::Literal) if c == b'\'' => open = None,
+            Some(OpenString::MultiLineBasic) if c == b'"' && quotes_at(pos, c) >= 3 => {
+                // up to two quotes before the closing delimiter belong to the string
+                pos += quotes_at(pos, c);
+                open = None;
+                continue;
+            }
+            Some(OpenString::MultiLineLiteral) if c == b'\'' && quotes_at(pos, c) >= 3 => {
+                pos += quotes_at(pos, c);
+                open = None;
+                continue;
+            }
+            Some(_) => {}
+        }
+        pos += 1;
+    }
+    let open = open.filter(|open| {
+        matches!(
+            open,
+            OpenString::MultiLineBasic | OpenString::MultiLineLiteral
+        )
+    });
+    (None, open)
+}
+
+/// Per line: the multi-line string it starts in - quotes are tracked across the whole file,
+/// so that `'''`/`"""` strings are not mistaken for headers, comments or code.
+fn line_states(lines: &[&str]) -> Vec<Option<OpenString>> {
+    let mut open = None;
+    lines
+        .iter()
+        .map(|line| {
+            let start = open;
+            open = scan_line(line, open).1;
+            start
+        })
+        .collect()
+}
+
+/// The header of each line, if it is one
+pub(super) fn headers(lines: &[&str]) -> Vec<Option<(bool, String)>> {
+    lines
+        .iter()
+        .zip(line_states(lines))
+        .map(|(line, open)| {
+            if open.is_none() {
+                parse_header(line)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+fn is_content_line(line: &str) -> bool {
+    let line = line.trim();
+    !line.is_empty() && !line.starts_with('#')
+}
+
+fn is_include_line(line: &str) -> bool {
+    line.trim_start()
+        .strip_prefix("include")
+        .is_some_and(|rest| rest.trim_start().starts_with('='))
+}
+
+/// Replace `${name}` by the variable's value, in strings as well as outside of them.
+/// `$${` is a literal `${`.
+///
+/// Names start with a letter or '_' - `${1}` (e.g. a regex replacement group) is left alone.
+/// Returns the unknown variable's position and name as error.
+fn interpolate(
+    code: &str,
+    vars: &IndexMap<String, String>,
+) -> std::result::Result<String, (usize, String)> {
+    let mut res = String::new();
+    let mut pos = 0;
+    while let Some(found) = code[pos..].find("${") {
+        let start = pos + found;
+        if code[..start].ends_with('$') {
+            res.push_str(&code[pos..start - 1]);
+            res.push_str("${");
+            pos = start + 2;
+            continue;
+        }
+        res.push_str(&code[pos..start]);
+        let after = &code[start + 2..];
+        let name_len = after
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+            .unwrap_or(after.len());
+        let name = &after[..name_len];
+        if !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            || !after[name_len..].starts_with('}')
+        {
+            res.push_str("${");
+            pos = start + 2;
+            continue;
+        }
+        match vars.get(name) {
+            Some(value) => res.push_str(value),
+            None => return Err((start, name.to_string())),
+        }
+        pos = start + 2 + name_len + 1;
+    }
+    res.push_str(&code[pos..]);
+    Ok(res)
+}
+
+/// Interpolate one line, leaving its comment alone
+fn interpolate_line(
+    line: &str,
+    open: Option<OpenString>,
+    vars: &IndexMap<String, String>,
+) -> std::result::Result<String, (usize, String)> {
+    let code_end = scan_line(line, open).0.unwrap_or(line.len());
+    let mut res = interpolate(&line[..code_end], vars)?;
+    res.push_str(&line[code_end..]);
+    Ok(res)
+}
+
+fn parse_vars(lines: &[&str]) -> Result<IndexMap<String, String>> {
+    let text = format!("[vars]\n{}", lines.join("\n"));
+    let doc: DocumentMut = text.parse().context("Could not parse [vars]")?;
+    let mut vars = IndexMap::new();
+    if let Some(table) = doc.get("vars").and_then(Item::as_table) {
+        for (key, item) in table {
+            let value = match item.as_value() {
+                Some(Value::String(value)) => value.value().clone(),
+                Some(Value::Integer(value)) => value.value().to_string(),
+                Some(Value::Float(value)) => value.value().to_string(),
+                Some(Value::Boolean(value)) => value.value().to_string(),
+                _ => bail!("[vars]: '{key}' must be a string, number or boolean"),
+            };
+            vars.insert(key.to_string(), value);
+        }
+    }
+    Ok(vars)
+}
+
+/// Error for an unknown variable at byte `pos` of `line`, with the placeholder underlined
+fn unknown_variable(
+    location: &str,
+    line: &str,
+    pos: usize,
+    name: &str,
+    vars: &IndexMap<String, String>,
+) -> anyhow::Error {
+    let defined = vars.keys().map(String::as_str).collect::<Vec<_>>();
+    let column = line[..pos].chars().count();
+    anyhow::anyhow!(
+        "{location}, column {}: unknown variable '${{{name}}}'. Defined in [vars]: {}\n{line}\n{}{}\n\
+        Use '$${{' for a literal '${{'.",
+        column + 1,
+        if defined.is_empty() {
+            "(none)".to_string()
+        } else {
+            defined.join(", ")
+        },
+        " ".repeat(column),
+        "^".repeat(name.len() + 3),
+    )
+}
+
+/// Expand `[vars]` and includes. Returns None if the configuration uses neither.
+///
+/// Included files are resolved relative to the including file's directory
+/// and may only contain `[[step]]` sections.
+pub fn expand_config(raw_config: &str, config_dir: &Path) -> Result<Option<ExpandedConfig>> {
+    let lines: Vec<&str> = raw_config.lines().collect();
+    let states = line_states(&lines);
+    let headers = headers(&lines);
+    let mut vars_lines = None;
+    let mut has_include = false;
+    // a placeholder without [vars] is still an (unknown variable) error
+    let mut has_placeholder = false;
+    let mut current_header = None;
+    for (ii, line) in lines.iter().enumerate() {
+        if let Some(header) = &headers[ii] {
+            if *header == (false, "vars".to_string()) {
+                let end = headers[ii + 1..]
+                    .iter()
+                    .position(Option::is_some)
+                    .map_or(lines.len(), |pos| ii + 1 + pos);
+                vars_lines = Some(&lines[ii + 1..end]);
+            }
+            current_header = Some(header);
+        } else if current_header == Some(&(true, "step".to_string()))
+            && states[ii].is_none()
+            && is_include_line(line)
+        {
+            has_include = true;
+        } else if interpolate_line(line, states[ii], &IndexMap::new()).as_deref() != Ok(line) {
+            has_placeholder = true;
+        }
+    }
+    if vars_lines.is_none() && !has_include && !has_placeholder {
+        return Ok(None);
+    }
+    let vars = match vars_lines {
+        Some(vars_lines) => parse_vars(vars_lines)?,
+        None => IndexMap::new(),
+    };
+    let mut expanded = ExpandedConfig {
+        toml: String::new(),
+        included_files: Vec::new(),
+        source_lines: Vec::new(),
+    };
+    expanded.expand_lines(&lines, None, config_dir, Path::new(""), &vars, 0)?;
+    Ok(Some(expanded))
+}
+
+impl ExpandedConfig {
+    fn file_name(&self, file: Option<usize>, main_filename: &str) -> String {
+        file.map_or_else(
+            || main_filename.to_string(),
+            |idx| self.included_files[idx].display().to_string(),
+        )
+    }
+
+    fn push_line(&mut self, line: &str, file: Option<usize>, line_no: usize) {
+        self.toml.push_str(line);
+        self.toml.push('\n');
+        self.source_lines.push((file, line_no));
+    }
+
+    /// `dir` resolves includes, `relative_dir` is the same directory
+    /// relative to the main configuration
+    fn expand_lines(
+        &mut self,
+        lines: &[&str],
+        file: Option<usize>,
+        dir: &Path,
+        relative_dir: &Path,
+        vars: &IndexMap<String, String>,
+        depth: usize,
+    ) -> Result<()> {
+        let file_label = self.file_name(file, "config.toml");
+        let location = |line_idx: usize| format!("{file_label}, line {}", line_idx + 1);
+        let states = line_states(lines);
+        let headers = headers(lines);
+        // blocks: a header line and everything up to the next header
+        let mut block_starts: Vec<usize> = headers
+            .iter()
+            .enumerate()
+            .filter(|(_, header)| header.is_some())
+            .map(|(ii, _)| ii)
+            .collect();
+        if lines.is_empty() {
+            return Ok(());
+        }
+        if block_starts.first() != Some(&0) {
+            block_starts.insert(0, 0);
+        }
+        for (block_no, start) in block_starts.iter().enumerate() {
+            let end = block_starts
+                .get(block_no + 1)
+                .copied()
+                .unwrap_or(lines.len());
+            let header = headers[*start].as_ref();
+            let is_step = header == Some(&(true, "step".to_string()));
+            if header == Some(&(false, "vars".to_string())) {
+                if file.is_some() {
+                    bail!(
+                        "{}: [vars] can only be defined in the main configuration",
+                        location(*start)
+                    );
+                }
+                continue;
+            }
+            if file.is_some() && !is_step {
+                let first_content = (*start..end).find(|ii| is_content_line(lines[*ii]));
+                if let Some(line_idx) = first_content {
+                    bail!(
+                        "{}: included files may only contain [[step]] sections",
+                        location(line_idx)
+                    );
+                }
+            }
+            let mut block = Vec::new();
+            for (line_idx, line) in lines.iter().enumerate().take(end).skip(*start) {
+                let line =
+                    interpolate_line(line, states[line_idx], vars).map_err(|(pos, name)| {
+                        unknown_variable(&location(line_idx), line, pos, &name, vars)
+                    })?;
+                block.push((line_idx, line));
+            }
+            let include_line = block.iter().find(|(line_idx, line)| {
+                is_step && states[*line_idx].is_none() && is_include_line(line)
+            });
+            let Some((include_idx, include_line)) = include_line else {
+                for (line_idx, line) in &block {
+                    self.push_line(line, file, line_idx + 1);
+                }
+                continue;
+            };
+            if let Some((line_idx, _)) = block
+                .iter()
+                .skip(1)
+                .find(|(line_idx, line)| line_idx != include_idx && is_content_line(line))
+            {
+                bail!(
+                    "{}: a [[step]] with 'include' can not contain anything else",
+                    location(*line_idx)
+                );
+            }
+            if depth >= MAX_INCLUDE_DEPTH {
+                bail!(
+                    "{}: includes nested too deeply (include cycle?)",
+                    location(*include_idx)
+                );
+            }
+            let doc: DocumentMut = include_line
+                .parse()
+                .with_context(|| format!("{}: could not parse include", location(*include_idx)))?;
+            let filenames: Vec<String> = match doc.get("include").and_then(Item::as_value) {
+                Some(Value::String(filename)) => vec![filename.value().clone()],
+                Some(Value::Array(filenames)) => filenames
+                    .iter()
+                    .map(|filename| filename.as_str().map(str::to_string))
+                    .collect::<Option<_>>()
+                    .with_context(|| {
+                        format!(
+                            "{}: include must be a file name or a list of file names",
+                            location(*include_idx)
+                        )
+                    })?,
+                _ => bail!(
+                    "{}: include must be a file name or a list of file names",
+                    location(*include_idx)
+                ),
+            };
+            for filename in filenames {
+                let path = dir.join(&filename);
+                let raw = ex::fs::read_to_string(&path).with_context(|| {
+                    format!(
+                        "{}: could not read included file {}",
+                        location(*include_idx),
+                        path.display()
+                    )
+                })?;
+                let relative_path = relative_dir.join(&filename);
+                self.included_files.push(relative_path.clone());
+                let included_idx = self.included_files.len() - 1;
+                let included_lines: Vec<&str> = raw.lines().collect();
+                let parent = Path::new(&filename).parent().unwrap_or(Path::new(""));
+                self.expand_lines(
+                    &included_lines,
+                    Some(included_idx),
+                    &dir.join(parent),
+                    &relative_dir.join(parent),
+                    vars,
+                    depth + 1,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Point the line numbers of a pretty (parser) error at the original files.
+    pub fn remap_error(&self, pretty: &str, main_filename: &str) -> String {
+        let mut out: Vec<String> = Vec::new();
+        let mut pending_header = None;
+        let mut current_file = None;
+        for line in pretty.lines() {
+            if line.contains("╭─") {
+                out.push(line.to_string());
+                pending_header = Some(out.len() - 1);
+                continue;
+            }
+            let trimmed = line.trim_start();
+            let digits = trimmed.chars().take_while(char::is_ascii_digit).count();
+            let line_no = trimmed[..digits].parse::<usize>().ok();
+            let Some((file, original_line_no)) = line_no
+                .filter(|_| trimmed[digits..].starts_with(" │"))
+                .and_then(|line_no| self.source_lines.get(line_no.wrapping_sub(1)))
+                .copied()
+            else {
+                out.push(line.to_string());
+                continue;
+            };
+            let width = line.len() - trimmed.len() + digits;
+            let file_name = self.file_name(file, main_filename);
+            if let Some(header_idx) = pending_header.take() {
+                let header = &out[header_idx];
+                let pos = header.find("╭─").expect("checked above") + "╭─".len();
+                out[header_idx] = format!("{}{file_name}", &header[..pos]);
+            } else if current_file != Some(file) {
+                out.push(format!("{}╭─{file_name}", " ".repeat(width)));
+            }
+            current_file = Some(file);
+            out.push(format!("{original_line_no:>width$}{}", &trimmed[digits..]));
+        }
+        let mut res = out.join("\n");
+        if pretty.ends_with('\n') {
+            res.push('\n');
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interpolate() {
+        let vars: IndexMap<String, String> = [("adapter", "AGATC"), ("n", "3")]
+            .iter()
+            .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+            .collect();
+        assert_eq!(
+            interpolate_line("query = '${adapter}' # ${other}", None, &vars),
+            Ok("query = 'AGATC' # ${other}".to_string())
+        );
+        assert_eq!(interpolate("n = ${n}", &vars), Ok("n = 3".to_string()));
+        assert_eq!(
+            interpolate("prefix = '${prefx}'", &vars),
+            Err((10, "prefx".to_string()))
+        );
+        // regex replacement groups & escaped placeholders
+        assert_eq!(
+            interpolate("replacement = '${1}_$${umi}_{n}'", &vars),
+            Ok("replacement = '${1}_${umi}_{n}'".to_string())
+        );
+        assert_eq!(
+            interpolate_line("a = \"#\\\"\" # ${n}", None, &vars),
+            Ok("a = \"#\\\"\" # ${n}".to_string())
+        );
+    }
+
+    #[test]
+    fn test_multi_line_strings() {
+        let lines = [
+            "a = \"\"\"",
+            "[not_a_header] # ${n}",
+            "\"\"\"\" # a comment",
+            "b = '''x",
+            "#${n}'''",
+            "c = 'x' # '''",
+            "[input]",
+        ];
+        let vars: IndexMap<String, String> = [("n".to_string(), "3".to_string())].into();
+        let states = line_states(&lines);
+        assert_eq!(
+            states,
+            vec![
+                None,
+                Some(OpenString::MultiLineBasic),
+                Some(OpenString::MultiLineBasic),
+                None,
+                Some(OpenString::MultiLineLiteral),
+                None,
+                None,
+            ]
+        );
+        assert_eq!(
+            headers(&lines)
+                .iter()
+                .map(Option::is_some)
+                .collect::<Vec<_>>(),
+            vec![false, false, false, false, false, false, true]
+        );
+        assert_eq!(
+            interpolate_line(lines[1], states[1], &vars),
+            Ok("[not_a_header] # 3".to_string())
+        );
+        assert_eq!(
+            interpolate_line(lines[4], states[4], &vars),
+            Ok("#3'''".to_string())
+        );
+    }
+
+    #[test]
+    fn test_expand_and_remap() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        std::fs::write(
+            dir.path().join("common.toml"),
+            "# shared\n[[step]]\n    action = 'CutStart'\n    n = ${n}\n",
+        )
+        .expect("write");
+        let raw = "[vars]\n    n = 2\n\n[input]\n    read1 = 'a.fq'\n\n[[step]]\n    include = 'common.toml'\n\n[output]\n    prefix = 'out'\n";
+        let expanded = expand_config(raw, dir.path())
+            .expect("expands")
+            .expect("uses vars");
+        assert_eq!(
+            expanded.toml,
+            "[input]\n    read1 = 'a.fq'\n\n# shared\n[[step]]\n    action = 'CutStart'\n    n = 2\n[output]\n    prefix = 'out'\n"
+        );
+        assert_eq!(expanded.included_files, vec![PathBuf::from("common.toml")]);
+        let pretty = "  ╭─config.toml\n  ┆\n7 │     n = 2\n  ┆         ┬\n──╯\n";
+        assert_eq!(
+            expanded.remap_error(pretty, "config.toml"),
+            "  ╭─common.toml\n  ┆\n4 │     n = 2\n  ┆         ┬\n──╯\n"
+        );
+        assert!(
+            expand_config("[input]\nread1 = 'a.fq'\n", dir.path())
+                .expect("no error")
+                .is_none()
+        );
+    }
+}
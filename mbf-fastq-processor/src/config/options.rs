@@ -1,4 +1,5 @@
 #![allow(clippy::struct_field_names)]
+use crate::config::deser::tpd_adapt_memory_size;
 use crate::config::{PartialConfig, StructuredInput};
 // FailureOptions - eserde(?) interferes with clippy here.
 use crate::io::output::compressed_output::{SimulatedWriteError, SimulatedWriteFailure};
@@ -92,6 +93,9 @@ pub struct Options {
     pub accept_duplicate_files: bool,
     //#[serde(default = "default_spot_check_read_pairing")]
     pub spot_check_read_pairing: bool,
+    /// bytes, shared among the steps whose state grows with the input
+    #[tpd(with = "tpd_adapt_memory_size")]
+    pub memory_limit: Option<usize>,
     #[tpd(nested)]
     pub debug_failures: FailureOptions,
 }
@@ -211,5 +215,33 @@ mod tests {
             config_no_options.options.spot_check_read_pairing,
             config_empty_options.options.spot_check_read_pairing
         );
+        assert_eq!(
+            config_no_options.options.memory_limit,
+            config_empty_options.options.memory_limit
+        );
+    }
+
+    #[test]
+    fn test_options_memory_limit() {
+        let parse = |memory_limit: &str| {
+            Config::tpd_from_toml(
+                &format!(
+                    "[input]\n read1 = 'test.fq'\n[options]\n memory_limit = {memory_limit}\n[output]\n prefix = 'out'"
+                ),
+                FieldMatchMode::AnyCase,
+                VecMode::SingleOk,
+            )
+            .map(|config| config.options.memory_limit)
+        };
+        assert_eq!(parse("1000").unwrap(), Some(1000));
+        assert_eq!(parse("'512K'").unwrap(), Some(512 * 1024));
+        assert_eq!(parse("'4G'").unwrap(), Some(4 << 30));
+        assert_eq!(parse("'1.5GiB'").unwrap(), Some(3 << 29));
+        assert_eq!(parse("'2 MB'").unwrap(), Some(2_000_000));
+        assert_eq!(parse("'2 MiB'").unwrap(), Some(2 << 20));
+        assert_eq!(parse("'3kb'").unwrap(), Some(3000));
+        assert!(parse("0").is_err());
+        assert!(parse("'4X'").is_err());
+        assert!(parse("true").is_err());
     }
 }
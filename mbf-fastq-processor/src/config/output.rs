@@ -32,6 +32,12 @@ pub struct Output {
     pub report_json: bool,
     #[tpd(default)]
     pub report_timing: bool,
+    /// Names of two Report steps to compare (before/after) in the json/html report
+    #[tpd(default)]
+    pub report_compare: Option<Vec<String>>,
+    /// Count reads & bases going in/out of every step into the json/html report
+    #[tpd(default)]
+    pub report_funnel: bool,
 
     #[tpd(default)]
     pub stdout: bool,
@@ -40,6 +46,9 @@ pub struct Output {
 
     #[tpd(default)]
     pub output: Option<Vec<String>>,
+    /// Write reads removed by filter steps to '{prefix}_rejected_{segment}.{suffix}'
+    #[tpd(default)]
+    pub rejected_output: bool,
 
     #[tpd(default)]
     pub output_hash_uncompressed: bool,
@@ -49,6 +58,11 @@ pub struct Output {
 
     #[tpd(default)]
     pub chunksize: Option<usize>,
+
+    /// Write a checkpoint every n seconds, so an interrupted run can be continued
+    /// with 'process --resume'
+    #[tpd(default)]
+    pub checkpoint_interval: Option<u64>,
 }
 
 impl VerifyIn<super::PartialConfig> for PartialOutput {
@@ -97,6 +111,19 @@ impl VerifyIn<super::PartialConfig> for PartialOutput {
         });
         self.ix_separator.or_with(default_ix_separator);
 
+        let any_report = self.report_json.as_ref().is_some_and(|x| *x)
+            || self.report_html.as_ref().is_some_and(|x| *x);
+        self.report_funnel.verify(|report_funnel| {
+            if *report_funnel && !any_report {
+                Err(ValidationFailure::new(
+                    "report_funnel needs a report",
+                    Some("Set report_json = true and/or report_html = true"),
+                ))
+            } else {
+                Ok(())
+            }
+        });
+
         if let Some(Some(_level)) = self.compression_level.value {
             if self
                 .compression
@@ -113,6 +140,8 @@ impl VerifyIn<super::PartialConfig> for PartialOutput {
             }
         }
         self.verify_compression_and_stdout();
+        self.verify_rejected_output();
+        self.verify_checkpoint_interval();
 
         self.verify_stdout(parent);
         self.compression.or_default();
@@ -122,6 +151,63 @@ impl VerifyIn<super::PartialConfig> for PartialOutput {
 }
 
 impl PartialOutput {
+    fn verify_rejected_output(&mut self) {
+        let stdout = self.stdout.as_ref().is_some_and(|x| *x);
+        let no_files = matches!(self.format.as_ref(), Some(FileFormat::None));
+        self.rejected_output.verify(|rejected_output| {
+            if !*rejected_output {
+                Ok(())
+            } else if stdout {
+                Err(ValidationFailure::new(
+                    "Invalid when stdout = true",
+                    Some("Rejected reads are written to files. Either remove 'rejected_output' or set 'stdout' to false"),
+                ))
+            } else if no_files {
+                Err(ValidationFailure::new(
+                    "Invalid when format = 'None'",
+                    Some("Rejected reads use the output format. Either remove 'rejected_output' or choose a format"),
+                ))
+            } else {
+                Ok(())
+            }
+        });
+    }
+
+    /// Checkpoints truncate & append to the output files - they must be plain files,
+    /// not rotated, and we can't hash them.
+    fn verify_checkpoint_interval(&mut self) {
+        let conflict = if matches!(self.format.as_ref(), Some(FileFormat::Bam)) {
+            Some(("format = 'BAM'", "Use FASTQ/FASTA output"))
+        } else if self.stdout.as_ref().is_some_and(|x| *x) {
+            Some(("stdout = true", "Write to files"))
+        } else if self.chunksize.as_ref().is_some_and(Option::is_some) {
+            Some(("chunksize", "Remove 'chunksize'"))
+        } else if self.output_hash_uncompressed.as_ref().is_some_and(|x| *x)
+            || self.output_hash_compressed.as_ref().is_some_and(|x| *x)
+        {
+            Some((
+                "output_hash_uncompressed/output_hash_compressed",
+                "Remove the output hashes",
+            ))
+        } else if self.report_funnel.as_ref().is_some_and(|x| *x) {
+            Some(("report_funnel", "Remove 'report_funnel'"))
+        } else {
+            None
+        };
+        self.checkpoint_interval.verify(|checkpoint_interval| {
+            if checkpoint_interval.is_none() {
+                Ok(())
+            } else if let Some((what, help)) = conflict {
+                Err(ValidationFailure::new(
+                    format!("Checkpoints are not supported with {what}"),
+                    Some(format!("{help}, or remove 'checkpoint_interval'")),
+                ))
+            } else {
+                Ok(())
+            }
+        });
+    }
+
     fn verify_compression_and_stdout(&mut self) {
         if let Some(FileFormat::Bam) = self.format.as_ref() {
             if *self.output_hash_uncompressed.unwrap_ref() {
@@ -325,9 +411,12 @@ impl Output {
             report_html: false,
             report_json: false,
             report_timing: false,
+            report_compare: None,
+            report_funnel: false,
             stdout: false,
             interleave: None,
             output: None,
+            rejected_output: false,
             output_hash_uncompressed: false,
             output_hash_compressed: false,
             ix_separator: default_ix_separator(),
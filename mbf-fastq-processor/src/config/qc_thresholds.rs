@@ -0,0 +1,100 @@
+use schemars::JsonSchema;
+use toml_pretty_deser::prelude::*;
+
+/// Pass/warn/fail limits on report metrics
+#[derive(Clone, JsonSchema)]
+#[tpd]
+#[derive(Debug)]
+pub struct QcThresholds {
+    /// Exit with this code (after all output has been written)
+    /// if any 'fail' threshold is hit. Unset: only mark it in the report.
+    #[tpd(default)]
+    pub fail_exit_code: Option<i32>,
+
+    #[tpd(nested)]
+    pub check: Vec<QcThreshold>,
+}
+
+#[derive(Clone, JsonSchema)]
+#[tpd]
+#[derive(Debug)]
+pub struct QcThreshold {
+    /// 'report_name.path.in.the.report', e.g. 'report.read1.q30_fraction'
+    pub metric: String,
+    #[tpd(default)]
+    pub warn_below: Option<f64>,
+    #[tpd(default)]
+    pub fail_below: Option<f64>,
+    #[tpd(default)]
+    pub warn_above: Option<f64>,
+    #[tpd(default)]
+    pub fail_above: Option<f64>,
+}
+
+impl VerifyIn<super::PartialConfig> for PartialQcThresholds {
+    fn verify(
+        &mut self,
+        _parent: &super::PartialConfig,
+        _options: &VerifyOptions,
+    ) -> Result<(), ValidationFailure>
+    where
+        Self: Sized + toml_pretty_deser::Visitor,
+    {
+        self.fail_exit_code
+            .verify(|fail_exit_code| match fail_exit_code {
+                Some(code) if !(2..=255).contains(code) => Err(ValidationFailure::new(
+                    "fail_exit_code must be between 2 and 255",
+                    Some("0 is success, 1 is used for all other errors."),
+                )),
+                _ => Ok(()),
+            });
+        self.check.verify(|checks| {
+            if checks.is_empty() {
+                Err(ValidationFailure::new(
+                    "Must contain at least one check",
+                    Some("Add [[qc_thresholds.check]] sections with a metric and limits."),
+                ))
+            } else {
+                Ok(())
+            }
+        });
+        Ok(())
+    }
+}
+
+impl<R> VerifyIn<R> for PartialQcThreshold {
+    fn verify(&mut self, _parent: &R, _options: &VerifyOptions) -> Result<(), ValidationFailure>
+    where
+        Self: Sized + toml_pretty_deser::Visitor,
+    {
+        let get = |tv: &TomlValue<Option<f64>>| tv.as_ref().copied().flatten();
+        let (warn_below, fail_below) = (get(&self.warn_below), get(&self.fail_below));
+        let (warn_above, fail_above) = (get(&self.warn_above), get(&self.fail_above));
+        if [warn_below, fail_below, warn_above, fail_above]
+            .iter()
+            .all(Option::is_none)
+        {
+            return Err(ValidationFailure::new(
+                "No threshold set",
+                Some("Set at least one of warn_below, fail_below, warn_above, fail_above."),
+            ));
+        }
+        if let (Some(warn), Some(fail)) = (warn_below, fail_below)
+            && fail > warn
+        {
+            self.fail_below.state =
+                TomlValueState::new_validation_failed("fail_below must be <= warn_below");
+            self.fail_below.help =
+                Some("Values below fail_below fail, values below warn_below warn.".to_string());
+        }
+        if let (Some(warn), Some(fail)) = (warn_above, fail_above)
+            && fail < warn
+        {
+            self.fail_above.state =
+                TomlValueState::new_validation_failed("fail_above must be >= warn_above");
+            self.fail_above.help =
+                Some("Values above fail_above fail, values above warn_above warn.".to_string());
+        }
+        Ok(())
+    }
+}
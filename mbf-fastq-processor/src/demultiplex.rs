@@ -10,7 +10,7 @@ use indexmap::IndexMap;
 
 pub type Tag = u64;
 
-#[derive(Debug)]
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct DemultiplexedData<T>(BTreeMap<Tag, T>);
 
 // explicitly not DemultiplexedData, for that is uncloneable at runtime
@@ -237,6 +237,44 @@ pub fn find_iupac(
     None
 }
 
+/// Find all non-overlapping hits of this IUPAC string, scanning left to right.
+/// A match is taken as soon as it is within `max_mismatches`, and the search
+/// continues after its end.
+pub fn find_iupac_all(
+    reference: &[u8],
+    query: &[u8],
+    max_mismatches: u8,
+    segment: SegmentIndex,
+) -> Vec<Hit> {
+    let mut hits = Vec::new();
+    if query.is_empty() || reference.len() < query.len() {
+        return hits;
+    }
+    let mut start = 0;
+    while start + query.len() <= reference.len() {
+        let end = start + query.len();
+        let hd = iupac_hamming_distance_with_limit(
+            query,
+            &reference[start..end],
+            max_mismatches as usize + 1,
+        );
+        if hd <= max_mismatches as usize {
+            hits.push(Hit {
+                location: Some(HitRegion {
+                    start,
+                    len: query.len(),
+                    segment_index: segment,
+                }),
+                sequence: reference[start..end].into(),
+            });
+            start = end;
+        } else {
+            start += 1;
+        }
+    }
+    hits
+}
+
 #[inline]
 fn iupac_alignment_score(a: u8, b: u8) -> i32 {
     if iupac_hamming_distance(&[a], &[b]) == 0 {
@@ -334,6 +372,43 @@ pub fn find_iupac_with_indel(
     ))
 }
 
+/// Find all non-overlapping hits allowing for indels, by repeatedly aligning
+/// against the remainder of the reference after the previous hit.
+pub fn find_iupac_with_indel_all(
+    reference: &[u8],
+    query: &[u8],
+    max_mismatches: usize,
+    max_indel_bases: usize,
+    max_total_edits: Option<usize>,
+    segment: SegmentIndex,
+) -> Vec<Hit> {
+    let mut hits = Vec::new();
+    let mut offset = 0;
+    while offset < reference.len() {
+        let Some(mut hit) = find_iupac_with_indel(
+            &reference[offset..],
+            query,
+            Anchor::Anywhere,
+            max_mismatches,
+            max_indel_bases,
+            max_total_edits,
+            segment,
+        )
+        .and_then(|found| found.0.into_iter().next()) else {
+            break;
+        };
+        let location = hit
+            .location
+            .as_mut()
+            .expect("find_iupac_with_indel always returns a location");
+        location.start += offset;
+        // an empty alignment must not stall us
+        offset = (location.start + location.len).max(offset + 1);
+        hits.push(hit);
+    }
+    hits
+}
+
 /// Find the best hit for this IUPAC string, on parity, earlier hits preferred.
 /// Optimized pure Rust implementation with early exit on perfect matches.
 /// Returns the start position of the best match, or None if no match within `max_mismatches`.
@@ -959,6 +1034,55 @@ mod test {
 
     use super::*;
 
+    #[test]
+    fn test_find_iupac_all() {
+        let hits = |reference: &[u8], query: &[u8], max_mismatches| {
+            super::find_iupac_all(reference, query, max_mismatches, SegmentIndex(0))
+                .into_iter()
+                .map(|hit| {
+                    let location = hit.location.expect("always set");
+                    (location.start, hit.sequence)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            hits(b"AGAATTCCCGAATTCA", b"GAATTC", 0),
+            vec![(1, "GAATTC".into()), (9, "GAATTC".into())]
+        );
+        // non-overlapping
+        assert_eq!(
+            hits(b"AAAAA", b"AA", 0),
+            vec![(0, "AA".into()), (2, "AA".into())]
+        );
+        assert_eq!(
+            hits(b"GAATTCGAGTTC", b"GAATTC", 1),
+            vec![(0, "GAATTC".into()), (6, "GAGTTC".into())]
+        );
+        assert_eq!(
+            hits(b"GAATTCGAGTTC", b"GAATTC", 0),
+            vec![(0, "GAATTC".into())]
+        );
+        assert!(hits(b"GAA", b"GAATTC", 0).is_empty());
+    }
+
+    #[test]
+    fn test_find_iupac_with_indel_all() {
+        let hits = |reference: &[u8]| {
+            super::find_iupac_with_indel_all(reference, b"AGTC", 0, 1, None, SegmentIndex(0))
+                .into_iter()
+                .map(|hit| {
+                    let location = hit.location.expect("always set");
+                    (location.start, location.len, hit.sequence)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            hits(b"AGTCCCCAGGTC"),
+            vec![(0, 4, "AGTC".into()), (7, 5, "AGGTC".into())]
+        );
+        assert!(hits(b"CCCCCC").is_empty());
+    }
+
     #[test]
     fn test_positions_compatible() {
         // Same base should be compatible
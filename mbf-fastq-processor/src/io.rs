@@ -6,6 +6,7 @@ pub mod input;
 pub mod output;
 pub mod parsers;
 pub mod reads;
+pub mod table;
 
 use crate::config::InputOptions;
 use crate::config::options::{default_block_size, default_buffer_size};
@@ -1,5 +1,6 @@
 use anyhow::{Context, Result, bail};
 use ex::Wrapper;
+use std::io::{Seek, SeekFrom};
 use std::path::PathBuf;
 use std::process::{Command, Stdio};
 use std::{fs, io::Read, path::Path};
@@ -13,6 +14,8 @@ pub enum InputFile {
     Fastq(ex::fs::File, Option<PathBuf>),
     Fasta(ex::fs::File, Option<PathBuf>),
     Bam(ex::fs::File, PathBuf),
+    /// FASTQ (optionally compressed) from any reader, e.g. an in-memory buffer
+    Reader(Box<dyn Read + Send>),
 }
 
 #[derive(Copy, Clone)]
@@ -31,6 +34,7 @@ impl InputFile {
         match self {
             InputFile::Fastq(_, filename) | InputFile::Fasta(_, filename) => filename.as_ref(),
             InputFile::Bam(_, filename) => Some(filename),
+            InputFile::Reader(_) => None,
         }
     }
 
@@ -55,12 +59,19 @@ impl InputFile {
         };
         match self {
             InputFile::Fastq(file, filename) => Ok(Box::new(parsers::FastqParser::new(
-                file.into_inner(),
+                Box::new(file.into_inner()),
                 filename.as_ref(),
                 target_reads_per_block,
                 buffer_size,
                 decompression_options,
             )?)),
+            InputFile::Reader(reader) => Ok(Box::new(parsers::FastqParser::new(
+                reader,
+                None,
+                target_reads_per_block,
+                buffer_size,
+                decompression_options,
+            )?)),
             InputFile::Fasta(file, filename) => {
                 let fake_quality = options
                     .fasta_fake_quality
@@ -93,6 +104,31 @@ impl InputFile {
             }
         }
     }
+
+    /// A parser continuing at a checkpoint's `InputPosition` -
+    /// only FASTQ files record positions.
+    pub fn get_parser_at(
+        self,
+        target_reads_per_block: usize,
+        buffer_size: usize,
+        position: &parsers::InputPosition,
+    ) -> Result<Box<dyn parsers::Parser>> {
+        let InputFile::Fastq(file, Some(filename)) = self else {
+            bail!("Can not resume this input at a byte offset - only FASTQ files.");
+        };
+        let mut file = file.into_inner();
+        file.seek(SeekFrom::Start(position.seek_to))
+            .with_context(|| format!("Could not seek in {}", filename.display()))?;
+        let parser = parsers::FastqParser::new_at(
+            Box::new(file),
+            target_reads_per_block,
+            buffer_size,
+            position.seek_to,
+            position.skip,
+        )
+        .with_context(|| format!("Could not resume reading {}", filename.display()))?;
+        Ok(Box::new(parser))
+    }
 }
 
 pub struct InputFiles {
@@ -109,6 +145,7 @@ pub fn total_file_size(readers: &Vec<InputFile>) -> Option<u64> {
             InputFile::Fastq(f, __opt_filename) => f,
             InputFile::Fasta(f, _opt_filename) => f,
             InputFile::Bam(f, _) => f,
+            InputFile::Reader(_) => return None,
         };
         match file.metadata() {
             Ok(metadata) => {
@@ -2,7 +2,7 @@ use std::path::PathBuf;
 
 use crate::config::InputOptions;
 use crate::io::{FastQBlock, InputFile};
-use anyhow::Result;
+use anyhow::{Context, Result, bail};
 
 mod bam;
 mod fasta;
@@ -20,6 +20,25 @@ pub struct ParseResult {
 pub trait Parser: Send {
     fn parse(&mut self) -> Result<ParseResult>;
     fn bytes_per_base(&self) -> f64;
+
+    /// Where the reads not yet returned by `parse` start:
+    /// (file offset to seek to, decompressed bytes to skip from there).
+    /// None if the input can not be continued at a byte offset.
+    fn resume_position(&self) -> Option<(u64, u64)> {
+        None
+    }
+}
+
+/// Where a segment's input continues - recorded in checkpoints,
+/// so `process --resume` can seek instead of reading everything again.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct InputPosition {
+    /// index into the segment's input files
+    pub file: usize,
+    /// file offset - of the read for uncompressed input, of its frame for zstd
+    pub seek_to: u64,
+    /// decompressed bytes between `seek_to` and the read
+    pub skip: u64,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -39,6 +58,8 @@ pub struct ChainedParser {
     expected_read_count: Option<usize>,
     first_block_done: bool,
     total_input_file_size: Option<u64>,
+    /// files handed to a parser so far
+    files_started: usize,
 }
 
 pub struct ChainParseResult {
@@ -93,6 +114,55 @@ impl ChainedParser {
             expected_read_count: None,
             first_block_done: false,
             total_input_file_size,
+            files_started: 0,
+        }
+    }
+
+    /// Continue where a checkpoint left off: skip the files before `position.file`,
+    /// and start reading that one at the recorded offset.
+    pub fn resume_at(&mut self, position: &InputPosition) -> Result<()> {
+        for _ in 0..position.file {
+            if self.pending.pop().is_none() {
+                bail!(
+                    "The checkpoint refers to input file {}, but there are fewer input files.",
+                    position.file + 1
+                );
+            }
+        }
+        self.files_started = position.file;
+        if position.seek_to > 0 || position.skip > 0 {
+            let file = self
+                .pending
+                .pop()
+                .context("The checkpoint refers to an input file that does not exist.")?;
+            self.current_filename = file.get_filename().cloned();
+            self.current = Some(file.get_parser_at(
+                self.target_reads_per_block,
+                self.buffer_size,
+                position,
+            )?);
+            self.files_started += 1;
+        }
+        Ok(())
+    }
+
+    /// Where the reads not yet returned by `parse` start - None if that's not a byte offset
+    #[must_use]
+    pub fn position(&self) -> Option<InputPosition> {
+        match &self.current {
+            Some(parser) => parser
+                .resume_position()
+                .map(|(seek_to, skip)| InputPosition {
+                    file: self.files_started - 1,
+                    seek_to,
+                    skip,
+                }),
+            // at the start of the next file
+            None => Some(InputPosition {
+                file: self.files_started,
+                seek_to: 0,
+                skip: 0,
+            }),
         }
     }
 
@@ -108,6 +178,7 @@ impl ChainedParser {
                         &self.options,
                     )?;
                     self.current = Some(parser);
+                    self.files_started += 1;
                 }
                 None => return Ok(false),
             }
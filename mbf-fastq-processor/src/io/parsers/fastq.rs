@@ -6,8 +6,13 @@ use crate::io::{
 use anyhow::{Context, Result, bail};
 use bstr::BString;
 use niffler;
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader};
+use std::sync::{Arc, Mutex};
 use std::{io::Read, path::PathBuf};
 
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+
 pub struct FastqParser {
     current_reader: Box<dyn Read + Send>,
     current_block: Option<FastQBlock>,
@@ -17,17 +22,138 @@ pub struct FastqParser {
     last_status: PartialStatus,
     windows_mode: Option<bool>,
     compression_format: niffler::send::compression::Format,
+    /// offset of `current_block.block[0]` in the decompressed input
+    stream_offset: u64,
+    /// offset of the read in `last_partial` in the decompressed input
+    partial_offset: u64,
+    /// offset of the first read not yet returned in the decompressed input
+    next_read_offset: u64,
+    seekable: Seekable,
+}
+
+/// Can a (reopened) input file be continued at a read?
+enum Seekable {
+    No,
+    /// offsets in the decompressed input are file offsets
+    Uncompressed,
+    /// seek to the start of the frame, then skip
+    Zstd(FrameStarts),
+}
+
+/// (compressed, decompressed) offsets of the zstd frames started so far, oldest first
+type FrameStarts = Arc<Mutex<VecDeque<(u64, u64)>>>;
+
+/// Counts the bytes read from the inner reader
+struct CountingReader<R> {
+    inner: R,
+    count: u64,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count += read as u64;
+        Ok(read)
+    }
+}
+
+/// Decompresses zstd one frame at a time, recording where each frame starts -
+/// so a resume can seek to the frame a read is in
+/// instead of decompressing everything before it.
+struct ZstdFrames<R: Read> {
+    decoder: Option<zstd::stream::read::Decoder<'static, BufReader<CountingReader<R>>>>,
+    frame_starts: FrameStarts,
+    decompressed: u64,
+}
+
+impl<R: Read> ZstdFrames<R> {
+    /// `compressed_offset`: where in the file `reader` is positioned
+    fn new(reader: R, compressed_offset: u64) -> std::io::Result<Self> {
+        let reader = BufReader::new(CountingReader {
+            inner: reader,
+            count: compressed_offset,
+        });
+        Ok(ZstdFrames {
+            decoder: Some(zstd::stream::read::Decoder::with_buffer(reader)?.single_frame()),
+            frame_starts: Arc::new(Mutex::new(VecDeque::from([(compressed_offset, 0)]))),
+            decompressed: 0,
+        })
+    }
+}
+
+impl<R: Read> Read for ZstdFrames<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while let Some(decoder) = self.decoder.as_mut() {
+            let read = decoder.read(buf)?;
+            if read > 0 {
+                self.decompressed += read as u64;
+                return Ok(read);
+            }
+            // end of the frame - the decoder consumed exactly up to it
+            let mut reader = self.decoder.take().expect("checked above").finish();
+            let compressed_offset = reader.get_ref().count - reader.buffer().len() as u64;
+            if !reader.fill_buf()?.is_empty() {
+                self.frame_starts
+                    .lock()
+                    .expect("frame starts mutex poisoned")
+                    .push_back((compressed_offset, self.decompressed));
+                self.decoder =
+                    Some(zstd::stream::read::Decoder::with_buffer(reader)?.single_frame());
+            }
+        }
+        Ok(0)
+    }
+}
+
+/// Is `file` zstd compressed? Returns a reader that still starts at the beginning.
+fn sniff_zstd(
+    mut file: Box<dyn std::io::Read + Send>,
+) -> Result<(Box<dyn std::io::Read + Send>, bool)> {
+    let mut magic = [0u8; 4];
+    let mut len = 0;
+    while len < magic.len() {
+        let read = file.read(&mut magic[len..])?;
+        if read == 0 {
+            break;
+        }
+        len += read;
+    }
+    let is_zstd = magic[..len] == ZSTD_MAGIC;
+    Ok((
+        Box::new(std::io::Cursor::new(magic[..len].to_vec()).chain(file)),
+        is_zstd,
+    ))
 }
 
 impl FastqParser {
     pub fn new(
-        file: std::fs::File,
+        file: Box<dyn std::io::Read + Send>,
         filename: Option<&PathBuf>,
         target_reads_per_block: usize,
         buf_size: usize,
         decompression_options: DecompressionOptions,
     ) -> Result<FastqParser> {
-        let (mut reader, format) = niffler::send::get_reader(Box::new(file))?;
+        // only files can be reopened to resume at a read
+        let (file, is_zstd) = if filename.is_some() {
+            sniff_zstd(file)?
+        } else {
+            (file, false)
+        };
+        if is_zstd {
+            let reader = ZstdFrames::new(file, 0)?;
+            let seekable = Seekable::Zstd(reader.frame_starts.clone());
+            return Ok(Self::from_reader(
+                Box::new(reader),
+                niffler::send::compression::Format::Zstd,
+                seekable,
+                target_reads_per_block,
+                buf_size,
+            ));
+        }
+        let (mut reader, format) = niffler::send::get_reader(file)?;
         // enable rapidgzip.
         if let DecompressionOptions::Rapidgzip {
             thread_count,
@@ -48,8 +174,73 @@ impl FastqParser {
                 reader = Box::new(file);
             }
         }
+        let seekable = if filename.is_some() && format == niffler::send::compression::Format::No {
+            Seekable::Uncompressed
+        } else {
+            Seekable::No
+        };
+
+        Ok(Self::from_reader(
+            reader,
+            format,
+            seekable,
+            target_reads_per_block,
+            buf_size,
+        ))
+    }
+
+    /// Continue a (reopened) file at a `Parser::resume_position` -
+    /// `file` has already been positioned at `seek_to`.
+    pub fn new_at(
+        file: Box<dyn std::io::Read + Send>,
+        target_reads_per_block: usize,
+        buf_size: usize,
+        seek_to: u64,
+        skip: u64,
+    ) -> Result<FastqParser> {
+        let (file, is_zstd) = sniff_zstd(file)?;
+        let mut parser = if is_zstd {
+            let reader = ZstdFrames::new(file, seek_to)?;
+            let seekable = Seekable::Zstd(reader.frame_starts.clone());
+            // offsets are counted from the start of the frame
+            Self::from_reader(
+                Box::new(reader),
+                niffler::send::compression::Format::Zstd,
+                seekable,
+                target_reads_per_block,
+                buf_size,
+            )
+        } else {
+            let mut parser = Self::from_reader(
+                file,
+                niffler::send::compression::Format::No,
+                Seekable::Uncompressed,
+                target_reads_per_block,
+                buf_size,
+            );
+            parser.stream_offset = seek_to;
+            parser
+        };
+        let skipped = std::io::copy(
+            &mut (&mut parser.current_reader).take(skip),
+            &mut std::io::sink(),
+        )?;
+        if skipped != skip {
+            bail!("Input file is shorter than the checkpoint recorded. Was it replaced?");
+        }
+        parser.stream_offset += skip;
+        parser.next_read_offset = parser.stream_offset;
+        Ok(parser)
+    }
 
-        Ok(FastqParser {
+    fn from_reader(
+        reader: Box<dyn std::io::Read + Send>,
+        format: niffler::send::compression::Format,
+        seekable: Seekable,
+        target_reads_per_block: usize,
+        buf_size: usize,
+    ) -> FastqParser {
+        FastqParser {
             current_reader: reader,
             current_block: Some(FastQBlock {
                 block: Vec::new(),
@@ -61,7 +252,11 @@ impl FastqParser {
             last_status: PartialStatus::NoPartial,
             windows_mode: None,
             compression_format: format,
-        })
+            stream_offset: 0,
+            partial_offset: 0,
+            next_read_offset: 0,
+            seekable,
+        }
     }
 
     fn advance(&mut self, start: &mut usize) -> Result<bool> {
@@ -156,6 +351,9 @@ impl FastqParser {
             )?;
             self.last_status = parse_result.status;
             self.last_partial = parse_result.partial_read;
+            if let Some(partial_start) = parse_result.partial_start {
+                self.partial_offset = self.stream_offset + partial_start as u64;
+            }
 
             self.windows_mode = Some(parse_result.windows_mode);
         }
@@ -165,11 +363,35 @@ impl FastqParser {
             .block
             .resize(start, 0);
 
-        let (mut out_block, new_block) = self
+        let current_block = self
             .current_block
             .take()
-            .expect("current_block must be initialized")
-            .split_at(self.target_reads_per_block);
+            .expect("current_block must be initialized");
+        let pending_start = current_block
+            .entries
+            .get(self.target_reads_per_block)
+            .map(|read| match &read.name {
+                // the name follows the '@'
+                FastQElement::Local(position) => position.start - 1,
+                FastQElement::Owned(_) => unreachable!("only the first read of a block is owned"),
+            });
+        let (mut out_block, new_block) = current_block.split_at(self.target_reads_per_block);
+        self.next_read_offset = match pending_start {
+            Some(pending_start) => self.stream_offset + pending_start as u64,
+            None if self.last_partial.is_some() => self.partial_offset,
+            None => self.stream_offset + start as u64,
+        };
+        // split_at keeps the bytes from the end of the returned reads
+        self.stream_offset += (start - new_block.block.len()) as u64;
+        if let Seekable::Zstd(frame_starts) = &self.seekable {
+            let mut frame_starts = frame_starts.lock().expect("frame starts mutex poisoned");
+            while frame_starts
+                .get(1)
+                .is_some_and(|(_, decompressed)| *decompressed <= self.next_read_offset)
+            {
+                frame_starts.pop_front();
+            }
+        }
 
         self.current_block = Some(new_block);
         if was_final && let Some(partial) = self.last_partial.take() {
@@ -195,6 +417,21 @@ impl Parser for FastqParser {
         })
     }
 
+    fn resume_position(&self) -> Option<(u64, u64)> {
+        match &self.seekable {
+            Seekable::No => None,
+            Seekable::Uncompressed => Some((self.next_read_offset, 0)),
+            Seekable::Zstd(frame_starts) => {
+                let frame_starts = frame_starts.lock().expect("frame starts mutex poisoned");
+                let (compressed, decompressed) = frame_starts
+                    .iter()
+                    .rev()
+                    .find(|(_, decompressed)| *decompressed <= self.next_read_offset)?;
+                Some((*compressed, self.next_read_offset - decompressed))
+            }
+        }
+    }
+
     fn bytes_per_base(&self) -> f64 {
         match self.compression_format {
             niffler::send::compression::Format::Gzip
@@ -224,6 +461,8 @@ pub struct FastQBlockParseResult {
     //pub block: FastQBlock,
     pub status: PartialStatus,
     pub partial_read: Option<FastQRead>,
+    /// where the '@' of a partial read started in this call is
+    pub partial_start: Option<usize>,
     pub windows_mode: bool,
 }
 
@@ -312,6 +551,7 @@ pub fn parse_to_fastq_block(
                 return Ok(FastQBlockParseResult {
                     status,
                     partial_read: Some(last_read.expect("last_read must be Some")),
+                    partial_start: None,
                     windows_mode,
                 });
             }
@@ -351,6 +591,7 @@ pub fn parse_to_fastq_block(
                 return Ok(FastQBlockParseResult {
                     status,
                     partial_read: Some(last_read.expect("last_read must be Some")),
+                    partial_start: None,
                     windows_mode,
                 });
             }
@@ -368,6 +609,7 @@ pub fn parse_to_fastq_block(
             return Ok(FastQBlockParseResult {
                 status: PartialStatus::InSpacerExpectPlus,
                 partial_read: Some(last_read.expect("last_read must be Some")),
+                partial_start: None,
                 windows_mode,
             });
         }
@@ -393,6 +635,7 @@ pub fn parse_to_fastq_block(
             return Ok(FastQBlockParseResult {
                 status: PartialStatus::InSpacerExpectPlus,
                 partial_read: Some(last_read.expect("last_read must be Some")),
+                partial_start: None,
                 windows_mode,
             });
         }
@@ -420,6 +663,7 @@ pub fn parse_to_fastq_block(
                 return Ok(FastQBlockParseResult {
                     status,
                     partial_read: Some(last_read.expect("last_read must be Some")),
+                    partial_start: None,
                     windows_mode,
                 });
             }
@@ -466,6 +710,7 @@ pub fn parse_to_fastq_block(
                 return Ok(FastQBlockParseResult {
                     status,
                     partial_read: Some(last_read.expect("last_read must be Some")),
+                    partial_start: None,
                     windows_mode,
                 });
             }
@@ -493,6 +738,7 @@ pub fn parse_to_fastq_block(
 
     let mut status = PartialStatus::NoPartial;
     let mut partial_read: Option<FastQRead> = None;
+    let mut read_start = pos;
     // debug!("before loop pos {pos} stop {stop}");
 
     loop {
@@ -514,6 +760,7 @@ pub fn parse_to_fastq_block(
                 );
             }
         }
+        read_start = pos;
         let end_of_name = newline_iterator.next();
         let (name_start, name_end) = match end_of_name {
             Some(end_of_name) => {
@@ -648,6 +895,7 @@ pub fn parse_to_fastq_block(
 
     Ok(FastQBlockParseResult {
         status,
+        partial_start: partial_read.as_ref().map(|_| read_start),
         partial_read,
         windows_mode,
     })
@@ -1,6 +1,6 @@
 use crate::config::SegmentIndexOrAll;
 use crate::config::deser::TagLabel;
-use crate::dna::HitRegion;
+use crate::dna::{Hit, HitRegion};
 use crate::transformations::prelude::DemultiplexTag;
 use crate::{
     config::SegmentIndex,
@@ -345,6 +345,17 @@ impl FastQRead {
     }
 }
 
+/// One output read of `FastQBlocksCombined::split_reads`
+#[derive(Debug, Clone)]
+pub struct ReadFragment {
+    /// index of the read it is cut from
+    pub source: usize,
+    /// the part of the sequence (and quality) to keep
+    pub range: Range<usize>,
+    /// inserted at the end of the read name (before the comment)
+    pub name_suffix: Option<BString>,
+}
+
 pub enum NewLocation {
     Remove,
     Keep,
@@ -870,6 +881,37 @@ impl WrappedFastQRead<'_> {
         )
     }
 
+    #[must_use]
+    pub fn find_iupac_all(
+        &self,
+        query: &[u8],
+        max_mismatches: u8,
+        target: SegmentIndex,
+    ) -> Vec<crate::dna::Hit> {
+        let seq = self.0.seq.get(self.1);
+        crate::dna::find_iupac_all(seq, query, max_mismatches, target)
+    }
+
+    #[must_use]
+    pub fn find_iupac_with_indel_all(
+        &self,
+        query: &[u8],
+        max_mismatches: usize,
+        max_indel_bases: usize,
+        max_total_edits: Option<usize>,
+        target: SegmentIndex,
+    ) -> Vec<crate::dna::Hit> {
+        let seq = self.0.seq.get(self.1);
+        crate::dna::find_iupac_with_indel_all(
+            seq,
+            query,
+            max_mismatches,
+            max_indel_bases,
+            max_total_edits,
+            target,
+        )
+    }
+
     // fn to_owned(&self) -> FastQRead {
     //     FastQRead {
     //         name: FastQElement::Owned(self.name().to_vec()),
@@ -1213,6 +1255,8 @@ pub struct FastQBlocksCombined {
     pub output_tags: Option<Vec<crate::demultiplex::Tag>>, // used by Demultiplex
     pub tags: IndexMap<TagLabel, Vec<TagValue>>,
     pub is_final: bool,
+    /// reads removed by filters so far - only collected if output.rejected_output is set
+    pub rejected: Option<Box<FastQBlocksCombined>>,
 }
 
 impl FastQBlocksCombined {
@@ -1228,7 +1272,50 @@ impl FastQBlocksCombined {
             },
             tags: IndexMap::default(),
             is_final: self.is_final,
+            rejected: None,
+        }
+    }
+
+    /// create an empty sink for rejected reads - same segments, never demultiplexed
+    #[must_use]
+    pub fn empty_rejected(&self) -> FastQBlocksCombined {
+        FastQBlocksCombined {
+            segments: vec![FastQBlock::empty(); self.segments.len()],
+            output_tags: None,
+            tags: IndexMap::default(),
+            is_final: false,
+            rejected: None,
+        }
+    }
+
+    /// Copy the reads at `indices` into the rejected sink (if we're collecting rejects)
+    fn record_rejected(&mut self, indices: impl Iterator<Item = usize>) {
+        if let Some(rejected) = self.rejected.as_mut() {
+            for idx in indices {
+                for (target, source) in rejected.segments.iter_mut().zip(&self.segments) {
+                    target.append_read(&source.get(idx));
+                }
+            }
+        }
+    }
+
+    /// Mark rejected reads from `start` on with the step that removed them
+    /// ('|rejected_by=<step>' in the read comment, like `StoreTagInComment`)
+    pub fn label_rejected(&mut self, start: usize, step: &[u8]) -> Result<()> {
+        for segment in &mut self.segments {
+            for idx in start..segment.len() {
+                let mut read = segment.get_mut(idx);
+                let new_name = crate::transformations::tag::store_tag_in_comment(
+                    read.name(),
+                    b"rejected_by",
+                    step,
+                    crate::transformations::tag::default_comment_separator(),
+                    crate::config::deser::default_comment_insert_char(),
+                )?;
+                read.replace_name(&new_name);
+            }
         }
+        Ok(())
     }
 
     #[must_use]
@@ -1257,6 +1344,7 @@ impl FastQBlocksCombined {
     }
 
     pub fn resize(&mut self, len: usize) {
+        self.record_rejected(len..self.len());
         for v in &mut self.segments {
             v.entries.resize_with(len, || {
                 panic!("Read amplification not expected. Can't resize to larger")
@@ -1275,6 +1363,7 @@ impl FastQBlocksCombined {
     }
 
     pub fn drain(&mut self, range: Range<usize>) {
+        self.record_rejected(range.clone());
         for v in &mut self.segments {
             v.entries.drain(range.clone());
         }
@@ -1440,6 +1529,11 @@ impl FastQBlocksCombined {
     /// Apply a boolean filter (vec) to all segments and tags
     pub fn apply_bool_filter(&mut self, keep: &[bool]) {
         let should: usize = keep.iter().map(|x| usize::from(*x)).sum();
+        self.record_rejected(
+            keep.iter()
+                .enumerate()
+                .filter_map(|(idx, keep)| (!keep).then_some(idx)),
+        );
         for segment_block in &mut self.segments {
             let mut iter = keep.iter();
             segment_block.entries.retain(|_| {
@@ -1469,6 +1563,200 @@ impl FastQBlocksCombined {
         }
     }
 
+    /// Split off the reads where `condition` is true, for steps with an `if_tag`.
+    /// Returns (matching reads, other reads), undo with `merge_by_condition`.
+    /// The rejected reads stay with the other reads.
+    #[must_use]
+    pub fn split_by_condition(
+        mut self,
+        condition: &[bool],
+    ) -> (FastQBlocksCombined, FastQBlocksCombined) {
+        let matching_indices: Vec<usize> = condition
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, matches)| matches.then_some(idx))
+            .collect();
+        let mut matching = self.empty();
+        for (target, source) in matching.segments.iter_mut().zip(&self.segments) {
+            for idx in &matching_indices {
+                target.append_read(&source.get(*idx));
+            }
+        }
+        if let (Some(target), Some(source)) = (&mut matching.output_tags, &self.output_tags) {
+            target.extend(matching_indices.iter().map(|idx| source[*idx]));
+        }
+        for (label, values) in &self.tags {
+            matching.tags.insert(
+                label.clone(),
+                matching_indices
+                    .iter()
+                    .map(|idx| values[*idx].clone())
+                    .collect(),
+            );
+        }
+
+        // these are not filtered, just set aside
+        let rejected = self.rejected.take();
+        let others: Vec<bool> = condition.iter().map(|matches| !matches).collect();
+        self.apply_bool_filter(&others);
+        self.rejected = rejected;
+        (matching, self)
+    }
+
+    /// Undo `split_by_condition` once a step processed the matching reads.
+    /// Tags the step declared are Missing on the other reads,
+    /// tags it removed are removed from them as well.
+    pub fn merge_by_condition(
+        matching: FastQBlocksCombined,
+        mut others: FastQBlocksCombined,
+        condition: &[bool],
+    ) -> Result<FastQBlocksCombined> {
+        fn interleave<T>(
+            condition: &[bool],
+            matching: impl IntoIterator<Item = T>,
+            mut others: impl Iterator<Item = T>,
+        ) -> Vec<T> {
+            let mut matching = matching.into_iter();
+            condition
+                .iter()
+                .map(|matches| {
+                    if *matches {
+                        matching.next()
+                    } else {
+                        others.next()
+                    }
+                    .expect("condition and read counts agree")
+                })
+                .collect()
+        }
+
+        let matching_count = condition.iter().filter(|matches| **matches).count();
+        if matching.len() != matching_count {
+            bail!(
+                "A step with if_tag changed the number of reads ({matching_count} -> {}). That's not supported.",
+                matching.len()
+            );
+        }
+        for (target, source) in others.segments.iter_mut().zip(&matching.segments) {
+            let other_entries = std::mem::take(&mut target.entries);
+            // copy the matching reads into our block
+            for idx in 0..source.len() {
+                target.append_read(&source.get(idx));
+            }
+            let matching_entries = std::mem::take(&mut target.entries);
+            target.entries = interleave(condition, matching_entries, other_entries.into_iter());
+        }
+        if let (Some(target), Some(source)) = (others.output_tags.take(), matching.output_tags) {
+            others.output_tags = Some(interleave(condition, source, target.into_iter()));
+        }
+        let mut other_tags = std::mem::take(&mut others.tags);
+        for (label, values) in matching.tags {
+            let merged = match other_tags.shift_remove(&label) {
+                Some(other_values) => interleave(condition, values, other_values.into_iter()),
+                None => interleave(condition, values, std::iter::repeat(TagValue::Missing)),
+            };
+            others.tags.insert(label, merged);
+        }
+        Ok(others)
+    }
+
+    /// Replace each read by the fragments listed for it - the read count may grow
+    /// ('read amplification') or shrink. Single segment blocks only.
+    ///
+    /// Tags and demultiplex tags are copied to each fragment of their read.
+    /// Locations are moved into the fragment they fall into, those crossing a
+    /// fragment border are dropped (and location tags without any left become missing).
+    /// Reads without any fragment count as rejected.
+    pub fn split_reads(&mut self, fragments: &[ReadFragment], comment_insert_char: u8) {
+        assert_eq!(
+            self.segments.len(),
+            1,
+            "split_reads only supports single segment blocks"
+        );
+        let mut has_fragment = vec![false; self.len()];
+        for fragment in fragments {
+            has_fragment[fragment.source] = true;
+        }
+        self.record_rejected(
+            has_fragment
+                .iter()
+                .enumerate()
+                .filter_map(|(idx, has_fragment)| (!has_fragment).then_some(idx)),
+        );
+
+        let source = std::mem::replace(&mut self.segments[0], FastQBlock::empty());
+        let target = &mut self.segments[0];
+        for fragment in fragments {
+            let read = source.get(fragment.source);
+            let name = read.name();
+            let name = match &fragment.name_suffix {
+                Some(suffix) => {
+                    let insert_pos = name
+                        .iter()
+                        .position(|&x| x == comment_insert_char)
+                        .unwrap_or(name.len());
+                    let mut new_name = Vec::with_capacity(name.len() + suffix.len());
+                    new_name.extend_from_slice(&name[..insert_pos]);
+                    new_name.extend_from_slice(suffix);
+                    new_name.extend_from_slice(&name[insert_pos..]);
+                    new_name
+                }
+                None => name.to_vec(),
+            };
+            let name = target.append_element(&name);
+            let seq = target.append_element(&read.seq()[fragment.range.clone()]);
+            let qual = target.append_element(&read.qual()[fragment.range.clone()]);
+            target.entries.push(
+                FastQRead::new(name, seq, qual).expect("fragment of a valid read is a valid read"),
+            );
+        }
+
+        if let Some(output_tags) = self.output_tags.as_mut() {
+            let split: Vec<_> = fragments
+                .iter()
+                .map(|fragment| output_tags[fragment.source])
+                .collect();
+            *output_tags = split;
+        }
+        for values in self.tags.values_mut() {
+            let split: Vec<TagValue> = fragments
+                .iter()
+                .map(|fragment| match &values[fragment.source] {
+                    TagValue::Location(hits) => {
+                        let kept: Vec<Hit> = hits
+                            .0
+                            .iter()
+                            .filter_map(|hit| match &hit.location {
+                                None => Some(hit.clone()),
+                                Some(location)
+                                    if location.start >= fragment.range.start
+                                        && location.start + location.len <= fragment.range.end =>
+                                {
+                                    Some(Hit {
+                                        location: Some(HitRegion {
+                                            start: location.start - fragment.range.start,
+                                            len: location.len,
+                                            segment_index: location.segment_index,
+                                        }),
+                                        sequence: hit.sequence.clone(),
+                                    })
+                                }
+                                Some(_) => None,
+                            })
+                            .collect();
+                        if kept.is_empty() {
+                            TagValue::Missing
+                        } else {
+                            TagValue::Location(Hits::new_multiple(kept))
+                        }
+                    }
+                    other => other.clone(),
+                })
+                .collect();
+            *values = split;
+        }
+    }
+
     pub fn filter_tag_locations(
         &mut self,
         segment: SegmentIndex,
@@ -2196,6 +2484,7 @@ mod test {
             output_tags: None,
             tags: Default::default(),
             is_final: false,
+            rejected: None,
         });
         assert!(blocks.is_empty());
     }
@@ -2207,6 +2496,7 @@ mod test {
             output_tags: None,
             tags: Default::default(),
             is_final: false,
+            rejected: None,
         };
         empty
             .sanity_check()
@@ -2232,6 +2522,7 @@ mod test {
             output_tags: None,
             tags: Default::default(),
             is_final: false,
+            rejected: None,
         };
         empty
             .sanity_check()
@@ -2265,6 +2556,7 @@ mod test {
             output_tags: None,
             tags: Default::default(),
             is_final: false,
+            rejected: None,
         };
         empty
             .sanity_check()
@@ -2305,6 +2597,7 @@ mod test {
             output_tags: None,
             tags: Default::default(),
             is_final: false,
+            rejected: None,
         };
         empty
             .sanity_check()
@@ -2352,12 +2645,172 @@ mod test {
             output_tags: Some(vec![]),
             tags: Default::default(),
             is_final: false,
+            rejected: None,
         };
         empty
             .sanity_check()
             .expect("sanity check should pass in test");
     }
 
+    #[test]
+    fn test_apply_bool_filter_records_rejected() {
+        let read = |name: &[u8]| FastQRead {
+            name: FastQElement::Owned(name.to_vec()),
+            seq: FastQElement::Owned(b"AGTC".to_vec()),
+            qual: FastQElement::Owned(b"ABCD".to_vec()),
+        };
+        let mut block = FastQBlocksCombined {
+            segments: vec![FastQBlock {
+                block: Vec::new(),
+                entries: vec![read(b"r1 comment"), read(b"r2"), read(b"r3")],
+            }],
+            output_tags: None,
+            tags: Default::default(),
+            is_final: false,
+            rejected: None,
+        };
+        block.rejected = Some(Box::new(block.empty_rejected()));
+        block.apply_bool_filter(&[false, true, false]);
+        assert_eq!(block.len(), 1);
+        let rejected = block.rejected.as_mut().expect("rejected sink set above");
+        rejected
+            .label_rejected(0, b"2")
+            .expect("label contains no separator");
+        assert_eq!(rejected.len(), 2);
+        assert_eq!(
+            rejected.segments[0].get(0).name(),
+            b"r1|rejected_by=2 comment"
+        );
+        assert_eq!(rejected.segments[0].get(1).name(), b"r3|rejected_by=2");
+    }
+
+    #[test]
+    fn test_split_and_merge_by_condition() {
+        let read = |name: &[u8]| FastQRead {
+            name: FastQElement::Owned(name.to_vec()),
+            seq: FastQElement::Owned(b"AGTC".to_vec()),
+            qual: FastQElement::Owned(b"ABCD".to_vec()),
+        };
+        let mut block = FastQBlocksCombined {
+            segments: vec![FastQBlock {
+                block: Vec::new(),
+                entries: vec![read(b"r1"), read(b"r2"), read(b"r3")],
+            }],
+            output_tags: None,
+            tags: Default::default(),
+            is_final: false,
+            rejected: None,
+        };
+        block.tags.insert(
+            TagLabel("cond".to_string()),
+            vec![
+                TagValue::Bool(true),
+                TagValue::Bool(false),
+                TagValue::Bool(true),
+            ],
+        );
+        let condition = [true, false, true];
+        let (mut matching, others) = block.split_by_condition(&condition);
+        assert_eq!(matching.len(), 2);
+        assert_eq!(others.len(), 1);
+        assert_eq!(others.segments[0].get(0).name(), b"r2");
+        matching.tags.insert(
+            TagLabel("new".to_string()),
+            vec![TagValue::Numeric(1.0), TagValue::Numeric(3.0)],
+        );
+        matching.segments[0].get_mut(1).replace_name(b"r3_changed");
+
+        let merged = FastQBlocksCombined::merge_by_condition(matching, others, &condition)
+            .expect("read counts unchanged");
+        let names: Vec<_> = (0..3)
+            .map(|ii| merged.segments[0].get(ii).name().to_vec())
+            .collect();
+        assert_eq!(
+            names,
+            vec![b"r1".to_vec(), b"r2".to_vec(), b"r3_changed".to_vec()]
+        );
+        assert_eq!(
+            merged.tags[&TagLabel("new".to_string())],
+            vec![
+                TagValue::Numeric(1.0),
+                TagValue::Missing,
+                TagValue::Numeric(3.0)
+            ]
+        );
+        assert_eq!(
+            merged.tags[&TagLabel("cond".to_string())][1],
+            TagValue::Bool(false)
+        );
+    }
+
+    #[test]
+    fn test_split_reads() {
+        let read = |name: &[u8], seq: &[u8]| FastQRead {
+            name: FastQElement::Owned(name.to_vec()),
+            seq: FastQElement::Owned(seq.to_vec()),
+            qual: FastQElement::Owned(seq.to_ascii_lowercase()),
+        };
+        let mut block = FastQBlocksCombined {
+            segments: vec![FastQBlock {
+                block: Vec::new(),
+                entries: vec![read(b"r1 comment", b"AACCGGTT"), read(b"r2", b"TTTT")],
+            }],
+            output_tags: None,
+            tags: Default::default(),
+            is_final: false,
+            rejected: None,
+        };
+        block.rejected = Some(Box::new(block.empty_rejected()));
+        block.tags.insert(
+            TagLabel("loc".to_string()),
+            vec![
+                TagValue::Location(Hits::new_multiple(vec![
+                    Hits::new(1, 2, SegmentIndex(0), b"AC".into()).0.remove(0),
+                    Hits::new(6, 2, SegmentIndex(0), b"TT".into()).0.remove(0),
+                ])),
+                TagValue::Missing,
+            ],
+        );
+        block.tags.insert(
+            TagLabel("num".to_string()),
+            vec![TagValue::Numeric(1.0), TagValue::Numeric(2.0)],
+        );
+        let fragments = [
+            ReadFragment {
+                source: 0,
+                range: 0..4,
+                name_suffix: Some("_1".into()),
+            },
+            ReadFragment {
+                source: 0,
+                range: 4..8,
+                name_suffix: Some("_2".into()),
+            },
+        ];
+        block.split_reads(&fragments, b' ');
+        block.sanity_check().expect("segments in lockstep");
+        assert_eq!(block.len(), 2);
+        assert_eq!(block.segments[0].get(0).name(), b"r1_1 comment");
+        assert_eq!(block.segments[0].get(0).seq(), b"AACC");
+        assert_eq!(block.segments[0].get(0).qual(), b"aacc");
+        assert_eq!(block.segments[0].get(1).name(), b"r1_2 comment");
+        assert_eq!(block.segments[0].get(1).seq(), b"GGTT");
+        assert_eq!(
+            block.tags[&TagLabel("loc".to_string())],
+            vec![
+                TagValue::Location(Hits::new(1, 2, SegmentIndex(0), b"AC".into())),
+                TagValue::Location(Hits::new(2, 2, SegmentIndex(0), b"TT".into())),
+            ]
+        );
+        assert_eq!(
+            block.tags[&TagLabel("num".to_string())],
+            vec![TagValue::Numeric(1.0), TagValue::Numeric(1.0)]
+        );
+        let rejected = block.rejected.as_ref().expect("rejected sink set above");
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected.segments[0].get(0).name(), b"r2");
+    }
+
     // Tests for FastQElement::swap_with
     #[test]
     fn test_fastq_element_swap_both_local() {
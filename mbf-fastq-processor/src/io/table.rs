@@ -0,0 +1,53 @@
+use anyhow::{Context, Result, bail};
+use std::io::Read;
+use std::path::Path;
+
+/// The delimiter we use when the user did not specify one:
+/// ',' for .csv (optionally compressed), tab otherwise.
+#[must_use]
+pub fn default_delimiter(filename: impl AsRef<Path>) -> u8 {
+    let name = filename.as_ref().to_string_lossy().to_lowercase();
+    let name = name
+        .strip_suffix(".gz")
+        .or_else(|| name.strip_suffix(".zst"))
+        .unwrap_or(&name);
+    if name.ends_with(".csv") { b',' } else { b'\t' }
+}
+
+/// Open a TSV/CSV file (gzip/zstd compressed or not) that starts with a header line.
+/// Lines starting with '#' are skipped.
+pub fn open_table(
+    filename: impl AsRef<Path>,
+    delimiter: Option<u8>,
+) -> Result<csv::Reader<Box<dyn Read + Send>>> {
+    let filename = filename.as_ref();
+    let file = super::open_file(filename)?;
+    let (reader, _format) = niffler::send::get_reader(Box::new(file))
+        .with_context(|| format!("Could not read table {}", filename.display()))?;
+    Ok(csv::ReaderBuilder::new()
+        .delimiter(delimiter.unwrap_or_else(|| default_delimiter(filename)))
+        .comment(Some(b'#'))
+        .has_headers(true)
+        .from_reader(reader))
+}
+
+/// Find a column by its header name.
+pub fn column_index(
+    headers: &csv::ByteRecord,
+    column: &str,
+    filename: impl AsRef<Path>,
+) -> Result<usize> {
+    if let Some(idx) = headers.iter().position(|x| x == column.as_bytes()) {
+        Ok(idx)
+    } else {
+        let available: Vec<_> = headers
+            .iter()
+            .map(|x| String::from_utf8_lossy(x).to_string())
+            .collect();
+        bail!(
+            "Column '{column}' not found in {}. Available columns: {}",
+            filename.as_ref().display(),
+            available.join(", ")
+        );
+    }
+}
@@ -5,6 +5,8 @@
 #![allow(clippy::single_match_else)]
 #![allow(clippy::default_trait_access)]
 
+pub mod api;
+mod checkpoint;
 pub mod cli;
 pub mod config;
 pub mod cookbooks;
@@ -16,11 +18,17 @@ pub mod io;
 pub mod list_steps;
 mod output;
 mod pipeline;
+mod pipeline_funnel;
+mod pipeline_timing;
 mod pipeline_workpool;
+mod spill;
 mod transformations;
 
-pub use cli::process::run;
-pub use cli::validate::validate_config;
+pub use cli::explain::{ExplainFormat, Explanation, explain};
+pub use cli::process::{QcFailure, resume, run};
+pub use cli::report_merge::merge_reports;
+pub use cli::samples::run_samples;
+pub use cli::validate::{Validation, validate_config};
 pub use cli::verify::decompress_file; // used by parser tests
 pub use cli::verify::verify_outputs;
 
@@ -63,6 +63,38 @@ Docs:
                         .help("Allow overwriting existing output files")
                         .action(ArgAction::SetTrue),
                 )
+                .arg(
+                    Arg::new("resume")
+                        .long("resume")
+                        .help("Continue an interrupted run from its last checkpoint (see output.checkpoint_interval)")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("samples"),
+                )
+                .arg(
+                    Arg::new("samples")
+                        .long("samples")
+                        .help("Batch mode: tab separated sample table (header with a 'sample' column). Every '{column}' in [input]/[output] is replaced per sample")
+                        .value_name("SAMPLES_TSV")
+                        .value_hint(ValueHint::FilePath),
+                )
+                .arg(
+                    Arg::new("jobs")
+                        .long("jobs")
+                        .short('j')
+                        .help("Batch mode: number of samples processed in parallel (default: 1)")
+                        .value_name("N")
+                        .default_value("1")
+                        .requires("samples")
+                        .value_parser(clap::value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("summary-prefix")
+                        .long("summary-prefix")
+                        .help("Batch mode: writes <PREFIX>_status.tsv and <PREFIX>_report.json/tsv/html (default: samples)")
+                        .value_name("PREFIX")
+                        .default_value("samples")
+                        .requires("samples"),
+                )
         )
         .subcommand(
             Command::new("template")
@@ -95,6 +127,32 @@ Docs:
                         .value_hint(ValueHint::FilePath),
                 ),
         )
+        .subcommand(
+            Command::new("explain")
+                .about("Show what each step does with tags and segments")
+                .long_about(
+                    "Prints one row per step: the tag it declares, the tags it uses and removes,\n\
+                    the segments it modifies and whether it needs to see the reads in order (serial).\n\
+                    With --format dot or mermaid, prints the pipeline as a graph instead,\n\
+                    with an edge from each tag's declaring step to every step using or removing it.\n\n\
+                    Warns about tags that are declared, but only ever forgotten."
+                )
+                .arg(
+                    Arg::new("config")
+                        .help("Path to the TOML configuration file to explain")
+                        .required(false)
+                        .value_name("CONFIG_TOML")
+                        .value_hint(ValueHint::FilePath),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .short('f')
+                        .help("Output format")
+                        .value_parser(["table", "dot", "mermaid"])
+                        .default_value("table"),
+                ),
+        )
         .subcommand(
             Command::new("verify")
                 .about("Run processing in a temp directory and verify outputs match expected outputs or expected panics")
@@ -130,6 +188,41 @@ This command is used by the test runner but can also be run manually to verify t
                         .action(ArgAction::SetTrue),
                 )
         )
+        .subcommand(
+            Command::new("report-merge")
+                .about("Merge several JSON reports into one combined HTML/JSON/TSV report")
+                .long_about(
+                    "Reads the JSON reports of several runs (report_json = true) and writes\n\
+                    - <PREFIX>.json: all reports, keyed by sample name\n\
+                    - <PREFIX>.tsv: one row per sample, one column per scalar metric\n\
+                    - <PREFIX>.html: per-sample tables and overlaid curves/histograms\n\n\
+                    Sample names are the report file names without '.json'. If these are not unique, \
+                    parent directory names are prepended until they are."
+                )
+                .arg(
+                    Arg::new("reports")
+                        .help("JSON report files to merge")
+                        .required(true)
+                        .num_args(1..)
+                        .value_name("REPORT_JSON")
+                        .value_hint(ValueHint::FilePath),
+                )
+                .arg(
+                    Arg::new("output-prefix")
+                        .long("output-prefix")
+                        .short('o')
+                        .help("Output prefix (default: merged_report)")
+                        .value_name("PREFIX")
+                        .default_value("merged_report")
+                        .value_hint(ValueHint::FilePath),
+                )
+                .arg(
+                    Arg::new("allow-overwrite")
+                        .long("allow-overwrite")
+                        .help("Allow overwriting existing output files")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
         .subcommand(
             Command::new("interactive")
                 .about("Interactive mode: watch a TOML file and show live results")
@@ -303,6 +396,14 @@ fn main() -> Result<()> {
             .support("Open a github issue at https://github.com/TyberiusPrime/mbf-fastq-processor/issues/new and attach the crash report.")
     );
     }
+    // a panic in any of the pipeline's threads ends the process,
+    // instead of leaving the other threads waiting for it
+    let orig_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |panic_info| {
+        // invoke the default handler and exit the process
+        orig_hook(panic_info);
+        std::process::exit(1);
+    }));
 
     assert!(
         !std::env::args().any(|x| x == "--test-friendly-panic"),
@@ -324,7 +425,28 @@ fn main() -> Result<()> {
         Some(("process", sub_matches)) => {
             let toml_path = handle_toml_arg(sub_matches.get_one::<String>("config"));
             let allow_overwrites = sub_matches.get_flag("allow-overwrite");
-            run_with_optional_measure(|| process_from_toml_file(&toml_path, allow_overwrites));
+            let resume = sub_matches.get_flag("resume");
+            if let Some(samples) = sub_matches.get_one::<String>("samples") {
+                let jobs = *sub_matches
+                    .get_one::<usize>("jobs")
+                    .expect("jobs has a default value");
+                let summary_prefix = sub_matches
+                    .get_one::<String>("summary-prefix")
+                    .expect("summary-prefix has a default value");
+                run_with_optional_measure(|| {
+                    process_samples(
+                        &toml_path,
+                        Path::new(samples),
+                        allow_overwrites,
+                        jobs,
+                        summary_prefix,
+                    );
+                });
+            } else {
+                run_with_optional_measure(|| {
+                    process_from_toml_file(&toml_path, allow_overwrites, resume);
+                });
+            }
         }
         Some(("template", sub_matches)) => {
             let section = sub_matches.get_one::<String>("section");
@@ -347,6 +469,13 @@ fn main() -> Result<()> {
             let toml_path = handle_toml_arg(sub_matches.get_one::<String>("config"));
             validate_config_file(&toml_path);
         }
+        Some(("explain", sub_matches)) => {
+            let toml_path = handle_toml_arg(sub_matches.get_one::<String>("config"));
+            let format = sub_matches
+                .get_one::<String>("format")
+                .expect("format has a default value");
+            explain_config_file(&toml_path, format);
+        }
         Some(("verify", sub_matches)) => {
             let output_dir = sub_matches.get_one::<String>("output-dir");
             let unsafe_prep = sub_matches.get_flag("unsafe-call-prep-sh");
@@ -354,6 +483,18 @@ fn main() -> Result<()> {
             let toml_path = handle_toml_arg(sub_matches.get_one::<String>("config"));
             verify_config_file(&toml_path, output_dir.map(PathBuf::from), unsafe_prep);
         }
+        Some(("report-merge", sub_matches)) => {
+            let reports: Vec<PathBuf> = sub_matches
+                .get_many::<String>("reports")
+                .expect("reports is a required argument")
+                .map(PathBuf::from)
+                .collect();
+            let output_prefix = sub_matches
+                .get_one::<String>("output-prefix")
+                .expect("output-prefix has a default value");
+            let allow_overwrite = sub_matches.get_flag("allow-overwrite");
+            merge_reports(&reports, Path::new(output_prefix), allow_overwrite);
+        }
         Some(("interactive", sub_matches)) => {
             let toml_path = handle_toml_arg(sub_matches.get_one::<String>("config"));
             let head = sub_matches.get_one::<u64>("head").copied();
@@ -386,9 +527,18 @@ fn print_version_and_exit() {
     std::process::exit(0);
 }
 
-fn process_from_toml_file(toml_file: &Path, allow_overwrites: bool) {
+fn process_from_toml_file(toml_file: &Path, allow_overwrites: bool, resume: bool) {
     let current_dir = std::env::current_dir().expect("failed to get current directory");
-    if let Err(e) = mbf_fastq_processor::run(toml_file, &current_dir, allow_overwrites) {
+    let res = if resume {
+        mbf_fastq_processor::resume(toml_file, &current_dir)
+    } else {
+        mbf_fastq_processor::run(toml_file, &current_dir, allow_overwrites)
+    };
+    if let Err(e) = res {
+        if let Some(qc_failure) = e.downcast_ref::<mbf_fastq_processor::QcFailure>() {
+            eprintln!("{qc_failure}");
+            std::process::exit(qc_failure.exit_code);
+        }
         eprintln!("Unfortunately, an error was detected and led to an early exit.\n");
         // let docs = docs_matching_error_message(&e);
         // if !docs.is_empty() {
@@ -408,9 +558,40 @@ fn process_from_toml_file(toml_file: &Path, allow_overwrites: bool) {
     }
 }
 
+fn process_samples(
+    toml_file: &Path,
+    samples_file: &Path,
+    allow_overwrites: bool,
+    jobs: usize,
+    summary_prefix: &str,
+) {
+    let current_dir = std::env::current_dir().expect("failed to get current directory");
+    if let Err(e) = mbf_fastq_processor::run_samples(
+        toml_file,
+        samples_file,
+        &current_dir,
+        allow_overwrites,
+        jobs,
+        summary_prefix,
+    ) {
+        if let Some(qc_failure) = e.downcast_ref::<mbf_fastq_processor::QcFailure>() {
+            eprintln!("{qc_failure}");
+            std::process::exit(qc_failure.exit_code);
+        }
+        eprintln!("# == Error Details ==\n{e:?}");
+        std::process::exit(1);
+    }
+}
+
 fn validate_config_file(toml_path: &Path) {
     match mbf_fastq_processor::validate_config(toml_path) {
-        Ok(warnings) => {
+        Ok(validation) => {
+            if let Some(expanded_config) = validation.expanded_config {
+                println!(
+                    "# == Expanded configuration ==\n{expanded_config}# == End of expanded configuration =="
+                );
+            }
+            let warnings = validation.warnings;
             if warnings.is_empty() {
                 println!("✓ Configuration is valid");
                 std::process::exit(0);
@@ -445,6 +626,26 @@ fn validate_config_file(toml_path: &Path) {
     }
 }
 
+fn explain_config_file(toml_path: &Path, format: &str) {
+    let format = format
+        .parse::<mbf_fastq_processor::ExplainFormat>()
+        .expect("clap only accepts known formats");
+    match mbf_fastq_processor::explain(toml_path, format) {
+        Ok(explanation) => {
+            print!("{}", explanation.text);
+            for warning in explanation.warnings {
+                eprintln!("Warning: {warning}");
+            }
+            std::process::exit(0);
+        }
+        Err(e) => {
+            eprintln!("Configuration validation failed:\n");
+            eprintln!("# == Error Details ==\n{e:?}");
+            std::process::exit(1);
+        }
+    }
+}
+
 #[allow(clippy::needless_pass_by_value)]
 fn verify_config_file(toml_file: &Path, output_dir: Option<PathBuf>, unsafe_prep: bool) {
     match mbf_fastq_processor::verify_outputs(toml_file, output_dir.as_deref(), unsafe_prep) {
@@ -462,6 +663,24 @@ fn verify_config_file(toml_file: &Path, output_dir: Option<PathBuf>, unsafe_prep
     }
 }
 
+fn merge_reports(reports: &[PathBuf], output_prefix: &Path, allow_overwrite: bool) {
+    match mbf_fastq_processor::merge_reports(reports, output_prefix, allow_overwrite) {
+        Ok(()) => {
+            println!(
+                "✓ Merged {} reports into {}.html",
+                reports.len(),
+                output_prefix.display()
+            );
+            std::process::exit(0);
+        }
+        Err(e) => {
+            eprintln!("Report merge failed:\n");
+            eprintln!("# == Error Details ==\n{e:?}");
+            std::process::exit(1);
+        }
+    }
+}
+
 fn run_interactive_mode(
     toml_path: &Path,
     head: Option<u64>,
@@ -1,11 +1,14 @@
+mod qc_thresholds;
+mod report_comparison;
+
 use anyhow::{Context, Result, anyhow};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
 use std::io::{BufWriter, Write};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 
-use crate::config::{CheckedConfig, CompressionFormat, FileFormat};
+use crate::config::{CheckedConfig, CompressionFormat, FileFormat, QcThresholds};
 use crate::demultiplex::OptDemultiplex;
 use crate::io::{
     self,
@@ -155,6 +158,8 @@ pub struct OutputFileConfig {
     chunk_index: usize,
     chunk_digit_count: usize,
     fragments_written_in_chunk: usize,
+    /// `process --resume`: truncate to this length & append instead of recreating
+    resume_length: Option<u64>,
 }
 
 pub struct OutputFile<'a> {
@@ -213,6 +218,22 @@ impl OutputFile<'_> {
         Ok(())
     }
 
+    /// Finish the current compression frame and continue in a fresh one,
+    /// so the file is complete up to here. Returns (checkpoint key, length).
+    fn checkpoint(&mut self) -> Result<(String, u64)> {
+        let filename = self.config.filename();
+        let old_handle =
+            std::mem::replace(&mut self.handle, OutputFileHandle::TemporarilyOutOfAction);
+        old_handle.finish(&filename)?;
+        let handle = OutputFileConfig::open_for_append(&filename)?;
+        handle
+            .sync_all()
+            .with_context(|| format!("Failed to sync output file: {}", filename.display()))?;
+        let length = handle.metadata()?.len();
+        self.handle = self.config.build_writer(handle)?;
+        Ok((self.config.checkpoint_key(), length))
+    }
+
     fn after_bam_fragment(&mut self) -> Result<()> {
         if let Some(chunk_size) = self.config.chunk_size {
             self.config.fragments_written_in_chunk += 1;
@@ -324,6 +345,7 @@ impl OutputFileConfig {
             chunk_index: 0,
             chunk_digit_count: usize::from(chunk_size.is_some()),
             fragments_written_in_chunk: 0,
+            resume_length: None,
         })
     }
 
@@ -355,16 +377,28 @@ impl OutputFileConfig {
             chunk_index: 0,
             chunk_digit_count: 0,
             fragments_written_in_chunk: 0,
+            resume_length: None,
         })
     }
 
     fn into_writer<'a>(self) -> Result<OutputFile<'a>> {
-        let handle = ex::fs::File::create(self.filename()).with_context(|| {
-            format!(
-                "Could not open file for output: {}",
-                self.filename().display()
-            )
-        })?;
+        let handle = if let Some(resume_length) = self.resume_length {
+            let handle = Self::open_for_append(&self.filename())?;
+            handle.set_len(resume_length).with_context(|| {
+                format!(
+                    "Could not truncate output file to checkpoint: {}",
+                    self.filename().display()
+                )
+            })?;
+            handle
+        } else {
+            ex::fs::File::create(self.filename()).with_context(|| {
+                format!(
+                    "Could not open file for output: {}",
+                    self.filename().display()
+                )
+            })?
+        };
         let handle = self.build_writer(handle)?;
         Ok(OutputFile {
             config: self,
@@ -372,6 +406,23 @@ impl OutputFileConfig {
         })
     }
 
+    fn open_for_append(filename: &Path) -> Result<ex::fs::File> {
+        ex::fs::OpenOptions::new()
+            .append(true)
+            .open(filename)
+            .with_context(|| format!("Could not reopen output file: {}", filename.display()))
+    }
+
+    /// the key in `Checkpoint::output_lengths`
+    fn checkpoint_key(&self) -> String {
+        let filename = self.filename();
+        filename
+            .strip_prefix(&self.directory)
+            .unwrap_or(&filename)
+            .to_string_lossy()
+            .to_string()
+    }
+
     fn make_filename(
         basename: &str,
         chunk_size: Option<usize>,
@@ -646,7 +697,38 @@ impl OutputFastqs<OutputFileConfig> {
     }
 }
 
+impl OutputFastqs<OutputFileConfig> {
+    fn resume_from(&mut self, output_lengths: &BTreeMap<String, u64>) -> Result<()> {
+        for config in self
+            .interleaved_file
+            .iter_mut()
+            .chain(self.segment_files.iter_mut().flatten())
+        {
+            let key = config.checkpoint_key();
+            match output_lengths.get(&key) {
+                Some(length) => config.resume_length = Some(*length),
+                None => anyhow::bail!(
+                    "Output file {key} is not part of the checkpoint - can not resume."
+                ),
+            }
+        }
+        Ok(())
+    }
+}
+
 impl OutputFastqs<OutputFile<'_>> {
+    fn checkpoint(&mut self, output_lengths: &mut BTreeMap<String, u64>) -> Result<()> {
+        for file in self
+            .interleaved_file
+            .iter_mut()
+            .chain(self.segment_files.iter_mut().flatten())
+        {
+            let (key, length) = file.checkpoint()?;
+            output_lengths.insert(key, length);
+        }
+        Ok(())
+    }
+
     pub fn finish(&mut self) -> Result<()> {
         if let Some(interleaved) = self.interleaved_file.take() {
             interleaved
@@ -663,6 +745,7 @@ impl OutputFastqs<OutputFile<'_>> {
 pub struct OutputReports {
     pub html: Option<BufWriter<ex::fs::File>>,
     pub json: Option<BufWriter<ex::fs::File>>,
+    pub timing: Option<BufWriter<ex::fs::File>>,
 }
 
 #[allow(clippy::fn_params_excessive_bools)]
@@ -672,6 +755,7 @@ impl OutputReports {
         prefix: &String,
         report_html: bool,
         report_json: bool,
+        report_timing: bool,
         allow_overwrite: bool,
     ) -> Result<OutputReports> {
         Ok(OutputReports {
@@ -697,6 +781,17 @@ impl OutputReports {
             } else {
                 None
             },
+            timing: if report_timing {
+                let filename = output_directory.join(format!("{prefix}.timing.json"));
+                let _ = ensure_output_destination_available(&filename, allow_overwrite)?;
+                Some(BufWriter::new(
+                    ex::fs::File::create(&filename).with_context(|| {
+                        format!("Could not open output file: {}", filename.display())
+                    })?,
+                ))
+            } else {
+                None
+            },
         })
     }
 }
@@ -808,11 +903,14 @@ fn open_one_set_of_output_files(
 pub struct OutputFiles {
     pub output_segments:
         BTreeMap<crate::demultiplex::Tag, Arc<Mutex<OutputFastqs<OutputFileConfig>>>>,
+    /// output.rejected_output - never demultiplexed
+    pub rejected: Option<OutputFastqs<OutputFileConfig>>,
     pub output_reports: OutputReports,
 }
 
 pub struct OutputFilesReadyToWrite<'a> {
     pub output_segments: BTreeMap<crate::demultiplex::Tag, OutputFastqs<OutputFile<'a>>>,
+    pub rejected: Option<OutputFastqs<OutputFile<'a>>>,
     pub output_reports: OutputReports,
 }
 
@@ -828,9 +926,38 @@ impl OutputFiles {
         }
         Ok(OutputFilesReadyToWrite {
             output_segments,
+            rejected: self.rejected.map(OutputFastqs::into_writer).transpose()?,
             output_reports: self.output_reports,
         })
     }
+
+    /// `process --resume`: continue the output files at their checkpointed lengths
+    pub fn resume_from(&mut self, output_lengths: &BTreeMap<String, u64>) -> Result<()> {
+        for output_fastqs in self.output_segments.values() {
+            output_fastqs
+                .lock()
+                .map_err(|_| anyhow!("Mutex was poisoned"))?
+                .resume_from(output_lengths)?;
+        }
+        if let Some(rejected) = self.rejected.as_mut() {
+            rejected.resume_from(output_lengths)?;
+        }
+        Ok(())
+    }
+}
+
+impl OutputFilesReadyToWrite<'_> {
+    /// Complete every output file up to here - returns their lengths
+    pub fn checkpoint(&mut self) -> Result<BTreeMap<String, u64>> {
+        let mut output_lengths = BTreeMap::new();
+        for output_fastqs in self.output_segments.values_mut() {
+            output_fastqs.checkpoint(&mut output_lengths)?;
+        }
+        if let Some(rejected) = self.rejected.as_mut() {
+            rejected.checkpoint(&mut output_lengths)?;
+        }
+        Ok(output_lengths)
+    }
 }
 
 #[allow(clippy::fn_params_excessive_bools)]
@@ -848,13 +975,29 @@ pub fn open_output_files(
             &output_config.prefix,
             report_html,
             report_json,
+            output_config.report_timing,
             allow_overwrite,
         )?,
         None => OutputReports {
             html: None,
             json: None,
+            timing: None,
         },
     };
+    let rejected = if parsed_config
+        .output
+        .as_ref()
+        .is_some_and(|output_config| output_config.rejected_output)
+    {
+        Some(open_one_set_of_output_files(
+            parsed_config,
+            output_directory,
+            Some("rejected"),
+            allow_overwrite,
+        )?)
+    } else {
+        None
+    };
     match demultiplexed {
         OptDemultiplex::No => {
             let output_files = open_one_set_of_output_files(
@@ -867,6 +1010,7 @@ pub fn open_output_files(
                 output_segments: vec![(0, Arc::new(Mutex::new(output_files)))]
                     .into_iter()
                     .collect(),
+                rejected,
                 output_reports,
             })
         }
@@ -895,6 +1039,7 @@ pub fn open_output_files(
             }
             Ok(OutputFiles {
                 output_segments: res,
+                rejected,
                 output_reports,
             })
         }
@@ -938,6 +1083,16 @@ pub fn output_block(
     Ok(())
 }
 
+/// Write the reads filters removed from this block (output.rejected_output)
+pub fn output_rejected_block(
+    block: &io::FastQBlocksCombined,
+    output_files: &mut OutputFastqs<OutputFile<'_>>,
+    interleave_order: &[usize],
+    buffer_size: usize,
+) -> Result<()> {
+    output_block_demultiplex(block, output_files, interleave_order, None, buffer_size)
+}
+
 #[allow(clippy::if_not_else)]
 fn output_block_demultiplex(
     block: &io::FastQBlocksCombined,
@@ -1215,14 +1370,20 @@ fn write_interleaved_blocks_to_bam(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn output_json_report(
     output_file: Option<&mut BufWriter<ex::fs::File>>,
     report_collector: &Arc<Mutex<Vec<FinalizeReportResult>>>,
+    funnel_collector: &Arc<Mutex<Option<serde_json::Value>>>,
+    timing_collector: &Arc<Mutex<Option<serde_json::Value>>>,
     report_labels: &[String],
+    report_compare: Option<&[String]>,
+    qc_thresholds: Option<&QcThresholds>,
+    demultiplex_names: &HashSet<String>,
     current_dir: &str,
     input_config: &crate::config::Input,
     raw_config: &str,
-) -> Result<String> {
+) -> Result<(String, Vec<String>)> {
     use json_value_merge::Merge;
     let mut output: serde_json::Map<String, serde_json::Value> = serde_json::Map::new();
     //store run info such as version in "__"
@@ -1266,6 +1427,44 @@ pub fn output_json_report(
         );
     }
 
+    if let Some([pre_label, post_label]) = report_compare {
+        let comparison = report_comparison::compare_reports(
+            pre_label,
+            output
+                .get(pre_label)
+                .expect("report_compare names were verified to exist"),
+            post_label,
+            output
+                .get(post_label)
+                .expect("report_compare names were verified to exist"),
+            demultiplex_names,
+        );
+        output.insert("report_comparison".to_string(), comparison);
+    }
+
+    if let Some(funnel) = funnel_collector
+        .lock()
+        .expect("mutex lock should not be poisoned")
+        .take()
+    {
+        output.insert("funnel".to_string(), funnel);
+    }
+
+    if let Some(timing) = timing_collector
+        .lock()
+        .expect("mutex lock should not be poisoned")
+        .clone()
+    {
+        output.insert("timing".to_string(), timing);
+    }
+
+    let mut qc_failed_metrics = Vec::new();
+    if let Some(qc_thresholds) = qc_thresholds {
+        let (qc, failed) = qc_thresholds::evaluate(qc_thresholds, &output);
+        output.insert("qc".to_string(), qc);
+        qc_failed_metrics = failed;
+    }
+
     let mut run_info = serde_json::Map::new();
 
     run_info.insert(
@@ -1295,7 +1494,7 @@ pub fn output_json_report(
     if let Some(output_file) = output_file {
         output_file.write_all(str_output.as_bytes())?;
     }
-    Ok(str_output)
+    Ok((str_output, qc_failed_metrics))
 }
 
 pub fn output_html_report(
@@ -1312,3 +1511,17 @@ pub fn output_html_report(
     output_file.write_all(html.as_bytes())?;
     Ok(())
 }
+
+pub fn output_timing_report(
+    output_file: &mut BufWriter<ex::fs::File>,
+    timing_collector: &Arc<Mutex<Option<serde_json::Value>>>,
+) -> Result<()> {
+    let timing = timing_collector
+        .lock()
+        .expect("mutex lock should not be poisoned");
+    if let Some(timing) = timing.as_ref() {
+        output_file.write_all(serde_json::to_string_pretty(timing)?.as_bytes())?;
+    }
+    output_file.flush()?;
+    Ok(())
+}
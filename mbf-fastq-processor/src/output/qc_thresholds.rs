@@ -0,0 +1,172 @@
+//! pass/warn/fail evaluation of report metrics ([qc_thresholds])
+use serde_json::{Map, Value};
+
+use crate::config::{QcThreshold, QcThresholds};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum QcStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl QcStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            QcStatus::Pass => "pass",
+            QcStatus::Warn => "warn",
+            QcStatus::Fail => "fail",
+        }
+    }
+}
+
+/// Judge every check against the report sections (keyed by report name).
+///
+/// Returns the 'qc' json entry and the metrics that failed.
+/// A metric that can not be found (or computed) fails.
+pub fn evaluate(thresholds: &QcThresholds, reports: &Map<String, Value>) -> (Value, Vec<String>) {
+    let mut overall = QcStatus::Pass;
+    let mut failed = Vec::new();
+    let mut checks = Vec::new();
+    for check in &thresholds.check {
+        let value = resolve_metric(reports, &check.metric);
+        let status = value.map_or(QcStatus::Fail, |value| judge(check, value));
+        overall = overall.max(status);
+        if status == QcStatus::Fail {
+            failed.push(check.metric.clone());
+        }
+        let mut entry = Map::new();
+        entry.insert("metric".to_string(), check.metric.clone().into());
+        entry.insert("value".to_string(), value.into());
+        entry.insert("status".to_string(), status.as_str().into());
+        for (key, limit) in [
+            ("warn_below", check.warn_below),
+            ("fail_below", check.fail_below),
+            ("warn_above", check.warn_above),
+            ("fail_above", check.fail_above),
+        ] {
+            if let Some(limit) = limit {
+                entry.insert(key.to_string(), limit.into());
+            }
+        }
+        checks.push(Value::Object(entry));
+    }
+    let res = serde_json::json!({
+        "status": overall.as_str(),
+        "checks": checks,
+    });
+    (res, failed)
+}
+
+fn judge(check: &QcThreshold, value: f64) -> QcStatus {
+    let below = |limit: Option<f64>| limit.is_some_and(|limit| value < limit);
+    let above = |limit: Option<f64>| limit.is_some_and(|limit| value > limit);
+    if below(check.fail_below) || above(check.fail_above) {
+        QcStatus::Fail
+    } else if below(check.warn_below) || above(check.warn_above) {
+        QcStatus::Warn
+    } else {
+        QcStatus::Pass
+    }
+}
+
+/// Follow the dotted path through the reports.
+///
+/// If the last key is missing and ends in '_fraction', it's derived:
+/// `q20/q30/gc_fraction` from a segment's `base_statistics` (relative to `total_bases`),
+/// anything else relative to the closest enclosing `molecule_count`.
+#[allow(clippy::cast_precision_loss)]
+fn resolve_metric(reports: &Map<String, Value>, metric: &str) -> Option<f64> {
+    let mut keys = metric.split('.');
+    let mut current = reports.get(keys.next()?)?;
+    let mut molecule_count = None;
+    let mut keys = keys.peekable();
+    while let Some(key) = keys.next() {
+        if let Some(count) = current.get("molecule_count").and_then(Value::as_u64) {
+            molecule_count = Some(count);
+        }
+        match current.get(key) {
+            Some(next) => current = next,
+            None if keys.peek().is_none() => {
+                let stem = key.strip_suffix("_fraction")?;
+                let (numerator, denominator) = if let Some(base_statistics) =
+                    current.get("base_statistics")
+                    && let Some(bases) = base_statistics.get(format!("{stem}_bases"))
+                {
+                    (
+                        bases.as_u64()?,
+                        base_statistics.get("total_bases")?.as_u64()?,
+                    )
+                } else {
+                    (current.get(stem)?.as_u64()?, molecule_count?)
+                };
+                return (denominator > 0).then(|| numerator as f64 / denominator as f64);
+            }
+            None => return None,
+        }
+    }
+    current.as_f64()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn check(metric: &str, warn_below: Option<f64>, fail_below: Option<f64>) -> QcThreshold {
+        QcThreshold {
+            metric: metric.to_string(),
+            warn_below,
+            fail_below,
+            warn_above: None,
+            fail_above: None,
+        }
+    }
+
+    #[test]
+    fn test_resolve_metric() {
+        let reports = json!({
+            "report": {
+                "molecule_count": 10,
+                "read1": {
+                    "duplicate_count": 2,
+                    "base_statistics": {"total_bases": 100, "q30_bases": 90}
+                },
+                "count_oligos": {"AGATCGGAAGAGC": 1}
+            }
+        });
+        let reports = reports.as_object().expect("object");
+        assert_eq!(resolve_metric(reports, "report.molecule_count"), Some(10.0));
+        assert_eq!(
+            resolve_metric(reports, "report.read1.q30_fraction"),
+            Some(0.9)
+        );
+        assert_eq!(
+            resolve_metric(reports, "report.read1.duplicate_count_fraction"),
+            Some(0.2)
+        );
+        assert_eq!(
+            resolve_metric(reports, "report.count_oligos.AGATCGGAAGAGC_fraction"),
+            Some(0.1)
+        );
+        assert_eq!(resolve_metric(reports, "report.read2.q30_fraction"), None);
+        assert_eq!(resolve_metric(reports, "other.molecule_count"), None);
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let thresholds = QcThresholds {
+            fail_exit_code: None,
+            check: vec![
+                check("report.molecule_count", Some(20.0), Some(5.0)),
+                check("report.missing", None, Some(1.0)),
+            ],
+        };
+        let reports = json!({"report": {"molecule_count": 10}});
+        let (res, failed) = evaluate(&thresholds, reports.as_object().expect("object"));
+        assert_eq!(res["status"], "fail");
+        assert_eq!(res["checks"][0]["status"], "warn");
+        assert_eq!(res["checks"][1]["value"], Value::Null);
+        assert_eq!(failed, vec!["report.missing".to_string()]);
+    }
+}
@@ -0,0 +1,245 @@
+//! before/after comparison of two Report steps (output.report_compare)
+use indexmap::IndexMap;
+use serde_json::{Value, json};
+use std::collections::HashSet;
+
+/// Values that can't be summed across demultiplexed outputs.
+const NON_ADDITIVE_KEYS: [&str; 1] = ["expected_errors_from_quality_curve"];
+
+/// Compare the report sections `pre` and `post`.
+///
+/// Produces the per metric deltas (post - pre), reads removed, bases removed per segment,
+/// and the curves present in both for overlaying.
+/// If only one side is demultiplexed, its barcodes are summed up first.
+pub fn compare_reports(
+    pre_label: &str,
+    pre: &Value,
+    post_label: &str,
+    post: &Value,
+    demultiplex_names: &HashSet<String>,
+) -> Value {
+    let pre_demultiplexed = is_demultiplexed(pre, demultiplex_names);
+    let post_demultiplexed = is_demultiplexed(post, demultiplex_names);
+    let (pre, post) = if pre_demultiplexed == post_demultiplexed {
+        (pre.clone(), post.clone())
+    } else {
+        (
+            if pre_demultiplexed {
+                collapse_demultiplexed(pre)
+            } else {
+                pre.clone()
+            },
+            if post_demultiplexed {
+                collapse_demultiplexed(post)
+            } else {
+                post.clone()
+            },
+        )
+    };
+
+    let mut pre_scalars = IndexMap::new();
+    let mut pre_curves = IndexMap::new();
+    collect_numbers("", &pre, &mut pre_scalars, &mut pre_curves);
+    let mut post_scalars = IndexMap::new();
+    let mut post_curves = IndexMap::new();
+    collect_numbers("", &post, &mut post_scalars, &mut post_curves);
+
+    let mut deltas = serde_json::Map::new();
+    for (path, pre_value) in &pre_scalars {
+        if let Some(post_value) = post_scalars.get(path) {
+            deltas.insert(
+                path.clone(),
+                json!({
+                    "pre": pre_value,
+                    "post": post_value,
+                    "delta": subtract(post_value, pre_value),
+                }),
+            );
+        }
+    }
+
+    let mut curves = serde_json::Map::new();
+    for (path, pre_value) in &pre_curves {
+        if let Some(post_value) = post_curves.get(path) {
+            curves.insert(path.clone(), json!({"pre": pre_value, "post": post_value}));
+        }
+    }
+
+    let mut res = serde_json::Map::new();
+    res.insert("pre".to_string(), json!(pre_label));
+    res.insert("post".to_string(), json!(post_label));
+    if let (Some(pre_count), Some(post_count)) = (
+        pre_scalars.get("molecule_count"),
+        post_scalars.get("molecule_count"),
+    ) {
+        res.insert("reads_removed".to_string(), subtract(pre_count, post_count));
+    }
+    let pre_bases = bases_per_segment(&pre);
+    let post_bases = bases_per_segment(&post);
+    let mut bases_removed = serde_json::Map::new();
+    for (segment, pre_count) in &pre_bases {
+        if let Some(post_count) = post_bases.get(segment) {
+            bases_removed.insert(
+                segment.clone(),
+                subtract(&json!(pre_count), &json!(post_count)),
+            );
+        }
+    }
+    if !bases_removed.is_empty() {
+        res.insert("bases_removed".to_string(), Value::Object(bases_removed));
+    }
+    res.insert("deltas".to_string(), Value::Object(deltas));
+    res.insert("curves".to_string(), Value::Object(curves));
+    Value::Object(res)
+}
+
+/// A demultiplexed report section has only the output names as top level keys
+fn is_demultiplexed(section: &Value, demultiplex_names: &HashSet<String>) -> bool {
+    section
+        .as_object()
+        .is_some_and(|obj| !obj.is_empty() && obj.keys().all(|key| demultiplex_names.contains(key)))
+}
+
+fn collapse_demultiplexed(section: &Value) -> Value {
+    let mut acc = Value::Null;
+    if let Some(obj) = section.as_object() {
+        for sub_section in obj.values() {
+            add_values(&mut acc, sub_section);
+        }
+    }
+    acc
+}
+
+fn add_values(acc: &mut Value, other: &Value) {
+    if acc.is_null() {
+        *acc = other.clone();
+        strip_non_additive(acc);
+        return;
+    }
+    if acc.is_number() && other.is_number() {
+        *acc = add(acc, other);
+        return;
+    }
+    match (acc, other) {
+        (Value::Array(acc), Value::Array(other)) => {
+            if acc.len() < other.len() {
+                acc.resize(other.len(), json!(0));
+            }
+            for (a, b) in acc.iter_mut().zip(other) {
+                *a = add(a, b);
+            }
+        }
+        (Value::Object(acc), Value::Object(other)) => {
+            for (key, value) in other {
+                if !NON_ADDITIVE_KEYS.contains(&key.as_str()) {
+                    add_values(acc.entry(key.clone()).or_insert(Value::Null), value);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn strip_non_additive(value: &mut Value) {
+    if let Value::Object(obj) = value {
+        for key in NON_ADDITIVE_KEYS {
+            obj.remove(key);
+        }
+        for sub_value in obj.values_mut() {
+            strip_non_additive(sub_value);
+        }
+    }
+}
+
+fn add(a: &Value, b: &Value) -> Value {
+    match (a.as_u64(), b.as_u64()) {
+        (Some(a), Some(b)) => json!(a + b),
+        _ => json!(a.as_f64().unwrap_or(0.0) + b.as_f64().unwrap_or(0.0)),
+    }
+}
+
+fn subtract(a: &Value, b: &Value) -> Value {
+    match (a.as_i64(), b.as_i64()) {
+        (Some(a), Some(b)) => json!(a - b),
+        _ => json!(a.as_f64().unwrap_or(0.0) - b.as_f64().unwrap_or(0.0)),
+    }
+}
+
+/// Collect numeric scalars and numeric arrays (curves) by their '.' joined path
+fn collect_numbers(
+    prefix: &str,
+    value: &Value,
+    scalars: &mut IndexMap<String, Value>,
+    curves: &mut IndexMap<String, Value>,
+) {
+    match value {
+        Value::Object(obj) => {
+            for (key, sub_value) in obj {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{prefix}.{key}")
+                };
+                collect_numbers(&path, sub_value, scalars, curves);
+            }
+        }
+        Value::Array(values) => {
+            if values.iter().all(Value::is_number) {
+                curves.insert(prefix.to_string(), value.clone());
+            }
+        }
+        Value::Number(_) => {
+            scalars.insert(prefix.to_string(), value.clone());
+        }
+        _ => {}
+    }
+}
+
+/// Bases per segment - from base_statistics if available, otherwise from the length distribution
+fn bases_per_segment(section: &Value) -> IndexMap<String, u64> {
+    let mut res = IndexMap::new();
+    if let Some(obj) = section.as_object() {
+        for (segment, segment_data) in obj {
+            if let Some(total) = segment_data
+                .pointer("/base_statistics/total_bases")
+                .and_then(Value::as_u64)
+            {
+                res.insert(segment.clone(), total);
+            } else if let Some(distribution) = segment_data
+                .get("length_distribution")
+                .and_then(Value::as_array)
+            {
+                let total = distribution
+                    .iter()
+                    .enumerate()
+                    .map(|(length, count)| length as u64 * count.as_u64().unwrap_or(0))
+                    .sum();
+                res.insert(segment.clone(), total);
+            }
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_collapses_demultiplexed() {
+        let pre = json!({"molecule_count": 10, "read1": {"length_distribution": [0, 4, 6]}});
+        let post = json!({
+            "a": {"molecule_count": 3, "read1": {"length_distribution": [0, 3]}},
+            "b": {"molecule_count": 4, "read1": {"length_distribution": [0, 0, 4]}},
+        });
+        let names: HashSet<String> = ["a".to_string(), "b".to_string()].into_iter().collect();
+        let res = compare_reports("pre", &pre, "post", &post, &names);
+        assert_eq!(res["reads_removed"], json!(3));
+        assert_eq!(res["bases_removed"]["read1"], json!(16 - 11));
+        assert_eq!(res["deltas"]["molecule_count"]["delta"], json!(-3));
+        assert_eq!(
+            res["curves"]["read1.length_distribution"]["post"],
+            json!([0, 3, 4])
+        );
+    }
+}
@@ -2,32 +2,36 @@ use anyhow::{Context, Result, bail};
 use crossbeam::channel::{bounded, unbounded};
 use indexmap::IndexMap;
 use std::{
-    collections::BTreeMap,
-    panic,
+    collections::{BTreeMap, HashSet},
     path::{Path, PathBuf},
     sync::{Arc, Mutex},
     thread,
 };
 
 use crate::{
+    checkpoint::{CheckpointSettings, InputPositions, PendingCheckpoint},
     config::{CheckedConfig, StructuredInput},
     demultiplex::{DemultiplexBarcodes, DemultiplexInfo, OptDemultiplex},
     io::{
         self,
-        parsers::{ChainedParser, ThreadCount},
+        parsers::{ChainedParser, InputPosition, ThreadCount},
+    },
+    output::{
+        open_output_files, output_block, output_html_report, output_json_report,
+        output_rejected_block, output_timing_report,
     },
-    output::{open_output_files, output_block, output_html_report, output_json_report},
     transformations::{self, FinalizeReportResult, Step, Transformation},
 };
 
 #[allow(clippy::collapsible_if)]
 fn parse_and_send(
     readers: Vec<io::InputFile>,
-    raw_tx: &crossbeam::channel::Sender<(io::FastQBlock, Option<usize>)>,
+    raw_tx: &crossbeam::channel::Sender<(io::FastQBlock, Option<usize>, Option<InputPosition>)>,
     buffer_size: usize,
     block_size: usize,
     input_thread_count: ThreadCount,
     input_options: crate::config::InputOptions,
+    resume_at: Option<InputPosition>,
 ) -> Result<()> {
     let mut parser = ChainedParser::new(
         readers,
@@ -36,11 +40,14 @@ fn parse_and_send(
         input_thread_count,
         input_options,
     );
+    if let Some(position) = resume_at {
+        parser.resume_at(&position)?;
+    }
     loop {
         let res = parser.parse()?;
         if !res.fastq_block.entries.is_empty() || !res.was_final {
             if raw_tx
-                .send((res.fastq_block, res.expected_read_count))
+                .send((res.fastq_block, res.expected_read_count, parser.position()))
                 .is_err()
             {
                 break;
@@ -53,6 +60,7 @@ fn parse_and_send(
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 fn parse_interleaved_and_send(
     readers: Vec<io::InputFile>,
     combiner_output_tx: &crossbeam::channel::Sender<(
@@ -65,6 +73,8 @@ fn parse_interleaved_and_send(
     input_thread_count: ThreadCount,
     block_size: usize,
     input_options: crate::config::InputOptions,
+    resume_at: Option<InputPosition>,
+    input_positions: Option<InputPositions>,
 ) -> Result<()> {
     let mut parser = ChainedParser::new(
         readers,
@@ -73,6 +83,9 @@ fn parse_interleaved_and_send(
         input_thread_count,
         input_options,
     );
+    if let Some(position) = resume_at {
+        parser.resume_at(&position)?;
+    }
     let mut block_no = 1;
     let mut expected_read_count = None;
     loop {
@@ -91,9 +104,13 @@ fn parse_interleaved_and_send(
                     output_tags: None,
                     tags: IndexMap::default(),
                     is_final: false,
+                    rejected: None,
                 },
                 expected_read_count,
             );
+            if let Some(input_positions) = &input_positions {
+                input_positions.insert(block_no, vec![parser.position()]);
+            }
             block_no += 1; // the receiver verifies this!
             if combiner_output_tx.send(out).is_err() {
                 break;
@@ -107,6 +124,7 @@ fn parse_interleaved_and_send(
                 output_tags: None,
                 tags: IndexMap::default(),
                 is_final: true,
+                rejected: None,
             };
             let _ = combiner_output_tx.send((block_no, final_block, expected_read_count));
             break;
@@ -117,10 +135,13 @@ fn parse_interleaved_and_send(
 
 #[allow(clippy::needless_pass_by_value)]
 fn run_combiner_thread(
-    raw_rx_readers: Vec<crossbeam::channel::Receiver<(io::FastQBlock, Option<usize>)>>,
+    raw_rx_readers: Vec<
+        crossbeam::channel::Receiver<(io::FastQBlock, Option<usize>, Option<InputPosition>)>,
+    >,
     combiner_output_tx: crossbeam::channel::Sender<(usize, io::FastQBlocksCombined, Option<usize>)>,
     largest_segment_idx: usize,
     error_collector: Arc<Mutex<Vec<String>>>,
+    input_positions: Option<InputPositions>,
 ) {
     //I need to receive the blocks (from all segment input threads)
     //and then, match them up into something that's the same length!
@@ -128,22 +149,26 @@ fn run_combiner_thread(
     let mut expected_read_count = None;
     loop {
         let mut blocks = Vec::new();
+        let mut positions = Vec::new();
         for receiver in &raw_rx_readers {
             //since we read the channels in order,
             //the resulting blocks will also be in order.
-            if let Ok((block, block_expected_read_count)) = receiver.recv() {
+            if let Ok((block, block_expected_read_count, position)) = receiver.recv() {
                 if block_no == 1 && blocks.len() == largest_segment_idx {
                     //println!("Received expected read count for largest segment: {:?}", block_expected_read_count);
                     expected_read_count = block_expected_read_count;
                 }
                 blocks.push(block);
+                positions.push(position);
             } else if blocks.is_empty() {
                 //The first segment reader is done.
                 //that's the expected behaviour when we're running out of reads.
                 //now every other reader should also be returning an error.
                 //because otherwise the others have more remaining reads
                 for other_receiver in &raw_rx_readers[1..] {
-                    if let Ok((_block, _block_expected_read_count)) = other_receiver.recv() {
+                    if let Ok((_block, _block_expected_read_count, _position)) =
+                        other_receiver.recv()
+                    {
                         error_collector.lock().expect("mutex lock should not be poisoned").push("Unequal number of reads in the segment inputs (first < later). Check your fastqs for identical read counts".to_string());
                     }
                 }
@@ -157,6 +182,7 @@ fn run_combiner_thread(
                     output_tags: None,
                     tags: Default::default(),
                     is_final: true,
+                    rejected: None,
                 };
                 let _ = combiner_output_tx.send((block_no, final_block, expected_read_count));
                 return;
@@ -179,9 +205,13 @@ fn run_combiner_thread(
                 output_tags: None,
                 tags: Default::default(),
                 is_final: false,
+                rejected: None,
             },
             expected_read_count,
         );
+        if let Some(input_positions) = &input_positions {
+            input_positions.insert(block_no, positions);
+        }
         block_no += 1;
         match combiner_output_tx.send(out) {
             Ok(()) => {}
@@ -251,6 +281,7 @@ fn run_benchmark_combiner_thread(
         output_tags: None,
         tags: Default::default(),
         is_final: true,
+        rejected: None,
     };
     let _ = combiner_output_tx.send((block_no, final_block, Some(molecule_count)));
 }
@@ -285,6 +316,7 @@ fn run_benchmark_interleaved_thread(
                 output_tags: None,
                 tags: Default::default(),
                 is_final: false,
+                rejected: None,
             },
             Some(molecule_count),
         );
@@ -310,13 +342,119 @@ fn run_benchmark_interleaved_thread(
         output_tags: None,
         tags: Default::default(),
         is_final: true,
+        rejected: None,
     };
     let _ = combiner_output_tx.send((block_no, final_block, Some(molecule_count)));
 }
 
+/// [[branch]]: the fan out's connection to the common steps' workpool
+struct CommonStepsLink {
+    output_done_tx: crossbeam::channel::Sender<usize>,
+    /// tell the coordinator that no branch takes further blocks
+    output_closed_tx: crossbeam::channel::Sender<()>,
+    /// dropped once the common steps are done, and their reports complete
+    _finished_tx: crossbeam::channel::Sender<()>,
+}
+
+/// [[branch]]: hand every block to each branch's pipeline, in block order.
+/// Branches that stopped accepting blocks (e.g. after a Head) are skipped,
+/// and once all of them did, we stop reading - after common steps by having
+/// those stop, and discarding what they still had in flight.
+/// The last active branch gets the block itself, the others a copy.
+#[allow(clippy::needless_pass_by_value)]
+fn run_branch_fan_out_thread(
+    incoming_rx: crossbeam::channel::Receiver<(usize, io::FastQBlocksCombined, Option<usize>)>,
+    branch_txs: Vec<crossbeam::channel::Sender<(usize, io::FastQBlocksCombined, Option<usize>)>>,
+    common_steps: Option<CommonStepsLink>,
+) {
+    let mut branch_txs: Vec<_> = branch_txs.into_iter().map(Some).collect();
+    // the common steps finish blocks out of order
+    let mut buffer = BTreeMap::new();
+    let mut next_block_no = 1;
+    while let Ok((block_no, block, expected_read_count)) = incoming_rx.recv() {
+        buffer.insert(block_no, (block, expected_read_count));
+        while let Some((block, expected_read_count)) = buffer.remove(&next_block_no) {
+            send_to_branches(&mut branch_txs, next_block_no, block, expected_read_count);
+            if let Some(common_steps) = common_steps.as_ref() {
+                common_steps.output_done_tx.send(next_block_no).ok();
+            }
+            next_block_no += 1;
+        }
+        if branch_txs.iter().all(Option::is_none) {
+            break;
+        }
+    }
+    if let Some(common_steps) = common_steps {
+        // (if the input isn't done yet, the branches are)
+        common_steps.output_closed_tx.send(()).ok();
+        for block_no in buffer
+            .into_keys()
+            .chain(incoming_rx.iter().map(|(block_no, _, _)| block_no))
+        {
+            common_steps.output_done_tx.send(block_no).ok();
+        }
+    }
+}
+
+fn send_to_branches(
+    branch_txs: &mut [Option<
+        crossbeam::channel::Sender<(usize, io::FastQBlocksCombined, Option<usize>)>,
+    >],
+    block_no: usize,
+    block: io::FastQBlocksCombined,
+    expected_read_count: Option<usize>,
+) {
+    let Some(last_active) = branch_txs.iter().rposition(Option::is_some) else {
+        return;
+    };
+    let mut block = Some(block);
+    for (ii, branch_tx) in branch_txs.iter_mut().enumerate().take(last_active + 1) {
+        let Some(tx) = branch_tx.as_ref() else {
+            continue;
+        };
+        let branch_block = if ii == last_active {
+            block.take().expect("the last branch takes the block")
+        } else {
+            block.as_ref().expect("not taken yet").clone()
+        };
+        if tx
+            .send((block_no, branch_block, expected_read_count))
+            .is_err()
+        {
+            *branch_tx = None;
+        }
+    }
+}
+
+/// Called with every output block, in input order (library use, see [`crate::api`])
+pub type BlockSink = Box<dyn FnMut(&io::FastQBlocksCombined) -> Result<()> + Send>;
+/// Called once with the finished json report (library use, see [`crate::api`])
+pub type ReportSink = Box<dyn FnOnce(serde_json::Value) + Send>;
+
+/// In-process receivers for the pipeline's output.
+///
+/// Output files are still written as configured (use `format = 'None'` to write none),
+/// but a report sink replaces the report files.
+#[derive(Default)]
+pub struct OutputSinks {
+    pub blocks: Option<BlockSink>,
+    pub report: Option<ReportSink>,
+}
+
 pub struct RunStage0 {
     report_html: bool,
     report_json: bool,
+    sinks: OutputSinks,
+    checkpoint: Option<CheckpointSettings>,
+    upstream: Option<UpstreamState>,
+}
+
+/// [[branch]]: what the common steps hand on to each branch's steps
+#[derive(Clone)]
+struct UpstreamState {
+    demultiplex_info: OptDemultiplex,
+    demultiplex_bits: u16,
+    progress_output: Option<transformations::reports::Progress>,
 }
 
 #[allow(clippy::cast_possible_truncation)]
@@ -334,7 +472,37 @@ impl RunStage0 {
         RunStage0 {
             report_html: parsed.output.as_ref().is_some_and(|o| o.report_html),
             report_json: parsed.output.as_ref().is_some_and(|o| o.report_json),
+            sinks: OutputSinks::default(),
+            checkpoint: None,
+            upstream: None,
+        }
+    }
+
+    /// [[branch]]: these stages continue where the common steps left off
+    /// (their demultiplexing, their Progress).
+    #[must_use]
+    pub fn after_common_steps(mut self, common_steps: &RunStage1) -> Self {
+        self.upstream = Some(common_steps.upstream_state.clone());
+        self
+    }
+
+    /// Write checkpoints (output.checkpoint_interval) and/or resume from one.
+    #[must_use]
+    pub(crate) fn with_checkpoint(mut self, checkpoint: Option<CheckpointSettings>) -> Self {
+        self.checkpoint = checkpoint;
+        self
+    }
+
+    /// Hand output blocks and/or the report to callbacks.
+    /// A report sink replaces the report files.
+    #[must_use]
+    pub fn with_output_sinks(mut self, sinks: OutputSinks) -> Self {
+        if sinks.report.is_some() {
+            self.report_html = false;
+            self.report_json = false;
         }
+        self.sinks = sinks;
+        self
     }
 
     #[allow(clippy::too_many_lines)]
@@ -350,6 +518,24 @@ impl RunStage0 {
             .map_or("mbf_fastq_preprocessor_output", |x| &x.prefix)
             .to_string();
         let output_ix_separator = parsed.get_ix_separator();
+        if self.checkpoint.is_some() {
+            for stage in &parsed.stages {
+                if !stage.transformation.supports_checkpoint() {
+                    let action = stage.transformation.action_name();
+                    let step = match stage.step_no {
+                        Some(step_no) => format!("Step {step_no} ({action})"),
+                        None => format!("Internal step ({action})"),
+                    };
+                    bail!(
+                        "{step} does not support checkpoints (output.checkpoint_interval / --resume)."
+                    );
+                }
+            }
+        }
+
+        if let Some(memory_limit) = parsed.options.memory_limit {
+            crate::spill::share_memory_limit(&mut parsed.stages, memory_limit);
+        }
 
         let input_info = transformations::InputInfo {
             segment_order: parsed.input.get_segment_order().clone(),
@@ -359,10 +545,20 @@ impl RunStage0 {
             use_rapidgzip: parsed.input.options.use_rapidgzip.is_some_and(|x| x),
         };
         let mut demultiplex_infos: Vec<(usize, OptDemultiplex)> = Vec::new();
+        let mut current_bit_start = 0;
+        if let Some(upstream) = self.upstream.as_ref() {
+            if matches!(upstream.demultiplex_info, OptDemultiplex::Yes(_)) {
+                demultiplex_infos.push((0, upstream.demultiplex_info.clone()));
+            }
+            current_bit_start = upstream.demultiplex_bits;
+        }
         // we need to initialize the progress_output first
         // so we can store it on each stage before the stages' init
         let progress_output = {
-            let mut res = None;
+            let mut res = self
+                .upstream
+                .as_ref()
+                .and_then(|upstream| upstream.progress_output.clone());
             for step in &mut parsed.stages {
                 if let Transformation::Progress(inner) = &mut step.transformation {
                     inner.init(
@@ -383,8 +579,6 @@ impl RunStage0 {
         // and at each stage, it get's to see the tag->output names up to the latest defined
         // demultiplexing step
         // We then have two
-        let mut current_bit_start = 0;
-
         for (index, stage) in (parsed.stages).iter_mut().enumerate() {
             if !matches!(stage.transformation, Transformation::Progress(_)) {
                 //progress was initialized before hand
@@ -498,6 +692,36 @@ impl RunStage0 {
             }
         }
 
+        let mut checkpoint = self.checkpoint;
+        if let Some(resume_from) = checkpoint
+            .as_mut()
+            .and_then(|checkpoint| checkpoint.resume_from.as_mut())
+        {
+            let step_states = std::mem::take(&mut resume_from.step_states);
+            if step_states.len() != parsed.stages.len() {
+                bail!(
+                    "Checkpoint has {} step states, but there are {} steps - can not resume.",
+                    step_states.len(),
+                    parsed.stages.len()
+                );
+            }
+            for (stage, state) in parsed.stages.iter_mut().zip(step_states) {
+                if let Some(state) = state {
+                    stage
+                        .transformation
+                        .restore_checkpoint_state(state)
+                        .context("Failed to restore step state from checkpoint")?;
+                }
+            }
+        }
+
+        let upstream_state = UpstreamState {
+            demultiplex_info: demultiplex_infos
+                .last()
+                .map_or(OptDemultiplex::No, |x| x.1.clone()),
+            demultiplex_bits: current_bit_start,
+            progress_output,
+        };
         Ok(RunStage1 {
             input_info,
             report_html: self.report_html,
@@ -505,6 +729,9 @@ impl RunStage0 {
             output_directory: output_directory.to_owned(),
             demultiplex_infos,
             allow_overwrite,
+            sinks: self.sinks,
+            checkpoint,
+            upstream_state,
         })
     }
 }
@@ -519,17 +746,26 @@ pub struct RunStage1 {
     report_html: bool,
     report_json: bool,
     allow_overwrite: bool,
+    sinks: OutputSinks,
+    checkpoint: Option<CheckpointSettings>,
+    upstream_state: UpstreamState,
 }
 
 impl RunStage1 {
-    #[allow(clippy::too_many_lines, clippy::similar_names)]
     pub fn create_input_threads(self, parsed: &CheckedConfig) -> Result<RunStage2> {
-        let orig_hook = panic::take_hook();
-        panic::set_hook(Box::new(move |panic_info| {
-            // invoke the default handler and exit the process
-            orig_hook(panic_info);
-            std::process::exit(1);
-        }));
+        let input_files =
+            io::open_input_files(&parsed.input).context("Error opening input files")?;
+        self.create_input_threads_from(parsed, input_files)
+    }
+
+    /// Like `create_input_threads`, but reads from already opened
+    /// (e.g. in-memory) input files.
+    #[allow(clippy::too_many_lines, clippy::similar_names)]
+    pub fn create_input_threads_from(
+        self,
+        parsed: &CheckedConfig,
+        mut input_files: io::InputFiles,
+    ) -> Result<RunStage2> {
         let input_config = &parsed.input;
         let threads_per_parser = ThreadCount(
             input_config
@@ -537,9 +773,6 @@ impl RunStage1 {
                 .threads_per_segment
                 .expect("Must have been set by config"),
         );
-        let mut input_files =
-            io::open_input_files(input_config).context("Error opening input files")?;
-
         let block_size = parsed.options.block_size;
         let buffer_size = parsed.options.buffer_size;
         let channel_size = 2;
@@ -547,6 +780,18 @@ impl RunStage1 {
         let input_options = parsed.input.options.clone();
 
         let largest_segment_idx = input_files.largest_segment_idx;
+        let mut checkpoint = self.checkpoint;
+        let input_positions = checkpoint
+            .as_ref()
+            .map(|checkpoint| checkpoint.input_positions.clone());
+        // resuming: continue every input at its recorded position - if all of them have one
+        let mut resume_positions = |parser_count: usize| {
+            checkpoint.as_mut().and_then(|checkpoint| {
+                let positions = checkpoint.resume_positions(parser_count)?;
+                checkpoint.seek_inputs = true;
+                Some(positions)
+            })
+        };
 
         let (input_threads, combiner_thread, combiner_output_rx) = if let Some(benchmark) =
             &parsed.benchmark
@@ -641,6 +886,7 @@ impl RunStage1 {
                             output_tags: None,
                             tags: Default::default(),
                             is_final: false,
+                            rejected: None,
                         };
 
                         let combiner_thread = thread::Builder::new()
@@ -666,6 +912,7 @@ impl RunStage1 {
                 StructuredInput::Interleaved { segment_order, .. } => {
                     let error_collector = error_collector.clone();
                     let segment_order_len = segment_order.len();
+                    let resume_at = resume_positions(1).and_then(|mut positions| positions.pop());
                     let input_threads = Vec::new();
                     let (combiner_output_tx, combiner_output_rx) =
                         bounded::<(usize, io::FastQBlocksCombined, Option<usize>)>(channel_size);
@@ -683,6 +930,8 @@ impl RunStage1 {
                             threads_per_parser,
                             block_size,
                             options,
+                            resume_at,
+                            input_positions,
                         ) {
                             error_collector
                                 .lock()
@@ -703,10 +952,13 @@ impl RunStage1 {
                     // and another thread that collects the blocks into combined blocks
                     let mut threads = Vec::new();
                     let mut raw_rx_readers = Vec::new();
+                    let mut segment_resume_positions =
+                        resume_positions(segment_order.len()).map(Vec::into_iter);
                     for (segment_name, this_segments_input_files) in segment_order
                         .iter()
                         .zip(input_files.segment_files.segments.into_iter())
                     {
+                        let resume_at = segment_resume_positions.as_mut().and_then(Iterator::next);
                         let segment_name = segment_name.clone();
                         let error_collector = error_collector.clone();
                         let options = input_options.clone();
@@ -721,6 +973,7 @@ impl RunStage1 {
                                     block_size,
                                     threads_per_parser,
                                     options,
+                                    resume_at,
                                 ) {
                                     error_collector
                                     .lock()
@@ -747,6 +1000,7 @@ impl RunStage1 {
                                     combiner_output_tx,
                                     largest_segment_idx,
                                     error_collector,
+                                    input_positions,
                                 );
                             })
                             .expect("thread spawn should not fail");
@@ -763,14 +1017,88 @@ impl RunStage1 {
             report_json: self.report_json,
             demultiplex_infos: self.demultiplex_infos,
             input_threads,
-            combiner_thread,
+            combiner_threads: vec![combiner_thread],
             combiner_output_rx,
             error_collector,
             allow_overwrite: self.allow_overwrite,
+            sinks: self.sinks,
+            checkpoint,
+            common_reports: None,
         })
     }
 }
 
+impl RunStage1 {
+    /// [[branch]]: one set of input threads for all branches, followed by the
+    /// common steps (`self`, with `parsed.stages` being the common stages) - run once,
+    /// every block they finish is handed to each branch.
+    /// `branches` are configured `after_common_steps`, in order.
+    /// Returns one `RunStage2` per branch.
+    pub fn create_branched_input_threads(
+        self,
+        branches: Vec<RunStage1>,
+        parsed: &mut CheckedConfig,
+    ) -> Result<Vec<RunStage2>> {
+        let channel_size = 2;
+        let mut common_steps = self.create_input_threads(parsed)?;
+        let input_threads = std::mem::take(&mut common_steps.input_threads);
+        let mut combiner_threads = std::mem::take(&mut common_steps.combiner_threads);
+        let error_collector = common_steps.error_collector.clone();
+        let (incoming_rx, link, common_reports) = if parsed.stages.is_empty() {
+            (common_steps.combiner_output_rx, None, None)
+        } else {
+            let (output_closed_tx, output_closed_rx) = bounded(1);
+            let (finished_tx, finished_rx) = bounded(0);
+            let common_steps = common_steps.create_workpool(parsed, output_closed_rx);
+            let link = CommonStepsLink {
+                output_done_tx: common_steps.output_done_tx,
+                output_closed_tx,
+                _finished_tx: finished_tx,
+            };
+            (
+                common_steps.stage_to_output_channel,
+                Some(link),
+                Some((common_steps.report_collector, finished_rx)),
+            )
+        };
+
+        let mut res = Vec::new();
+        let mut branch_txs = Vec::new();
+        for branch in branches {
+            let (tx, rx) = bounded(channel_size);
+            branch_txs.push(tx);
+            res.push(RunStage2 {
+                input_info: branch.input_info,
+                output_directory: branch.output_directory,
+                report_html: branch.report_html,
+                report_json: branch.report_json,
+                demultiplex_infos: branch.demultiplex_infos,
+                input_threads: Vec::new(),
+                combiner_threads: Vec::new(),
+                combiner_output_rx: rx,
+                error_collector: Arc::new(Mutex::new(Vec::new())),
+                allow_overwrite: branch.allow_overwrite,
+                sinks: branch.sinks,
+                checkpoint: branch.checkpoint,
+                common_reports: common_reports.clone(),
+            });
+        }
+        let fan_out = thread::Builder::new()
+            .name("BranchFanOut".into())
+            .spawn(move || {
+                run_branch_fan_out_thread(incoming_rx, branch_txs, link);
+            })
+            .expect("thread spawn should not fail");
+        combiner_threads.push(fan_out);
+        // the first branch reports the input's & the common steps' errors
+        let first = res.first_mut().context("[[branch]]: no branches")?;
+        first.input_threads = input_threads;
+        first.combiner_threads = combiner_threads;
+        first.error_collector = error_collector;
+        Ok(res)
+    }
+}
+
 #[allow(clippy::struct_excessive_bools)]
 pub struct RunStage2 {
     input_info: transformations::InputInfo,
@@ -780,13 +1108,24 @@ pub struct RunStage2 {
     demultiplex_infos: Vec<(usize, OptDemultiplex)>,
 
     input_threads: Vec<thread::JoinHandle<()>>,
-    combiner_thread: thread::JoinHandle<()>,
+    /// the combiner (and the [[branch]] fan out) - empty for all but the first branch
+    combiner_threads: Vec<thread::JoinHandle<()>>,
     combiner_output_rx:
         crossbeam::channel::Receiver<(usize, io::FastQBlocksCombined, Option<usize>)>,
 
     error_collector: Arc<Mutex<Vec<String>>>,
     allow_overwrite: bool,
+    sinks: OutputSinks,
+    checkpoint: Option<CheckpointSettings>,
+    /// [[branch]]: the common steps' reports - complete once the receiver disconnects
+    common_reports: Option<CommonReports>,
 }
+
+type CommonReports = (
+    Arc<Mutex<Vec<FinalizeReportResult>>>,
+    crossbeam::channel::Receiver<()>,
+);
+
 impl RunStage2 {
     #[allow(clippy::too_many_lines)]
     pub fn create_stage_threads(self, parsed: &mut CheckedConfig) -> RunStage3 {
@@ -794,7 +1133,16 @@ impl RunStage2 {
     }
 
     pub fn create_workpool_pipeline(self, parsed: &mut CheckedConfig) -> RunStage3 {
-        use crate::pipeline_workpool::{WorkpoolCoordinator, worker_thread};
+        self.create_workpool(parsed, crossbeam::channel::never())
+    }
+
+    #[allow(clippy::too_many_lines)]
+    fn create_workpool(
+        self,
+        parsed: &mut CheckedConfig,
+        output_closed_rx: crossbeam::channel::Receiver<()>,
+    ) -> RunStage3 {
+        use crate::pipeline_workpool::{CoordinatorCheckpoint, WorkpoolCoordinator, worker_thread};
 
         //take the stages out of parsed now
         let stages = std::mem::take(&mut parsed.stages);
@@ -819,6 +1167,41 @@ impl RunStage2 {
         ));
         let coordinator_report_collector = report_collector.clone();
         let coordinator_demultiplex_infos = demultiplex_infos.clone();
+        let count_funnel = parsed
+            .output
+            .as_ref()
+            .is_some_and(|output| output.report_funnel);
+        let funnel_collector = Arc::new(Mutex::new(None));
+        let measure_timing = parsed
+            .output
+            .as_ref()
+            .is_some_and(|output| output.report_timing);
+        let timing_collector = Arc::new(Mutex::new(None));
+        let record_rejected = parsed
+            .output
+            .as_ref()
+            .is_some_and(|output| output.rejected_output);
+
+        let (coordinator_checkpoint, checkpoint) = match self.checkpoint {
+            Some(settings) => {
+                let (checkpoint_tx, checkpoint_rx) = unbounded();
+                let reads_done = settings
+                    .resume_from
+                    .as_ref()
+                    .map_or(0, |checkpoint| checkpoint.reads_done);
+                (
+                    Some(CoordinatorCheckpoint::new(
+                        settings.interval,
+                        checkpoint_tx,
+                        reads_done,
+                        settings.seek_inputs,
+                        settings.input_positions.clone(),
+                    )),
+                    Some((settings, checkpoint_rx)),
+                )
+            }
+            None => (None, None),
+        };
 
         let (coordinator, shared_stages) = WorkpoolCoordinator::new(
             stages,
@@ -830,7 +1213,11 @@ impl RunStage2 {
             output_done_rx,
             coordinator_report_collector,
             self.error_collector.clone(),
+            count_funnel.then(|| funnel_collector.clone()),
+            measure_timing.then(|| timing_collector.clone()),
+            coordinator_checkpoint,
         );
+        let coordinator = coordinator.with_output_closed(output_closed_rx);
 
         let coordinator_thread = thread::Builder::new()
             .name("WorkpoolCoordinator".into())
@@ -859,6 +1246,9 @@ impl RunStage2 {
                         &stages,
                         &input_info,
                         &demultiplex_infos,
+                        count_funnel,
+                        measure_timing,
+                        record_rejected,
                     );
                 })
                 .expect("thread spawn should not fail");
@@ -876,13 +1266,18 @@ impl RunStage2 {
             report_json: self.report_json,
             demultiplex_infos: self.demultiplex_infos,
             input_threads: self.input_threads,
-            combiner_thread: self.combiner_thread,
+            combiner_threads: self.combiner_threads,
             stage_threads: all_threads,
             stage_to_output_channel: output_rx,
             report_collector,
+            funnel_collector,
+            timing_collector,
             error_collector: self.error_collector,
             allow_overwrite: self.allow_overwrite,
             output_done_tx,
+            sinks: self.sinks,
+            checkpoint,
+            common_reports: self.common_reports,
         }
     }
 }
@@ -896,13 +1291,21 @@ pub struct RunStage3 {
     allow_overwrite: bool,
 
     input_threads: Vec<thread::JoinHandle<()>>,
-    combiner_thread: thread::JoinHandle<()>,
+    combiner_threads: Vec<thread::JoinHandle<()>>,
     stage_threads: Vec<thread::JoinHandle<()>>,
     stage_to_output_channel:
         crossbeam::channel::Receiver<(usize, io::FastQBlocksCombined, Option<usize>)>,
     report_collector: Arc<Mutex<Vec<FinalizeReportResult>>>,
+    funnel_collector: Arc<Mutex<Option<serde_json::Value>>>,
+    timing_collector: Arc<Mutex<Option<serde_json::Value>>>,
     error_collector: Arc<Mutex<Vec<String>>>,
     output_done_tx: crossbeam::channel::Sender<usize>,
+    sinks: OutputSinks,
+    checkpoint: Option<(
+        CheckpointSettings,
+        crossbeam::channel::Receiver<PendingCheckpoint>,
+    )>,
+    common_reports: Option<CommonReports>,
 }
 
 fn collect_thread_failures(
@@ -954,7 +1357,7 @@ impl RunStage3 {
             .last()
             .map_or(OptDemultiplex::No, |x| x.1.clone()); // we pass int onto the thread later on.
 
-        let output_files = open_output_files(
+        let mut output_files = open_output_files(
             parsed,
             &self.output_directory,
             &demultiplex_info,
@@ -962,9 +1365,27 @@ impl RunStage3 {
             self.report_json,
             self.allow_overwrite,
         )?;
+        if let Some(resume_from) = self
+            .checkpoint
+            .as_ref()
+            .and_then(|(settings, _)| settings.resume_from.as_ref())
+        {
+            output_files.resume_from(&resume_from.output_lengths)?;
+        }
+        let (checkpoint_settings, checkpoint_rx) = match self.checkpoint {
+            Some((settings, checkpoint_rx)) => (Some(settings), checkpoint_rx),
+            None => (None, crossbeam::channel::never()),
+        };
 
         let output_directory = self.output_directory.clone();
+        let OutputSinks {
+            blocks: mut block_sink,
+            report: report_sink,
+        } = self.sinks;
         let report_collector = self.report_collector.clone();
+        let common_reports = self.common_reports;
+        let funnel_collector = self.funnel_collector.clone();
+        let timing_collector = self.timing_collector.clone();
 
         let mut interleave_order = Vec::new();
         if let Some(output) = &parsed.output
@@ -982,9 +1403,26 @@ impl RunStage3 {
         }
         let output_done_tx = self.output_done_tx;
         let report_labels = parsed.report_labels.clone();
+        let report_compare = parsed
+            .output
+            .as_ref()
+            .and_then(|output| output.report_compare.clone());
+        let qc_thresholds = parsed.qc_thresholds.clone();
+        let qc_failed_metrics = Arc::new(Mutex::new(Vec::new()));
+        // reports may be demultiplexed by any of the demultiplex steps
+        let demultiplex_names: HashSet<String> = self
+            .demultiplex_infos
+            .iter()
+            .filter_map(|(_, info)| match info {
+                OptDemultiplex::Yes(info) => Some(info.tag_to_name.values().flatten().cloned()),
+                OptDemultiplex::No => None,
+            })
+            .flatten()
+            .collect();
 
         let output = {
             let error_collector = self.error_collector.clone();
+            let qc_failed_metrics = qc_failed_metrics.clone();
             thread::Builder::new()
                 .name("output".into())
                 .spawn(move || {
@@ -999,7 +1437,46 @@ impl RunStage3 {
                         return;
                     }
                     let mut output_files = output_files.expect("Output_file was error?");
-                    while let Ok((block_no, block, _expected_read_count)) = input_channel.recv() {
+                    let mut checkpoint_rx = checkpoint_rx;
+                    loop {
+                        let mut pending_checkpoints = Vec::new();
+                        let mut coordinator_done = false;
+                        let received = crossbeam::channel::select! {
+                            recv(input_channel) -> msg => match msg {
+                                Ok((block_no, block, _expected_read_count)) => Some((block_no, block)),
+                                Err(_) => break,
+                            },
+                            recv(checkpoint_rx) -> msg => {
+                                match msg {
+                                    Ok(pending) => pending_checkpoints.push(pending),
+                                    Err(_) => coordinator_done = true,
+                                }
+                                None
+                            }
+                        };
+                        if coordinator_done {
+                            checkpoint_rx = crossbeam::channel::never();
+                        }
+                        // a checkpoint is sent before any later block is accepted,
+                        // make sure it's written before that block
+                        pending_checkpoints.extend(checkpoint_rx.try_iter());
+                        for pending in pending_checkpoints {
+                            if let Err(e) = write_checkpoint(
+                                pending,
+                                last_block_outputted,
+                                &mut output_files,
+                                checkpoint_settings.as_ref(),
+                            ) {
+                                error_collector
+                                    .lock()
+                                    .expect("mutex lock should not be poisoned")
+                                    .push(format!("Error writing checkpoint: {e:?}"));
+                                return;
+                            }
+                        }
+                        let Some((block_no, block)) = received else {
+                            continue;
+                        };
                         //resort out of order blocks into the right order.
                         buffer.push((block_no, block));
                         loop {
@@ -1028,6 +1505,30 @@ impl RunStage3 {
                                         .push(format!("Error in output thread: {e:?}"));
                                     return;
                                 }
+                                if let Some(block_sink) = block_sink.as_mut()
+                                    && let Err(e) = block_sink(&to_output.1)
+                                {
+                                    error_collector
+                                        .lock()
+                                        .expect("mutex lock should not be poisoned")
+                                        .push(format!("Error in output callback: {e:?}"));
+                                    return;
+                                }
+                                if let Some(rejected_files) = output_files.rejected.as_mut()
+                                    && let Some(rejected) = to_output.1.rejected.as_deref()
+                                    && let Err(e) = output_rejected_block(
+                                        rejected,
+                                        rejected_files,
+                                        &interleave_order,
+                                        output_buffer_size,
+                                    )
+                                {
+                                    error_collector
+                                        .lock()
+                                        .expect("mutex lock should not be poisoned")
+                                        .push(format!("Error in output thread: {e:?}"));
+                                    return;
+                                }
                             } else {
                                 break;
                             }
@@ -1041,6 +1542,20 @@ impl RunStage3 {
                     for thread in self.stage_threads {
                         thread.join().expect("thread join failure");
                     }
+                    // [[branch]]: the common steps may still be running, if this branch terminated early
+                    if let Some((common_reports, finished_rx)) = common_reports {
+                        finished_rx.recv().ok();
+                        let common_reports: Vec<_> = common_reports
+                            .lock()
+                            .expect("mutex lock should not be poisoned")
+                            .iter()
+                            .cloned()
+                            .collect();
+                        report_collector
+                            .lock()
+                            .expect("mutex lock should not be poisoned")
+                            .extend(common_reports);
+                    }
                     /* assert!(
                         stage_errors.is_empty(),
                         "Error in stage threads occured: {stage_errors:?}"
@@ -1055,20 +1570,49 @@ impl RunStage3 {
                             return;
                         }
                     }
+                    if let Some(rejected_files) = output_files.rejected.as_mut()
+                        && let Err(e) = rejected_files.finish()
+                    {
+                        error_collector
+                            .lock()
+                            .expect("mutex lock should not be poisoned")
+                            .push(format!("Error finishing output files: {e:?}"));
+                        return;
+                    }
+                    if let Some(output_timing) = output_files.output_reports.timing.as_mut()
+                        && let Err(e) = output_timing_report(output_timing, &timing_collector)
+                    {
+                        error_collector
+                            .lock()
+                            .expect("mutex lock should not be poisoned")
+                            .push(format!("Error writing timing report: {e:?}"));
+                        return;
+                    }
                     let json_report = {
                         let need_json = output_files.output_reports.json.is_some()
-                            | output_files.output_reports.html.is_some();
+                            | output_files.output_reports.html.is_some()
+                            | report_sink.is_some();
                         if need_json {
                             match output_json_report(
                                 output_files.output_reports.json.as_mut(), // None if no .json file
                                 // generated
                                 &report_collector,
+                                &funnel_collector,
+                                &timing_collector,
                                 &report_labels,
+                                report_compare.as_deref(),
+                                qc_thresholds.as_ref(),
+                                &demultiplex_names,
                                 &output_directory.to_string_lossy(),
                                 &cloned_input_config,
                                 &raw_config,
                             ) {
-                                Ok(res) => Some(res),
+                                Ok((res, failed)) => {
+                                    *qc_failed_metrics
+                                        .lock()
+                                        .expect("mutex lock should not be poisoned") = failed;
+                                    Some(res)
+                                }
                                 Err(e) => {
                                     error_collector
                                         .lock()
@@ -1082,6 +1626,23 @@ impl RunStage3 {
                         }
                     };
 
+                    if let Some(report_sink) = report_sink {
+                        match serde_json::from_str(
+                            json_report
+                                .as_deref()
+                                .expect("json_report must be Some when a report sink is set"),
+                        ) {
+                            Ok(value) => report_sink(value),
+                            Err(e) => {
+                                error_collector
+                                    .lock()
+                                    .expect("mutex lock should not be poisoned")
+                                    .push(format!("Error parsing json report: {e:?}"));
+                                return;
+                            }
+                        }
+                    }
+
                     if let Some(output_html) = output_files.output_reports.html.as_mut()
                         && let Err(e) = output_html_report(
                             output_html,
@@ -1100,18 +1661,45 @@ impl RunStage3 {
 
         Ok(RunStage4 {
             input_threads: self.input_threads,
-            combiner_thread: self.combiner_thread,
+            combiner_threads: self.combiner_threads,
             output_thread: output,
             error_collector: self.error_collector,
+            qc_failed_metrics,
         })
     }
 }
 
+/// Complete the output files up to the checkpointed block, and record their lengths
+/// along with the step states.
+fn write_checkpoint(
+    pending: PendingCheckpoint,
+    last_block_outputted: usize,
+    output_files: &mut crate::output::OutputFilesReadyToWrite,
+    settings: Option<&CheckpointSettings>,
+) -> Result<()> {
+    let settings = settings.expect("checkpoints are only sent with checkpoint settings");
+    if pending.block_no != last_block_outputted {
+        // blocks were dropped after a premature termination (head) -
+        // we're about to be done anyway.
+        return Ok(());
+    }
+    let output_lengths = output_files.checkpoint()?;
+    crate::checkpoint::Checkpoint {
+        config_hash: settings.config_hash.clone(),
+        reads_done: pending.reads_done,
+        output_lengths,
+        input_positions: pending.input_positions,
+        step_states: pending.step_states,
+    }
+    .write(&settings.path)
+}
+
 pub struct RunStage4 {
     error_collector: Arc<Mutex<Vec<String>>>,
     input_threads: Vec<thread::JoinHandle<()>>,
-    combiner_thread: thread::JoinHandle<()>,
+    combiner_threads: Vec<thread::JoinHandle<()>>,
     output_thread: thread::JoinHandle<()>,
+    qc_failed_metrics: Arc<Mutex<Vec<String>>>,
 }
 
 impl RunStage4 {
@@ -1120,7 +1708,7 @@ impl RunStage4 {
         for (threads, msg) in [
             (vec![self.output_thread], "Failure in output thread"),
             (
-                vec![self.combiner_thread],
+                self.combiner_threads,
                 "Failure in read-combination-thread thread",
             ),
             //            (self.stage_threads, "Failure in stage processor thread"),
@@ -1129,12 +1717,24 @@ impl RunStage4 {
             errors.extend(collect_thread_failures(threads, msg, &self.error_collector));
         }
 
-        RunStage5 { errors }
+        let qc_failed_metrics = std::mem::take(
+            &mut *self
+                .qc_failed_metrics
+                .lock()
+                .expect("mutex lock should not be poisoned"),
+        );
+
+        RunStage5 {
+            errors,
+            qc_failed_metrics,
+        }
     }
 }
 
 pub struct RunStage5 {
     pub errors: Vec<String>,
+    /// metrics that hit a [qc_thresholds] 'fail' limit
+    pub qc_failed_metrics: Vec<String>,
 }
 
 #[cfg(test)]
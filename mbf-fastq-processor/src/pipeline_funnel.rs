@@ -0,0 +1,192 @@
+/// Per stage read & base accounting (output.report_funnel)
+///
+/// Workers count reads/bases going into and coming out of every stage,
+/// the coordinator sums them up per stage (and per demultiplex tag),
+/// and the result ends up as 'funnel' in the json/html report.
+use std::collections::BTreeMap;
+
+use crate::{
+    config::Stage,
+    demultiplex::{OptDemultiplex, Tag},
+    io,
+};
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StageCounts {
+    pub reads_in: usize,
+    pub reads_out: usize,
+    pub bases_in: usize,
+    pub bases_out: usize,
+}
+
+impl StageCounts {
+    fn add(&mut self, other: &StageCounts) {
+        self.reads_in += other.reads_in;
+        self.reads_out += other.reads_out;
+        self.bases_in += other.bases_in;
+        self.bases_out += other.bases_out;
+    }
+
+    fn to_json(self) -> serde_json::Value {
+        serde_json::json!({
+            "reads_in": self.reads_in,
+            "reads_out": self.reads_out,
+            "bases_in": self.bases_in,
+            "bases_out": self.bases_out,
+        })
+    }
+}
+
+/// Counts of one block passing one stage, by demultiplex tag (0 if not demultiplexed)
+pub type BlockCounts = BTreeMap<Tag, StageCounts>;
+
+/// (reads, bases) per demultiplex tag. Bases are summed over all segments.
+fn count_block(block: &io::FastQBlocksCombined) -> BTreeMap<Tag, (usize, usize)> {
+    let mut res: BTreeMap<Tag, (usize, usize)> = BTreeMap::new();
+    for read_no in 0..block.len() {
+        let tag = block
+            .output_tags
+            .as_ref()
+            .map_or(0, |output_tags| output_tags[read_no]);
+        let bases: usize = block
+            .segments
+            .iter()
+            .map(|segment| segment.entries[read_no].seq.len())
+            .sum();
+        let entry = res.entry(tag).or_default();
+        entry.0 += 1;
+        entry.1 += bases;
+    }
+    res
+}
+
+/// Called before `Step::apply` - returns the incoming counts
+pub fn count_in(block: &io::FastQBlocksCombined) -> BlockCounts {
+    count_block(block)
+        .into_iter()
+        .map(|(tag, (reads, bases))| {
+            (
+                tag,
+                StageCounts {
+                    reads_in: reads,
+                    bases_in: bases,
+                    ..Default::default()
+                },
+            )
+        })
+        .collect()
+}
+
+/// Called after `Step::apply` - adds the outgoing counts
+pub fn count_out(counts: &mut BlockCounts, block: &io::FastQBlocksCombined) {
+    for (tag, (reads, bases)) in count_block(block) {
+        let entry = counts.entry(tag).or_default();
+        entry.reads_out += reads;
+        entry.bases_out += bases;
+    }
+}
+
+/// Summed up counts, one entry per stage
+pub struct Funnel {
+    stages: Vec<BlockCounts>,
+}
+
+impl Funnel {
+    pub fn new(stage_count: usize) -> Self {
+        Self {
+            stages: vec![BlockCounts::new(); stage_count],
+        }
+    }
+
+    pub fn add(&mut self, stage_index: usize, counts: &BlockCounts) {
+        let stage = &mut self.stages[stage_index];
+        for (tag, tag_counts) in counts {
+            stage.entry(*tag).or_default().add(tag_counts);
+        }
+    }
+
+    /// One entry per user visible stage.
+    /// Stages that are demultiplexed on entry get a per barcode breakdown.
+    pub fn to_json(
+        &self,
+        stages: &[std::sync::Arc<Stage>],
+        demultiplex_infos: &[(usize, OptDemultiplex)],
+    ) -> serde_json::Value {
+        let mut res = Vec::new();
+        for (stage_index, (stage, counts)) in stages.iter().zip(&self.stages).enumerate() {
+            let action = stage.transformation.action_name();
+            if is_bookkeeping(action) {
+                continue;
+            }
+            let mut total = StageCounts::default();
+            for tag_counts in counts.values() {
+                total.add(tag_counts);
+            }
+            let mut entry = serde_json::Map::new();
+            entry.insert("action".to_string(), action.trim_start_matches('_').into());
+            if let serde_json::Value::Object(total) = total.to_json() {
+                entry.extend(total);
+            }
+            // the demultiplex step itself sees undemultiplexed reads
+            let demultiplex_info = demultiplex_infos
+                .iter()
+                .rev()
+                .find(|(idx, _)| *idx < stage_index)
+                .map(|(_, info)| info);
+            if let Some(OptDemultiplex::Yes(demultiplex_info)) = demultiplex_info {
+                let mut by_barcode = serde_json::Map::new();
+                for (tag, name) in &demultiplex_info.tag_to_name {
+                    if let Some(name) = name {
+                        by_barcode.insert(
+                            name.clone(),
+                            counts.get(tag).copied().unwrap_or_default().to_json(),
+                        );
+                    }
+                }
+                entry.insert(
+                    "demultiplexed".to_string(),
+                    serde_json::Value::Object(by_barcode),
+                );
+            }
+            res.push(serde_json::Value::Object(entry));
+        }
+        serde_json::Value::Array(res)
+    }
+}
+
+/// Report parts and internal steps never change reads - no need to list them
+fn is_bookkeeping(action: &str) -> bool {
+    action.starts_with("_Report") || action.starts_with("_Internal")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_funnel_add() {
+        let mut funnel = Funnel::new(2);
+        let mut counts = BlockCounts::new();
+        counts.insert(
+            0,
+            StageCounts {
+                reads_in: 10,
+                reads_out: 8,
+                bases_in: 100,
+                bases_out: 70,
+            },
+        );
+        funnel.add(1, &counts);
+        funnel.add(1, &counts);
+        assert!(funnel.stages[0].is_empty());
+        assert_eq!(
+            funnel.stages[1][&0],
+            StageCounts {
+                reads_in: 20,
+                reads_out: 16,
+                bases_in: 200,
+                bases_out: 140,
+            }
+        );
+    }
+}
@@ -0,0 +1,170 @@
+/// Per stage runtime & memory profiling (output.report_timing)
+///
+/// Workers measure every `Step::apply` call - wall time, CPU time of the worker thread,
+/// the time the block waited before a worker picked it up, and the peak allocation -
+/// the coordinator sums them up per stage, and the result ends up in
+/// '{prefix}.timing.json' (and as 'timing' in the json/html report).
+use std::time::{Duration, Instant};
+
+use crate::config::Stage;
+
+/// One block passing one stage
+#[derive(Default, Clone, Copy, Debug)]
+pub struct BlockTiming {
+    pub wall: Duration,
+    /// None if the platform can't tell us the thread's CPU time
+    pub cpu: Option<Duration>,
+    /// from entering the stage's queue (possibly waiting for its turn in a serial stage)
+    /// until a worker started on it
+    pub queue_wait: Duration,
+    /// bytes, allocated and not yet freed during `apply`
+    pub peak_allocation: u64,
+}
+
+/// Run `apply` and measure it
+pub fn measure<T>(queued_at: Instant, apply: impl FnOnce() -> T) -> (T, BlockTiming) {
+    let queue_wait = queued_at.elapsed();
+    let cpu_start = thread_cpu_time();
+    let start = Instant::now();
+    let mut result = None;
+    let allocation = allocation_counter::measure(|| result = Some(apply()));
+    let wall = start.elapsed();
+    let cpu = cpu_start
+        .zip(thread_cpu_time())
+        .map(|(start, stop)| stop.saturating_sub(start));
+    (
+        result.expect("measured closure always runs"),
+        BlockTiming {
+            wall,
+            cpu,
+            queue_wait,
+            peak_allocation: allocation.bytes_max,
+        },
+    )
+}
+
+#[cfg(unix)]
+#[allow(clippy::cast_sign_loss)]
+fn thread_cpu_time() -> Option<Duration> {
+    let mut time = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: clock_gettime only writes into the timespec we hand it
+    let res = unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &raw mut time) };
+    (res == 0).then(|| Duration::new(time.tv_sec as u64, time.tv_nsec as u32))
+}
+
+#[cfg(not(unix))]
+fn thread_cpu_time() -> Option<Duration> {
+    None
+}
+
+#[derive(Default, Clone, Copy, Debug)]
+struct StageTiming {
+    blocks: usize,
+    wall: Duration,
+    cpu: Option<Duration>,
+    queue_wait: Duration,
+    max_queue_wait: Duration,
+    peak_allocation: u64,
+}
+
+impl StageTiming {
+    fn add(&mut self, block: &BlockTiming) {
+        self.blocks += 1;
+        self.wall += block.wall;
+        self.cpu = match (self.blocks, self.cpu, block.cpu) {
+            (1, _, cpu) => cpu,
+            (_, Some(total), Some(cpu)) => Some(total + cpu),
+            _ => None,
+        };
+        self.queue_wait += block.queue_wait;
+        self.max_queue_wait = self.max_queue_wait.max(block.queue_wait);
+        self.peak_allocation = self.peak_allocation.max(block.peak_allocation);
+    }
+}
+
+/// Summed up timings, one entry per stage
+pub struct Timing {
+    start: Instant,
+    stages: Vec<StageTiming>,
+}
+
+impl Timing {
+    pub fn new(stage_count: usize) -> Self {
+        Self {
+            start: Instant::now(),
+            stages: vec![StageTiming::default(); stage_count],
+        }
+    }
+
+    pub fn add(&mut self, stage_index: usize, block: &BlockTiming) {
+        self.stages[stage_index].add(block);
+    }
+
+    /// Times in seconds. Stage times are summed over all blocks,
+    /// which were processed in parallel, so they may add up to more than the total.
+    pub fn to_json(&self, stages: &[std::sync::Arc<Stage>]) -> serde_json::Value {
+        let steps: Vec<serde_json::Value> = stages
+            .iter()
+            .zip(&self.stages)
+            .map(|(stage, timing)| {
+                serde_json::json!({
+                    "step": stage.step_no,
+                    "action": stage.transformation.action_name().trim_start_matches('_'),
+                    "blocks": timing.blocks,
+                    "wall_time_s": timing.wall.as_secs_f64(),
+                    "cpu_time_s": timing.cpu.map(|cpu| cpu.as_secs_f64()),
+                    "queue_wait_s": timing.queue_wait.as_secs_f64(),
+                    "max_queue_wait_s": timing.max_queue_wait.as_secs_f64(),
+                    "peak_allocation_bytes": timing.peak_allocation,
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "wall_time_s": self.start.elapsed().as_secs_f64(),
+            "steps": steps,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stage_timing_add() {
+        let mut timing = StageTiming::default();
+        let block = BlockTiming {
+            wall: Duration::from_millis(10),
+            cpu: Some(Duration::from_millis(8)),
+            queue_wait: Duration::from_millis(3),
+            peak_allocation: 100,
+        };
+        timing.add(&block);
+        timing.add(&BlockTiming {
+            queue_wait: Duration::from_millis(5),
+            peak_allocation: 50,
+            ..block
+        });
+        assert_eq!(timing.blocks, 2);
+        assert_eq!(timing.wall, Duration::from_millis(20));
+        assert_eq!(timing.cpu, Some(Duration::from_millis(16)));
+        assert_eq!(timing.queue_wait, Duration::from_millis(8));
+        assert_eq!(timing.max_queue_wait, Duration::from_millis(5));
+        assert_eq!(timing.peak_allocation, 100);
+
+        timing.add(&BlockTiming { cpu: None, ..block });
+        assert_eq!(timing.cpu, None);
+    }
+
+    #[test]
+    fn test_measure() {
+        let ((), timing) = measure(Instant::now(), || {
+            let data = vec![0u8; 1 << 20];
+            std::hint::black_box(&data);
+        });
+        assert!(timing.peak_allocation >= 1 << 20);
+    }
+}
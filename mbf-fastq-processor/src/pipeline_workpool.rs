@@ -5,13 +5,17 @@
 /// No over subscription (like the old n threads per non-serial stage + 1 thread per serial stage model)
 ///
 use anyhow::{Result, bail};
-use crossbeam::channel::{Receiver, Sender, select};
+use crossbeam::channel::{Receiver, Sender, never, select};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use crate::{
+    checkpoint::{InputPositions, PendingCheckpoint},
     config::Stage,
     demultiplex::OptDemultiplex,
     io,
+    pipeline_funnel::{self, BlockCounts, Funnel},
+    pipeline_timing::{self, BlockTiming, Timing},
     transformations::{self, Step},
 };
 
@@ -20,6 +24,8 @@ pub struct WorkItem {
     pub block: io::FastQBlocksCombined,
     pub expected_read_count: Option<usize>,
     pub stage_index: usize,
+    /// when the block was queued for this stage
+    pub queued_at: Instant,
 }
 
 #[derive(Clone)]
@@ -28,6 +34,7 @@ pub struct BlockStatus {
     pub current_stage: usize,
     pub block: io::FastQBlocksCombined,
     pub expected_read_count: Option<usize>,
+    pub queued_at: Instant,
 }
 
 pub struct StageProgress {
@@ -41,6 +48,53 @@ pub struct WorkResult {
     pub work_item: WorkItem,
     pub stage_continue: bool,
     pub error: Option<anyhow::Error>,
+    pub funnel_counts: Option<BlockCounts>,
+    pub timing: Option<BlockTiming>,
+}
+
+/// output.checkpoint_interval / process --resume
+pub struct CoordinatorCheckpoint {
+    interval: Option<Duration>,
+    checkpoint_tx: crossbeam::channel::Sender<PendingCheckpoint>,
+    last_checkpoint: Instant,
+    /// the last block covered by a checkpoint
+    last_checkpoint_block: usize,
+    /// not accepting new blocks until the ones in flight have been written
+    draining: bool,
+    /// molecules received from the input so far (= position in the input)
+    reads_seen: usize,
+    /// resuming: the molecules that were already processed
+    reads_to_skip: usize,
+    /// resuming: blocks that were skipped completely - the remaining ones are renumbered
+    blocks_skipped: usize,
+    input_positions: InputPositions,
+    /// where the inputs continue after the last block received
+    current_positions: Vec<Option<crate::io::parsers::InputPosition>>,
+}
+
+impl CoordinatorCheckpoint {
+    /// `reads_done`: resuming, the molecules that were already processed.
+    /// `seek_inputs`: the inputs continue after those, instead of starting over.
+    pub fn new(
+        interval: Option<Duration>,
+        checkpoint_tx: crossbeam::channel::Sender<PendingCheckpoint>,
+        reads_done: usize,
+        seek_inputs: bool,
+        input_positions: InputPositions,
+    ) -> Self {
+        CoordinatorCheckpoint {
+            interval,
+            checkpoint_tx,
+            last_checkpoint: Instant::now(),
+            last_checkpoint_block: 0,
+            draining: false,
+            reads_seen: if seek_inputs { reads_done } else { 0 },
+            reads_to_skip: reads_done,
+            blocks_skipped: 0,
+            input_positions,
+            current_positions: Vec::new(),
+        }
+    }
 }
 
 pub struct WorkpoolCoordinator {
@@ -57,11 +111,21 @@ pub struct WorkpoolCoordinator {
     done_rx: Receiver<WorkResult>, //back from workers
     output_tx: Sender<(usize, io::FastQBlocksCombined, Option<usize>)>,
     output_done_rx: Receiver<usize>,
+    /// [[branch]]: fires once no branch takes further blocks
+    output_closed_rx: Receiver<()>,
 
     report_collector: Arc<Mutex<Vec<transformations::FinalizeReportResult>>>,
     error_collector: Arc<Mutex<Vec<String>>>,
 
+    funnel: Option<Funnel>, // only if output.report_funnel
+    funnel_collector: Arc<Mutex<Option<serde_json::Value>>>,
+
+    timing: Option<Timing>, // only if output.report_timing
+    timing_collector: Arc<Mutex<Option<serde_json::Value>>>,
+
     last_incoming_block: Option<usize>,
+
+    checkpoint: Option<CoordinatorCheckpoint>,
 }
 
 enum CanTake {
@@ -83,6 +147,9 @@ impl WorkpoolCoordinator {
 
         report_collector: Arc<Mutex<Vec<transformations::FinalizeReportResult>>>,
         error_collector: Arc<Mutex<Vec<String>>>,
+        funnel_collector: Option<Arc<Mutex<Option<serde_json::Value>>>>,
+        timing_collector: Option<Arc<Mutex<Option<serde_json::Value>>>>,
+        checkpoint: Option<CoordinatorCheckpoint>,
     ) -> (Self, Vec<Arc<Stage>>) {
         let stage_progress: Vec<StageProgress> = stages
             .iter()
@@ -96,6 +163,13 @@ impl WorkpoolCoordinator {
             })
             .collect();
 
+        let funnel = funnel_collector
+            .as_ref()
+            .map(|_| Funnel::new(stage_progress.len()));
+        let timing = timing_collector
+            .as_ref()
+            .map(|_| Timing::new(stage_progress.len()));
+
         let arc_stages: Vec<Arc<Stage>> = stages.into_iter().map(Arc::new).collect();
 
         let stages_for_workers = arc_stages.clone();
@@ -112,20 +186,38 @@ impl WorkpoolCoordinator {
             done_rx,
             output_tx,
             output_done_rx,
+            output_closed_rx: never(),
 
             error_collector,
             report_collector,
+            funnel,
+            funnel_collector: funnel_collector.unwrap_or_default(),
+            timing,
+            timing_collector: timing_collector.unwrap_or_default(),
             last_incoming_block: None,
+            checkpoint,
         };
 
         (coordinator, stages_for_workers)
     }
 
+    /// [[branch]]: the common steps' coordinator learns via `output_closed_rx`
+    /// that all branches stopped taking blocks (e.g. after a Head).
+    #[must_use]
+    pub fn with_output_closed(mut self, output_closed_rx: Receiver<()>) -> Self {
+        self.output_closed_rx = output_closed_rx;
+        self
+    }
+
     #[allow(clippy::too_many_lines)]
     pub fn run(mut self, demultiplex_infos: &[(usize, OptDemultiplex)]) {
         loop {
+            if self.handle_checkpoint().is_err() {
+                break;
+            }
             // Check if we're at capacity
-            let accept_new_incoming = self.current_blocks_in_flight < self.max_blocks_in_flight;
+            let accept_new_incoming = self.current_blocks_in_flight < self.max_blocks_in_flight
+                && !self.checkpoint.as_ref().is_some_and(|cp| cp.draining);
             if self.incoming_rx.is_none() || !accept_new_incoming {
                 // Only listen for completed work when input is closed
                 select! {
@@ -169,6 +261,9 @@ impl WorkpoolCoordinator {
                             }
                         }
                     }
+                    recv(self.output_closed_rx) -> _msg => {
+                        self.close_output();
+                    }
                 }
             } else {
                 // Listen for both incoming and done messages
@@ -221,6 +316,9 @@ impl WorkpoolCoordinator {
                             }
                         }
                     }
+                    recv(self.output_closed_rx) -> _msg => {
+                        self.close_output();
+                    }
                 }
 
                 if !self.stages.is_empty() && self.stage_progress[0].closed {
@@ -249,6 +347,95 @@ impl WorkpoolCoordinator {
 
         // Finalize reports before ending
         self.finalize_reports(demultiplex_infos);
+        self.finalize_funnel(demultiplex_infos);
+        self.finalize_timing();
+    }
+
+    /// Once the checkpoint interval has passed, stop taking blocks,
+    /// and once all blocks in flight have been written, hand the step states to the output.
+    fn handle_checkpoint(&mut self) -> Result<()> {
+        let Some(checkpoint) = self.checkpoint.as_mut() else {
+            return Ok(());
+        };
+        let Some(interval) = checkpoint.interval else {
+            return Ok(());
+        };
+        if self.incoming_rx.is_none() {
+            // all input is in the pipeline, no point in checkpointing now
+            checkpoint.draining = false;
+            return Ok(());
+        }
+        if !checkpoint.draining {
+            if checkpoint.last_checkpoint.elapsed() >= interval {
+                checkpoint.draining = true;
+            } else {
+                return Ok(());
+            }
+        }
+        if self.current_blocks_in_flight > 0 {
+            return Ok(());
+        }
+        checkpoint.draining = false;
+        let block_no = self.last_incoming_block.unwrap_or(0);
+        if block_no == checkpoint.last_checkpoint_block {
+            // nothing new since the last checkpoint
+            return Ok(());
+        }
+        let mut step_states = Vec::new();
+        for (stage_index, stage) in self.stages.iter().enumerate() {
+            match stage.transformation.checkpoint_state() {
+                Ok(state) => step_states.push(state),
+                Err(err) => {
+                    self.error_collector
+                        .lock()
+                        .expect("error collector mutex poisoned")
+                        .push(format!(
+                            "Error collecting checkpoint state of stage {stage_index}: {err:?}"
+                        ));
+                    bail!("error detected");
+                }
+            }
+        }
+        // the output might have gone away because of an error - it reports that itself
+        checkpoint
+            .checkpoint_tx
+            .send(PendingCheckpoint {
+                block_no,
+                reads_done: checkpoint.reads_seen,
+                input_positions: checkpoint.current_positions.clone(),
+                step_states,
+            })
+            .ok();
+        checkpoint.last_checkpoint_block = block_no;
+        checkpoint.last_checkpoint = Instant::now();
+        Ok(())
+    }
+
+    /// When resuming (without seeking the inputs): drop the reads that were processed
+    /// before the checkpoint. Returns None if nothing remains of the block.
+    /// Also notes where the inputs continue after the block.
+    fn skip_resumed_reads(
+        &mut self,
+        block_no: usize,
+        mut block: io::FastQBlocksCombined,
+    ) -> Option<(usize, io::FastQBlocksCombined)> {
+        let Some(checkpoint) = self.checkpoint.as_mut() else {
+            return Some((block_no, block));
+        };
+        checkpoint.current_positions = checkpoint.input_positions.take(block_no);
+        let reads_before = checkpoint.reads_seen;
+        checkpoint.reads_seen += block.len();
+        if reads_before < checkpoint.reads_to_skip {
+            // the (empty) final block is always needed
+            if checkpoint.reads_seen <= checkpoint.reads_to_skip && !block.is_final {
+                checkpoint.blocks_skipped += 1;
+                return None;
+            }
+            let to_skip = checkpoint.reads_to_skip - reads_before;
+            let keep: Vec<bool> = (0..block.len()).map(|ii| ii >= to_skip).collect();
+            block.apply_bool_filter(&keep);
+        }
+        Some((block_no - checkpoint.blocks_skipped, block))
     }
 
     pub fn process_incoming_block(
@@ -258,6 +445,9 @@ impl WorkpoolCoordinator {
         expected_read_count: Option<usize>,
     ) -> Result<()> {
         // eprintln!("Adding to pipeline: {block_no}");
+        let Some((block_no, block)) = self.skip_resumed_reads(block_no, block) else {
+            return Ok(());
+        };
         if let Some(last_block_no) = self.last_incoming_block {
             assert!(
                 block_no == last_block_no + 1,
@@ -271,6 +461,7 @@ impl WorkpoolCoordinator {
             current_stage: 0,
             block,
             expected_read_count,
+            queued_at: Instant::now(),
         };
         self.current_blocks_in_flight += 1;
         self.queue_block(block_status)?;
@@ -332,6 +523,7 @@ impl WorkpoolCoordinator {
             block: block_status.block,
             expected_read_count: block_status.expected_read_count,
             stage_index: block_status.current_stage,
+            queued_at: block_status.queued_at,
         };
         if self.todo_tx.send(work_item).is_ok() {
             Ok(())
@@ -372,9 +564,22 @@ impl WorkpoolCoordinator {
             current_stage: stage_index + 1,
             block: work_result.work_item.block,
             expected_read_count: work_result.work_item.expected_read_count,
+            queued_at: Instant::now(),
         };
 
         let was_already_closed = self.stage_progress[stage_index].closed;
+        // blocks processed by an already closed stage are discarded, don't count them
+        if !was_already_closed
+            && let Some(funnel) = self.funnel.as_mut()
+            && let Some(counts) = &work_result.funnel_counts
+        {
+            funnel.add(stage_index, counts);
+        }
+        if let Some(timing) = self.timing.as_mut()
+            && let Some(block_timing) = &work_result.timing
+        {
+            timing.add(stage_index, block_timing);
+        }
         if !work_result.stage_continue {
             // Stage requested premature termination - mark block as final
             block_status.block.is_final = true;
@@ -458,6 +663,39 @@ impl WorkpoolCoordinator {
         }
     }
 
+    /// Nobody takes further blocks - treat it like a premature termination after the last stage,
+    /// so the input is no longer read unless a stage needs to see all reads (e.g. a Report).
+    /// Blocks still in flight are output (and discarded by the receiver) as usual.
+    fn close_output(&mut self) {
+        self.output_closed_rx = never();
+        for stage_index in (0..self.stages.len()).rev() {
+            if self.stage_progress[stage_index].transmits_premature_termination {
+                self.stage_progress[stage_index].closed = true;
+            } else {
+                break;
+            }
+        }
+    }
+
+    pub fn finalize_funnel(&mut self, demultiplex_infos: &[(usize, OptDemultiplex)]) {
+        if let Some(funnel) = &self.funnel {
+            *self
+                .funnel_collector
+                .lock()
+                .expect("funnel collector mutex poisoned") =
+                Some(funnel.to_json(&self.stages, demultiplex_infos));
+        }
+    }
+
+    pub fn finalize_timing(&mut self) {
+        if let Some(timing) = &self.timing {
+            *self
+                .timing_collector
+                .lock()
+                .expect("timing collector mutex poisoned") = Some(timing.to_json(&self.stages));
+        }
+    }
+
     pub fn finalize_reports(&mut self, demultiplex_infos: &[(usize, OptDemultiplex)]) {
         for (stage_index, stage) in self.stages.iter().enumerate() {
             // Find appropriate demultiplex info for this stage
@@ -487,6 +725,7 @@ impl WorkpoolCoordinator {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn worker_thread(
     _worker_id: usize,
     todo_rx: &Receiver<WorkItem>,
@@ -494,9 +733,20 @@ pub fn worker_thread(
     stages: &[Arc<Stage>],
     input_info: &transformations::InputInfo,
     demultiplex_infos: &[(usize, OptDemultiplex)],
+    count_funnel: bool,
+    measure_timing: bool,
+    record_rejected: bool,
 ) {
     while let Ok(work_item) = todo_rx.recv() {
-        let result = process_work_item(work_item, stages, input_info, demultiplex_infos);
+        let result = process_work_item(
+            work_item,
+            stages,
+            input_info,
+            demultiplex_infos,
+            count_funnel,
+            measure_timing,
+            record_rejected,
+        );
 
         if done_tx.send(result).is_err() {
             break; // Coordinator shut down
@@ -509,6 +759,9 @@ fn process_work_item(
     stages: &[Arc<Stage>],
     input_info: &transformations::InputInfo,
     demultiplex_infos: &[(usize, OptDemultiplex)],
+    count_funnel: bool,
+    measure_timing: bool,
+    record_rejected: bool,
 ) -> WorkResult {
     use itertools::Itertools;
     let stage_index = work_item.stage_index;
@@ -524,6 +777,7 @@ fn process_work_item(
 
     let block_no = work_item.block_no;
     let expected_read_count = work_item.expected_read_count;
+    let queued_at = work_item.queued_at;
     let stage = &stages[stage_index];
     let unused_tags: Vec<_> = work_item
         .block
@@ -531,6 +785,21 @@ fn process_work_item(
         .extract_if(.., |k, _v| !stage.allowed_tags.contains(k))
         .collect();
 
+    let mut funnel_counts = count_funnel.then(|| pipeline_funnel::count_in(&work_item.block));
+
+    // output.rejected_output: filters copy what they remove into block.rejected,
+    // we label it with the step number afterwards
+    if record_rejected && work_item.block.rejected.is_none() {
+        work_item.block.rejected = Some(Box::new(work_item.block.empty_rejected()));
+    }
+    let rejected_before = work_item.block.rejected.as_ref().map(|x| x.len());
+    let parked_rejected = if stage.transformation.records_rejected_reads() {
+        None
+    } else {
+        work_item.block.rejected.take()
+    };
+
+    let mut timing = None;
     let result = {
         let mut input_info = input_info.clone();
         input_info.initial_filter_capacity = expected_read_count;
@@ -538,10 +807,23 @@ fn process_work_item(
         let len_before = work_item.block.len();
 
         let block_tag_count = work_item.block.tags.len();
-        let result =
-            stage
-                .transformation
-                .apply(work_item.block, &input_info, block_no, demultiplex_info);
+        let block = work_item.block;
+        let apply = || {
+            crate::transformations::apply_step(
+                &stage.transformation,
+                block,
+                &input_info,
+                block_no,
+                demultiplex_info,
+            )
+        };
+        let result = if measure_timing {
+            let (result, block_timing) = pipeline_timing::measure(queued_at, apply);
+            timing = Some(block_timing);
+            result
+        } else {
+            apply()
+        };
 
         if let Ok(ref result) = result {
             let len_after = result.0.len();
@@ -560,6 +842,19 @@ fn process_work_item(
 
     match result {
         Ok((mut result_block, stage_continue)) => {
+            if let Some(funnel_counts) = funnel_counts.as_mut() {
+                pipeline_funnel::count_out(funnel_counts, &result_block);
+            }
+            if parked_rejected.is_some() {
+                result_block.rejected = parked_rejected;
+            } else if let Some(start) = rejected_before
+                && let Some(step_no) = stage.step_no
+                && let Some(rejected) = result_block.rejected.as_mut()
+            {
+                rejected
+                    .label_rejected(start, step_no.to_string().as_bytes())
+                    .expect("step numbers never contain the comment separator");
+            }
             result_block.tags.extend(unused_tags);
             //make sure all tags have the same length
             let all_tag_lengths_equal = result_block.tags.values().map(std::vec::Vec::len).all_equal();
@@ -582,9 +877,12 @@ fn process_work_item(
                     block: result_block,
                     expected_read_count,
                     stage_index,
+                    queued_at,
                 },
                 stage_continue,
                 error: None,
+                funnel_counts,
+                timing,
             }
         }
         Err(e) => WorkResult {
@@ -595,12 +893,16 @@ fn process_work_item(
                     output_tags: None,
                     tags: Default::default(),
                     is_final: false,
+                    rejected: None,
                 },
                 expected_read_count,
                 stage_index,
+                queued_at,
             },
             stage_continue: false,
             error: Some(e),
+            funnel_counts: None,
+            timing: None,
         },
     }
 }
@@ -0,0 +1,591 @@
+/// Spilling step state to disk (options.memory_limit)
+///
+/// The memory limit is shared out equally among the steps whose state grows with the input
+/// (`Step::uses_memory_budget`). They keep their state in a `SpillingSet` or
+/// `SpillingCounter`: once their share is used up, the in-memory part is written to an
+/// (anonymous) temporary file as one run of records sorted by key, and cleared.
+/// Reservoir samples keep theirs in `SpillingSlots`, which are replaced in place instead.
+/// Temporary files go to $TMPDIR.
+use anyhow::{Context, Result};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashSet};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+
+use crate::config::Stage;
+
+/// Rough per entry overhead of a `HashSet<Vec<u8>>` / `BTreeMap<Vec<u8>, usize>`, in bytes
+const ENTRY_OVERHEAD: usize = 48;
+/// Every INDEX_EVERY'th key of a run is kept in memory to find the others
+const INDEX_EVERY: usize = 64;
+/// Once there are more runs than this, they are merged into one
+const MAX_RUNS: usize = 16;
+
+/// Give every step that asks for it an equal share of `memory_limit`
+pub fn share_memory_limit(stages: &mut [Stage], memory_limit: usize) {
+    let users = stages
+        .iter()
+        .filter(|stage| stage.transformation.uses_memory_budget())
+        .count();
+    if users == 0 {
+        return;
+    }
+    let share = memory_limit / users;
+    for stage in stages {
+        if stage.transformation.uses_memory_budget() {
+            stage.transformation.set_memory_budget(share);
+        }
+    }
+}
+
+fn entry_size(key: &[u8]) -> usize {
+    key.len() + ENTRY_OVERHEAD
+}
+
+fn write_record(writer: &mut impl Write, key: &[u8], count: u64) -> std::io::Result<()> {
+    let key_len = u32::try_from(key.len()).expect("spilled keys are shorter than 4 GB");
+    writer.write_all(&key_len.to_le_bytes())?;
+    writer.write_all(key)?;
+    writer.write_all(&count.to_le_bytes())
+}
+
+/// None at the end of the run
+fn read_record(reader: &mut impl Read) -> std::io::Result<Option<(Vec<u8>, u64)>> {
+    let mut key_len = [0u8; 4];
+    match reader.read_exact(&mut key_len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let mut key = vec![0u8; u32::from_le_bytes(key_len) as usize];
+    reader.read_exact(&mut key)?;
+    let mut count = [0u8; 8];
+    reader.read_exact(&mut count)?;
+    Ok(Some((key, u64::from_le_bytes(count))))
+}
+
+/// Writes one `SortedRun` - records must be pushed in key order
+struct RunWriter {
+    writer: BufWriter<std::fs::File>,
+    index: Vec<(Vec<u8>, u64)>,
+    count: usize,
+    offset: u64,
+}
+
+impl RunWriter {
+    fn new() -> Result<Self> {
+        let file = tempfile::tempfile().context("Failed to create temporary file to spill to")?;
+        Ok(RunWriter {
+            writer: BufWriter::new(file),
+            index: Vec::new(),
+            count: 0,
+            offset: 0,
+        })
+    }
+
+    fn push(&mut self, key: &[u8], count: u64) -> Result<()> {
+        if self.count.is_multiple_of(INDEX_EVERY) {
+            self.index.push((key.to_vec(), self.offset));
+        }
+        write_record(&mut self.writer, key, count).context("Failed to spill to temporary file")?;
+        self.count += 1;
+        self.offset += (4 + key.len() + 8) as u64;
+        Ok(())
+    }
+
+    fn finish(self) -> Result<SortedRun> {
+        let mut file = self
+            .writer
+            .into_inner()
+            .context("Failed to spill to temporary file")?;
+        file.seek(SeekFrom::Start(0))?;
+        Ok(SortedRun {
+            file: BufReader::new(file),
+            index: self.index,
+        })
+    }
+}
+
+/// (key, count) records sorted by key, in a temporary file
+struct SortedRun {
+    file: BufReader<std::fs::File>,
+    /// first key & file offset of every `INDEX_EVERY` records
+    index: Vec<(Vec<u8>, u64)>,
+}
+
+impl SortedRun {
+    fn contains(&mut self, key: &[u8]) -> Result<bool> {
+        let chunk = self
+            .index
+            .partition_point(|(first_key, _offset)| first_key.as_slice() <= key);
+        if chunk == 0 {
+            return Ok(false);
+        }
+        self.file.seek(SeekFrom::Start(self.index[chunk - 1].1))?;
+        for _ in 0..INDEX_EVERY {
+            match read_record(&mut self.file).context("Failed to read spilled data")? {
+                Some((run_key, _count)) if run_key.as_slice() == key => return Ok(true),
+                Some((run_key, _count)) if run_key.as_slice() > key => return Ok(false),
+                Some(_) => {}
+                None => return Ok(false),
+            }
+        }
+        Ok(false)
+    }
+
+    fn into_reader(mut self) -> Result<BufReader<std::fs::File>> {
+        self.file.seek(SeekFrom::Start(0))?;
+        Ok(self.file)
+    }
+}
+
+/// All records of some runs, in key order. Counts of equal keys are summed.
+pub struct MergedRuns {
+    readers: Vec<BufReader<std::fs::File>>,
+    heap: BinaryHeap<Reverse<(Vec<u8>, usize, u64)>>,
+}
+
+impl MergedRuns {
+    fn new(runs: Vec<SortedRun>) -> Result<Self> {
+        let mut readers = Vec::new();
+        let mut heap = BinaryHeap::new();
+        for (ii, run) in runs.into_iter().enumerate() {
+            let mut reader = run.into_reader()?;
+            if let Some((key, count)) = read_record(&mut reader)? {
+                heap.push(Reverse((key, ii, count)));
+            }
+            readers.push(reader);
+        }
+        Ok(MergedRuns { readers, heap })
+    }
+
+    fn pop(&mut self) -> Result<Option<(Vec<u8>, u64)>> {
+        let Some(Reverse((key, ii, count))) = self.heap.pop() else {
+            return Ok(None);
+        };
+        if let Some((next_key, next_count)) =
+            read_record(&mut self.readers[ii]).context("Failed to read spilled data")?
+        {
+            self.heap.push(Reverse((next_key, ii, next_count)));
+        }
+        Ok(Some((key, count)))
+    }
+}
+
+impl Iterator for MergedRuns {
+    type Item = Result<(Vec<u8>, u64)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key, mut count) = match self.pop() {
+            Ok(Some(record)) => record,
+            Ok(None) => return None,
+            Err(e) => return Some(Err(e)),
+        };
+        while self
+            .heap
+            .peek()
+            .is_some_and(|Reverse((next_key, _, _))| *next_key == key)
+        {
+            match self.pop() {
+                Ok(Some((_, next_count))) => count += next_count,
+                Ok(None) => break,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        Some(Ok((key, count)))
+    }
+}
+
+/// Too many runs make lookups slow - merge them into one
+fn compact(runs: &mut Vec<SortedRun>) -> Result<()> {
+    if runs.len() > MAX_RUNS {
+        let mut writer = RunWriter::new()?;
+        for record in MergedRuns::new(std::mem::take(runs))? {
+            let (key, count) = record?;
+            writer.push(&key, count)?;
+        }
+        runs.push(writer.finish()?);
+    }
+    Ok(())
+}
+
+/// An exact set of byte strings that spills to disk once it holds more than `budget` bytes.
+///
+/// Lookups then also search the spilled runs - slower, but bounded in memory.
+pub struct SpillingSet {
+    in_memory: HashSet<Vec<u8>>,
+    in_memory_bytes: usize,
+    budget: usize,
+    runs: Vec<SortedRun>,
+}
+
+impl SpillingSet {
+    pub fn new(budget: usize) -> Self {
+        SpillingSet {
+            in_memory: HashSet::new(),
+            in_memory_bytes: 0,
+            budget,
+            runs: Vec::new(),
+        }
+    }
+
+    /// Was `key` seen before? Remembers it if not.
+    pub fn containsert(&mut self, key: Vec<u8>) -> Result<bool> {
+        if self.in_memory.contains(&key) {
+            return Ok(true);
+        }
+        for run in &mut self.runs {
+            if run.contains(&key)? {
+                return Ok(true);
+            }
+        }
+        self.in_memory_bytes += entry_size(&key);
+        self.in_memory.insert(key);
+        if self.in_memory_bytes > self.budget {
+            self.spill()?;
+        }
+        Ok(false)
+    }
+
+    fn spill(&mut self) -> Result<()> {
+        let mut keys: Vec<Vec<u8>> = self.in_memory.drain().collect();
+        keys.sort_unstable();
+        let mut writer = RunWriter::new()?;
+        for key in &keys {
+            writer.push(key, 0)?;
+        }
+        self.runs.push(writer.finish()?);
+        self.in_memory_bytes = 0;
+        compact(&mut self.runs)
+    }
+}
+
+/// Counts per byte string, spilling to disk once it holds more than `budget` bytes
+pub struct SpillingCounter {
+    in_memory: BTreeMap<Vec<u8>, usize>,
+    in_memory_bytes: usize,
+    budget: usize,
+    runs: Vec<SortedRun>,
+}
+
+impl SpillingCounter {
+    pub fn new(budget: usize) -> Self {
+        SpillingCounter {
+            in_memory: BTreeMap::new(),
+            in_memory_bytes: 0,
+            budget,
+            runs: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, key: Vec<u8>, count: usize) -> Result<()> {
+        match self.in_memory.get_mut(&key) {
+            Some(existing) => *existing += count,
+            None => {
+                self.in_memory_bytes += entry_size(&key);
+                self.in_memory.insert(key, count);
+                if self.in_memory_bytes > self.budget {
+                    self.spill()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// None once parts were spilled to disk - use `into_merged` then.
+    pub fn in_memory(&self) -> Option<&BTreeMap<Vec<u8>, usize>> {
+        self.runs.is_empty().then_some(&self.in_memory)
+    }
+
+    /// All counts, in key order
+    pub fn into_merged(mut self) -> Result<MergedRuns> {
+        if !self.in_memory.is_empty() {
+            self.spill()?;
+        }
+        MergedRuns::new(self.runs)
+    }
+
+    fn spill(&mut self) -> Result<()> {
+        let mut writer = RunWriter::new()?;
+        for (key, count) in std::mem::take(&mut self.in_memory) {
+            writer.push(&key, count as u64)?;
+        }
+        self.runs.push(writer.finish()?);
+        self.in_memory_bytes = 0;
+        compact(&mut self.runs)
+    }
+}
+
+/// A list of byte records that are replaced in place - e.g. a reservoir sample.
+///
+/// Once the records in memory take more than `budget` bytes, they are appended to a
+/// temporary file, and only their offsets are kept. Replaced records leave garbage in the file,
+/// which is compacted once it makes up more than half of it.
+pub struct SpillingSlots {
+    slots: Vec<Slot>,
+    in_memory_bytes: usize,
+    budget: usize,
+    file: Option<SlotFile>,
+}
+
+enum Slot {
+    InMemory(Vec<u8>),
+    Spilled { offset: u64, len: usize },
+}
+
+struct SlotFile {
+    file: std::fs::File,
+    len: u64,
+    /// bytes of the records that slots still point to
+    live: u64,
+}
+
+impl SlotFile {
+    fn new() -> Result<Self> {
+        Ok(SlotFile {
+            file: tempfile::tempfile().context("Failed to create temporary file to spill to")?,
+            len: 0,
+            live: 0,
+        })
+    }
+
+    fn read(&mut self, offset: u64, len: usize) -> Result<Vec<u8>> {
+        let mut record = vec![0u8; len];
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file
+            .read_exact(&mut record)
+            .context("Failed to read spilled data")?;
+        Ok(record)
+    }
+}
+
+impl SpillingSlots {
+    pub fn new(budget: usize) -> Self {
+        SpillingSlots {
+            slots: Vec::new(),
+            in_memory_bytes: 0,
+            budget,
+            file: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    #[allow(dead_code)]
+    #[mutants::skip] // unused, but makes clippy happy
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    pub fn push(&mut self, record: Vec<u8>) -> Result<()> {
+        self.in_memory_bytes += entry_size(&record);
+        self.slots.push(Slot::InMemory(record));
+        self.spill_if_over_budget()
+    }
+
+    pub fn replace(&mut self, index: usize, record: Vec<u8>) -> Result<()> {
+        self.in_memory_bytes += entry_size(&record);
+        match std::mem::replace(&mut self.slots[index], Slot::InMemory(record)) {
+            Slot::InMemory(old) => self.in_memory_bytes -= entry_size(&old),
+            Slot::Spilled { len, .. } => {
+                self.file.as_mut().expect("spilled slots have a file").live -= len as u64;
+            }
+        }
+        self.spill_if_over_budget()
+    }
+
+    /// Call `f` with every record, in slot order
+    pub fn for_each(&mut self, mut f: impl FnMut(&[u8]) -> Result<()>) -> Result<()> {
+        for slot in &self.slots {
+            match slot {
+                Slot::InMemory(record) => f(record)?,
+                Slot::Spilled { offset, len } => {
+                    let file = self.file.as_mut().expect("spilled slots have a file");
+                    f(&file.read(*offset, *len)?)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn spill_if_over_budget(&mut self) -> Result<()> {
+        if self.in_memory_bytes <= self.budget {
+            return Ok(());
+        }
+        if self.file.is_none() {
+            self.file = Some(SlotFile::new()?);
+        }
+        let file = self.file.as_mut().expect("created above");
+        file.file.seek(SeekFrom::Start(file.len))?;
+        let mut writer = BufWriter::new(&mut file.file);
+        for slot in &mut self.slots {
+            if let Slot::InMemory(record) = slot {
+                writer
+                    .write_all(record)
+                    .context("Failed to spill to temporary file")?;
+                let len = record.len();
+                *slot = Slot::Spilled {
+                    offset: file.len,
+                    len,
+                };
+                file.len += len as u64;
+                file.live += len as u64;
+            }
+        }
+        writer
+            .flush()
+            .context("Failed to spill to temporary file")?;
+        drop(writer);
+        self.in_memory_bytes = 0;
+        if file.len > 2 * file.live {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    /// Copy the records the slots still point to into a new file, in file order
+    fn compact(&mut self) -> Result<()> {
+        let mut old = self.file.take().expect("only called after spilling");
+        let mut new = SlotFile::new()?;
+        let mut spilled: Vec<&mut Slot> = self
+            .slots
+            .iter_mut()
+            .filter(|slot| matches!(slot, Slot::Spilled { .. }))
+            .collect();
+        spilled.sort_by_key(|slot| match slot {
+            Slot::Spilled { offset, .. } => *offset,
+            Slot::InMemory(_) => unreachable!(),
+        });
+        let mut writer = BufWriter::new(&mut new.file);
+        for slot in spilled {
+            if let Slot::Spilled { offset, len } = slot {
+                writer
+                    .write_all(&old.read(*offset, *len)?)
+                    .context("Failed to spill to temporary file")?;
+                *offset = new.len;
+                new.len += *len as u64;
+            }
+        }
+        writer
+            .flush()
+            .context("Failed to spill to temporary file")?;
+        drop(writer);
+        new.live = new.len;
+        self.file = Some(new);
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for SpillingSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SpillingSet")
+            .field("in_memory_len", &self.in_memory.len())
+            .field("runs", &self.runs.len())
+            .finish()
+    }
+}
+
+impl std::fmt::Debug for SpillingCounter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SpillingCounter")
+            .field("in_memory_len", &self.in_memory.len())
+            .field("runs", &self.runs.len())
+            .finish()
+    }
+}
+
+impl std::fmt::Debug for SpillingSlots {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SpillingSlots")
+            .field("len", &self.slots.len())
+            .field("spilled", &self.file.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn key(ii: usize) -> Vec<u8> {
+        // not inserted in sorted order
+        format!("key_{}", (ii * 7919) % 1000).into_bytes()
+    }
+
+    #[test]
+    fn test_spilling_set() {
+        let mut set = SpillingSet::new(500);
+        let mut reference = HashSet::new();
+        for ii in 0..3000 {
+            let key = key(ii);
+            assert_eq!(
+                set.containsert(key.clone()).unwrap(),
+                !reference.insert(key),
+                "{ii}"
+            );
+        }
+        assert!(!set.runs.is_empty());
+        assert!(set.runs.len() <= MAX_RUNS);
+        assert!(!set.containsert(b"never seen".to_vec()).unwrap());
+        assert!(set.containsert(b"never seen".to_vec()).unwrap());
+        // before the first key of every run
+        assert!(!set.containsert(b"a".to_vec()).unwrap());
+    }
+
+    #[test]
+    fn test_spilling_counter() {
+        let mut counter = SpillingCounter::new(300);
+        let mut reference: BTreeMap<Vec<u8>, u64> = BTreeMap::new();
+        for ii in 0..2500 {
+            counter.add(key(ii), ii % 3).unwrap();
+            *reference.entry(key(ii)).or_default() += (ii % 3) as u64;
+        }
+        assert!(counter.in_memory().is_none());
+        let merged: Vec<(Vec<u8>, u64)> = counter
+            .into_merged()
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(merged, reference.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_spilling_slots() {
+        let mut slots = SpillingSlots::new(200);
+        let mut reference = Vec::new();
+        for ii in 0..20 {
+            slots.push(key(ii)).unwrap();
+            reference.push(key(ii));
+        }
+        assert!(slots.file.is_some());
+        for ii in 0..500 {
+            let index = (ii * 31) % 20;
+            slots.replace(index, key(ii + 20)).unwrap();
+            reference[index] = key(ii + 20);
+        }
+        // garbage is compacted away: at most twice the 20 live records of up to 7 bytes
+        assert!(slots.file.as_ref().unwrap().len <= 2 * 20 * 7);
+        let mut records = Vec::new();
+        slots
+            .for_each(|record| {
+                records.push(record.to_vec());
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(records, reference);
+    }
+
+    #[test]
+    fn test_spilling_counter_in_memory() {
+        let mut counter = SpillingCounter::new(usize::MAX);
+        counter.add(b"a".to_vec(), 1).unwrap();
+        counter.add(b"a".to_vec(), 2).unwrap();
+        assert_eq!(counter.in_memory().unwrap().get(b"a".as_slice()), Some(&3));
+    }
+}
@@ -13,7 +13,10 @@ use std::{cell::RefCell, path::Path, rc::Rc};
 use anyhow::Result;
 
 use crate::{
-    config::{ResolvedSourceNoAll, SegmentIndex, deser::TagLabel},
+    config::{
+        ResolvedSourceAll, ResolvedSourceNoAll, SegmentIndex, SegmentIndexOrAll,
+        deser::{ConditionalTagLabel, TagLabel},
+    },
     demultiplex::{DemultiplexBarcodes, OptDemultiplex},
     dna::TagValue,
     io,
@@ -25,6 +28,7 @@ pub(crate) mod calc;
 pub(crate) mod convert;
 pub(crate) mod demultiplex;
 pub(crate) mod edits;
+pub(crate) mod external_command;
 pub(crate) mod extract;
 pub(crate) mod filters;
 pub(crate) mod hamming_correct;
@@ -34,8 +38,8 @@ pub(crate) mod reports;
 pub(crate) mod tag;
 pub(crate) mod validation;
 pub use internal_steps::{
-    _InduceFailure, _InternalDelay, _InternalReadCount, Partial_InduceFailure,
-    Partial_InternalDelay, Partial_InternalReadCount,
+    _CustomStep, _InduceFailure, _InternalDelay, _InternalReadCount, Partial_CustomStep,
+    Partial_InduceFailure, Partial_InternalDelay, Partial_InternalReadCount,
 };
 
 #[derive(Clone, JsonSchema)]
@@ -164,7 +168,7 @@ pub enum KeepOrRemove {
     Remove,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct FinalizeReportResult {
     pub report_no: usize,
     pub contents: serde_json::Value,
@@ -214,6 +218,8 @@ pub(crate) struct DeclaredTag<'a> {
     pub(crate) toml_source_state: &'a mut TomlValueState,
     pub(crate) toml_source_help: &'a mut Option<String>,
     pub(crate) toml_source_span: std::ops::Range<usize>,
+    /// The values a String tag can take, if known up front (e.g. `BinNumericTag` labels)
+    pub(crate) categories: Option<Vec<String>>,
 }
 
 pub trait ToDeclaredTag {
@@ -229,11 +235,52 @@ pub enum RemovedTags<'a> {
     Some(Vec<(TagLabel, &'a mut TomlValue<TagLabel>)>),
 }
 
+/// The segments whose reads a step changes, see `Step::modified_segments`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ModifiedSegments {
+    #[default]
+    None,
+    All,
+    Some(Vec<SegmentIndex>),
+    /// wherever the regions of this location tag are
+    TagRegions(TagLabel),
+}
+
+impl From<SegmentIndex> for ModifiedSegments {
+    fn from(segment: SegmentIndex) -> Self {
+        ModifiedSegments::Some(vec![segment])
+    }
+}
+
+impl From<SegmentIndexOrAll> for ModifiedSegments {
+    fn from(segment: SegmentIndexOrAll) -> Self {
+        match segment {
+            SegmentIndexOrAll::All => ModifiedSegments::All,
+            SegmentIndexOrAll::Indexed(idx) => ModifiedSegments::Some(vec![SegmentIndex(idx)]),
+        }
+    }
+}
+
+impl From<&ResolvedSourceAll> for ModifiedSegments {
+    fn from(source: &ResolvedSourceAll) -> Self {
+        match source {
+            ResolvedSourceAll::Segment(segment) => (*segment).into(),
+            ResolvedSourceAll::Tag(label) => ModifiedSegments::TagRegions(label.clone()),
+            ResolvedSourceAll::Name {
+                segment_index_or_all,
+                ..
+            } => (*segment_index_or_all).into(),
+        }
+    }
+}
+
 #[derive(Default, Debug)]
 pub struct TagUsageInfo<'a> {
     pub used_tags: Vec<Option<UsedTag<'a>>>,
     pub removed_tags: RemovedTags<'a>,
     pub declared_tag: Option<DeclaredTag<'a>>,
+    /// for steps that declare more than one tag
+    pub declared_tags: Vec<DeclaredTag<'a>>,
     pub must_see_all_tags: bool,
 }
 
@@ -259,11 +306,15 @@ pub trait TagUser {
 }
 
 #[enum_dispatch(Transformation)]
+/// One processing step.
+///
+/// Blocks of reads are handed to `apply` - by default from multiple threads in parallel,
+/// in no particular order. Implement this for your own type to add custom steps
+/// when embedding the pipeline (see [`crate::api::Pipeline::add_step`]).
 pub trait Step {
-    fn store_progress_output(&mut self, _progress: &crate::transformations::reports::Progress) {
-        //default does nothing
-    }
-
+    /// Called once before any reads are processed.
+    /// Open files, start helpers etc. here.
+    /// Steps that demultiplex return their barcodes.
     fn init(
         &mut self,
         _input_info: &InputInfo,
@@ -276,9 +327,14 @@ pub trait Step {
         Ok(None)
     }
 
+    /// Called once after all reads have been processed.
+    /// Reports return their section of the json report.
     fn finalize(&self, _demultiplex_info: &OptDemultiplex) -> Result<Option<FinalizeReportResult>> {
         Ok(None)
     }
+
+    /// Process one block of reads. Remove reads with `FastQBlocksCombined::apply_bool_filter`.
+    /// Return false as second value to stop processing ('I have seen enough reads', see `Head`).
     fn apply(
         &self,
         block: crate::io::FastQBlocksCombined,
@@ -305,6 +361,61 @@ pub trait Step {
     fn transmits_premature_termination(&self) -> bool {
         true
     }
+
+    /// Do the reads this step removes end up in the rejected output
+    /// (output.rejected_output)?
+    /// Steps that remove reads for reasons other than the read itself
+    /// (e.g. having seen enough reads) opt out.
+    fn records_rejected_reads(&self) -> bool {
+        true
+    }
+
+    /// Can a run with this step be resumed from a checkpoint (output.checkpoint_interval)?
+    /// Opt-in: stateless steps, and steps that keep state across blocks and
+    /// implement `checkpoint_state`/`restore_checkpoint_state`, return true.
+    fn supports_checkpoint(&self) -> bool {
+        false
+    }
+
+    /// The state accumulated so far. Called between blocks, never concurrently with `apply`.
+    fn checkpoint_state(&self) -> Result<Option<serde_json::Value>> {
+        Ok(None)
+    }
+
+    /// Continue from a `checkpoint_state` - called after `init`.
+    fn restore_checkpoint_state(&mut self, _state: serde_json::Value) -> Result<()> {
+        Ok(())
+    }
+
+    /// Does this step keep state that grows with the input?
+    /// Those steps share `options.memory_limit`, see `set_memory_budget`.
+    fn uses_memory_budget(&self) -> bool {
+        false
+    }
+
+    /// This step's share of `options.memory_limit`, in bytes. Called before `init`.
+    /// Spill to disk (see `crate::spill`) once it is used up.
+    fn set_memory_budget(&mut self, _bytes: usize) {}
+
+    /// Does this step keep state in memory that `options.memory_limit` can not bound?
+    /// Configurations with a `memory_limit` warn about those steps.
+    fn ignores_memory_limit(&self) -> bool {
+        false
+    }
+
+    /// `if_tag`: only apply to the reads where this tag is truthy.
+    /// The pipeline hands the step just those reads (see `apply_step`), and merges
+    /// the result back - tags the step declares are Missing on the other reads.
+    /// Steps that filter, or check `if_tag` on their own, keep returning None.
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        None
+    }
+
+    /// Which segments' reads (sequence, quality or name) does this step change?
+    /// Only informational - shown by the `explain` command.
+    fn modified_segments(&self) -> ModifiedSegments {
+        ModifiedSegments::None
+    }
 }
 
 //#[serde(tag = "action")]
@@ -321,6 +432,7 @@ pub enum Transformation {
     ConvertQuality(edits::ConvertQuality),
     ReverseComplement(edits::ReverseComplement),
     Rename(edits::Rename),
+    RenameFromTemplate(edits::RenameFromTemplate),
     Swap(edits::Swap),
     Lowercase(edits::Lowercase),
     Uppercase(edits::Uppercase),
@@ -328,6 +440,7 @@ pub enum Transformation {
     #[schemars(skip)]
     _ChangeCase(edits::_ChangeCase), // public interface is Lowercase/Uppercase
     TrimAtTag(edits::TrimAtTag),
+    SplitAtTag(edits::SplitAtTag),
     MergeReads(edits::MergeReads),
     //
     FilterByTag(filters::ByTag),
@@ -364,10 +477,13 @@ pub enum Transformation {
     CalcQualifiedBases(calc::QualifiedBases),
     CalcExpectedError(calc::ExpectedError),
     CalcKmers(calc::Kmers),
+    CalcTagHitCount(calc::TagHitCount),
     //
     ConvertRegionsToLength(convert::RegionsToLength),
     #[tpd(alias = "EvaluateExpression")]
     EvalExpression(Box<convert::EvalExpression>),
+    MapTag(convert::MapTag),
+    BinNumericTag(convert::BinNumericTag),
     ExtractRegionsOfLowQuality(extract::RegionsOfLowQuality),
     ExtractLongestPolyX(extract::LongestPolyX),
     ExtractPolyTail(extract::PolyTail),
@@ -377,6 +493,7 @@ pub enum Transformation {
     // // bool tags
     TagDuplicates(extract::tag::Duplicates),
     TagOtherFile(extract::tag::OtherFile),
+    TagFromTable(extract::tag::FromTable),
     //
     // //edit
     StoreTagInSequence(tag::StoreTagInSequence),
@@ -384,6 +501,7 @@ pub enum Transformation {
     //
     // //store
     ConcatTags(tag::ConcatTags),
+    CombineLocations(tag::CombineLocations),
     ForgetAllTags(tag::ForgetAllTags),
     ForgetTag(tag::ForgetTag),
     StoreTagInComment(tag::StoreTagInComment),
@@ -415,6 +533,9 @@ pub enum Transformation {
     #[schemars(skip)] // nodefault
     #[tpd(skip)]
     _ReportCountOligos(Box<reports::_ReportCountOligos>),
+    #[schemars(skip)] // nodefault
+    #[tpd(skip)]
+    _ReportKmerEnrichment(Box<reports::_ReportKmerEnrichment>),
     #[schemars(skip)]
     #[tpd(skip)]
     _ReportTagHistogram(Box<reports::_ReportTagHistogram>),
@@ -424,6 +545,8 @@ pub enum Transformation {
     Demultiplex(demultiplex::Demultiplex),
     HammingCorrect(hamming_correct::HammingCorrect),
     //
+    ExternalCommand(external_command::ExternalCommand),
+    //
     #[schemars(skip)]
     _InternalDelay(Box<_InternalDelay>),
     #[schemars(skip)]
@@ -431,6 +554,131 @@ pub enum Transformation {
     //
     #[schemars(skip)]
     _InduceFailure(Box<_InduceFailure>),
+    //
+    #[schemars(skip)]
+    #[tpd(skip)]
+    _CustomStep(Box<_CustomStep>),
+}
+
+impl Transformation {
+    /// Hand the (first) Progress step to steps that report their own progress
+    pub(crate) fn store_progress_output(&mut self, progress: &reports::Progress) {
+        if let Transformation::TagOtherFile(step) = self {
+            step.progress_output = Some(progress.clone());
+        }
+    }
+
+    /// The step's action name, e.g. 'CutStart' (the variant name, not an alias)
+    #[must_use]
+    pub fn action_name(&self) -> &'static str {
+        match self {
+            Transformation::CutStart(_) => "CutStart",
+            Transformation::CutEnd(_) => "CutEnd",
+            Transformation::Truncate(_) => "Truncate",
+            Transformation::Prefix(_) => "Prefix",
+            Transformation::Postfix(_) => "Postfix",
+            Transformation::ConvertQuality(_) => "ConvertQuality",
+            Transformation::ReverseComplement(_) => "ReverseComplement",
+            Transformation::Rename(_) => "Rename",
+            Transformation::RenameFromTemplate(_) => "RenameFromTemplate",
+            Transformation::Swap(_) => "Swap",
+            Transformation::Lowercase(_) => "Lowercase",
+            Transformation::Uppercase(_) => "Uppercase",
+            Transformation::_ChangeCase(_) => "_ChangeCase",
+            Transformation::TrimAtTag(_) => "TrimAtTag",
+            Transformation::SplitAtTag(_) => "SplitAtTag",
+            Transformation::MergeReads(_) => "MergeReads",
+            Transformation::FilterByTag(_) => "FilterByTag",
+            Transformation::FilterByNumericTag(_) => "FilterByNumericTag",
+            Transformation::Head(_) => "Head",
+            Transformation::Skip(_) => "Skip",
+            Transformation::FilterEmpty(_) => "FilterEmpty",
+            Transformation::FilterSample(_) => "FilterSample",
+            Transformation::FilterReservoirSample(_) => "FilterReservoirSample",
+            Transformation::ValidateReadPairing(_) => "ValidateReadPairing",
+            Transformation::ValidateSeq(_) => "ValidateSeq",
+            Transformation::ValidateQuality(_) => "ValidateQuality",
+            Transformation::ValidateName(_) => "ValidateName",
+            Transformation::ValidateAllReadsSameLength(_) => "ValidateAllReadsSameLength",
+            Transformation::ExtractIUPAC(_) => "ExtractIUPAC",
+            Transformation::ExtractIUPACWithIndel(_) => "ExtractIUPACWithIndel",
+            Transformation::ExtractRegex(_) => "ExtractRegex",
+            Transformation::ExtractRegion(_) => "ExtractRegion",
+            Transformation::ExtractRegions(_) => "ExtractRegions",
+            Transformation::CalcLength(_) => "CalcLength",
+            Transformation::CalcBaseContent(_) => "CalcBaseContent",
+            Transformation::CalcGCContent(_) => "CalcGCContent",
+            Transformation::CalcNCount(_) => "CalcNCount",
+            Transformation::CalcComplexity(_) => "CalcComplexity",
+            Transformation::CalcQualifiedBases(_) => "CalcQualifiedBases",
+            Transformation::CalcExpectedError(_) => "CalcExpectedError",
+            Transformation::CalcKmers(_) => "CalcKmers",
+            Transformation::CalcTagHitCount(_) => "CalcTagHitCount",
+            Transformation::ConvertRegionsToLength(_) => "ConvertRegionsToLength",
+            Transformation::EvalExpression(_) => "EvalExpression",
+            Transformation::MapTag(_) => "MapTag",
+            Transformation::BinNumericTag(_) => "BinNumericTag",
+            Transformation::ExtractRegionsOfLowQuality(_) => "ExtractRegionsOfLowQuality",
+            Transformation::ExtractLongestPolyX(_) => "ExtractLongestPolyX",
+            Transformation::ExtractPolyTail(_) => "ExtractPolyTail",
+            Transformation::ExtractIUPACSuffix(_) => "ExtractIUPACSuffix",
+            Transformation::ExtractLowQualityStart(_) => "ExtractLowQualityStart",
+            Transformation::ExtractLowQualityEnd(_) => "ExtractLowQualityEnd",
+            Transformation::TagDuplicates(_) => "TagDuplicates",
+            Transformation::TagOtherFile(_) => "TagOtherFile",
+            Transformation::TagFromTable(_) => "TagFromTable",
+            Transformation::StoreTagInSequence(_) => "StoreTagInSequence",
+            Transformation::ReplaceTagWithLetter(_) => "ReplaceTagWithLetter",
+            Transformation::ConcatTags(_) => "ConcatTags",
+            Transformation::CombineLocations(_) => "CombineLocations",
+            Transformation::ForgetAllTags(_) => "ForgetAllTags",
+            Transformation::ForgetTag(_) => "ForgetTag",
+            Transformation::StoreTagInComment(_) => "StoreTagInComment",
+            Transformation::StoreTagInFastQ(_) => "StoreTagInFastQ",
+            Transformation::StoreTagLocationInComment(_) => "StoreTagLocationInComment",
+            Transformation::StoreTagsInTable(_) => "StoreTagsInTable",
+            Transformation::QuantifyTag(_) => "QuantifyTag",
+            Transformation::Progress(_) => "Progress",
+            Transformation::Report(_) => "Report",
+            Transformation::_ReportCount(_) => "_ReportCount",
+            Transformation::_ReportLengthDistribution(_) => "_ReportLengthDistribution",
+            Transformation::_ReportDuplicateCount(_) => "_ReportDuplicateCount",
+            Transformation::_ReportDuplicateFragmentCount(_) => "_ReportDuplicateFragmentCount",
+            Transformation::_ReportBaseStatisticsPart1(_) => "_ReportBaseStatisticsPart1",
+            Transformation::_ReportBaseStatisticsPart2(_) => "_ReportBaseStatisticsPart2",
+            Transformation::_ReportCountOligos(_) => "_ReportCountOligos",
+            Transformation::_ReportKmerEnrichment(_) => "_ReportKmerEnrichment",
+            Transformation::_ReportTagHistogram(_) => "_ReportTagHistogram",
+            Transformation::Inspect(_) => "Inspect",
+            Transformation::Demultiplex(_) => "Demultiplex",
+            Transformation::HammingCorrect(_) => "HammingCorrect",
+            Transformation::ExternalCommand(_) => "ExternalCommand",
+            Transformation::_InternalDelay(_) => "_InternalDelay",
+            Transformation::_InternalReadCount(_) => "_InternalReadCount",
+            Transformation::_InduceFailure(_) => "_InduceFailure",
+            Transformation::_CustomStep(_) => "_CustomStep",
+        }
+    }
+}
+
+/// `Step::apply`, restricted to the reads matching the step's `condition`
+pub(crate) fn apply_step(
+    step: &Transformation,
+    block: io::FastQBlocksCombined,
+    input_info: &InputInfo,
+    block_no: usize,
+    demultiplex_info: &OptDemultiplex,
+) -> Result<(io::FastQBlocksCombined, bool)> {
+    let Some(cond_tag) = step.condition() else {
+        return step.apply(block, input_info, block_no, demultiplex_info);
+    };
+    let condition = edits::get_bool_vec_from_tag(&block, cond_tag);
+    let (matching, others) = block.split_by_condition(&condition);
+    let (matching, do_continue) = step.apply(matching, input_info, block_no, demultiplex_info)?;
+    Ok((
+        io::FastQBlocksCombined::merge_by_condition(matching, others, &condition)?,
+        do_continue,
+    ))
 }
 
 #[derive(Debug)]
@@ -5,6 +5,7 @@ mod kmers;
 mod length;
 mod n_count;
 mod qualified_bases;
+mod tag_hit_count;
 
 mod gc_content;
 
@@ -22,6 +23,7 @@ pub use kmers::{Kmers, PartialKmers};
 pub use length::{Length, PartialLength};
 pub use n_count::{NCount, PartialNCount};
 pub use qualified_bases::{PartialQualifiedBases, QualifiedBases};
+pub use tag_hit_count::{PartialTagHitCount, TagHitCount};
 
 pub(crate) fn extract_numeric_tags<F>(
     segment: SegmentIndex,
@@ -26,6 +26,8 @@ pub struct BaseContent {
     #[schemars(with = "String")]
     #[tpd(with = "tpd_adapt_bstring_uppercase")]
     pub bases_to_ignore: BString,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 
     #[tpd(skip)]
     #[schemars(skip)]
@@ -105,6 +107,7 @@ impl PartialBaseContent {
         relative: bool,
         bases_to_count: BString,
         bases_to_ignore: BString,
+        if_tag: TomlValue<Option<ConditionalTagLabel>>,
     ) -> PartialBaseContent {
         Self {
             out_label,
@@ -114,6 +117,7 @@ impl PartialBaseContent {
             bases_to_ignore_lookup: Some(build_lookup(&bases_to_ignore)),
             bases_to_count: TomlValue::new_ok_unplaced(bases_to_count),
             bases_to_ignore: TomlValue::new_ok_unplaced(bases_to_ignore),
+            if_tag,
         }
     }
 }
@@ -164,6 +168,7 @@ impl TagUser for PartialTaggedVariant<PartialBaseContent> {
             .as_mut()
             .expect("get_tag_usage should only be called after successful verification");
         TagUsageInfo {
+            used_tags: vec![inner.if_tag.to_used_tag(&[][..])],
             declared_tag: inner.out_label.to_declared_tag(TagValueType::Numeric),
             ..Default::default()
         }
@@ -171,6 +176,14 @@ impl TagUser for PartialTaggedVariant<PartialBaseContent> {
 }
 
 impl Step for BaseContent {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
     #[allow(clippy::cast_precision_loss)]
     fn apply(
         &self,
@@ -13,6 +13,8 @@ pub struct Complexity {
     #[schemars(with = "String")]
     #[tpd(adapt_in_verify(String))]
     segment: SegmentIndexOrAll,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 }
 
 impl VerifyIn<PartialConfig> for PartialComplexity {
@@ -40,6 +42,7 @@ impl TagUser for PartialTaggedVariant<PartialComplexity> {
             .as_mut()
             .expect("get_tag_usage should only be called after successful verification");
         TagUsageInfo {
+            used_tags: vec![inner.if_tag.to_used_tag(&[][..])],
             declared_tag: inner.out_label.to_declared_tag(TagValueType::Numeric),
             ..Default::default()
         }
@@ -47,6 +50,14 @@ impl TagUser for PartialTaggedVariant<PartialComplexity> {
 }
 
 impl Step for Complexity {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
     #[allow(
         clippy::cast_sign_loss,
         clippy::cast_possible_truncation,
@@ -28,6 +28,8 @@ pub struct ExpectedError {
     pub segment: SegmentIndexOrAll,
 
     pub aggregate: ExpectedErrorAggregate,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 }
 
 impl VerifyIn<PartialConfig> for PartialExpectedError {
@@ -56,6 +58,7 @@ impl TagUser for PartialTaggedVariant<PartialExpectedError> {
             .as_mut()
             .expect("get_tag_usage should only be called after successful verification");
         TagUsageInfo {
+            used_tags: vec![inner.if_tag.to_used_tag(&[][..])],
             declared_tag: inner.out_label.to_declared_tag(TagValueType::Numeric),
             ..Default::default()
         }
@@ -63,6 +66,14 @@ impl TagUser for PartialTaggedVariant<PartialExpectedError> {
 }
 
 impl Step for ExpectedError {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
@@ -11,6 +11,8 @@ pub struct GCContent {
     #[schemars(with = "String")]
     #[tpd(adapt_in_verify(String))]
     pub segment: SegmentIndexOrAll,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 }
 
 impl VerifyIn<PartialConfig> for PartialGCContent {
@@ -38,6 +40,10 @@ impl TagUser for PartialTaggedVariant<PartialGCContent> {
 }
 
 impl Step for GCContent {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
     fn apply(
         &self,
         _block: FastQBlocksCombined,
@@ -30,6 +30,8 @@ pub struct Kmers {
     pub count_reverse_complement: bool,
 
     pub min_count: usize,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 
     #[schemars(skip)]
     #[tpd(skip, default)]
@@ -89,6 +91,7 @@ impl TagUser for PartialTaggedVariant<PartialKmers> {
             .as_mut()
             .expect("get_tag_usage should only be called after successful verification");
         TagUsageInfo {
+            used_tags: vec![inner.if_tag.to_used_tag(&[][..])],
             declared_tag: inner.out_label.to_declared_tag(TagValueType::Numeric),
             ..Default::default()
         }
@@ -96,6 +99,18 @@ impl TagUser for PartialTaggedVariant<PartialKmers> {
 }
 
 impl Step for Kmers {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
+    fn ignores_memory_limit(&self) -> bool {
+        true // the reference kmer counts
+    }
+
     fn init(
         &mut self,
         input_info: &InputInfo,
@@ -15,6 +15,8 @@ pub struct Length {
     #[schemars(with = "String")]
     #[tpd(adapt_in_verify(String))]
     pub segment: SegmentIndexOrAll,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 }
 
 impl VerifyIn<PartialConfig> for PartialLength {
@@ -42,6 +44,7 @@ impl TagUser for PartialTaggedVariant<PartialLength> {
             .as_mut()
             .expect("get_tag_usage should only be called after successful verification");
         TagUsageInfo {
+            used_tags: vec![inner.if_tag.to_used_tag(&[][..])],
             declared_tag: inner.out_label.to_declared_tag(TagValueType::Numeric),
             ..Default::default()
         }
@@ -49,6 +52,14 @@ impl TagUser for PartialTaggedVariant<PartialLength> {
 }
 
 impl Step for Length {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
@@ -10,6 +10,8 @@ pub struct NCount {
     #[schemars(with = "String")]
     #[tpd(adapt_in_verify(String))]
     pub segment: SegmentIndexOrAll,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 }
 
 impl VerifyIn<PartialConfig> for PartialNCount {
@@ -37,6 +39,10 @@ impl TagUser for PartialTaggedVariant<PartialNCount> {
 }
 
 impl Step for NCount {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
     fn apply(
         &self,
         _block: FastQBlocksCombined,
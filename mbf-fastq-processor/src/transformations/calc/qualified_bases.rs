@@ -49,6 +49,8 @@ pub struct QualifiedBases {
     #[schemars(with = "String")]
     #[tpd(adapt_in_verify(String))]
     segment: SegmentIndexOrAll,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 }
 
 impl VerifyIn<PartialConfig> for PartialQualifiedBases {
@@ -76,6 +78,7 @@ impl TagUser for PartialTaggedVariant<PartialQualifiedBases> {
             .as_mut()
             .expect("get_tag_usage should only be called after successful verification");
         TagUsageInfo {
+            used_tags: vec![inner.if_tag.to_used_tag(&[][..])],
             declared_tag: inner.out_label.to_declared_tag(TagValueType::Numeric),
             ..Default::default()
         }
@@ -83,6 +86,14 @@ impl TagUser for PartialTaggedVariant<PartialQualifiedBases> {
 }
 
 impl Step for QualifiedBases {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
     #[allow(
         clippy::cast_sign_loss,
         clippy::cast_possible_truncation,
@@ -0,0 +1,98 @@
+#![allow(clippy::unnecessary_wraps)]
+use toml_pretty_deser::PartialTaggedVariant;
+
+use crate::dna::TagValue;
+use crate::transformations::prelude::*;
+
+/// Count the hits in a location tag (e.g. from `find_all` extractions)
+
+#[derive(Clone, JsonSchema)]
+#[tpd]
+#[derive(Debug)]
+pub struct TagHitCount {
+    pub in_label: TagLabel,
+    pub out_label: TagLabel,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
+}
+
+impl VerifyIn<PartialConfig> for PartialTagHitCount {
+    fn verify(
+        &mut self,
+        _parent: &PartialConfig,
+        _options: &VerifyOptions,
+    ) -> std::result::Result<(), ValidationFailure>
+    where
+        Self: Sized + toml_pretty_deser::Visitor,
+    {
+        if let Some(out) = self.out_label.as_ref()
+            && let Some(in_) = self.in_label.as_ref()
+            && out == in_
+        {
+            let spans = vec![
+                (self.out_label.span(), "Same as in_label".to_string()),
+                (self.in_label.span(), "Same as out_label".to_string()),
+            ];
+            self.out_label.state = TomlValueState::Custom { spans };
+            self.out_label.help = Some(
+                "'out_label' must differ from 'in_label' to avoid overwriting the source tag."
+                    .to_string(),
+            );
+        }
+        Ok(())
+    }
+}
+
+impl TagUser for PartialTaggedVariant<PartialTagHitCount> {
+    fn get_tag_usage(
+        &mut self,
+        _tags_available: &IndexMap<TagLabel, TagMetadata>,
+        _segment_order: &[String],
+    ) -> TagUsageInfo<'_> {
+        let inner = self
+            .toml_value
+            .as_mut()
+            .expect("get_tag_usage should only be called after successful verification");
+        TagUsageInfo {
+            used_tags: vec![
+                inner.in_label.to_used_tag(&[TagValueType::Location]),
+                inner.if_tag.to_used_tag(&[][..]),
+            ],
+            declared_tag: inner.out_label.to_declared_tag(TagValueType::Numeric),
+            ..Default::default()
+        }
+    }
+}
+
+impl Step for TagHitCount {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
+    fn apply(
+        &self,
+        mut block: FastQBlocksCombined,
+        _input_info: &InputInfo,
+        _block_no: usize,
+        _demultiplex_info: &OptDemultiplex,
+    ) -> anyhow::Result<(FastQBlocksCombined, bool)> {
+        let input_tags = block
+            .tags
+            .get(&self.in_label)
+            .expect("Input tag not found - should have been caught in validation");
+        let counts: Vec<TagValue> = input_tags
+            .iter()
+            .map(|tag_value| {
+                let count = tag_value.as_sequence().map_or(0, |hits| hits.0.len());
+                #[allow(clippy::cast_precision_loss)]
+                TagValue::Numeric(count as f64)
+            })
+            .collect();
+        block.tags.insert(self.out_label.clone(), counts);
+        Ok((block, true))
+    }
+}
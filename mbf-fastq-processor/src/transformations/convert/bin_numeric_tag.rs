@@ -0,0 +1,406 @@
+#![allow(clippy::unnecessary_wraps)] //eserde false positives
+use crate::dna::TagValue;
+use crate::transformations::prelude::*;
+
+/// Sort a numeric tag into classes (String tag), by fixed breakpoints
+/// or by quantiles of all values.
+///
+/// ```toml
+/// [[step]]
+/// action = "BinNumericTag"
+/// in_label = "length"
+/// out_label = "length_class"
+/// breakpoints = [50, 100]
+/// labels = ["short", "medium", "long"]
+/// ```
+#[derive(Clone, JsonSchema)]
+#[tpd]
+#[derive(Debug)]
+pub struct BinNumericTag {
+    pub in_label: TagLabel,
+    pub out_label: TagLabel,
+    /// Bin borders, strictly increasing. A value equal to a border goes into the upper bin
+    pub breakpoints: Option<Vec<f64>>,
+    /// Quantiles (0..1, strictly increasing) of all values to use as borders.
+    /// Holds back all reads until the input is exhausted
+    pub quantiles: Option<Vec<f64>>,
+    /// One label per bin (borders + 1).
+    /// Defaults to 'min..<border>', '<border>..<border>', '<border>..max' for breakpoints
+    /// and 'q1', 'q2', ... for quantiles
+    pub labels: Option<Vec<String>>,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
+
+    #[tpd(skip, default)]
+    #[schemars(skip)]
+    pub resolved_labels: Vec<String>,
+
+    /// quantile mode: the reads seen so far
+    #[tpd(skip, default)]
+    #[schemars(skip)]
+    held_back: Option<Arc<Mutex<Option<FastQBlocksCombined>>>>,
+}
+
+fn verify_borders(values: &[TomlValue<f64>], are_quantiles: bool) -> Result<(), ValidationFailure> {
+    let values: Vec<f64> = values.iter().filter_map(|v| v.as_ref().copied()).collect();
+    if values.is_empty() {
+        return Err(ValidationFailure::new("Must not be empty", None));
+    }
+    if are_quantiles && values.iter().any(|q| q.is_nan() || *q <= 0.0 || *q >= 1.0) {
+        return Err(ValidationFailure::new(
+            "Quantiles must be between 0 and 1 (exclusive)",
+            Some("For quartiles, use [0.25, 0.5, 0.75]"),
+        ));
+    }
+    if !values.windows(2).all(|pair| pair[0] < pair[1]) {
+        return Err(ValidationFailure::new("Must be strictly increasing", None));
+    }
+    Ok(())
+}
+
+fn default_labels(breakpoints: Option<&[f64]>, bin_count: usize) -> Vec<String> {
+    match breakpoints {
+        Some(breakpoints) => {
+            let mut labels = Vec::with_capacity(bin_count);
+            let mut lower = "min".to_string();
+            for border in breakpoints {
+                labels.push(format!("{lower}..{border}"));
+                lower = border.to_string();
+            }
+            labels.push(format!("{lower}..max"));
+            labels
+        }
+        None => (1..=bin_count).map(|ii| format!("q{ii}")).collect(),
+    }
+}
+
+impl VerifyIn<PartialConfig> for PartialBinNumericTag {
+    fn verify(
+        &mut self,
+        _parent: &PartialConfig,
+        _options: &VerifyOptions,
+    ) -> std::result::Result<(), ValidationFailure>
+    where
+        Self: Sized + toml_pretty_deser::Visitor,
+    {
+        if let Some(out_label) = self.out_label.as_ref()
+            && let Some(in_label) = self.in_label.as_ref()
+            && out_label == in_label
+        {
+            let spans = vec![
+                (self.in_label.span(), "The same as out_label".to_string()),
+                (self.out_label.span(), "The same as in_label".to_string()),
+            ];
+            self.out_label.state = TomlValueState::Custom { spans };
+            self.out_label.help = Some(
+                "'out_label' must differ from 'in_label' to avoid overwriting the source tag."
+                    .to_string(),
+            );
+        }
+        self.breakpoints.verify(|v| match v {
+            Some(values) => verify_borders(values, false),
+            None => Ok(()),
+        });
+        self.quantiles.verify(|v| match v {
+            Some(values) => verify_borders(values, true),
+            None => Ok(()),
+        });
+        //since options are not 'missing'
+        match (self.breakpoints.as_ref(), self.quantiles.as_ref()) {
+            (Some(None), Some(None)) => {
+                return Err(ValidationFailure::new(
+                    "One of breakpoints or quantiles must be specified",
+                    Some(
+                        "breakpoints = [50, 100] for fixed borders, quantiles = [0.5] for a median split",
+                    ),
+                ));
+            }
+            (Some(Some(_)), Some(Some(_))) => {
+                let spans = vec![
+                    (self.breakpoints.span(), "Set here".to_string()),
+                    (self.quantiles.span(), "And here".to_string()),
+                ];
+                self.quantiles.state = TomlValueState::Custom { spans };
+                self.quantiles.help =
+                    Some("Use either fixed breakpoints or quantiles, not both.".to_string());
+            }
+            _ => {}
+        }
+        if matches!(self.quantiles.as_ref(), Some(Some(_)))
+            && matches!(self.if_tag.as_ref(), Some(Some(_)))
+        {
+            self.if_tag.state =
+                TomlValueState::new_validation_failed("Not supported with quantiles");
+            self.if_tag.help = Some(
+                "Quantile binning holds back all reads until the end. Bin all reads, then use EvalExpression to pick the ones you need."
+                    .to_string(),
+            );
+        }
+
+        let borders: Option<Vec<f64>> = match (self.breakpoints.as_ref(), self.quantiles.as_ref()) {
+            (Some(Some(values)), _) | (_, Some(Some(values))) => {
+                Some(values.iter().filter_map(|v| v.as_ref().copied()).collect())
+            }
+            _ => None,
+        };
+        if let Some(borders) = borders {
+            let bin_count = borders.len() + 1;
+            let is_breakpoints = matches!(self.breakpoints.as_ref(), Some(Some(_)));
+            if let Some(Some(labels)) = self.labels.as_ref() {
+                let labels: Vec<String> =
+                    labels.iter().filter_map(|l| l.as_ref().cloned()).collect();
+                let mut seen = std::collections::HashSet::new();
+                if labels.len() != bin_count {
+                    self.labels.state = TomlValueState::new_validation_failed(format!(
+                        "Expected {bin_count} labels (one per bin), found {}",
+                        labels.len()
+                    ));
+                    self.labels.help = Some(format!(
+                        "{} borders make {bin_count} bins - below the first, between each pair and above the last.",
+                        borders.len()
+                    ));
+                } else if labels.iter().any(String::is_empty) {
+                    self.labels.state =
+                        TomlValueState::new_validation_failed("Labels must not be empty");
+                } else if !labels.iter().all(|label| seen.insert(label)) {
+                    self.labels.state =
+                        TomlValueState::new_validation_failed("Labels must be unique");
+                } else {
+                    self.resolved_labels = Some(labels);
+                }
+            } else {
+                self.resolved_labels = Some(default_labels(
+                    is_breakpoints.then_some(&borders[..]),
+                    bin_count,
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TagUser for PartialTaggedVariant<PartialBinNumericTag> {
+    fn get_tag_usage(
+        &mut self,
+        _tags_available: &IndexMap<TagLabel, TagMetadata>,
+        _segment_order: &[String],
+    ) -> TagUsageInfo<'_> {
+        let inner = self
+            .toml_value
+            .as_mut()
+            .expect("get_tag_usage should only be called after successful verification");
+        let is_quantile_mode = matches!(inner.quantiles.as_ref(), Some(Some(_)));
+        let categories = inner.resolved_labels.clone();
+        let mut declared_tag = inner.out_label.to_declared_tag(TagValueType::String);
+        if let Some(declared_tag) = declared_tag.as_mut() {
+            // so Demultiplex can split by them without a barcodes section
+            declared_tag.categories = categories;
+        }
+        TagUsageInfo {
+            used_tags: vec![
+                inner
+                    .in_label
+                    .to_used_tag(&[TagValueType::Numeric][..])
+                    .map(|used_tag| {
+                        used_tag.add_help(
+                            "Produce a numeric tag first, e.g. with CalcLength, CalcGCContent or CalcExpectedError.",
+                        )
+                    }),
+                inner.if_tag.to_used_tag(&[][..]),
+            ],
+            declared_tag,
+            // quantile mode holds back reads, changing the block sizes
+            must_see_all_tags: is_quantile_mode,
+            ..Default::default()
+        }
+    }
+}
+
+/// Linear interpolation between the closest ranks (numpy's default)
+fn quantile_borders(values: &[TagValue], quantiles: &[f64]) -> Vec<f64> {
+    let mut sorted: Vec<f64> = values
+        .iter()
+        .filter_map(TagValue::as_numeric)
+        .filter(|v| !v.is_nan())
+        .collect();
+    if sorted.is_empty() {
+        return Vec::new(); // everything is missing anyway
+    }
+    sorted.sort_by(f64::total_cmp);
+    #[allow(clippy::cast_precision_loss)]
+    let last_index = (sorted.len() - 1) as f64;
+    quantiles
+        .iter()
+        .map(|q| {
+            let pos = q * last_index;
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let (lower, upper) = (pos.floor() as usize, pos.ceil() as usize);
+            sorted[lower] + (sorted[upper] - sorted[lower]) * (pos - pos.floor())
+        })
+        .collect()
+}
+
+fn bin_values(values: &[TagValue], borders: &[f64], labels: &[String]) -> Vec<TagValue> {
+    values
+        .iter()
+        .map(|value| match value.as_numeric() {
+            Some(value) if !value.is_nan() => {
+                let bin = borders.partition_point(|border| *border <= value);
+                TagValue::String(labels[bin].as_str().into())
+            }
+            _ => TagValue::Missing,
+        })
+        .collect()
+}
+
+/// Append all reads (and their tags) of `source` to `target`
+fn append_block(target: &mut FastQBlocksCombined, source: FastQBlocksCombined) {
+    for (target_segment, source_segment) in target.segments.iter_mut().zip(&source.segments) {
+        for idx in 0..source_segment.len() {
+            target_segment.append_read(&source_segment.get(idx));
+        }
+    }
+    if let (Some(target_tags), Some(source_tags)) = (&mut target.output_tags, source.output_tags) {
+        target_tags.extend(source_tags);
+    }
+    for (label, values) in source.tags {
+        target.tags.entry(label).or_default().extend(values);
+    }
+}
+
+impl BinNumericTag {
+    fn apply_quantiles(
+        &self,
+        block: FastQBlocksCombined,
+        quantiles: &[f64],
+    ) -> Result<FastQBlocksCombined> {
+        let mut held_lock = self
+            .held_back
+            .as_ref()
+            .expect("held_back not set in init")
+            .lock();
+        let held = held_lock.as_mut().expect("held_back mutex poisoned");
+
+        let is_final = block.is_final;
+        // keep the tag keys, so downstream steps find their labels in the empty blocks
+        let mut empty = block.empty();
+        for label in block.tags.keys().chain(std::iter::once(&self.out_label)) {
+            empty.tags.insert(label.clone(), Vec::new());
+        }
+        append_block(held.get_or_insert_with(|| block.empty()), block);
+
+        if !is_final {
+            return Ok(empty);
+        }
+        let mut output = held.take().expect("just inserted");
+        output.is_final = true;
+        let values = output
+            .tags
+            .get(&self.in_label)
+            .expect("Input tag not found - should have been caught in validation");
+        let borders = quantile_borders(values, quantiles);
+        let binned = bin_values(values, &borders, &self.resolved_labels);
+        output.tags.insert(self.out_label.clone(), binned);
+        Ok(output)
+    }
+}
+
+impl Step for BinNumericTag {
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
+    fn init(
+        &mut self,
+        _input_info: &InputInfo,
+        _output_prefix: &str,
+        _output_directory: &Path,
+        _output_ix_separator: &str,
+        _demultiplex_info: &OptDemultiplex,
+        _allow_overwrite: bool,
+    ) -> Result<Option<DemultiplexBarcodes>> {
+        if self.quantiles.is_some() {
+            self.held_back = Some(Arc::new(Mutex::new(None)));
+        }
+        Ok(None)
+    }
+
+    // quantile mode: reads are held back until the last block, they are not removed
+    fn records_rejected_reads(&self) -> bool {
+        self.quantiles.is_none()
+    }
+
+    fn supports_checkpoint(&self) -> bool {
+        self.quantiles.is_none() // the held back reads are not serialized
+    }
+
+    fn ignores_memory_limit(&self) -> bool {
+        self.quantiles.is_some() // holds back all reads
+    }
+
+    fn needs_serial(&self) -> bool {
+        self.quantiles.is_some()
+    }
+
+    fn apply(
+        &self,
+        mut block: FastQBlocksCombined,
+        _input_info: &InputInfo,
+        _block_no: usize,
+        _demultiplex_info: &OptDemultiplex,
+    ) -> anyhow::Result<(FastQBlocksCombined, bool)> {
+        if let Some(quantiles) = &self.quantiles {
+            return Ok((self.apply_quantiles(block, quantiles)?, true));
+        }
+        let breakpoints = self
+            .breakpoints
+            .as_ref()
+            .expect("either breakpoints or quantiles - checked in validation");
+        let values = block
+            .tags
+            .get(&self.in_label)
+            .expect("Input tag not found - should have been caught in validation");
+        let binned = bin_values(values, breakpoints, &self.resolved_labels);
+        block.tags.insert(self.out_label.clone(), binned);
+        Ok((block, true))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numeric(values: &[f64]) -> Vec<TagValue> {
+        values.iter().map(|v| TagValue::Numeric(*v)).collect()
+    }
+
+    #[test]
+    fn test_bin_values_border_goes_up() {
+        let labels: Vec<String> = default_labels(Some(&[50.0, 100.0]), 3);
+        assert_eq!(labels, vec!["min..50", "50..100", "100..max"]);
+        let mut values = numeric(&[10.0, 50.0, 99.5, 100.0, 250.0]);
+        values.push(TagValue::Missing);
+        let binned = bin_values(&values, &[50.0, 100.0], &labels);
+        assert_eq!(
+            binned,
+            vec![
+                TagValue::String("min..50".into()),
+                TagValue::String("50..100".into()),
+                TagValue::String("50..100".into()),
+                TagValue::String("100..max".into()),
+                TagValue::String("100..max".into()),
+                TagValue::Missing,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_quantile_borders() {
+        let values = numeric(&[4.0, 1.0, 3.0, 2.0, 5.0]);
+        assert_eq!(quantile_borders(&values, &[0.5]), vec![3.0]);
+        assert_eq!(quantile_borders(&values, &[0.25, 0.75]), vec![2.0, 4.0]);
+        // interpolated between 1 and 2
+        assert_eq!(quantile_borders(&numeric(&[2.0, 1.0]), &[0.5]), vec![1.5]);
+        assert!(quantile_borders(&[TagValue::Missing], &[0.5]).is_empty());
+    }
+}
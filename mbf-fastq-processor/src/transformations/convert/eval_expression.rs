@@ -8,13 +8,26 @@ use std::{
     sync::atomic::Ordering,
 };
 
+use super::string_expression::StringExpression;
 use crate::{dna::TagValue, io};
 
 #[derive(Debug)]
-struct CompiledExpression {
-    slab: Slab,
-    instruction: fasteval::Instruction,
-    var_names: BTreeSet<String>,
+enum CompiledExpression {
+    Fasteval {
+        slab: Slab,
+        instruction: fasteval::Instruction,
+        var_names: BTreeSet<String>,
+    },
+    String(StringExpression),
+}
+
+impl CompiledExpression {
+    fn var_names(&self) -> &BTreeSet<String> {
+        match self {
+            CompiledExpression::Fasteval { var_names, .. } => var_names,
+            CompiledExpression::String(expression) => &expression.var_names,
+        }
+    }
 }
 
 /// Evaluate an equation on tags
@@ -24,13 +37,16 @@ struct CompiledExpression {
 pub struct EvalExpression {
     /// The tag label to store the result
     pub out_label: TagLabel,
-    /// The arithmetic expression to evaluate
-    /// Variables in the expression should match existing numeric tag names
+    /// The expression to evaluate - arithmetic (fasteval) for numeric/bool results,
+    /// the string expression language for string results.
+    /// Variables in the expression should match existing tag names
     #[tpd(alias = "expr")]
     pub expression: String,
 
     #[tpd(alias = "output_type")]
     pub result_type: ResultType,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 
     #[tpd(skip)]
     #[schemars(skip)]
@@ -68,7 +84,20 @@ impl VerifyIn<PartialConfig> for PartialEvalExpression {
             }
             Ok(())
         });
-        if let Some(expression) = self.expression.as_ref() {
+        if let Some(expression) = self.expression.as_ref()
+            && self.result_type.as_ref() == Some(&ResultType::String)
+        {
+            match StringExpression::parse(expression) {
+                Err(e) => {
+                    let help_message = format!("Inner error message {e}");
+                    return Err(ValidationFailure::new(
+                        "Syntax error".to_string(),
+                        Some(help_message),
+                    ));
+                }
+                Ok(parsed) => self.compiled = Some(CompiledExpression::String(parsed)),
+            }
+        } else if let Some(expression) = self.expression.as_ref() {
             // Try parsing the expression to catch syntax errors early
             let mut slab = Slab::new();
             let parser = Parser::new();
@@ -82,7 +111,7 @@ impl VerifyIn<PartialConfig> for PartialEvalExpression {
                 }
                 Ok(parsed) => {
                     let instruction = parsed.from(&slab.ps).compile(&slab.ps, &mut slab.cs);
-                    self.compiled = Some(CompiledExpression {
+                    self.compiled = Some(CompiledExpression::Fasteval {
                         var_names: instruction.var_names(&slab),
                         slab,
                         instruction,
@@ -121,6 +150,7 @@ pub enum ResultType {
     #[default]
     Numeric,
     Bool,
+    String,
 }
 
 impl TagUser for PartialTaggedVariant<Box<PartialEvalExpression>> {
@@ -136,7 +166,7 @@ impl TagUser for PartialTaggedVariant<Box<PartialEvalExpression>> {
         // Extract variable names and declare them as numeric tags
         // Since we support both numeric and bool tags in expressions,
         // we use TagValueType::Any for flexibility
-        let var_names = &inner.compiled.as_ref().expect("expected ok").var_names;
+        let var_names = inner.compiled.as_ref().expect("expected ok").var_names();
         let used_tags = {
             let mut used_tags = Vec::new();
             let toml_source = Rc::new(RefCell::new((
@@ -169,6 +199,7 @@ impl TagUser for PartialTaggedVariant<Box<PartialEvalExpression>> {
                     }));
                 }
             }
+            used_tags.push(inner.if_tag.to_used_tag(&[][..]));
             used_tags
         };
         TagUsageInfo {
@@ -177,6 +208,7 @@ impl TagUser for PartialTaggedVariant<Box<PartialEvalExpression>> {
                 match inner.result_type.as_ref().expect("parent was ok?") {
                     ResultType::Numeric => TagValueType::Numeric,
                     ResultType::Bool => TagValueType::Bool,
+                    ResultType::String => TagValueType::String,
                 },
             ),
             ..Default::default()
@@ -185,8 +217,28 @@ impl TagUser for PartialTaggedVariant<Box<PartialEvalExpression>> {
 }
 
 impl Step for Box<EvalExpression> {
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
     #[allow(clippy::too_many_lines)]
     #[allow(clippy::cast_precision_loss)]
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn checkpoint_state(&self) -> Result<Option<serde_json::Value>> {
+        Ok(Some(serde_json::json!(
+            self.next_index.load(Ordering::Relaxed)
+        )))
+    }
+
+    fn restore_checkpoint_state(&mut self, state: serde_json::Value) -> Result<()> {
+        self.next_index
+            .store(serde_json::from_value(state)?, Ordering::Relaxed);
+        Ok(())
+    }
+
     fn apply(
         &self,
         mut block: io::FastQBlocksCombined,
@@ -194,11 +246,7 @@ impl Step for Box<EvalExpression> {
         _block_no: usize,
         _demultiplex_info: &OptDemultiplex,
     ) -> anyhow::Result<(io::FastQBlocksCombined, bool)> {
-        // Parse and compile the expression for better performance
-        let eval = &self.compiled;
-        let slab = &eval.slab;
-        let compiled = &eval.instruction;
-        let var_names = &eval.var_names;
+        let var_names = self.compiled.var_names();
 
         // Get all tag data for the variables we need
         let mut tag_data: Vec<(&str, &Vec<TagValue>)> = Vec::new();
@@ -266,6 +314,17 @@ impl Step for Box<EvalExpression> {
             tag_data.push((var_name, tag_values));
         }
 
+        let (slab, compiled) = match &self.compiled {
+            CompiledExpression::Fasteval {
+                slab, instruction, ..
+            } => (slab, instruction),
+            CompiledExpression::String(expression) => {
+                let results = self.eval_string_expression(expression, &tag_data, block.len())?;
+                block.tags.insert(self.out_label.clone(), results);
+                return Ok((block, true));
+            }
+        };
+
         // Evaluate expression for each read
         let mut results: Vec<TagValue> = Vec::with_capacity(block.len());
 
@@ -315,6 +374,7 @@ impl Step for Box<EvalExpression> {
                     // Treat 0.0 as false, any other value as true
                     TagValue::Bool(result.abs() > f64::EPSILON)
                 }
+                ResultType::String => unreachable!("string expressions are evaluated above"),
             };
 
             results.push(tag_value);
@@ -326,3 +386,42 @@ impl Step for Box<EvalExpression> {
         Ok((block, true))
     }
 }
+
+impl EvalExpression {
+    /// Strings keep their value here - in fasteval they're just 'present or not'
+    fn eval_string_expression(
+        &self,
+        expression: &StringExpression,
+        tag_data: &[(&str, &Vec<TagValue>)],
+        read_count: usize,
+    ) -> Result<Vec<TagValue>> {
+        // in the order evaluate expects them
+        let columns: Vec<&Vec<TagValue>> = expression
+            .var_names
+            .iter()
+            .map(|name| {
+                tag_data
+                    .iter()
+                    .find(|(var_name, _)| *var_name == name.as_str())
+                    .expect("all variables were collected above")
+                    .1
+            })
+            .collect();
+        let mut vars = Vec::with_capacity(columns.len());
+        let mut results = Vec::with_capacity(read_count);
+        for read_idx in 0..read_count {
+            vars.clear();
+            vars.extend(columns.iter().map(|column| &column[read_idx]));
+            match expression.evaluate(&vars) {
+                Ok(value) => results.push(value),
+                Err(e) => bail!(
+                    "EvalExpression: error evaluating expression '{}' for read {}: {}",
+                    self.expression,
+                    read_idx,
+                    e
+                ),
+            }
+        }
+        Ok(results)
+    }
+}
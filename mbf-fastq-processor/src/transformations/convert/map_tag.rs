@@ -0,0 +1,377 @@
+#![allow(clippy::unnecessary_wraps)] //eserde false positives
+use crate::config::deser::tpd_adapt_u8_from_byte_or_char;
+use crate::dna::TagValue;
+use crate::io::table::{column_index, open_table};
+use crate::transformations::hamming_correct::BarcodeIndex;
+use crate::transformations::prelude::*;
+
+/// Map a tag's value to a new tag through a lookup table (TSV/CSV)
+#[derive(Clone, JsonSchema)]
+#[tpd]
+#[derive(Debug)]
+pub struct MapTag {
+    pub in_label: TagLabel,
+    pub out_label: TagLabel,
+    /// TSV/CSV file with a header line, may be gzip/zstd compressed
+    pub filename: String,
+    /// Column holding the keys. Defaults to the first column
+    pub key_column: Option<String>,
+    /// Column holding the values. Defaults to the second column
+    pub value_column: Option<String>,
+    /// Defaults to ',' for .csv files and tab otherwise
+    #[tpd(with = "tpd_adapt_u8_from_byte_or_char")]
+    pub delimiter: Option<u8>,
+    #[tpd(default)]
+    pub value_type: TableValueType,
+    /// Value for tag values not in the table. Missing if not set
+    pub default: Option<String>,
+    /// Accept the (unique) closest key within this hamming distance
+    #[tpd(default)]
+    pub max_hamming_distance: u8,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
+
+    #[tpd(skip, default)]
+    #[schemars(skip)]
+    pub table: Option<Arc<LookupTable>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, JsonSchema)]
+#[tpd]
+pub enum TableValueType {
+    #[default]
+    String,
+    Numeric,
+}
+
+#[derive(Debug)]
+pub struct LookupTable {
+    keys: BarcodeIndex,
+    /// in the order of `keys`
+    values: Vec<TagValue>,
+    default: TagValue,
+}
+
+impl VerifyIn<PartialConfig> for PartialMapTag {
+    fn verify(
+        &mut self,
+        _parent: &PartialConfig,
+        _options: &VerifyOptions,
+    ) -> std::result::Result<(), ValidationFailure>
+    where
+        Self: Sized + toml_pretty_deser::Visitor,
+    {
+        self.filename.verify(|v| {
+            if v.is_empty() {
+                Err(ValidationFailure::new("Must not be empty", None))
+            } else {
+                Ok(())
+            }
+        });
+        if let Some(out_label) = self.out_label.as_ref()
+            && let Some(in_label) = self.in_label.as_ref()
+            && out_label == in_label
+        {
+            let spans = vec![
+                (self.in_label.span(), "The same as out_label".to_string()),
+                (self.out_label.span(), "The same as in_label".to_string()),
+            ];
+            self.out_label.state = TomlValueState::Custom { spans };
+            self.out_label.help = Some(
+                "'out_label' must differ from 'in_label' to avoid overwriting the source tag."
+                    .to_string(),
+            );
+        }
+        if let Some(Some(key_column)) = self.key_column.as_ref()
+            && let Some(Some(value_column)) = self.value_column.as_ref()
+            && key_column == value_column
+        {
+            let spans = vec![
+                (
+                    self.key_column.span(),
+                    "The same as value_column".to_string(),
+                ),
+                (
+                    self.value_column.span(),
+                    "The same as key_column".to_string(),
+                ),
+            ];
+            self.value_column.state = TomlValueState::Custom { spans };
+            self.value_column.help =
+                Some("Keys and values must come from different columns.".to_string());
+        }
+        if let Some(TableValueType::Numeric) = self.value_type.as_ref() {
+            self.default.verify(|v| match v {
+                Some(v) if v.parse::<f64>().is_err() => Err(ValidationFailure::new(
+                    "Not a number",
+                    Some("With value_type = 'numeric', the default must be a number, too."),
+                )),
+                _ => Ok(()),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl TagUser for PartialTaggedVariant<PartialMapTag> {
+    fn get_tag_usage(
+        &mut self,
+        _tags_available: &IndexMap<TagLabel, TagMetadata>,
+        _segment_order: &[String],
+    ) -> TagUsageInfo<'_> {
+        let inner = self
+            .toml_value
+            .as_mut()
+            .expect("get_tag_usage should only be called after successful verification");
+        let out_type = match inner.value_type.as_ref().expect("parent was ok?") {
+            TableValueType::String => TagValueType::String,
+            TableValueType::Numeric => TagValueType::Numeric,
+        };
+        TagUsageInfo {
+            used_tags: vec![
+                inner
+                    .in_label
+                    .to_used_tag(&[TagValueType::String, TagValueType::Location][..]),
+                inner.if_tag.to_used_tag(&[][..]),
+            ],
+            declared_tag: inner.out_label.to_declared_tag(out_type),
+            ..Default::default()
+        }
+    }
+}
+
+impl MapTag {
+    fn parse_value(&self, value: &[u8]) -> Result<TagValue> {
+        Ok(match self.value_type {
+            TableValueType::String => TagValue::String(value.into()),
+            TableValueType::Numeric => {
+                let str_value = std::str::from_utf8(value).context("Value not utf-8")?;
+                TagValue::Numeric(
+                    str_value
+                        .trim()
+                        .parse()
+                        .with_context(|| format!("Could not parse '{str_value}' as a number"))?,
+                )
+            }
+        })
+    }
+
+    fn read_table(&self) -> Result<LookupTable> {
+        let mut reader = open_table(&self.filename, self.delimiter)?;
+        let headers = reader
+            .byte_headers()
+            .with_context(|| format!("Could not read header line of {}", self.filename))?
+            .clone();
+        let key_idx = match &self.key_column {
+            Some(column) => column_index(&headers, column, &self.filename)?,
+            None => 0,
+        };
+        let value_idx = match &self.value_column {
+            Some(column) => column_index(&headers, column, &self.filename)?,
+            None => 1,
+        };
+        if headers.len() <= key_idx.max(value_idx) {
+            bail!(
+                "{} has only {} column(s) - need a key and a value column",
+                self.filename,
+                headers.len()
+            );
+        }
+        let mut entries: IndexMap<BString, TagValue> = IndexMap::new();
+        for record in reader.byte_records() {
+            let record =
+                record.with_context(|| format!("Could not read line from {}", self.filename))?;
+            let line_no = record.position().map_or(0, csv::Position::line);
+            let (Some(key), Some(value)) = (record.get(key_idx), record.get(value_idx)) else {
+                bail!("{}, line {line_no}: missing columns", self.filename);
+            };
+            let value = self
+                .parse_value(value)
+                .with_context(|| format!("{}, line {line_no}", self.filename))?;
+            if let Some(previous) = entries.insert(key.into(), value.clone())
+                && previous != value
+            {
+                bail!(
+                    "{}, line {line_no}: key '{}' is listed multiple times with different values",
+                    self.filename,
+                    BStr::new(key)
+                );
+            }
+        }
+        if entries.is_empty() {
+            bail!(
+                "No entries were read from {}. Check that the file is not empty and has a header line.",
+                self.filename
+            );
+        }
+        let default = match &self.default {
+            Some(default) => self.parse_value(default.as_bytes())?,
+            None => TagValue::Missing,
+        };
+        Ok(LookupTable::new(entries, default))
+    }
+}
+
+impl LookupTable {
+    fn new(entries: IndexMap<BString, TagValue>, default: TagValue) -> Self {
+        let (keys, values): (Vec<BString>, Vec<TagValue>) = entries.into_iter().unzip();
+        LookupTable {
+            keys: BarcodeIndex::from_keys(keys.into_iter()),
+            values,
+            default,
+        }
+    }
+
+    /// The exact match, or the single closest key within `max_hamming_distance`
+    fn lookup(&self, query: &[u8], max_hamming_distance: u8) -> &TagValue {
+        if let Some(idx) = self.keys.index_of(query) {
+            return &self.values[idx];
+        }
+        if max_hamming_distance > 0 {
+            let candidates = self.keys.candidates(query, max_hamming_distance, true);
+            if let Some(best_distance) = candidates.iter().map(|(_, distance)| *distance).min() {
+                let mut closest = candidates
+                    .iter()
+                    .filter(|(_, distance)| *distance == best_distance);
+                if let (Some((idx, _)), None) = (closest.next(), closest.next()) {
+                    return &self.values[*idx];
+                }
+            }
+        }
+        &self.default
+    }
+}
+
+impl Step for MapTag {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
+    fn init(
+        &mut self,
+        _input_info: &InputInfo,
+        _output_prefix: &str,
+        _output_directory: &Path,
+        _output_ix_separator: &str,
+        _demultiplex_info: &OptDemultiplex,
+        _allow_overwrite: bool,
+    ) -> Result<Option<DemultiplexBarcodes>> {
+        self.table = Some(Arc::new(self.read_table()?));
+        Ok(None)
+    }
+
+    fn apply(
+        &self,
+        mut block: FastQBlocksCombined,
+        _input_info: &InputInfo,
+        _block_no: usize,
+        _demultiplex_info: &OptDemultiplex,
+    ) -> anyhow::Result<(FastQBlocksCombined, bool)> {
+        let table = self
+            .table
+            .as_ref()
+            .expect("table must be read during initialization");
+        let input_tags = block
+            .tags
+            .get(&self.in_label)
+            .expect("Input tag not found - should have been caught in validation");
+        let output: Vec<TagValue> = input_tags
+            .iter()
+            .map(|tag_value| match tag_value.as_str(b"_") {
+                Some(query) => table.lookup(&query, self.max_hamming_distance).clone(),
+                None => TagValue::Missing,
+            })
+            .collect();
+        block.tags.insert(self.out_label.clone(), output);
+        Ok((block, true))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> LookupTable {
+        LookupTable::new(
+            [
+                (BString::from("AAAA"), TagValue::String("cell1".into())),
+                (BString::from("CCCC"), TagValue::String("cell2".into())),
+                (BString::from("CCGG"), TagValue::String("cell3".into())),
+            ]
+            .into_iter()
+            .collect(),
+            TagValue::String("unknown".into()),
+        )
+    }
+
+    #[test]
+    fn test_lookup_exact_and_default() {
+        let table = table();
+        assert_eq!(table.lookup(b"AAAA", 0), &TagValue::String("cell1".into()));
+        assert_eq!(
+            table.lookup(b"AAAT", 0),
+            &TagValue::String("unknown".into())
+        );
+    }
+
+    #[test]
+    fn test_lookup_hamming() {
+        let table = table();
+        assert_eq!(table.lookup(b"AAAT", 1), &TagValue::String("cell1".into()));
+        // CCCG is one away from both CCCC and CCGG
+        assert_eq!(
+            table.lookup(b"CCCG", 1),
+            &TagValue::String("unknown".into())
+        );
+        // different length never matches
+        assert_eq!(
+            table.lookup(b"AAAAA", 2),
+            &TagValue::String("unknown".into())
+        );
+    }
+
+    #[test]
+    fn test_lookup_hamming_neighbourhood() {
+        // enough keys that we look up the query's neighbours instead of scanning
+        let bases = [b'A', b'C', b'G', b'T'];
+        let entries: IndexMap<BString, TagValue> = bases
+            .iter()
+            .flat_map(|a| {
+                bases
+                    .iter()
+                    .flat_map(move |b| bases.iter().map(move |c| [*a, *b, *c]))
+            })
+            .map(|suffix| {
+                let key = BString::from([&b"A"[..], &suffix[..]].concat());
+                let value = TagValue::String(key.clone());
+                (key, value)
+            })
+            .collect();
+        let table = LookupTable::new(entries, TagValue::Missing);
+        assert_eq!(table.lookup(b"CACG", 1), &TagValue::String("AACG".into()));
+        assert_eq!(table.lookup(b"CACG", 0), &TagValue::Missing);
+        // one away from all of AACG, ACCG, AGCG, ATCG
+        assert_eq!(table.lookup(b"ANCG", 1), &TagValue::Missing);
+    }
+
+    #[test]
+    fn test_lookup_hamming_names() {
+        // not DNA - compared letter by letter
+        let table = LookupTable::new(
+            [
+                (BString::from("sample_R"), TagValue::String("r".into())),
+                (BString::from("sample_N"), TagValue::String("n".into())),
+            ]
+            .into_iter()
+            .collect(),
+            TagValue::Missing,
+        );
+        assert_eq!(table.lookup(b"sample_X", 0), &TagValue::Missing);
+        assert_eq!(table.lookup(b"sbmple_N", 1), &TagValue::String("n".into()));
+        assert_eq!(table.lookup(b"sample_X", 1), &TagValue::Missing);
+    }
+}
@@ -1,5 +1,10 @@
+mod bin_numeric_tag;
 mod eval_expression;
+mod map_tag;
 mod regions_to_length;
+mod string_expression;
 
+pub use bin_numeric_tag::{BinNumericTag, PartialBinNumericTag};
 pub use eval_expression::{EvalExpression, PartialEvalExpression};
+pub use map_tag::{MapTag, PartialMapTag, TableValueType};
 pub use regions_to_length::{PartialRegionsToLength, RegionsToLength};
@@ -10,6 +10,8 @@ use crate::{dna::TagValue, io};
 pub struct RegionsToLength {
     pub out_label: TagLabel,
     pub in_label: TagLabel,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 }
 
 impl VerifyIn<PartialConfig> for PartialRegionsToLength {
@@ -64,7 +66,10 @@ impl TagUser for PartialTaggedVariant<PartialRegionsToLength> {
             .as_mut()
             .expect("get_tag_usage should only be called after successful verification");
         TagUsageInfo {
-            used_tags: vec![inner.in_label.to_used_tag(&[TagValueType::Location])],
+            used_tags: vec![
+                inner.in_label.to_used_tag(&[TagValueType::Location]),
+                inner.if_tag.to_used_tag(&[][..]),
+            ],
             declared_tag: inner.out_label.to_declared_tag(TagValueType::Numeric),
             ..Default::default()
         }
@@ -72,6 +77,14 @@ impl TagUser for PartialTaggedVariant<PartialRegionsToLength> {
 }
 
 impl Step for RegionsToLength {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
     fn apply(
         &self,
         mut block: io::FastQBlocksCombined,
@@ -0,0 +1,789 @@
+/// The expression language of `EvalExpression` with `result_type = 'String'`.
+///
+/// fasteval only knows numbers, so string results get their own small language:
+/// string & number literals, tags as variables, comparisons, `and`/`or`/`not`,
+/// arithmetic, and the functions `concat`, `substr`, `if`, `contains`, `regex_match`,
+/// `lower` and `upper`.
+///
+/// Expressions are parsed (and regexs compiled) once, and evaluated per read.
+use anyhow::{Result, bail};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+
+use crate::dna::TagValue;
+use crate::transformations::tag::format_numeric_for_comment;
+
+const FUNCTIONS: &[&str] = &[
+    "concat",
+    "substr",
+    "if",
+    "contains",
+    "regex_match",
+    "lower",
+    "upper",
+];
+
+#[derive(Debug)]
+pub struct StringExpression {
+    root: Node,
+    /// the variables (tags and virtual tags) used,
+    /// `evaluate` expects their values in this order
+    pub var_names: BTreeSet<String>,
+}
+
+impl StringExpression {
+    pub fn parse(expression: &str) -> Result<Self> {
+        let tokens = tokenize(expression)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            var_names: BTreeSet::new(),
+        };
+        let mut root = parser.parse_or()?;
+        if let Some(token) = parser.peek() {
+            bail!(
+                "Unexpected {} after the end of the expression",
+                token.describe()
+            );
+        }
+        let var_names = parser.var_names;
+        root.resolve_vars(&var_names);
+        Ok(StringExpression { root, var_names })
+    }
+
+    /// `vars`: the value of each of `var_names`, in order.
+    pub fn evaluate(&self, vars: &[&TagValue]) -> Result<TagValue> {
+        Ok(match self.root.eval(vars)? {
+            Value::Missing => TagValue::Missing,
+            Value::Str(s) => TagValue::String(s.into_owned().into()),
+            Value::Num(n) => TagValue::String(format_numeric_for_comment(n).into()),
+            Value::Bool(b) => TagValue::String(if b { "true" } else { "false" }.into()),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value<'a> {
+    /// a tag without a value on this read
+    Missing,
+    Str(Cow<'a, [u8]>),
+    Num(f64),
+    Bool(bool),
+}
+
+impl<'a> Value<'a> {
+    fn from_tag(tag_value: &'a TagValue) -> Self {
+        match tag_value {
+            TagValue::Missing => Value::Missing,
+            TagValue::String(s) => Value::Str(Cow::Borrowed(s.as_slice())),
+            TagValue::Location(hits) => Value::Str(Cow::Owned(hits.joined_sequence(Some(b"_")))),
+            TagValue::Numeric(n) => Value::Num(*n),
+            TagValue::Bool(b) => Value::Bool(*b),
+        }
+    }
+
+    /// like the numeric expressions: any string is true, missing values are false
+    fn truthy(&self) -> bool {
+        match self {
+            Value::Missing => false,
+            Value::Str(_) => true,
+            Value::Num(n) => *n != 0.0,
+            Value::Bool(b) => *b,
+        }
+    }
+
+    fn into_bytes(self) -> Cow<'a, [u8]> {
+        match self {
+            Value::Missing => Cow::Borrowed(b""),
+            Value::Str(s) => s,
+            Value::Num(n) => Cow::Owned(format_numeric_for_comment(n).into_bytes()),
+            Value::Bool(b) => Cow::Borrowed(if b { b"true".as_slice() } else { b"false" }),
+        }
+    }
+
+    fn to_num(&self, context: &str) -> Result<f64> {
+        match self {
+            Value::Missing => Ok(0.0),
+            Value::Num(n) => Ok(*n),
+            Value::Bool(b) => Ok(if *b { 1.0 } else { 0.0 }),
+            Value::Str(s) => bail!(
+                "{context} needs a number, but got the string '{}'",
+                String::from_utf8_lossy(s)
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinaryOp {
+    Eq,
+    NotEq,
+    Less,
+    LessEq,
+    Greater,
+    GreaterEq,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+}
+
+impl BinaryOp {
+    fn symbol(self) -> &'static str {
+        match self {
+            BinaryOp::Eq => "==",
+            BinaryOp::NotEq => "!=",
+            BinaryOp::Less => "<",
+            BinaryOp::LessEq => "<=",
+            BinaryOp::Greater => ">",
+            BinaryOp::GreaterEq => ">=",
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Mod => "%",
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Literal {
+    Str(Vec<u8>),
+    Num(f64),
+    Bool(bool),
+}
+
+#[derive(Debug)]
+enum Node {
+    Literal(Literal),
+    /// name, index into `var_names`
+    Var(String, usize),
+    Not(Box<Node>),
+    Neg(Box<Node>),
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+    Concat(Vec<Node>),
+    Substr(Box<Node>, Box<Node>, Option<Box<Node>>),
+    If(Box<Node>, Box<Node>, Box<Node>),
+    Contains(Box<Node>, Box<Node>),
+    RegexMatch(Box<Node>, regex::bytes::Regex),
+    Lower(Box<Node>),
+    Upper(Box<Node>),
+}
+
+impl Node {
+    fn resolve_vars(&mut self, var_names: &BTreeSet<String>) {
+        match self {
+            Node::Literal(_) => {}
+            Node::Var(name, index) => {
+                *index = var_names
+                    .iter()
+                    .position(|x| x == name)
+                    .expect("all variables were collected while parsing");
+            }
+            Node::Not(inner)
+            | Node::Neg(inner)
+            | Node::Lower(inner)
+            | Node::Upper(inner)
+            | Node::RegexMatch(inner, _) => inner.resolve_vars(var_names),
+            Node::And(a, b) | Node::Or(a, b) | Node::Binary(_, a, b) | Node::Contains(a, b) => {
+                a.resolve_vars(var_names);
+                b.resolve_vars(var_names);
+            }
+            Node::Concat(parts) => {
+                for part in parts {
+                    part.resolve_vars(var_names);
+                }
+            }
+            Node::Substr(a, b, c) => {
+                a.resolve_vars(var_names);
+                b.resolve_vars(var_names);
+                if let Some(c) = c {
+                    c.resolve_vars(var_names);
+                }
+            }
+            Node::If(a, b, c) => {
+                a.resolve_vars(var_names);
+                b.resolve_vars(var_names);
+                c.resolve_vars(var_names);
+            }
+        }
+    }
+
+    fn eval<'a>(&'a self, vars: &[&'a TagValue]) -> Result<Value<'a>> {
+        Ok(match self {
+            Node::Literal(Literal::Str(s)) => Value::Str(Cow::Borrowed(s.as_slice())),
+            Node::Literal(Literal::Num(n)) => Value::Num(*n),
+            Node::Literal(Literal::Bool(b)) => Value::Bool(*b),
+            Node::Var(_, index) => Value::from_tag(vars[*index]),
+            Node::Not(inner) => Value::Bool(!inner.eval(vars)?.truthy()),
+            Node::Neg(inner) => Value::Num(-inner.eval(vars)?.to_num("'-'")?),
+            Node::And(a, b) => Value::Bool(a.eval(vars)?.truthy() && b.eval(vars)?.truthy()),
+            Node::Or(a, b) => Value::Bool(a.eval(vars)?.truthy() || b.eval(vars)?.truthy()),
+            Node::Binary(op, a, b) => binary(*op, a.eval(vars)?, b.eval(vars)?)?,
+            Node::Concat(parts) => {
+                let mut res = Vec::new();
+                for part in parts {
+                    res.extend_from_slice(&part.eval(vars)?.into_bytes());
+                }
+                Value::Str(Cow::Owned(res))
+            }
+            Node::Substr(s, start, len) => {
+                let s = s.eval(vars)?;
+                if s == Value::Missing {
+                    return Ok(Value::Missing);
+                }
+                let s = s.into_bytes();
+                let start = to_index(&start.eval(vars)?, "substr() start")?;
+                // negative start counts from the end
+                let start = if start < 0 {
+                    s.len().saturating_sub(to_usize(start.unsigned_abs()))
+                } else {
+                    to_usize(start.unsigned_abs()).min(s.len())
+                };
+                let end = match len {
+                    Some(len) => {
+                        let len = to_index(&len.eval(vars)?, "substr() length")?;
+                        if len < 0 {
+                            bail!("substr() length must not be negative, was {len}");
+                        }
+                        start
+                            .saturating_add(to_usize(len.unsigned_abs()))
+                            .min(s.len())
+                    }
+                    None => s.len(),
+                };
+                Value::Str(match s {
+                    Cow::Borrowed(s) => Cow::Borrowed(&s[start..end]),
+                    Cow::Owned(s) => Cow::Owned(s[start..end].to_vec()),
+                })
+            }
+            Node::If(condition, then, otherwise) => {
+                if condition.eval(vars)?.truthy() {
+                    then.eval(vars)?
+                } else {
+                    otherwise.eval(vars)?
+                }
+            }
+            Node::Contains(haystack, needle) => {
+                let haystack = haystack.eval(vars)?.into_bytes();
+                let needle = needle.eval(vars)?.into_bytes();
+                Value::Bool(memchr::memmem::find(&haystack, &needle).is_some())
+            }
+            Node::RegexMatch(s, regex) => Value::Bool(regex.is_match(&s.eval(vars)?.into_bytes())),
+            Node::Lower(inner) => match inner.eval(vars)? {
+                Value::Missing => Value::Missing,
+                value => Value::Str(Cow::Owned(value.into_bytes().to_ascii_lowercase())),
+            },
+            Node::Upper(inner) => match inner.eval(vars)? {
+                Value::Missing => Value::Missing,
+                value => Value::Str(Cow::Owned(value.into_bytes().to_ascii_uppercase())),
+            },
+        })
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn to_index(value: &Value, context: &str) -> Result<i64> {
+    let n = value.to_num(context)?;
+    if n.fract() != 0.0 || !n.is_finite() {
+        bail!("{context} must be a whole number, was {n}");
+    }
+    Ok(n as i64)
+}
+
+fn to_usize(n: u64) -> usize {
+    usize::try_from(n).unwrap_or(usize::MAX)
+}
+
+fn binary<'a>(op: BinaryOp, a: Value<'a>, b: Value<'a>) -> Result<Value<'a>> {
+    let context = format!("'{}'", op.symbol());
+    Ok(match op {
+        BinaryOp::Eq
+        | BinaryOp::NotEq
+        | BinaryOp::Less
+        | BinaryOp::LessEq
+        | BinaryOp::Greater
+        | BinaryOp::GreaterEq => {
+            // strings compare as bytes, everything else as numbers
+            let ordering = if matches!(a, Value::Str(_)) || matches!(b, Value::Str(_)) {
+                Some(a.into_bytes().cmp(&b.into_bytes()))
+            } else {
+                a.to_num(&context)?.partial_cmp(&b.to_num(&context)?)
+            };
+            Value::Bool(match op {
+                BinaryOp::Eq => ordering == Some(Ordering::Equal),
+                BinaryOp::NotEq => ordering != Some(Ordering::Equal),
+                BinaryOp::Less => ordering == Some(Ordering::Less),
+                BinaryOp::LessEq => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+                BinaryOp::Greater => ordering == Some(Ordering::Greater),
+                BinaryOp::GreaterEq => {
+                    matches!(ordering, Some(Ordering::Greater | Ordering::Equal))
+                }
+                _ => unreachable!(),
+            })
+        }
+        BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod => {
+            if op == BinaryOp::Add && (matches!(a, Value::Str(_)) || matches!(b, Value::Str(_))) {
+                bail!("'+' adds numbers. Use concat(a, b) to join strings");
+            }
+            let a = a.to_num(&context)?;
+            let b = b.to_num(&context)?;
+            Value::Num(match op {
+                BinaryOp::Add => a + b,
+                BinaryOp::Sub => a - b,
+                BinaryOp::Mul => a * b,
+                BinaryOp::Div => a / b,
+                BinaryOp::Mod => a % b,
+                _ => unreachable!(),
+            })
+        }
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(f64),
+    Str(Vec<u8>),
+    Ident(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Comma,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Num(n) => format!("number {n}"),
+            Token::Str(s) => format!("string '{}'", String::from_utf8_lossy(s)),
+            Token::Ident(name) => format!("'{name}'"),
+            Token::Op(op) => format!("'{op}'"),
+            Token::LParen => "'('".to_string(),
+            Token::RParen => "')'".to_string(),
+            Token::Comma => "','".to_string(),
+        }
+    }
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>> {
+    // longest first
+    const OPERATORS: &[&str] = &[
+        "==", "!=", "<=", ">=", "&&", "||", "<", ">", "!", "+", "-", "*", "/", "%",
+    ];
+    let bytes = expression.as_bytes();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    'outer: while pos < bytes.len() {
+        let c = bytes[pos];
+        match c {
+            b' ' | b'\t' | b'\n' | b'\r' => pos += 1,
+            b'(' => {
+                tokens.push(Token::LParen);
+                pos += 1;
+            }
+            b')' => {
+                tokens.push(Token::RParen);
+                pos += 1;
+            }
+            b',' => {
+                tokens.push(Token::Comma);
+                pos += 1;
+            }
+            b'\'' | b'"' => {
+                let mut value = Vec::new();
+                pos += 1;
+                loop {
+                    match bytes.get(pos) {
+                        None => bail!("Unterminated string literal"),
+                        Some(&quote) if quote == c => break,
+                        Some(b'\\') => {
+                            value.push(match bytes.get(pos + 1) {
+                                Some(b'n') => b'\n',
+                                Some(b't') => b'\t',
+                                Some(&other @ (b'\\' | b'\'' | b'"')) => other,
+                                _ => bail!(
+                                    "Unknown escape sequence in string literal. Use \\\\, \\', \\\", \\n or \\t"
+                                ),
+                            });
+                            pos += 2;
+                        }
+                        Some(&other) => {
+                            value.push(other);
+                            pos += 1;
+                        }
+                    }
+                }
+                pos += 1;
+                tokens.push(Token::Str(value));
+            }
+            b'0'..=b'9' | b'.' => {
+                let start = pos;
+                while pos < bytes.len() && (bytes[pos].is_ascii_digit() || bytes[pos] == b'.') {
+                    pos += 1;
+                }
+                let text = &expression[start..pos];
+                let value: f64 = text
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid number '{text}'"))?;
+                tokens.push(Token::Num(value));
+            }
+            c if c.is_ascii_alphabetic() || c == b'_' => {
+                let start = pos;
+                while pos < bytes.len()
+                    && (bytes[pos].is_ascii_alphanumeric() || bytes[pos] == b'_')
+                {
+                    pos += 1;
+                }
+                tokens.push(Token::Ident(expression[start..pos].to_string()));
+            }
+            _ => {
+                for op in OPERATORS {
+                    if bytes[pos..].starts_with(op.as_bytes()) {
+                        tokens.push(Token::Op(op));
+                        pos += op.len();
+                        continue 'outer;
+                    }
+                }
+                bail!(
+                    "Unexpected character '{}'",
+                    expression[pos..].chars().next().unwrap_or('?')
+                );
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    var_names: BTreeSet<String>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// consume the next token if it is one of these operators / keywords
+    fn accept(&mut self, ops: &[&str]) -> Option<&'static str> {
+        let found = match self.peek() {
+            Some(Token::Op(op)) => ops.iter().find(|x| *x == op).map(|_| *op),
+            Some(Token::Ident(name)) => match name.as_str() {
+                "and" if ops.contains(&"&&") => Some("&&"),
+                "or" if ops.contains(&"||") => Some("||"),
+                "not" if ops.contains(&"!") => Some("!"),
+                _ => None,
+            },
+            _ => None,
+        };
+        if found.is_some() {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, expected: &Token) -> Result<()> {
+        match self.next() {
+            Some(token) if token == *expected => Ok(()),
+            Some(token) => bail!(
+                "Expected {}, found {}",
+                expected.describe(),
+                token.describe()
+            ),
+            None => bail!("Expected {}, but the expression ended", expected.describe()),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Node> {
+        let mut node = self.parse_and()?;
+        while self.accept(&["||"]).is_some() {
+            node = Node::Or(Box::new(node), Box::new(self.parse_and()?));
+        }
+        Ok(node)
+    }
+
+    fn parse_and(&mut self) -> Result<Node> {
+        let mut node = self.parse_not()?;
+        while self.accept(&["&&"]).is_some() {
+            node = Node::And(Box::new(node), Box::new(self.parse_not()?));
+        }
+        Ok(node)
+    }
+
+    fn parse_not(&mut self) -> Result<Node> {
+        if self.accept(&["!"]).is_some() {
+            Ok(Node::Not(Box::new(self.parse_not()?)))
+        } else {
+            self.parse_comparison()
+        }
+    }
+
+    fn parse_comparison(&mut self) -> Result<Node> {
+        let node = self.parse_sum()?;
+        let op = match self.accept(&["==", "!=", "<=", ">=", "<", ">"]) {
+            Some("==") => BinaryOp::Eq,
+            Some("!=") => BinaryOp::NotEq,
+            Some("<=") => BinaryOp::LessEq,
+            Some(">=") => BinaryOp::GreaterEq,
+            Some("<") => BinaryOp::Less,
+            Some(">") => BinaryOp::Greater,
+            _ => return Ok(node),
+        };
+        Ok(Node::Binary(
+            op,
+            Box::new(node),
+            Box::new(self.parse_sum()?),
+        ))
+    }
+
+    fn parse_sum(&mut self) -> Result<Node> {
+        let mut node = self.parse_product()?;
+        while let Some(op) = self.accept(&["+", "-"]) {
+            let op = if op == "+" {
+                BinaryOp::Add
+            } else {
+                BinaryOp::Sub
+            };
+            node = Node::Binary(op, Box::new(node), Box::new(self.parse_product()?));
+        }
+        Ok(node)
+    }
+
+    fn parse_product(&mut self) -> Result<Node> {
+        let mut node = self.parse_unary()?;
+        while let Some(op) = self.accept(&["*", "/", "%"]) {
+            let op = match op {
+                "*" => BinaryOp::Mul,
+                "/" => BinaryOp::Div,
+                _ => BinaryOp::Mod,
+            };
+            node = Node::Binary(op, Box::new(node), Box::new(self.parse_unary()?));
+        }
+        Ok(node)
+    }
+
+    fn parse_unary(&mut self) -> Result<Node> {
+        if self.accept(&["-"]).is_some() {
+            Ok(Node::Neg(Box::new(self.parse_unary()?)))
+        } else {
+            self.parse_primary()
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Node> {
+        match self.next() {
+            Some(Token::Num(n)) => Ok(Node::Literal(Literal::Num(n))),
+            Some(Token::Str(s)) => Ok(Node::Literal(Literal::Str(s))),
+            Some(Token::LParen) => {
+                let node = self.parse_or()?;
+                self.expect(&Token::RParen)?;
+                Ok(node)
+            }
+            Some(Token::Ident(name)) => {
+                if self.peek() == Some(&Token::LParen) {
+                    self.pos += 1;
+                    self.parse_call(&name)
+                } else {
+                    match name.as_str() {
+                        "true" => Ok(Node::Literal(Literal::Bool(true))),
+                        "false" => Ok(Node::Literal(Literal::Bool(false))),
+                        "and" | "or" | "not" => bail!("Unexpected '{name}'"),
+                        _ => {
+                            self.var_names.insert(name.clone());
+                            Ok(Node::Var(name, 0))
+                        }
+                    }
+                }
+            }
+            Some(token) => bail!("Unexpected {}", token.describe()),
+            None => bail!("Unexpected end of expression"),
+        }
+    }
+
+    /// after the opening parenthesis
+    fn parse_call(&mut self, name: &str) -> Result<Node> {
+        let mut args = Vec::new();
+        if self.peek() == Some(&Token::RParen) {
+            self.pos += 1;
+        } else {
+            loop {
+                args.push(self.parse_or()?);
+                match self.next() {
+                    Some(Token::Comma) => {}
+                    Some(Token::RParen) => break,
+                    Some(token) => bail!(
+                        "Expected ',' or ')' in the arguments of {name}(), found {}",
+                        token.describe()
+                    ),
+                    None => bail!("Missing ')' after the arguments of {name}()"),
+                }
+            }
+        }
+        let arg_count = args.len();
+        let check_count = |range: std::ops::RangeInclusive<usize>, usage: &str| {
+            if range.contains(&arg_count) {
+                Ok(())
+            } else {
+                Err(anyhow::anyhow!(
+                    "{name}() takes {usage}, but got {arg_count} argument(s)"
+                ))
+            }
+        };
+        let mut args = args.into_iter().map(Box::new);
+        let mut arg = || args.next().expect("argument count checked");
+        Ok(match name {
+            "concat" => {
+                check_count(1..=usize::MAX, "at least one argument")?;
+                Node::Concat(args.map(|x| *x).collect())
+            }
+            "substr" => {
+                check_count(2..=3, "(string, start) or (string, start, length)")?;
+                Node::Substr(arg(), arg(), (arg_count == 3).then(arg))
+            }
+            "if" => {
+                check_count(3..=3, "(condition, value_if_true, value_if_false)")?;
+                Node::If(arg(), arg(), arg())
+            }
+            "contains" => {
+                check_count(2..=2, "(string, substring)")?;
+                Node::Contains(arg(), arg())
+            }
+            "regex_match" => {
+                check_count(2..=2, "(string, 'regex')")?;
+                let s = arg();
+                let Node::Literal(Literal::Str(pattern)) = *arg() else {
+                    bail!(
+                        "regex_match() needs a string literal as regex (it is compiled only once)"
+                    );
+                };
+                let pattern = String::from_utf8(pattern)
+                    .map_err(|_| anyhow::anyhow!("regex_match(): regex is not valid UTF-8"))?;
+                let regex = regex::bytes::Regex::new(&pattern)
+                    .map_err(|e| anyhow::anyhow!("regex_match(): invalid regex: {e}"))?;
+                Node::RegexMatch(s, regex)
+            }
+            "lower" => {
+                check_count(1..=1, "one argument")?;
+                Node::Lower(arg())
+            }
+            "upper" => {
+                check_count(1..=1, "one argument")?;
+                Node::Upper(arg())
+            }
+            _ => bail!(
+                "Unknown function '{name}'. Available: {}",
+                FUNCTIONS.join(", ")
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::dna::Hits;
+
+    fn eval(expression: &str, vars: &[(&str, TagValue)]) -> TagValue {
+        let parsed = StringExpression::parse(expression).unwrap();
+        let values: Vec<&TagValue> = parsed
+            .var_names
+            .iter()
+            .map(|name| &vars.iter().find(|(n, _)| n == name).unwrap().1)
+            .collect();
+        parsed.evaluate(&values).unwrap()
+    }
+
+    fn s(value: &str) -> TagValue {
+        TagValue::String(value.into())
+    }
+
+    #[test]
+    fn test_literals_and_functions() {
+        assert_eq!(eval("'abc'", &[]), s("abc"));
+        assert_eq!(eval("concat('a', \"b\", 3, 1.5)", &[]), s("ab31.5000"));
+        assert_eq!(eval("substr('ACGTACGT', 2, 3)", &[]), s("GTA"));
+        assert_eq!(eval("substr('ACGTACGT', -3)", &[]), s("CGT"));
+        assert_eq!(eval("substr('ACGT', 2, 100)", &[]), s("GT"));
+        assert_eq!(eval("substr('ACGT', 10)", &[]), s(""));
+        assert_eq!(eval("lower(concat('AB', 'c'))", &[]), s("abc"));
+        assert_eq!(eval("upper('ab\\'c')", &[]), s("AB'C"));
+        assert_eq!(eval("contains('ACGT', 'CG')", &[]), s("true"));
+        assert_eq!(eval("regex_match('ACGT', '^A.G')", &[]), s("true"));
+        assert_eq!(eval("regex_match('ACGT', '^G')", &[]), s("false"));
+        assert_eq!(eval("if(1 + 1 == 2, 'yes', 'no')", &[]), s("yes"));
+        assert_eq!(eval("2 * 3 - -1", &[]), s("7"));
+    }
+
+    #[test]
+    fn test_comparisons() {
+        assert_eq!(eval("'a' < 'b' and 2 >= 2", &[]), s("true"));
+        assert_eq!(eval("'a' == 'b' or !(1 != 1)", &[]), s("true"));
+        assert_eq!(eval("not true || false", &[]), s("false"));
+        assert_eq!(eval("'10' == 10", &[]), s("true"));
+    }
+
+    #[test]
+    fn test_variables() {
+        let vars = [
+            ("umi", s("ACGTTT")),
+            (
+                "barcode",
+                TagValue::Location(Hits::new_without_location("GG".into())),
+            ),
+            ("count", TagValue::Numeric(5.0)),
+            ("flag", TagValue::Bool(false)),
+            ("nothing", TagValue::Missing),
+        ];
+        assert_eq!(
+            eval("concat(barcode, '-', substr(umi, 0, 3))", &vars),
+            s("GG-ACG")
+        );
+        assert_eq!(eval("if(count > 3, 'many', 'few')", &vars), s("many"));
+        assert_eq!(eval("if(flag, 'a', 'b')", &vars), s("b"));
+        assert_eq!(eval("if(nothing, 'a', 'b')", &vars), s("b"));
+        assert_eq!(eval("nothing", &vars), TagValue::Missing);
+        assert_eq!(eval("substr(nothing, 1)", &vars), TagValue::Missing);
+        assert_eq!(eval("concat(nothing, 'x')", &vars), s("x"));
+    }
+
+    #[test]
+    fn test_var_names() {
+        let parsed = StringExpression::parse("concat(b, a, substr(b, len_read1))").unwrap();
+        assert_eq!(
+            parsed.var_names.iter().collect::<Vec<_>>(),
+            vec!["a", "b", "len_read1"]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        for (expression, error) in [
+            ("'abc", "Unterminated string literal"),
+            ("concat('a'", "Missing ')'"),
+            ("foo(1)", "Unknown function 'foo'"),
+            ("substr('a')", "substr() takes"),
+            ("regex_match('a', x)", "needs a string literal"),
+            ("regex_match('a', '(')", "invalid regex"),
+            ("1 2", "Unexpected number 2"),
+            ("a $ b", "Unexpected character '$'"),
+        ] {
+            let err = StringExpression::parse(expression).unwrap_err().to_string();
+            assert!(err.contains(error), "{expression}: {err}");
+        }
+        let parsed = StringExpression::parse("'a' + 1").unwrap();
+        let err = parsed.evaluate(&[]).unwrap_err().to_string();
+        assert!(err.contains("concat"), "{err}");
+    }
+}
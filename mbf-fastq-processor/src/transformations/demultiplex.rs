@@ -123,6 +123,9 @@ impl TagUser for PartialTaggedVariant<PartialDemultiplex> {
             .get(inner.in_label.as_ref().expect("parent was ok"))
             .map(|meta| &meta.tag_type);
         let upstream_label_is_bool = matches!(upstream_label_type, Some(TagValueType::Bool));
+        let upstream_categories = tags_available
+            .get(inner.in_label.as_ref().expect("parent was ok"))
+            .and_then(|meta| meta.categories.clone());
         if !upstream_label_is_bool
             && inner
                 .output_unmatched
@@ -141,6 +144,23 @@ impl TagUser for PartialTaggedVariant<PartialDemultiplex> {
             .as_mut()
             .expect("Was ok before, now might not be ok, but should be still set");
 
+        // tags with known values (e.g. BinNumericTag labels) get one output per value,
+        // just like bool tags
+        if matches!(inner.barcodes.as_ref(), Some(None))
+            && let Some(categories) = upstream_categories
+        {
+            let label = inner.in_label.as_ref().expect("parent was ok");
+            inner.resolved_barcodes = Some(
+                categories
+                    .into_iter()
+                    .map(|category| {
+                        let name = format!("{}={category}", label.0);
+                        (BString::from(category), name)
+                    })
+                    .collect(),
+            );
+        }
+
         TagUsageInfo {
             used_tags: vec![inner.in_label.to_used_tag(
                 &[
@@ -182,6 +202,24 @@ impl Step for Demultiplex {
         }))
     }
 
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn checkpoint_state(&self) -> Result<Option<serde_json::Value>> {
+        Ok(Some(serde_json::json!(
+            self.any_hit_observed
+                .load(std::sync::atomic::Ordering::Relaxed)
+        )))
+    }
+
+    fn restore_checkpoint_state(&mut self, state: serde_json::Value) -> Result<()> {
+        let value: bool = serde_json::from_value(state)?;
+        self.any_hit_observed
+            .store(value, std::sync::atomic::Ordering::Relaxed);
+        Ok(())
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
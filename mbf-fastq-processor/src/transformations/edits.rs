@@ -9,7 +9,9 @@ mod merge_reads;
 mod postfix;
 mod prefix;
 mod rename;
+mod rename_from_template;
 mod reverse_complement;
+mod split_at_tag;
 mod swap;
 mod trim_at_tag;
 mod truncate;
@@ -25,7 +27,9 @@ pub use merge_reads::{MergeReads, PartialMergeReads};
 pub use postfix::{PartialPostfix, Postfix};
 pub use prefix::{PartialPrefix, Prefix};
 pub use rename::{PartialRename, Rename};
+pub use rename_from_template::{PartialRenameFromTemplate, RenameFromTemplate};
 pub use reverse_complement::{PartialReverseComplement, ReverseComplement};
+pub use split_at_tag::{PartialSplitAtTag, SplitAtTag};
 pub use swap::{PartialSwap, Swap};
 pub use trim_at_tag::{PartialTrimAtTag, TrimAtTag};
 pub use truncate::{PartialTruncate, Truncate};
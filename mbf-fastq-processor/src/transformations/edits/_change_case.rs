@@ -93,6 +93,14 @@ impl TagUser for PartialTaggedVariant<Partial_ChangeCase> {
 }
 
 impl Step for _ChangeCase {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn modified_segments(&self) -> ModifiedSegments {
+        (&self.target).into()
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
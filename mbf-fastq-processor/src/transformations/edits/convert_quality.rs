@@ -11,6 +11,8 @@ use crate::config::PhredEncoding;
 pub struct ConvertQuality {
     pub from: PhredEncoding,
     to: PhredEncoding,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 }
 impl VerifyIn<PartialConfig> for PartialConvertQuality {
     fn verify(
@@ -51,10 +53,35 @@ fn solexa_to_phred(q_solexa: i16) -> i16 {
 }
 
 impl TagUser for PartialTaggedVariant<PartialConvertQuality> {
-    //empty is ok
+    fn get_tag_usage(
+        &mut self,
+        _tags_available: &IndexMap<TagLabel, TagMetadata>,
+        _segment_order: &[String],
+    ) -> TagUsageInfo<'_> {
+        let inner = self
+            .toml_value
+            .as_mut()
+            .expect("get_tag_usage should only be called after successful verification");
+        TagUsageInfo {
+            used_tags: vec![inner.if_tag.to_used_tag(&[][..])],
+            ..Default::default()
+        }
+    }
 }
 
 impl Step for ConvertQuality {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
+    fn modified_segments(&self) -> ModifiedSegments {
+        ModifiedSegments::All
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
@@ -58,6 +58,14 @@ impl TagUser for PartialTaggedVariant<PartialCutEnd> {
 }
 
 impl Step for CutEnd {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn modified_segments(&self) -> ModifiedSegments {
+        self.segment.into()
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
@@ -61,6 +61,14 @@ impl TagUser for PartialTaggedVariant<PartialCutStart> {
 }
 
 impl Step for CutStart {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn modified_segments(&self) -> ModifiedSegments {
+        self.segment.into()
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
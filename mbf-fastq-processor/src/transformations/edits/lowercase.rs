@@ -42,6 +42,14 @@ impl TagUser for PartialTaggedVariant<PartialLowercase> {
 }
 
 impl Step for Lowercase {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn modified_segments(&self) -> ModifiedSegments {
+        (&self.target).into()
+    }
+
     fn apply(
         &self,
         block: FastQBlocksCombined,
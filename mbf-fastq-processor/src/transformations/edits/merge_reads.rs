@@ -67,6 +67,8 @@ pub struct MergeReads {
     #[schemars(with = "String")]
     #[tpd(adapt_in_verify(String))]
     pub segment2: SegmentIndex,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 }
 
 impl VerifyIn<PartialConfig> for PartialMergeReads {
@@ -170,6 +172,7 @@ impl TagUser for PartialTaggedVariant<PartialMergeReads> {
             .as_mut()
             .expect("get_tag_usage should only be called after successful verification");
         TagUsageInfo {
+            used_tags: vec![inner.if_tag.to_used_tag(&[][..])],
             declared_tag: inner.out_label.to_declared_tag(TagValueType::Bool),
             ..Default::default()
         }
@@ -177,6 +180,18 @@ impl TagUser for PartialTaggedVariant<PartialMergeReads> {
 }
 
 impl Step for MergeReads {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
+    fn modified_segments(&self) -> ModifiedSegments {
+        ModifiedSegments::Some(vec![self.segment1, self.segment2])
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
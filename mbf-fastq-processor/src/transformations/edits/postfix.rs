@@ -87,6 +87,14 @@ impl TagUser for PartialTaggedVariant<PartialPostfix> {
 }
 
 impl Step for Postfix {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn modified_segments(&self) -> ModifiedSegments {
+        self.segment.into()
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
@@ -92,6 +92,14 @@ impl TagUser for PartialTaggedVariant<PartialPrefix> {
 }
 
 impl Step for Prefix {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn modified_segments(&self) -> ModifiedSegments {
+        self.segment.into()
+    }
+
     //to modify location tags
 
     fn apply(
@@ -19,6 +19,8 @@ pub struct Rename {
     #[tpd(with = "tpd_adapt_bstring")]
     #[schemars(with = "String")]
     pub replacement: BString,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 
     #[schemars(skip)]
     #[tpd(skip)]
@@ -53,9 +55,48 @@ impl VerifyIn<PartialConfig> for PartialRename {
     }
 }
 
-impl TagUser for PartialTaggedVariant<PartialRename> {}
+impl TagUser for PartialTaggedVariant<PartialRename> {
+    fn get_tag_usage(
+        &mut self,
+        _tags_available: &IndexMap<TagLabel, TagMetadata>,
+        _segment_order: &[String],
+    ) -> TagUsageInfo<'_> {
+        let inner = self
+            .toml_value
+            .as_mut()
+            .expect("get_tag_usage should only be called after successful verification");
+        TagUsageInfo {
+            used_tags: vec![inner.if_tag.to_used_tag(&[][..])],
+            ..Default::default()
+        }
+    }
+}
 
 impl Step for Rename {
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
+    fn modified_segments(&self) -> ModifiedSegments {
+        ModifiedSegments::All
+    }
+
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn checkpoint_state(&self) -> Result<Option<serde_json::Value>> {
+        Ok(Some(serde_json::json!(
+            self.next_index.load(Ordering::Relaxed)
+        )))
+    }
+
+    fn restore_checkpoint_state(&mut self, state: serde_json::Value) -> Result<()> {
+        self.next_index
+            .store(serde_json::from_value(state)?, Ordering::Relaxed);
+        Ok(())
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
@@ -0,0 +1,317 @@
+#![allow(clippy::unnecessary_wraps)] //eserde false positives
+
+use crate::{
+    config::deser::tpd_adapt_bstring,
+    dna::TagValue,
+    transformations::{
+        prelude::*,
+        tag::{default_region_separator, format_numeric_for_comment},
+    },
+};
+use std::{cell::RefCell, rc::Rc, sync::atomic::Ordering};
+
+use bstr::ByteSlice;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TemplatePart {
+    Literal(Vec<u8>),
+    Tag(TagLabel),
+    /// `{read_no}`
+    ReadNo,
+    /// `{segment}`
+    Segment,
+    /// `{name}`
+    Name,
+    /// `{name_<n>}`, 1-based
+    NamePart(usize),
+}
+
+/// Parse `template` into literals and `{placeholders}`. `{{` and `}}` are literal braces.
+fn parse_template(template: &str) -> std::result::Result<Vec<TemplatePart>, String> {
+    let mut parts = Vec::new();
+    let mut literal = Vec::new();
+    let mut rest = template;
+    while let Some(pos) = rest.find(['{', '}']) {
+        literal.extend_from_slice(&rest.as_bytes()[..pos]);
+        let brace = &rest[pos..=pos];
+        rest = &rest[pos + 1..];
+        if let Some(after) = rest.strip_prefix(brace) {
+            literal.extend_from_slice(brace.as_bytes());
+            rest = after;
+            continue;
+        }
+        if brace == "}" {
+            return Err("Unmatched '}' - use '}}' for a literal brace".to_string());
+        }
+        let Some(end) = rest.find('}') else {
+            return Err("Unclosed '{' - use '{{' for a literal brace".to_string());
+        };
+        let placeholder = &rest[..end];
+        rest = &rest[end + 1..];
+        if placeholder.is_empty() || placeholder.contains('{') {
+            return Err(format!("Invalid placeholder '{{{placeholder}}}'"));
+        }
+        if !literal.is_empty() {
+            parts.push(TemplatePart::Literal(std::mem::take(&mut literal)));
+        }
+        parts.push(match placeholder {
+            "read_no" => TemplatePart::ReadNo,
+            "segment" => TemplatePart::Segment,
+            "name" => TemplatePart::Name,
+            _ => match placeholder
+                .strip_prefix("name_")
+                .and_then(|no| no.parse::<usize>().ok())
+            {
+                Some(0) => {
+                    return Err(
+                        "Name parts are 1-based: '{name_1}' is the part before the first read_comment_character".to_string(),
+                    );
+                }
+                Some(no) => TemplatePart::NamePart(no),
+                None => TemplatePart::Tag(TagLabel(placeholder.to_string())),
+            },
+        });
+    }
+    literal.extend_from_slice(rest.as_bytes());
+    if !literal.is_empty() {
+        parts.push(TemplatePart::Literal(literal));
+    }
+    Ok(parts)
+}
+
+/// Build new read names from a template of tags, segment names,
+/// parts of the original name and a read counter
+#[derive(JsonSchema)]
+#[tpd]
+#[derive(Debug)]
+pub struct RenameFromTemplate {
+    /// e.g. '{sample}:{umi}:{cell}#{read_no}'
+    pub template: String,
+    /// Separator between the sequences of location tags with multiple regions
+    #[tpd(with = "tpd_adapt_bstring")]
+    #[schemars(with = "String")]
+    pub region_separator: BString,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
+
+    #[schemars(skip)]
+    #[tpd(skip)]
+    parts: Vec<TemplatePart>,
+
+    #[schemars(skip)]
+    #[tpd(skip)]
+    next_index: std::sync::atomic::AtomicU64,
+
+    #[schemars(skip)]
+    #[tpd(skip)]
+    needs_counting: bool,
+}
+
+impl VerifyIn<PartialConfig> for PartialRenameFromTemplate {
+    fn verify(
+        &mut self,
+        _parent: &PartialConfig,
+        _options: &VerifyOptions,
+    ) -> std::result::Result<(), ValidationFailure>
+    where
+        Self: Sized + toml_pretty_deser::Visitor,
+    {
+        self.region_separator.or_with(default_region_separator);
+        self.template.verify(|v| {
+            if v.is_empty() {
+                return Err(ValidationFailure::new(
+                    "Template must not be empty",
+                    Some("Provide a template such as '{sample}:{umi}#{read_no}'"),
+                ));
+            }
+            parse_template(v).map(|_| ()).map_err(|msg| {
+                ValidationFailure::new(
+                    msg,
+                    Some(
+                        "Placeholders are tag labels, {read_no}, {segment}, {name} or {name_<n>}.",
+                    ),
+                )
+            })
+        });
+        let parts = self
+            .template
+            .as_ref()
+            .and_then(|template| parse_template(template).ok())
+            .unwrap_or_default();
+        self.needs_counting = Some(parts.contains(&TemplatePart::ReadNo));
+        self.parts = Some(parts);
+        self.next_index = Some(std::sync::atomic::AtomicU64::new(0));
+        Ok(())
+    }
+}
+
+impl TagUser for PartialTaggedVariant<PartialRenameFromTemplate> {
+    fn get_tag_usage(
+        &mut self,
+        _tags_available: &IndexMap<TagLabel, TagMetadata>,
+        _segment_order: &[String],
+    ) -> TagUsageInfo<'_> {
+        let inner = self
+            .toml_value
+            .as_mut()
+            .expect("get_tag_usage should only be called after successful verification");
+        let mut used_tags = Vec::new();
+        let toml_source = Rc::new(RefCell::new((
+            &mut inner.template.state,
+            &mut inner.template.help,
+        )));
+        for part in inner.parts.as_ref().expect("set in verify") {
+            if let TemplatePart::Tag(label) = part {
+                used_tags.push(Some(UsedTag {
+                    name: label.clone(),
+                    accepted_tag_types: &[
+                        TagValueType::Bool,
+                        TagValueType::Numeric,
+                        TagValueType::String,
+                        TagValueType::Location,
+                    ][..],
+                    toml_source: toml_source.clone(),
+                    further_help: None,
+                }));
+            }
+        }
+        used_tags.push(inner.if_tag.to_used_tag(&[][..]));
+        TagUsageInfo {
+            used_tags,
+            ..Default::default()
+        }
+    }
+}
+
+impl RenameFromTemplate {
+    fn format_tag(&self, tag_value: &TagValue, out: &mut Vec<u8>) {
+        match tag_value {
+            TagValue::Location(hits) => {
+                out.extend_from_slice(&hits.joined_sequence(Some(&self.region_separator)));
+            }
+            TagValue::String(value) => out.extend_from_slice(value),
+            TagValue::Numeric(n) => {
+                out.extend_from_slice(format_numeric_for_comment(*n).as_bytes())
+            }
+            TagValue::Bool(b) => out.push(if *b { b'1' } else { b'0' }),
+            TagValue::Missing => {}
+        }
+    }
+}
+
+impl Step for RenameFromTemplate {
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
+    fn modified_segments(&self) -> ModifiedSegments {
+        ModifiedSegments::All
+    }
+
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn checkpoint_state(&self) -> Result<Option<serde_json::Value>> {
+        Ok(Some(serde_json::json!(
+            self.next_index.load(Ordering::Relaxed)
+        )))
+    }
+
+    fn restore_checkpoint_state(&mut self, state: serde_json::Value) -> Result<()> {
+        self.next_index
+            .store(serde_json::from_value(state)?, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn apply(
+        &self,
+        mut block: FastQBlocksCombined,
+        input_info: &InputInfo,
+        _block_no: usize,
+        _demultiplex_info: &OptDemultiplex,
+    ) -> anyhow::Result<(FastQBlocksCombined, bool)> {
+        let read_count = block.segments[0].entries.len();
+        if read_count == 0 {
+            return Ok((block, true));
+        }
+
+        let base_index = if self.needs_counting {
+            self.next_index
+                .fetch_add(read_count as u64, Ordering::Relaxed)
+        } else {
+            0
+        };
+
+        let mut new_name = Vec::new();
+        for (segment_idx, segment_block) in block.segments.iter_mut().enumerate() {
+            let segment_name = input_info.segment_order[segment_idx].as_bytes();
+            for read_idx in 0..read_count {
+                let mut read = segment_block.get_mut(read_idx);
+                new_name.clear();
+                for part in &self.parts {
+                    match part {
+                        TemplatePart::Literal(literal) => new_name.extend_from_slice(literal),
+                        TemplatePart::Tag(label) => self.format_tag(
+                            &block.tags.get(label).expect("Tag not found, bug")[read_idx],
+                            &mut new_name,
+                        ),
+                        TemplatePart::ReadNo => {
+                            let current_index = base_index.wrapping_add(read_idx as u64);
+                            new_name.extend_from_slice(current_index.to_string().as_bytes());
+                        }
+                        TemplatePart::Segment => new_name.extend_from_slice(segment_name),
+                        TemplatePart::Name => new_name.extend_from_slice(read.name()),
+                        TemplatePart::NamePart(no) => {
+                            if let Some(name_part) = read
+                                .name()
+                                .split_str(&[input_info.comment_insert_char])
+                                .nth(no - 1)
+                            {
+                                new_name.extend_from_slice(name_part);
+                            }
+                        }
+                    }
+                }
+                read.replace_name(&new_name);
+            }
+        }
+
+        Ok((block, true))
+    }
+
+    fn needs_serial(&self) -> bool {
+        self.needs_counting
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_template() {
+        assert_eq!(
+            parse_template("{sample}:{name_1}#{read_no}/{segment}{{x}}").expect("valid"),
+            vec![
+                TemplatePart::Tag(TagLabel("sample".to_string())),
+                TemplatePart::Literal(b":".to_vec()),
+                TemplatePart::NamePart(1),
+                TemplatePart::Literal(b"#".to_vec()),
+                TemplatePart::ReadNo,
+                TemplatePart::Literal(b"/".to_vec()),
+                TemplatePart::Segment,
+                TemplatePart::Literal(b"{x}".to_vec()),
+            ]
+        );
+        assert_eq!(
+            parse_template("{name}").expect("valid"),
+            vec![TemplatePart::Name]
+        );
+        assert!(parse_template("{name_0}").is_err());
+        assert!(parse_template("{umi").is_err());
+        assert!(parse_template("umi}").is_err());
+        assert!(parse_template("{}").is_err());
+        assert!(parse_template("{a{b}").is_err());
+    }
+}
@@ -50,6 +50,14 @@ impl TagUser for PartialTaggedVariant<PartialReverseComplement> {
 }
 
 impl Step for ReverseComplement {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn modified_segments(&self) -> ModifiedSegments {
+        self.segment.into()
+    }
+
     #[allow(clippy::redundant_closure_for_method_calls)] // otherwise the FnOnce is not general
     // enough
     fn apply(
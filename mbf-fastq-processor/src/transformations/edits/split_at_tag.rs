@@ -0,0 +1,173 @@
+#![allow(clippy::unnecessary_wraps)] //eserde false positives
+
+use crate::io::reads::ReadFragment;
+use crate::transformations::prelude::*;
+
+/// Split reads at a tag's regions (e.g. the adapters between concatenated molecules)
+/// into one read per fragment
+#[derive(Clone, JsonSchema)]
+#[tpd]
+#[derive(Debug)]
+pub struct SplitAtTag {
+    in_label: TagLabel,
+    /// Drop fragments shorter than this. Empty fragments are always dropped
+    #[tpd(default)]
+    min_length: usize,
+}
+
+impl VerifyIn<PartialConfig> for PartialSplitAtTag {
+    fn verify(
+        &mut self,
+        parent: &PartialConfig,
+        _options: &VerifyOptions,
+    ) -> std::result::Result<(), ValidationFailure>
+    where
+        Self: Sized + toml_pretty_deser::Visitor,
+    {
+        self.in_label.verify(|v| {
+            if v.0.is_empty() {
+                Err(ValidationFailure::new("Must not be empty", None))
+            } else {
+                Ok(())
+            }
+        });
+        if let Some(input_def) = parent.input.as_ref()
+            && input_def.get_segment_order().len() != 1
+        {
+            return Err(ValidationFailure::new(
+                "SplitAtTag requires a single segment input",
+                Some(
+                    "The fragments of the segments of one molecule would not line up.\n\
+                    This is an implementation limitation, not a design one, PR welcome.",
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl TagUser for PartialTaggedVariant<PartialSplitAtTag> {
+    fn get_tag_usage(
+        &mut self,
+        _tags_available: &IndexMap<TagLabel, TagMetadata>,
+        _segment_order: &[String],
+    ) -> TagUsageInfo<'_> {
+        let inner = self
+            .toml_value
+            .as_mut()
+            .expect("get_tag_usage should only be called after successful verification");
+        TagUsageInfo {
+            used_tags: vec![inner.in_label.to_used_tag(&[TagValueType::Location][..])],
+            must_see_all_tags: true, // for moving them into the fragments
+            ..Default::default()
+        }
+    }
+}
+
+impl SplitAtTag {
+    /// The parts of a read of length `read_len` between the (possibly overlapping) cut regions
+    fn fragment_ranges(
+        &self,
+        mut cuts: Vec<std::ops::Range<usize>>,
+        read_len: usize,
+    ) -> Vec<std::ops::Range<usize>> {
+        cuts.sort_by_key(|cut| cut.start);
+        let mut fragments = Vec::with_capacity(cuts.len() + 1);
+        let mut start = 0;
+        for cut in cuts {
+            if cut.start > start {
+                fragments.push(start..cut.start.min(read_len));
+            }
+            start = start.max(cut.end);
+        }
+        if start < read_len {
+            fragments.push(start..read_len);
+        }
+        fragments.retain(|range| !range.is_empty() && range.len() >= self.min_length);
+        fragments
+    }
+}
+
+impl Step for SplitAtTag {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn modified_segments(&self) -> ModifiedSegments {
+        ModifiedSegments::All
+    }
+
+    fn apply(
+        &self,
+        mut block: FastQBlocksCombined,
+        input_info: &InputInfo,
+        _block_no: usize,
+        _demultiplex_info: &OptDemultiplex,
+    ) -> anyhow::Result<(FastQBlocksCombined, bool)> {
+        let hits = block
+            .tags
+            .get(&self.in_label)
+            .expect("in_label tag must exist in block");
+        let mut fragments = Vec::with_capacity(block.len());
+        for (idx, tag_value) in hits.iter().enumerate() {
+            let read_len = block.segments[0].entries[idx].seq.len();
+            let cuts: Vec<_> = tag_value
+                .as_sequence()
+                .map(|hits| {
+                    hits.0
+                        .iter()
+                        .filter_map(|hit| hit.location.as_ref())
+                        .map(|location| location.start..location.start + location.len)
+                        .collect()
+                })
+                .unwrap_or_default();
+            if cuts.is_empty() {
+                // nothing to split - the read passes unchanged
+                fragments.push(ReadFragment {
+                    source: idx,
+                    range: 0..read_len,
+                    name_suffix: None,
+                });
+                continue;
+            }
+            for (fragment_no, range) in self.fragment_ranges(cuts, read_len).into_iter().enumerate()
+            {
+                fragments.push(ReadFragment {
+                    source: idx,
+                    range,
+                    name_suffix: Some(format!("_{}", fragment_no + 1).into()),
+                });
+            }
+        }
+        block.split_reads(&fragments, input_info.comment_insert_char);
+        Ok((block, true))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(min_length: usize) -> SplitAtTag {
+        SplitAtTag {
+            in_label: TagLabel("adapter".to_string()),
+            min_length,
+        }
+    }
+
+    #[test]
+    fn test_fragment_ranges() {
+        assert_eq!(step(0).fragment_ranges(vec![4..6], 10), vec![0..4, 6..10]);
+        // cuts at the ends leave no empty fragments
+        assert_eq!(step(0).fragment_ranges(vec![0..2, 8..10], 10), vec![2..8]);
+        // unsorted & overlapping cuts
+        assert_eq!(
+            step(0).fragment_ranges(vec![6..8, 2..4, 3..5], 10),
+            vec![0..2, 5..6, 8..10]
+        );
+        assert_eq!(
+            step(2).fragment_ranges(vec![6..8, 2..4, 3..5], 10),
+            vec![0..2, 8..10]
+        );
+    }
+}
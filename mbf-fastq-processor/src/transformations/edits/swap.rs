@@ -120,6 +120,14 @@ impl TagUser for PartialTaggedVariant<PartialSwap> {
 }
 
 impl Step for Swap {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn modified_segments(&self) -> ModifiedSegments {
+        ModifiedSegments::Some(vec![self.segment_a, self.segment_b])
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
@@ -22,6 +22,8 @@ pub struct TrimAtTag {
     in_label: TagLabel,
     direction: Direction,
     keep_tag: bool,
+    #[tpd(default)]
+    if_tag: Option<ConditionalTagLabel>,
 }
 
 impl VerifyIn<PartialConfig> for PartialTrimAtTag {
@@ -55,7 +57,10 @@ impl TagUser for PartialTaggedVariant<PartialTrimAtTag> {
             .as_mut()
             .expect("get_tag_usage should only be called after successful verification");
         TagUsageInfo {
-            used_tags: vec![inner.in_label.to_used_tag(&[TagValueType::Location][..])],
+            used_tags: vec![
+                inner.in_label.to_used_tag(&[TagValueType::Location][..]),
+                inner.if_tag.to_used_tag(&[][..]),
+            ],
             must_see_all_tags: true, // for cutting them down
             ..Default::default()
         }
@@ -104,6 +109,18 @@ impl TagUser for PartialTaggedVariant<PartialTrimAtTag> {
 }
 
 impl Step for TrimAtTag {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
+    fn modified_segments(&self) -> ModifiedSegments {
+        ModifiedSegments::TagRegions(self.in_label.clone())
+    }
+
     #[allow(clippy::too_many_lines)]
     fn apply(
         &self,
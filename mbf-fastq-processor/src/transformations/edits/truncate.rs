@@ -50,6 +50,14 @@ impl TagUser for PartialTaggedVariant<PartialTruncate> {
 }
 
 impl Step for Truncate {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn modified_segments(&self) -> ModifiedSegments {
+        self.segment.into()
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
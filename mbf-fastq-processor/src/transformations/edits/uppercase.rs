@@ -41,6 +41,14 @@ impl TagUser for PartialTaggedVariant<PartialUppercase> {
 }
 
 impl Step for Uppercase {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn modified_segments(&self) -> ModifiedSegments {
+        (&self.target).into()
+    }
+
     fn apply(
         &self,
         block: FastQBlocksCombined,
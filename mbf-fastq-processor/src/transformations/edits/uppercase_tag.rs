@@ -11,6 +11,10 @@ pub struct UppercaseTag {
 }
 
 impl Step for UppercaseTag {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
     fn uses_tags(
         &self,
         _tags_available: &IndexMap<TagLabel, TagMetadata>,
@@ -0,0 +1,570 @@
+#![allow(clippy::unnecessary_wraps)]
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use crate::transformations::prelude::*;
+
+use crate::dna::TagValue;
+
+/// How reads are handed to (and expected back from) the external program
+#[derive(Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[tpd]
+pub enum ExternalFormat {
+    /// 4 line FASTQ records
+    Fastq,
+    /// One line per read: name, seq, qual, `in_labels`... (tab separated)
+    Tsv,
+}
+
+/// Type of the tag read back from the external program (format = 'Tsv' only)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, JsonSchema)]
+#[tpd]
+pub enum ExternalTagType {
+    #[default]
+    String,
+    Numeric,
+    Bool,
+}
+
+type Workers = Arc<Vec<Mutex<Worker>>>;
+
+/// Stream reads through a long running external program and back
+#[derive(Clone, JsonSchema)]
+#[tpd]
+#[derive(Debug)]
+pub struct ExternalCommand {
+    /// Program and its arguments. No shell is involved.
+    command: Vec<String>,
+    format: ExternalFormat,
+    #[schemars(with = "String")]
+    #[tpd(adapt_in_verify(String))]
+    segment: SegmentIndex,
+
+    /// Tags sent along as additional columns (format = 'Tsv' only)
+    #[tpd(default)]
+    in_labels: Vec<TagLabel>,
+    /// Store the additional column the program returns in this tag (format = 'Tsv' only)
+    #[tpd(default)]
+    out_label: Option<TagLabel>,
+    #[tpd(default)]
+    out_tag_type: ExternalTagType,
+
+    /// Number of program instances. Blocks are distributed among them.
+    workers: usize,
+    /// Hand the blocks to the program one at a time, in input order
+    #[tpd(default)]
+    serial: bool,
+
+    #[schemars(skip)]
+    #[tpd(skip, default)]
+    worker_handles: Option<Workers>,
+}
+
+fn default_workers() -> usize {
+    1
+}
+
+impl VerifyIn<PartialConfig> for PartialExternalCommand {
+    fn verify(
+        &mut self,
+        parent: &PartialConfig,
+        _options: &VerifyOptions,
+    ) -> std::result::Result<(), ValidationFailure>
+    where
+        Self: Sized + toml_pretty_deser::Visitor,
+    {
+        self.segment.validate_segment(parent);
+        self.workers.or_with(default_workers);
+        self.command.verify(|command| {
+            match command.first().and_then(|program| program.as_ref()) {
+                Some(program) if !program.is_empty() => Ok(()),
+                _ => Err(ValidationFailure::new(
+                    "command must not be empty",
+                    Some("Provide the program and its arguments, e.g. ['python3', '-u', 'classify.py']"),
+                )),
+            }
+        });
+        self.workers.verify(|workers| {
+            if *workers == 0 {
+                Err(ValidationFailure::new(
+                    "workers must be > 0",
+                    Some("Set to the number of program instances to start."),
+                ))
+            } else {
+                Ok(())
+            }
+        });
+        if self.serial.as_ref() == Some(&true) && self.workers.as_ref().is_some_and(|w| *w > 1) {
+            self.workers.state =
+                TomlValueState::new_validation_failed("serial = true can only use one worker");
+            self.workers.help = Some(
+                "Serial steps see one block at a time, in order. Set workers = 1 or serial = false."
+                    .to_string(),
+            );
+        }
+        if self.format.as_ref() == Some(&ExternalFormat::Fastq) {
+            if self
+                .in_labels
+                .as_ref()
+                .is_some_and(|labels| !labels.is_empty())
+            {
+                self.in_labels.state = TomlValueState::new_validation_failed(
+                    "Tags can only be sent with format = 'Tsv'",
+                );
+                self.in_labels.help =
+                    Some("FASTQ records have no room for tags. Use format = 'Tsv'.".to_string());
+            }
+            if self.out_label.as_ref().is_some_and(Option::is_some) {
+                self.out_label.state = TomlValueState::new_validation_failed(
+                    "Tags can only be read back with format = 'Tsv'",
+                );
+                self.out_label.help =
+                    Some("FASTQ records have no room for tags. Use format = 'Tsv'.".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TagUser for PartialTaggedVariant<PartialExternalCommand> {
+    fn get_tag_usage(
+        &mut self,
+        _tags_available: &IndexMap<TagLabel, TagMetadata>,
+        _segment_order: &[String],
+    ) -> TagUsageInfo<'_> {
+        let inner = self
+            .toml_value
+            .as_mut()
+            .expect("get_tag_usage should only be called after successful verification");
+        let tag_type = match inner.out_tag_type.as_ref().expect("parent was ok") {
+            ExternalTagType::String => TagValueType::String,
+            ExternalTagType::Numeric => TagValueType::Numeric,
+            ExternalTagType::Bool => TagValueType::Bool,
+        };
+        TagUsageInfo {
+            used_tags: inner
+                .in_labels
+                .as_mut()
+                .expect("parent was ok")
+                .iter_mut()
+                .map(|label| label.to_used_tag(ANY_TAG_TYPE))
+                .collect(),
+            declared_tag: inner.out_label.to_declared_tag(tag_type),
+            // changed sequences invalidate the locations of all tags on that segment
+            must_see_all_tags: true,
+            ..Default::default()
+        }
+    }
+}
+
+/// One running instance of the external program
+#[derive(Debug)]
+struct Worker {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        // not finalized (an error occurred somewhere) - don't leave the program running
+        if self.stdin.take().is_some() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+/// A read as returned by the external program
+struct ReturnedRecord {
+    name: Vec<u8>,
+    seq: Vec<u8>,
+    qual: Vec<u8>,
+    tag: Option<Vec<u8>>,
+}
+
+fn read_line(reader: &mut impl BufRead, buf: &mut Vec<u8>) -> std::io::Result<bool> {
+    buf.clear();
+    if reader.read_until(b'\n', buf)? == 0 {
+        return Ok(false);
+    }
+    if buf.last() == Some(&b'\n') {
+        buf.pop();
+    }
+    if buf.last() == Some(&b'\r') {
+        buf.pop();
+    }
+    Ok(true)
+}
+
+impl ExternalCommand {
+    fn program(&self) -> &str {
+        &self.command[0]
+    }
+
+    fn serialize_block(
+        &self,
+        block: &FastQBlocksCombined,
+        input_info: &InputInfo,
+    ) -> Result<(Vec<u8>, Vec<Vec<u8>>)> {
+        let mut payload = Vec::new();
+        let mut read_ids = Vec::new();
+        let tags: Vec<&Vec<TagValue>> = self
+            .in_labels
+            .iter()
+            .map(|label| block.tags.get(label).expect("tag must exist in block.tags"))
+            .collect();
+        let mut iter = block.segments[self.segment.get_index()].get_pseudo_iter();
+        let mut ii = 0;
+        while let Some(read) = iter.pseudo_next() {
+            read_ids.push(
+                read.name_without_comment(input_info.comment_insert_char)
+                    .to_vec(),
+            );
+            match self.format {
+                ExternalFormat::Fastq => read.append_as_fastq(&mut payload),
+                ExternalFormat::Tsv => {
+                    let mut fields: Vec<Vec<u8>> = vec![
+                        read.name().to_vec(),
+                        read.seq().to_vec(),
+                        read.qual().to_vec(),
+                    ];
+                    for tag in &tags {
+                        fields.push(match &tag[ii] {
+                            TagValue::Location(hits) => hits.joined_sequence(Some(b"_")),
+                            TagValue::String(value) => value.to_vec(),
+                            TagValue::Numeric(n) => n.to_string().into_bytes(),
+                            TagValue::Bool(b) => {
+                                if *b {
+                                    b"1".to_vec()
+                                } else {
+                                    b"0".to_vec()
+                                }
+                            }
+                            TagValue::Missing => Vec::new(),
+                        });
+                    }
+                    if fields
+                        .iter()
+                        .any(|f| f.contains(&b'\t') || f.contains(&b'\n'))
+                    {
+                        bail!(
+                            "ExternalCommand '{}': read '{}' (or one of its tags) contains a tab or newline and can't be sent in Tsv format",
+                            self.program(),
+                            BStr::new(read.name())
+                        );
+                    }
+                    payload.extend(fields.join(&b'\t'));
+                    payload.push(b'\n');
+                }
+            }
+            ii += 1;
+        }
+        Ok((payload, read_ids))
+    }
+
+    /// Read back one record per sent read, checking the read ids as they arrive
+    /// (a program that drops or reorders reads is caught before we wait for
+    /// records that will never come).
+    fn read_records(
+        &self,
+        stdout: &mut BufReader<ChildStdout>,
+        read_ids: &[Vec<u8>],
+        comment_insert_char: u8,
+    ) -> Result<Vec<ReturnedRecord>> {
+        let read_count = read_ids.len();
+        let mut records: Vec<ReturnedRecord> = Vec::with_capacity(read_count);
+        let mut line = Vec::new();
+        let premature_end = |got: usize| {
+            anyhow!(
+                "ExternalCommand '{}': program returned {got} of {read_count} reads before closing its output",
+                self.program()
+            )
+        };
+        while records.len() < read_count {
+            if !read_line(stdout, &mut line)? {
+                return Err(premature_end(records.len()));
+            }
+            match self.format {
+                ExternalFormat::Fastq => {
+                    let Some(name) = line.strip_prefix(b"@") else {
+                        bail!(
+                            "ExternalCommand '{}': expected a FASTQ record starting with '@', got '{}'",
+                            self.program(),
+                            BStr::new(&line)
+                        );
+                    };
+                    let name = name.to_vec();
+                    let mut seq = Vec::new();
+                    let mut qual = Vec::new();
+                    if !read_line(stdout, &mut seq)?
+                        || !read_line(stdout, &mut line)?
+                        || !read_line(stdout, &mut qual)?
+                    {
+                        return Err(premature_end(records.len()));
+                    }
+                    if !line.starts_with(b"+") {
+                        bail!(
+                            "ExternalCommand '{}': expected the FASTQ '+' line for read '{}', got '{}'",
+                            self.program(),
+                            BStr::new(&name),
+                            BStr::new(&line)
+                        );
+                    }
+                    records.push(ReturnedRecord {
+                        name,
+                        seq,
+                        qual,
+                        tag: None,
+                    });
+                }
+                ExternalFormat::Tsv => {
+                    let expected_columns = if self.out_label.is_some() { 4 } else { 3 };
+                    let mut fields: Vec<Vec<u8>> =
+                        line.split(|c| *c == b'\t').map(<[u8]>::to_vec).collect();
+                    if fields.len() != expected_columns {
+                        bail!(
+                            "ExternalCommand '{}': expected {expected_columns} tab separated columns (name, seq, qual{}), got {} in line '{}'",
+                            self.program(),
+                            if expected_columns == 4 { ", tag" } else { "" },
+                            fields.len(),
+                            BStr::new(&line)
+                        );
+                    }
+                    let tag = (expected_columns == 4).then(|| fields.pop().expect("checked len"));
+                    let qual = fields.pop().expect("checked len");
+                    let seq = fields.pop().expect("checked len");
+                    let name = fields.pop().expect("checked len");
+                    records.push(ReturnedRecord {
+                        name,
+                        seq,
+                        qual,
+                        tag,
+                    });
+                }
+            }
+            let record = records.last().expect("just pushed");
+            let read_id = &read_ids[records.len() - 1];
+            let returned_id = record
+                .name
+                .split(|c| *c == comment_insert_char)
+                .next()
+                .unwrap_or_default();
+            if returned_id != read_id.as_slice() {
+                bail!(
+                    "ExternalCommand '{}': read order changed - sent '{}', got back '{}'. The program must return exactly one record per read, in input order.",
+                    self.program(),
+                    BStr::new(read_id),
+                    BStr::new(returned_id)
+                );
+            }
+            if record.seq.len() != record.qual.len() {
+                bail!(
+                    "ExternalCommand '{}': read '{}' was returned with a sequence of length {} but a quality of length {}",
+                    self.program(),
+                    BStr::new(read_id),
+                    record.seq.len(),
+                    record.qual.len()
+                );
+            }
+        }
+        Ok(records)
+    }
+
+    /// Send one block to the program and read back as many records as we sent
+    fn exchange(
+        &self,
+        worker: &mut Worker,
+        payload: &[u8],
+        read_ids: &[Vec<u8>],
+        comment_insert_char: u8,
+    ) -> Result<Vec<ReturnedRecord>> {
+        let Worker {
+            child,
+            stdin,
+            stdout,
+        } = worker;
+        let stdin = stdin.as_mut().expect("stdin is only closed in finalize");
+        // write from a second thread - the program may start answering
+        // before it has seen the whole block, and pipes have limited capacity
+        std::thread::scope(|scope| {
+            let writer = scope.spawn(move || -> std::io::Result<()> {
+                stdin.write_all(payload)?;
+                stdin.flush()
+            });
+            let records = self.read_records(stdout, read_ids, comment_insert_char);
+            if records.is_err() {
+                // the writer might be blocked on a program that no longer reads
+                let _ = child.kill();
+            }
+            let written = writer.join().expect("writer thread panicked");
+            let records = records?;
+            written.with_context(|| {
+                format!(
+                    "ExternalCommand '{}': failed to send reads to the program",
+                    self.program()
+                )
+            })?;
+            Ok(records)
+        })
+    }
+
+    fn parse_tag(&self, value: &[u8]) -> Result<TagValue> {
+        if value.is_empty() {
+            return Ok(TagValue::Missing);
+        }
+        Ok(match self.out_tag_type {
+            ExternalTagType::String => TagValue::String(value.into()),
+            ExternalTagType::Numeric => TagValue::Numeric(
+                std::str::from_utf8(value)
+                    .ok()
+                    .and_then(|v| v.trim().parse::<f64>().ok())
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "ExternalCommand '{}': could not parse '{}' as a number",
+                            self.program(),
+                            BStr::new(value)
+                        )
+                    })?,
+            ),
+            ExternalTagType::Bool => match value {
+                b"1" | b"true" | b"True" => TagValue::Bool(true),
+                b"0" | b"false" | b"False" => TagValue::Bool(false),
+                _ => bail!(
+                    "ExternalCommand '{}': expected a bool (1/0/true/false), got '{}'",
+                    self.program(),
+                    BStr::new(value)
+                ),
+            },
+        })
+    }
+}
+
+impl Step for ExternalCommand {
+    fn modified_segments(&self) -> ModifiedSegments {
+        self.segment.into()
+    }
+
+    fn init(
+        &mut self,
+        _input_info: &InputInfo,
+        _output_prefix: &str,
+        _output_directory: &Path,
+        _output_ix_separator: &str,
+        _demultiplex_info: &OptDemultiplex,
+        _allow_overwrite: bool,
+    ) -> Result<Option<DemultiplexBarcodes>> {
+        let mut workers = Vec::new();
+        for _ in 0..self.workers {
+            let mut child = Command::new(self.program())
+                .args(&self.command[1..])
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()
+                .with_context(|| {
+                    format!("ExternalCommand: could not start '{}'", self.program())
+                })?;
+            let stdin = child.stdin.take().expect("stdin was piped");
+            let stdout = BufReader::new(child.stdout.take().expect("stdout was piped"));
+            workers.push(Mutex::new(Worker {
+                child,
+                stdin: Some(stdin),
+                stdout,
+            }));
+        }
+        self.worker_handles = Some(Arc::new(workers));
+        Ok(None)
+    }
+
+    fn needs_serial(&self) -> bool {
+        self.serial
+    }
+
+    fn supports_checkpoint(&self) -> bool {
+        false // the external program keeps its own state
+    }
+
+    fn apply(
+        &self,
+        mut block: FastQBlocksCombined,
+        input_info: &InputInfo,
+        block_no: usize,
+        _demultiplex_info: &OptDemultiplex,
+    ) -> anyhow::Result<(FastQBlocksCombined, bool)> {
+        let (payload, read_ids) = self.serialize_block(&block, input_info)?;
+        let records = if read_ids.is_empty() {
+            Vec::new()
+        } else {
+            let workers = self.worker_handles.as_ref().expect("set in init");
+            let mut worker = workers[block_no % workers.len()]
+                .lock()
+                .expect("lock poisoned");
+            self.exchange(
+                &mut worker,
+                &payload,
+                &read_ids,
+                input_info.comment_insert_char,
+            )?
+        };
+
+        let segment = &mut block.segments[self.segment.get_index()];
+        let mut changed = vec![false; read_ids.len()];
+        let mut tag_values = Vec::with_capacity(read_ids.len());
+        for (ii, record) in records.into_iter().enumerate() {
+            let mut read = segment.get_mut(ii);
+            if read.name() != record.name.as_slice() {
+                read.replace_name(&record.name);
+            }
+            if read.seq() != record.seq.as_slice() {
+                changed[ii] = true;
+                read.replace_seq(&record.seq, &record.qual);
+            } else if read.qual() != record.qual.as_slice() {
+                read.replace_qual(&record.qual);
+            }
+            if let Some(tag) = record.tag {
+                tag_values.push(self.parse_tag(&tag)?);
+            }
+        }
+
+        if changed.iter().any(|x| *x) {
+            block.filter_tag_locations(
+                self.segment,
+                |_location, _pos, _seq, _read_len| NewLocation::Remove,
+                Some(&changed),
+            );
+        }
+        if let Some(label) = &self.out_label {
+            block.tags.insert(label.clone(), tag_values);
+        }
+        Ok((block, true))
+    }
+
+    fn finalize(&self, _demultiplex_info: &OptDemultiplex) -> Result<Option<FinalizeReportResult>> {
+        for worker in self.worker_handles.as_ref().expect("set in init").iter() {
+            let mut worker = worker.lock().expect("lock poisoned");
+            drop(worker.stdin.take()); // end of input for the program
+            let mut left_over = Vec::new();
+            worker
+                .stdout
+                .read_to_end(&mut left_over)
+                .context("ExternalCommand: failed to read program output")?;
+            let status = worker
+                .child
+                .wait()
+                .context("ExternalCommand: failed to wait for program")?;
+            if !status.success() {
+                bail!(
+                    "ExternalCommand '{}': program exited with {status}",
+                    self.program()
+                );
+            }
+            if !left_over.is_empty() {
+                bail!(
+                    "ExternalCommand '{}': program returned more reads than it was sent",
+                    self.program()
+                );
+            }
+        }
+        Ok(None)
+    }
+}
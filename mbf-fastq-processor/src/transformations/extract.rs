@@ -51,6 +51,27 @@ pub(crate) fn extract_region_tags(
     block.tags.insert(label.clone(), out);
 }
 
+/// `find_all` only makes sense when searching anywhere in the read.
+pub(crate) fn verify_find_all_anchor(
+    find_all: &mut toml_pretty_deser::prelude::TomlValue<bool>,
+    anchor: &toml_pretty_deser::prelude::TomlValue<crate::dna::Anchor>,
+) {
+    if let Some(true) = find_all.as_ref()
+        && let Some(anchor_value) = anchor.as_ref()
+        && !matches!(anchor_value, crate::dna::Anchor::Anywhere)
+    {
+        let spans = vec![
+            (find_all.span(), "Set here".to_string()),
+            (anchor.span(), "Not 'Anywhere'".to_string()),
+        ];
+        find_all.state = toml_pretty_deser::prelude::TomlValueState::Custom { spans };
+        find_all.help = Some(
+            "An anchored search can match only once. Use anchor = 'Anywhere' with find_all = true."
+                .to_string(),
+        );
+    }
+}
+
 pub(crate) fn extract_string_tags(
     block: &mut io::FastQBlocksCombined,
     segment: SegmentIndex,
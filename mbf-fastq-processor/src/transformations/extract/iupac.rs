@@ -1,9 +1,12 @@
 #![allow(clippy::unnecessary_wraps)] //eserde false positives
 
 use crate::transformations::prelude::*;
-use crate::{config::deser::tpd_adapt_iupac_bstring, dna::Anchor};
+use crate::{
+    config::deser::tpd_adapt_iupac_bstring,
+    dna::{Anchor, Hit, Hits},
+};
 
-use super::extract_region_tags;
+use super::{extract_region_tags, verify_find_all_anchor};
 
 /// Extract a IUPAC described sequence from the read. E.g. an adapter.
 /// Can be at the start (anchor = Left, the end (anchor = Right),
@@ -11,6 +14,7 @@ use super::extract_region_tags;
 /// The search parameter can be either a single IUPAC string or a list of IUPAC strings.
 /// If multiple strings are provided, all will be searched and they must be distinct
 /// (non-overlapping patterns).
+/// With `find_all`, every non-overlapping hit (of any of the patterns) is recorded.
 #[derive(Clone, JsonSchema)]
 #[tpd]
 #[allow(clippy::upper_case_acronyms)]
@@ -29,6 +33,11 @@ pub struct IUPAC {
     anchor: Anchor,
     out_label: TagLabel,
     max_mismatches: u8,
+    /// Record all non-overlapping hits instead of the first one. Requires anchor = 'Anywhere'
+    #[tpd(default)]
+    find_all: bool,
+    #[tpd(default)]
+    if_tag: Option<ConditionalTagLabel>,
 }
 
 impl VerifyIn<PartialConfig> for PartialIUPAC {
@@ -41,6 +50,7 @@ impl VerifyIn<PartialConfig> for PartialIUPAC {
         Self: Sized + toml_pretty_deser::Visitor,
     {
         self.segment.validate_segment(parent);
+        verify_find_all_anchor(&mut self.find_all, &self.anchor);
         Ok(())
     }
 }
@@ -65,6 +75,7 @@ impl TagUser for PartialTaggedVariant<PartialIUPAC> {
             .as_mut()
             .expect("get_tag_usage should only be called after successful verification");
         TagUsageInfo {
+            used_tags: vec![inner.if_tag.to_used_tag(&[][..])],
             declared_tag: inner.out_label.to_declared_tag(TagValueType::Location),
             ..Default::default()
         }
@@ -72,6 +83,14 @@ impl TagUser for PartialTaggedVariant<PartialIUPAC> {
 }
 
 impl Step for IUPAC {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
@@ -79,18 +98,49 @@ impl Step for IUPAC {
         _block_no: usize,
         _demultiplex_info: &OptDemultiplex,
     ) -> Result<(FastQBlocksCombined, bool)> {
-        extract_region_tags(&mut block, self.segment, &self.out_label, |read| {
-            // Try each query pattern and return the first match
-            for query in &self.search {
-                if let Some(hit) =
-                    read.find_iupac(query, self.anchor, self.max_mismatches, self.segment)
-                {
-                    return Some(hit);
+        if self.find_all {
+            extract_region_tags(&mut block, self.segment, &self.out_label, |read| {
+                let mut hits: Vec<Hit> = self
+                    .search
+                    .iter()
+                    .flat_map(|query| read.find_iupac_all(query, self.max_mismatches, self.segment))
+                    .collect();
+                keep_non_overlapping(&mut hits);
+                (!hits.is_empty()).then(|| Hits::new_multiple(hits))
+            });
+        } else {
+            extract_region_tags(&mut block, self.segment, &self.out_label, |read| {
+                // Try each query pattern and return the first match
+                for query in &self.search {
+                    if let Some(hit) =
+                        read.find_iupac(query, self.anchor, self.max_mismatches, self.segment)
+                    {
+                        return Some(hit);
+                    }
                 }
-            }
-            None
-        });
+                None
+            });
+        }
 
         Ok((block, true))
     }
 }
+
+/// Sort the hits of several patterns by position,
+/// dropping those overlapping an earlier one.
+fn keep_non_overlapping(hits: &mut Vec<Hit>) {
+    hits.sort_by_key(|hit| hit.location.as_ref().map(|location| location.start));
+    let mut covered_until = 0;
+    hits.retain(|hit| {
+        let location = hit
+            .location
+            .as_ref()
+            .expect("iupac hits always have a location");
+        if location.start < covered_until {
+            false
+        } else {
+            covered_until = location.start + location.len;
+            true
+        }
+    });
+}
@@ -25,6 +25,8 @@ pub struct IUPACSuffix {
     #[tpd(alias = "query")]
     #[tpd(alias = "pattern")]
     pub search: BString,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 }
 
 impl VerifyIn<PartialConfig> for PartialIUPACSuffix {
@@ -110,6 +112,7 @@ impl TagUser for PartialTaggedVariant<PartialIUPACSuffix> {
             .as_mut()
             .expect("get_tag_usage should only be called after successful verification");
         TagUsageInfo {
+            used_tags: vec![inner.if_tag.to_used_tag(&[][..])],
             declared_tag: inner.out_label.to_declared_tag(TagValueType::Location),
             ..Default::default()
         }
@@ -117,6 +120,14 @@ impl TagUser for PartialTaggedVariant<PartialIUPACSuffix> {
 }
 
 impl Step for IUPACSuffix {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
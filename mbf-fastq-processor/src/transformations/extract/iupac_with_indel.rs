@@ -1,12 +1,16 @@
 #![allow(clippy::unnecessary_wraps)] // eserde false positives
 use crate::transformations::prelude::*;
 
-use crate::{config::deser::tpd_adapt_iupac_bstring, dna::Anchor};
+use crate::{
+    config::deser::tpd_adapt_iupac_bstring,
+    dna::{Anchor, Hits},
+};
 
-use super::extract_region_tags;
+use super::{extract_region_tags, verify_find_all_anchor};
 
 /// Extract an IUPAC-described sequence while tolerating insertions and deletions.
 /// Useful for adapters where small indels are expected.
+/// With `find_all`, every non-overlapping hit is recorded.
 
 #[derive(Clone, JsonSchema)]
 #[tpd]
@@ -30,6 +34,11 @@ pub struct IUPACWithIndel {
     #[tpd(default)]
     max_indel_bases: usize,
     max_total_edits: Option<usize>,
+    /// Record all non-overlapping hits instead of the best one. Requires anchor = 'Anywhere'
+    #[tpd(default)]
+    find_all: bool,
+    #[tpd(default)]
+    if_tag: Option<ConditionalTagLabel>,
 }
 
 impl VerifyIn<PartialConfig> for PartialIUPACWithIndel {
@@ -51,6 +60,7 @@ impl VerifyIn<PartialConfig> for PartialIUPACWithIndel {
             }
             Ok(())
         });
+        verify_find_all_anchor(&mut self.find_all, &self.anchor);
 
         Ok(())
     }
@@ -67,6 +77,7 @@ impl TagUser for PartialTaggedVariant<PartialIUPACWithIndel> {
             .as_mut()
             .expect("get_tag_usage should only be called after successful verification");
         TagUsageInfo {
+            used_tags: vec![inner.if_tag.to_used_tag(&[][..])],
             declared_tag: inner.out_label.to_declared_tag(TagValueType::Location),
             ..Default::default()
         }
@@ -74,6 +85,14 @@ impl TagUser for PartialTaggedVariant<PartialIUPACWithIndel> {
 }
 
 impl Step for IUPACWithIndel {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
@@ -84,6 +103,16 @@ impl Step for IUPACWithIndel {
         let segment_index = self.segment;
 
         extract_region_tags(&mut block, segment_index, &self.out_label, |read| {
+            if self.find_all {
+                let hits = read.find_iupac_with_indel_all(
+                    &self.search,
+                    self.max_mismatches,
+                    self.max_indel_bases,
+                    self.max_total_edits,
+                    segment_index,
+                );
+                return (!hits.is_empty()).then(|| Hits::new_multiple(hits));
+            }
             read.find_iupac_with_indel(
                 &self.search,
                 self.anchor,
@@ -23,6 +23,8 @@ pub struct LongestPolyX {
     pub base: u8,
     pub max_mismatch_rate: f64, //toml is f64.
     pub max_consecutive_mismatches: usize,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 }
 
 impl VerifyIn<PartialConfig> for PartialLongestPolyX {
@@ -170,6 +172,7 @@ impl TagUser for PartialTaggedVariant<PartialLongestPolyX> {
             .as_mut()
             .expect("get_tag_usage should only be called after successful verification");
         TagUsageInfo {
+            used_tags: vec![inner.if_tag.to_used_tag(&[][..])],
             declared_tag: inner.out_label.to_declared_tag(TagValueType::Location),
             ..Default::default()
         }
@@ -177,6 +180,14 @@ impl TagUser for PartialTaggedVariant<PartialLongestPolyX> {
 }
 
 impl Step for LongestPolyX {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
@@ -17,6 +17,8 @@ pub struct LowQualityEnd {
     pub out_label: TagLabel,
     #[tpd(with = "tpd_adapt_u8_from_byte_or_char")]
     pub min_qual: u8,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 }
 
 impl VerifyIn<PartialConfig> for PartialLowQualityEnd {
@@ -44,6 +46,7 @@ impl TagUser for PartialTaggedVariant<PartialLowQualityEnd> {
             .as_mut()
             .expect("get_tag_usage should only be called after successful verification");
         TagUsageInfo {
+            used_tags: vec![inner.if_tag.to_used_tag(&[][..])],
             declared_tag: inner.out_label.to_declared_tag(TagValueType::Location),
             ..Default::default()
         }
@@ -51,6 +54,14 @@ impl TagUser for PartialTaggedVariant<PartialLowQualityEnd> {
 }
 
 impl Step for LowQualityEnd {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
@@ -18,6 +18,8 @@ pub struct LowQualityStart {
     pub out_label: TagLabel,
     #[tpd(with = "tpd_adapt_u8_from_byte_or_char")]
     pub min_qual: u8,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 }
 
 impl VerifyIn<PartialConfig> for PartialLowQualityStart {
@@ -45,6 +47,7 @@ impl TagUser for PartialTaggedVariant<PartialLowQualityStart> {
             .as_mut()
             .expect("get_tag_usage should only be called after successful verification");
         TagUsageInfo {
+            used_tags: vec![inner.if_tag.to_used_tag(&[][..])],
             declared_tag: inner.out_label.to_declared_tag(TagValueType::Location),
             ..Default::default()
         }
@@ -52,6 +55,14 @@ impl TagUser for PartialTaggedVariant<PartialLowQualityStart> {
 }
 
 impl Step for LowQualityStart {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
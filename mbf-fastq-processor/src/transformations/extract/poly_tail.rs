@@ -20,6 +20,8 @@ pub struct PolyTail {
     pub base: u8,
     pub max_mismatch_rate: f64,
     pub max_consecutive_mismatches: usize,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 }
 
 impl VerifyIn<PartialConfig> for PartialPolyTail {
@@ -67,6 +69,7 @@ impl TagUser for PartialTaggedVariant<PartialPolyTail> {
             .as_mut()
             .expect("get_tag_usage should only be called after successful verification");
         TagUsageInfo {
+            used_tags: vec![inner.if_tag.to_used_tag(&[][..])],
             declared_tag: inner.out_label.to_declared_tag(TagValueType::Location),
             ..Default::default()
         }
@@ -74,6 +77,14 @@ impl TagUser for PartialTaggedVariant<PartialPolyTail> {
 }
 
 impl Step for PolyTail {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
@@ -3,7 +3,7 @@
 use crate::config::deser::{tpd_adapt_bstring, tpd_adapt_regex};
 use crate::transformations::prelude::*;
 
-use crate::dna::Hits;
+use crate::dna::{Hit, HitRegion, Hits};
 
 use super::extract_region_tags;
 use super::extract_string_tags;
@@ -13,6 +13,7 @@ fn regex_replace_with_self() -> BString {
 }
 
 /// Region by regular expression
+/// With `find_all`, every non-overlapping match is recorded (sequence sources only).
 #[derive(Clone, JsonSchema)]
 #[tpd]
 #[derive(Debug)]
@@ -31,6 +32,11 @@ pub struct Regex {
     #[tpd(adapt_in_verify(String), alias = "segment")]
     #[schemars(with = "String")]
     source: SegmentOrNameIndex,
+    /// Record all non-overlapping matches instead of the first one
+    #[tpd(default)]
+    pub find_all: bool,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 }
 
 impl VerifyIn<PartialConfig> for PartialRegex {
@@ -43,6 +49,20 @@ impl VerifyIn<PartialConfig> for PartialRegex {
         Self: Sized + toml_pretty_deser::Visitor,
     {
         self.source.validate_segment(parent);
+        if let Some(true) = self.find_all.as_ref()
+            && let Some(source) = self.source.as_ref()
+            && source
+                .as_ref_post()
+                .is_some_and(SegmentOrNameIndex::is_name)
+        {
+            self.find_all.state = TomlValueState::new_validation_failed(
+                "find_all is only supported when searching in sequences",
+            );
+            self.find_all.help = Some(
+                "Matches in read names become a single string tag. Use a segment as source."
+                    .to_string(),
+            );
+        }
         self.replacement.or_with(regex_replace_with_self);
         self.replacement.verify(|replacement| {
             let group_hunting_regexp =
@@ -73,6 +93,7 @@ impl TagUser for PartialTaggedVariant<PartialRegex> {
             .as_mut()
             .expect("get_tag_usage should only be called after successful verification");
         TagUsageInfo {
+            used_tags: vec![inner.if_tag.to_used_tag(&[][..])],
             declared_tag: inner.out_label.to_declared_tag(
                 if inner
                     .source
@@ -93,6 +114,14 @@ impl TagUser for PartialTaggedVariant<PartialRegex> {
 }
 
 impl Step for Regex {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
@@ -123,6 +152,27 @@ impl Step for Regex {
                 // Choose source based on whether it's name or sequence
                 let source = read.seq();
 
+                if self.find_all {
+                    let hits: Vec<Hit> = self
+                        .search
+                        .captures_iter(source)
+                        .map(|hit| {
+                            let mut replacement = Vec::new();
+                            let g = hit.get(0).expect("Regex should always match");
+                            hit.expand(&self.replacement, &mut replacement);
+                            Hit {
+                                location: Some(HitRegion {
+                                    start: g.start(),
+                                    len: g.end() - g.start(),
+                                    segment_index,
+                                }),
+                                sequence: replacement.into(),
+                            }
+                        })
+                        .collect();
+                    return (!hits.is_empty()).then(|| Hits::new_multiple(hits));
+                }
+
                 let re_hit = self.search.captures(source);
                 if let Some(hit) = re_hit {
                     let mut replacement = Vec::new();
@@ -21,6 +21,8 @@ pub struct Region {
     pub anchor: RegionAnchor,
 
     pub out_label: TagLabel,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 }
 
 impl VerifyIn<PartialConfig> for PartialRegion {
@@ -48,6 +50,10 @@ impl TagUser for PartialTaggedVariant<PartialRegion> {
 }
 
 impl Step for Region {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
     fn apply(
         &self,
         _block: FastQBlocksCombined,
@@ -22,6 +22,8 @@ pub struct Regions {
     pub regions: Vec<RegionDefinition>, //validated to be non_empty in transformations::validate_regions
 
     pub out_label: TagLabel,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 
     #[tpd(skip)]
     #[schemars(skip)]
@@ -122,6 +124,7 @@ impl TagUser for PartialTaggedVariant<PartialRegions> {
             TagValueType::String
         };
         inner.output_tag_type = Some(output_tag_type);
+        used_tags.push(inner.if_tag.to_used_tag(&[][..]));
 
         TagUsageInfo {
             declared_tag: inner.out_label.to_declared_tag(output_tag_type),
@@ -132,6 +135,14 @@ impl TagUser for PartialTaggedVariant<PartialRegions> {
 }
 
 impl Step for Regions {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
     // fn uses_tags(
     //     &self,
     //     tags_available: &IndexMap<TagLabel, TagMetadata>,
@@ -21,6 +21,8 @@ pub struct RegionsOfLowQuality {
     pub min_quality: u8,
     pub min_length: usize,
     pub out_label: TagLabel,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 }
 
 impl VerifyIn<PartialConfig> for PartialRegionsOfLowQuality {
@@ -59,6 +61,7 @@ impl TagUser for PartialTaggedVariant<PartialRegionsOfLowQuality> {
             .as_mut()
             .expect("get_tag_usage should only be called after successful verification");
         TagUsageInfo {
+            used_tags: vec![inner.if_tag.to_used_tag(&[][..])],
             declared_tag: inner.out_label.to_declared_tag(TagValueType::Location),
             ..Default::default()
         }
@@ -66,6 +69,14 @@ impl TagUser for PartialTaggedVariant<PartialRegionsOfLowQuality> {
 }
 
 impl Step for RegionsOfLowQuality {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
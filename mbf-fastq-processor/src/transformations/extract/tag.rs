@@ -1,10 +1,12 @@
 mod duplicates;
+mod from_table;
 mod other_file;
 
 use crate::transformations::{
     FragmentEntry, FragmentEntryForCuckooFilter, OurCuckCooFilter, reproducible_cuckoofilter,
 };
 pub use duplicates::{Duplicates, PartialDuplicates};
+pub use from_table::{FromTable, PartialFromTable};
 pub use other_file::{OtherFile, PartialOtherFile};
 use std::collections::HashSet;
 // we settled on the cuckoo filter  after doing experiments/memory_usage_hashset_vs_radis
@@ -7,6 +7,7 @@ use std::cell::RefCell;
 use super::super::extract_bool_tags_plus_all;
 use super::ApproxOrExactFilter;
 use crate::dna::TagValue;
+use crate::spill::SpillingSet;
 use crate::transformations::extract::extract_bool_tags_from_tag;
 use crate::transformations::{read_name_canonical_prefix, tag::calculate_filter_capacity};
 
@@ -25,10 +26,33 @@ pub struct Duplicates {
     pub seed: Option<u64>,
 
     pub initial_filter_capacity: Option<usize>,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 
     #[tpd(skip, default)]
     #[schemars(skip)]
-    pub filters: Arc<Mutex<DemultiplexedData<ApproxOrExactFilter>>>,
+    pub filters: Arc<Mutex<DemultiplexedData<DuplicateFilter>>>,
+
+    /// share of options.memory_limit
+    #[tpd(skip, default)]
+    #[schemars(skip)]
+    pub memory_budget: Option<usize>,
+}
+
+/// The exact filter spills to disk once the step's share of `options.memory_limit` is used up
+#[derive(Debug)]
+pub enum DuplicateFilter {
+    InMemory(ApproxOrExactFilter),
+    Spilling(SpillingSet),
+}
+
+impl DuplicateFilter {
+    fn containsert(&mut self, seq: &FragmentEntry) -> Result<bool> {
+        match self {
+            DuplicateFilter::InMemory(filter) => Ok(filter.containsert(seq)),
+            DuplicateFilter::Spilling(set) => set.containsert(seq.to_continuous_vec()),
+        }
+    }
 }
 
 impl VerifyIn<PartialConfig> for PartialDuplicates {
@@ -58,6 +82,7 @@ impl TagUser for PartialTaggedVariant<PartialDuplicates> {
             .expect("get_tag_usage should only be called after successful verification");
         let mut used_tags = vec![];
         used_tags.extend(inner.source.to_used_tags());
+        used_tags.push(inner.if_tag.to_used_tag(&[][..]));
 
         TagUsageInfo {
             used_tags,
@@ -68,6 +93,10 @@ impl TagUser for PartialTaggedVariant<PartialDuplicates> {
 }
 
 impl Step for Duplicates {
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
     #[mutants::skip] // technically unecessary, since we have our own arc. But no point in blocking
     // multiple step-threads
     fn needs_serial(&self) -> bool {
@@ -88,6 +117,19 @@ impl Step for Duplicates {
     }
 
     #[allow(clippy::too_many_lines)]
+    fn supports_checkpoint(&self) -> bool {
+        false // the duplicate filter can not be serialized
+    }
+
+    fn uses_memory_budget(&self) -> bool {
+        // the approximate filter's size is set by initial_filter_capacity
+        self.false_positive_rate == 0.0
+    }
+
+    fn set_memory_budget(&mut self, bytes: usize) {
+        self.memory_budget = Some(bytes);
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
@@ -118,13 +160,29 @@ impl Step for Duplicates {
                 .lock()
                 .expect("Should have been provided by VerifyIn");
             for tag in demultiplex_info.iter_tags() {
-                filters.insert(
-                    tag,
-                    ApproxOrExactFilter::new(self.false_positive_rate, capacity, seed),
-                );
+                let filter = match self.memory_budget {
+                    Some(budget) if self.false_positive_rate == 0.0 => {
+                        DuplicateFilter::Spilling(SpillingSet::new(budget / demultiplex_info.len()))
+                    }
+                    _ => DuplicateFilter::InMemory(ApproxOrExactFilter::new(
+                        self.false_positive_rate,
+                        capacity,
+                        seed,
+                    )),
+                };
+                filters.insert(tag, filter);
             }
         }
 
+        // the extract helpers want plain bools - keep the first spill error for later
+        let spill_error = RefCell::new(None);
+        let containsert = |filter: &mut DuplicateFilter, entry: &FragmentEntry| {
+            filter.containsert(entry).unwrap_or_else(|e| {
+                spill_error.borrow_mut().get_or_insert(e);
+                false
+            })
+        };
+
         match &self.source {
             ResolvedSourceAll::Segment(segment_index_or_all) => {
                 let filters =
@@ -134,22 +192,26 @@ impl Step for Duplicates {
                     *segment_index_or_all,
                     &self.out_label,
                     |read, demultiplex_tag| {
-                        filters
-                            .borrow_mut()
-                            .get_mut(&demultiplex_tag)
-                            .expect("demultiplex_tag must exist in filters")
-                            .containsert(&FragmentEntry(&[read.seq()]))
+                        containsert(
+                            filters
+                                .borrow_mut()
+                                .get_mut(&demultiplex_tag)
+                                .expect("demultiplex_tag must exist in filters"),
+                            &FragmentEntry(&[read.seq()]),
+                        )
                     },
                     |reads, demultiplex_tag| {
                         // Virtually combine sequences for filter check
                         let inner: Vec<_> =
                             reads.iter().map(crate::io::WrappedFastQRead::seq).collect();
                         let entry = FragmentEntry(&inner);
-                        filters
-                            .borrow_mut()
-                            .get_mut(&demultiplex_tag)
-                            .expect("demultiplex_tag must exist in filters")
-                            .containsert(&entry)
+                        containsert(
+                            filters
+                                .borrow_mut()
+                                .get_mut(&demultiplex_tag)
+                                .expect("demultiplex_tag must exist in filters"),
+                            &entry,
+                        )
                     },
                 );
             }
@@ -161,10 +223,12 @@ impl Step for Duplicates {
                     tag_name,
                     |tag_value, demultiplex_tag| {
                         if let Some(value) = Self::tag_value_to_bytes(tag_value) {
-                            filters
-                                .get_mut(&demultiplex_tag)
-                                .expect("demultiplex_tag must exist in filters")
-                                .containsert(&FragmentEntry(&[value.as_slice()]))
+                            containsert(
+                                filters
+                                    .get_mut(&demultiplex_tag)
+                                    .expect("demultiplex_tag must exist in filters"),
+                                &FragmentEntry(&[value.as_slice()]),
+                            )
                         } else {
                             false
                         }
@@ -187,11 +251,13 @@ impl Step for Duplicates {
                         let name = read.name();
                         let canonical = read_name_canonical_prefix(name, Some(*split_character));
                         let owned = canonical.to_vec();
-                        filters
-                            .borrow_mut()
-                            .get_mut(&demultiplex_tag)
-                            .expect("demultiplex_tag must exist in filters")
-                            .containsert(&FragmentEntry(&[owned.as_slice()]))
+                        containsert(
+                            filters
+                                .borrow_mut()
+                                .get_mut(&demultiplex_tag)
+                                .expect("demultiplex_tag must exist in filters"),
+                            &FragmentEntry(&[owned.as_slice()]),
+                        )
                     },
                     |reads, demultiplex_tag| {
                         // Virtually combine sequences for filter check
@@ -200,15 +266,20 @@ impl Step for Duplicates {
                             .map(crate::io::WrappedFastQRead::name)
                             .collect();
                         let entry = FragmentEntry(&inner);
-                        filters
-                            .borrow_mut()
-                            .get_mut(&demultiplex_tag)
-                            .expect("demultiplex_tag must exist in filters")
-                            .containsert(&entry)
+                        containsert(
+                            filters
+                                .borrow_mut()
+                                .get_mut(&demultiplex_tag)
+                                .expect("demultiplex_tag must exist in filters"),
+                            &entry,
+                        )
                     },
                 );
             }
         }
+        if let Some(e) = spill_error.into_inner() {
+            return Err(e);
+        }
         Ok((block, true))
     }
 }
@@ -0,0 +1,436 @@
+#![allow(clippy::unnecessary_wraps)] //eserde false positives
+use std::collections::HashMap;
+use std::io::Read;
+
+use crate::config::deser::tpd_adapt_u8_from_byte_or_char;
+use crate::dna::TagValue;
+use crate::io::table::{column_index, open_table};
+use crate::transformations::convert::TableValueType;
+use crate::transformations::{prelude::*, read_name_canonical_prefix};
+
+/// Annotate reads from a per-read table (TSV/CSV), joined on the read name
+#[derive(Clone, JsonSchema)]
+#[tpd]
+#[derive(Debug)]
+pub struct FromTable {
+    /// TSV/CSV file with a header line, may be gzip/zstd compressed
+    pub filename: String,
+    /// Whose read names to look up
+    #[schemars(with = "String")]
+    #[tpd(adapt_in_verify(String))]
+    pub segment: SegmentIndex,
+    /// Column holding the read names. Defaults to the first column
+    pub key_column: Option<String>,
+    /// Defaults to ',' for .csv files and tab otherwise
+    #[tpd(with = "tpd_adapt_u8_from_byte_or_char")]
+    pub delimiter: Option<u8>,
+    /// Cut our read names at this character.
+    /// Defaults to `input.options.read_comment_character`
+    #[tpd(with = "tpd_adapt_u8_from_byte_or_char", alias = "read_name_end_char")]
+    pub readname_end_character: Option<u8>,
+    /// Cut the table's read names at this character
+    #[tpd(
+        with = "tpd_adapt_u8_from_byte_or_char",
+        alias = "other_read_name_end_char"
+    )]
+    pub other_readname_end_character: Option<u8>,
+    /// Which columns to turn into which tags
+    #[tpd(nested)]
+    pub tags: Vec<TableTag>,
+    #[tpd(default)]
+    pub join: TableJoin,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
+
+    #[tpd(skip, default)]
+    #[schemars(skip)]
+    pub table: Option<Arc<HashMap<BString, Vec<TagValue>>>>,
+    #[tpd(skip, default)]
+    #[schemars(skip)]
+    pub sorted_table: Option<Arc<Mutex<SortedTable>>>,
+}
+
+#[derive(Clone, JsonSchema)]
+#[tpd]
+#[derive(Debug)]
+pub struct TableTag {
+    pub column: String,
+    pub out_label: TagLabel,
+    #[tpd(default)]
+    pub value_type: TableValueType,
+}
+
+impl<R> VerifyIn<R> for PartialTableTag {
+    fn verify(
+        &mut self,
+        _parent: &R,
+        _options: &VerifyOptions,
+    ) -> std::result::Result<(), ValidationFailure>
+    where
+        Self: Sized + toml_pretty_deser::Visitor,
+    {
+        self.column.verify(|v| {
+            if v.is_empty() {
+                Err(ValidationFailure::new("Must not be empty", None))
+            } else {
+                Ok(())
+            }
+        });
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, JsonSchema)]
+#[tpd]
+pub enum TableJoin {
+    /// Read the whole table into a hash map
+    #[default]
+    Hash,
+    /// Stream the table alongside the reads - both must be sorted (byte-wise) by name
+    SortedMerge,
+}
+
+impl VerifyIn<PartialConfig> for PartialFromTable {
+    fn verify(
+        &mut self,
+        parent: &PartialConfig,
+        _options: &VerifyOptions,
+    ) -> std::result::Result<(), ValidationFailure>
+    where
+        Self: Sized + toml_pretty_deser::Visitor,
+    {
+        self.segment.validate_segment(parent);
+        self.filename.verify(|v| {
+            if v.is_empty() {
+                Err(ValidationFailure::new("Must not be empty", None))
+            } else {
+                Ok(())
+            }
+        });
+        let key_column = self.key_column.as_ref().cloned().flatten();
+        self.tags.verify(|tags| {
+            if tags.is_empty() {
+                return Err(ValidationFailure::new(
+                    "Must contain at least one tag definition",
+                    Some("e.g. tags = [{column = 'taxid', out_label = 'taxon'}]"),
+                ));
+            }
+            let mut seen = std::collections::HashSet::new();
+            for tag in tags.iter().filter_map(|tag| tag.value.as_ref()) {
+                if let Some(out_label) = tag.out_label.as_ref()
+                    && !seen.insert(out_label.clone())
+                {
+                    return Err(ValidationFailure::new(
+                        format!("Tag '{out_label}' is declared twice"),
+                        None,
+                    ));
+                }
+                if let Some(column) = tag.column.as_ref()
+                    && key_column.as_ref() == Some(column)
+                {
+                    return Err(ValidationFailure::new(
+                        format!("Column '{column}' is the key_column"),
+                        Some("The read names can not be turned into a tag here."),
+                    ));
+                }
+            }
+            Ok(())
+        });
+        Ok(())
+    }
+}
+
+impl TagUser for PartialTaggedVariant<PartialFromTable> {
+    fn get_tag_usage(
+        &mut self,
+        _tags_available: &IndexMap<TagLabel, TagMetadata>,
+        _segment_order: &[String],
+    ) -> TagUsageInfo<'_> {
+        let inner = self
+            .toml_value
+            .as_mut()
+            .expect("get_tag_usage should only be called after successful verification");
+        let declared_tags = inner
+            .tags
+            .value
+            .as_mut()
+            .expect("parent was ok")
+            .iter_mut()
+            .filter_map(|tag| {
+                let tag = tag.value.as_mut()?;
+                let tag_type = match tag.value_type.as_ref().expect("parent was ok?") {
+                    TableValueType::String => TagValueType::String,
+                    TableValueType::Numeric => TagValueType::Numeric,
+                };
+                tag.out_label.to_declared_tag(tag_type)
+            })
+            .collect();
+        TagUsageInfo {
+            used_tags: vec![inner.if_tag.to_used_tag(&[][..])],
+            declared_tags,
+            ..Default::default()
+        }
+    }
+}
+
+/// Where the key and the wanted columns are, and how to parse them
+#[derive(Debug, Clone)]
+struct Columns {
+    filename: String,
+    key: usize,
+    other_readname_end_character: Option<u8>,
+    values: Vec<(usize, TableValueType)>,
+}
+
+impl Columns {
+    fn parse(&self, record: &csv::ByteRecord) -> Result<(BString, Vec<TagValue>)> {
+        let line_no = record.position().map_or(0, csv::Position::line);
+        let Some(key) = record.get(self.key) else {
+            bail!("{}, line {line_no}: missing columns", self.filename);
+        };
+        let key = read_name_canonical_prefix(key, self.other_readname_end_character);
+        let mut values = Vec::with_capacity(self.values.len());
+        for (idx, value_type) in &self.values {
+            let Some(value) = record.get(*idx) else {
+                bail!("{}, line {line_no}: missing columns", self.filename);
+            };
+            values.push(if value.is_empty() {
+                TagValue::Missing
+            } else {
+                match value_type {
+                    TableValueType::String => TagValue::String(value.into()),
+                    TableValueType::Numeric => {
+                        let str_value = std::str::from_utf8(value)
+                            .ok()
+                            .map(str::trim)
+                            .unwrap_or_default();
+                        TagValue::Numeric(str_value.parse().with_context(|| {
+                            format!(
+                                "{}, line {line_no}: could not parse '{}' as a number",
+                                self.filename,
+                                BStr::new(value)
+                            )
+                        })?)
+                    }
+                }
+            });
+        }
+        Ok((key.into(), values))
+    }
+}
+
+/// The table for join = 'sorted_merge': we only ever hold the row
+/// at (or just after) the current read name.
+pub struct SortedTable {
+    columns: Columns,
+    records: csv::ByteRecordsIntoIter<Box<dyn Read + Send>>,
+    current: Option<(BString, Vec<TagValue>)>,
+    last_read_name: BString,
+}
+
+impl std::fmt::Debug for SortedTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SortedTable")
+            .field("current", &self.current)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SortedTable {
+    fn advance(&mut self) -> Result<()> {
+        let next = match self.records.next() {
+            Some(record) => {
+                let record = record.with_context(|| {
+                    format!("Could not read line from {}", self.columns.filename)
+                })?;
+                Some(self.columns.parse(&record)?)
+            }
+            None => None,
+        };
+        if let (Some((previous, _)), Some((key, _))) = (&self.current, &next)
+            && key <= previous
+        {
+            bail!(
+                "{} is not sorted by read name: '{}' follows '{}'. Sort it byte-wise (LC_ALL=C sort), or use join = 'hash'.",
+                self.columns.filename,
+                key,
+                previous
+            );
+        }
+        self.current = next;
+        Ok(())
+    }
+
+    fn lookup(&mut self, name: &[u8]) -> Result<Option<&[TagValue]>> {
+        if name < self.last_read_name.as_slice() {
+            bail!(
+                "TagFromTable with join = 'sorted_merge': reads are not sorted by name - '{}' follows '{}'. Sort them byte-wise, or use join = 'hash'.",
+                BStr::new(name),
+                self.last_read_name
+            );
+        }
+        self.last_read_name = name.into();
+        while self
+            .current
+            .as_ref()
+            .is_some_and(|(key, _)| key.as_slice() < name)
+        {
+            self.advance()?;
+        }
+        Ok(match &self.current {
+            Some((key, values)) if key == name => Some(values.as_slice()),
+            _ => None,
+        })
+    }
+}
+
+fn push_row(outputs: &mut [Vec<TagValue>], values: Option<&[TagValue]>) {
+    match values {
+        Some(values) => {
+            for (output, value) in outputs.iter_mut().zip(values) {
+                output.push(value.clone());
+            }
+        }
+        None => {
+            for output in outputs {
+                output.push(TagValue::Missing);
+            }
+        }
+    }
+}
+
+impl FromTable {
+    fn open(&self) -> Result<(Columns, csv::Reader<Box<dyn Read + Send>>)> {
+        let mut reader = open_table(&self.filename, self.delimiter)?;
+        let headers = reader
+            .byte_headers()
+            .with_context(|| format!("Could not read header line of {}", self.filename))?
+            .clone();
+        let key = match &self.key_column {
+            Some(column) => column_index(&headers, column, &self.filename)?,
+            None => 0,
+        };
+        let values = self
+            .tags
+            .iter()
+            .map(|tag| {
+                Ok((
+                    column_index(&headers, &tag.column, &self.filename)?,
+                    tag.value_type,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok((
+            Columns {
+                filename: self.filename.clone(),
+                key,
+                other_readname_end_character: self.other_readname_end_character,
+                values,
+            },
+            reader,
+        ))
+    }
+}
+
+impl Step for FromTable {
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
+    fn init(
+        &mut self,
+        _input_info: &InputInfo,
+        _output_prefix: &str,
+        _output_directory: &Path,
+        _output_ix_separator: &str,
+        _demultiplex_info: &OptDemultiplex,
+        _allow_overwrite: bool,
+    ) -> Result<Option<DemultiplexBarcodes>> {
+        let (columns, reader) = self.open()?;
+        match self.join {
+            TableJoin::Hash => {
+                let mut table = HashMap::new();
+                for record in reader.into_byte_records() {
+                    let record = record
+                        .with_context(|| format!("Could not read line from {}", self.filename))?;
+                    let (key, values) = columns.parse(&record)?;
+                    if table.contains_key(&key) {
+                        bail!(
+                            "{}: read name '{key}' is listed multiple times",
+                            self.filename
+                        );
+                    }
+                    table.insert(key, values);
+                }
+                if table.is_empty() {
+                    bail!(
+                        "No entries were read from {}. Check that the file is not empty and has a header line.",
+                        self.filename
+                    );
+                }
+                self.table = Some(Arc::new(table));
+            }
+            TableJoin::SortedMerge => {
+                let mut sorted_table = SortedTable {
+                    columns,
+                    records: reader.into_byte_records(),
+                    current: None,
+                    last_read_name: BString::default(),
+                };
+                sorted_table.advance()?;
+                self.sorted_table = Some(Arc::new(Mutex::new(sorted_table)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The sorted merge walks the table in read order
+    fn needs_serial(&self) -> bool {
+        self.join == TableJoin::SortedMerge
+    }
+
+    fn supports_checkpoint(&self) -> bool {
+        self.join == TableJoin::Hash
+    }
+
+    fn apply(
+        &self,
+        mut block: FastQBlocksCombined,
+        input_info: &InputInfo,
+        _block_no: usize,
+        _demultiplex_info: &OptDemultiplex,
+    ) -> anyhow::Result<(FastQBlocksCombined, bool)> {
+        let segment = &block.segments[self.segment.get_index()];
+        let names = segment.entries.iter().map(|read| {
+            read_name_canonical_prefix(
+                read.name.get(&segment.block),
+                Some(
+                    self.readname_end_character
+                        .unwrap_or(input_info.comment_insert_char),
+                ),
+            )
+        });
+        let mut outputs: Vec<Vec<TagValue>> = (0..self.tags.len())
+            .map(|_| Vec::with_capacity(segment.entries.len()))
+            .collect();
+        if let Some(table) = &self.table {
+            for name in names {
+                push_row(&mut outputs, table.get(BStr::new(name)).map(Vec::as_slice));
+            }
+        } else {
+            let mut sorted_table = self
+                .sorted_table
+                .as_ref()
+                .expect("table must be read during initialization")
+                .lock()
+                .expect("mutex poisoned");
+            for name in names {
+                push_row(&mut outputs, sorted_table.lookup(name)?);
+            }
+        }
+        for (tag, values) in self.tags.iter().zip(outputs) {
+            block.tags.insert(tag.out_label.clone(), values);
+        }
+        Ok((block, true))
+    }
+}
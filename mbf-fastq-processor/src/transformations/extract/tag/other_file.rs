@@ -34,6 +34,8 @@ pub struct OtherFile {
 
     pub include_mapped: bool,
     pub include_unmapped: bool,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 
     #[tpd(skip, default)]
     #[schemars(skip)]
@@ -117,6 +119,7 @@ impl TagUser for PartialTaggedVariant<PartialOtherFile> {
             .as_mut()
             .expect("get_tag_usage should only be called after successful verification");
         TagUsageInfo {
+            used_tags: vec![inner.if_tag.to_used_tag(&[][..])],
             declared_tag: inner.out_label.to_declared_tag(TagValueType::Bool),
             ..Default::default()
         }
@@ -170,8 +173,16 @@ impl TagUser for PartialTaggedVariant<PartialOtherFile> {
 }
 
 impl Step for OtherFile {
-    fn store_progress_output(&mut self, progress: &crate::transformations::reports::Progress) {
-        self.progress_output = Some(progress.clone());
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
+    fn ignores_memory_limit(&self) -> bool {
+        true // the read names of the other file
     }
 
     fn init(
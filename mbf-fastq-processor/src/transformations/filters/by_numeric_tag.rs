@@ -13,6 +13,8 @@ pub struct ByNumericTag {
     pub min_value: Option<f64>,
     pub max_value: Option<f64>,
     pub keep_or_remove: KeepOrRemove,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 }
 
 impl VerifyIn<PartialConfig> for PartialByNumericTag {
@@ -64,6 +66,7 @@ impl TagUser for PartialTaggedVariant<PartialByNumericTag> {
                             "Either switch to FilterByTag, or change the tag you are filtering on.",
                         )
                     }),
+                inner.if_tag.to_used_tag(&[][..]),
             ],
             must_see_all_tags: true, // for filtering them down
             ..Default::default()
@@ -72,6 +75,10 @@ impl TagUser for PartialTaggedVariant<PartialByNumericTag> {
 }
 
 impl Step for ByNumericTag {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
@@ -84,7 +91,7 @@ impl Step for ByNumericTag {
             .get(&self.in_label)
             .expect("Numeric tag not found");
 
-        let keep: Vec<bool> = tag_values
+        let mut keep: Vec<bool> = tag_values
             .iter()
             .map(|tag_val| {
                 if let Some(value) = tag_val.as_numeric() {
@@ -103,6 +110,13 @@ impl Step for ByNumericTag {
                 }
             })
             .collect();
+        if let Some(cond_tag) = &self.if_tag {
+            // reads not matching the condition are never filtered
+            let condition = get_bool_vec_from_tag(&block, cond_tag);
+            for (x, matches) in keep.iter_mut().zip(condition) {
+                *x |= !matches;
+            }
+        }
 
         block.apply_bool_filter(&keep);
         Ok((block, true))
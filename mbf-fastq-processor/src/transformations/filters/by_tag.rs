@@ -9,6 +9,8 @@ use crate::{dna::TagValue, transformations::prelude::*};
 pub struct ByTag {
     in_label: TagLabel,
     keep_or_remove: super::super::KeepOrRemove,
+    #[tpd(default)]
+    if_tag: Option<ConditionalTagLabel>,
 }
 
 impl VerifyIn<PartialConfig> for PartialByTag {
@@ -42,13 +44,16 @@ impl TagUser for PartialTaggedVariant<PartialByTag> {
             .as_mut()
             .expect("get_tag_usage should only be called after successful verification");
         TagUsageInfo {
-            used_tags: vec![inner.in_label.to_used_tag(
-                &[
-                    TagValueType::Bool,
-                    TagValueType::String,
-                    TagValueType::Location,
-                ][..],
-            )],
+            used_tags: vec![
+                inner.in_label.to_used_tag(
+                    &[
+                        TagValueType::Bool,
+                        TagValueType::String,
+                        TagValueType::Location,
+                    ][..],
+                ),
+                inner.if_tag.to_used_tag(&[][..]),
+            ],
             must_see_all_tags: true, // for filtering them down
             ..Default::default()
         }
@@ -56,6 +61,10 @@ impl TagUser for PartialTaggedVariant<PartialByTag> {
 }
 
 impl Step for ByTag {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
@@ -75,6 +84,13 @@ impl Step for ByTag {
                 *x = !*x;
             }
         }
+        if let Some(cond_tag) = &self.if_tag {
+            // reads not matching the condition are never filtered
+            let condition = get_bool_vec_from_tag(&block, cond_tag);
+            for (x, matches) in keep.iter_mut().zip(condition) {
+                *x |= !matches;
+            }
+        }
         block.apply_bool_filter(&keep);
 
         Ok((block, true))
@@ -9,6 +9,8 @@ pub struct Empty {
     #[schemars(with = "String")]
     #[tpd(adapt_in_verify(String))]
     pub segment: SegmentIndexOrAll,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 }
 
 impl VerifyIn<PartialConfig> for PartialEmpty {
@@ -30,6 +32,10 @@ impl TagUser for PartialTaggedVariant<PartialEmpty> {
 }
 
 impl Step for Empty {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
     fn apply(
         &self,
         mut _block: FastQBlocksCombined,
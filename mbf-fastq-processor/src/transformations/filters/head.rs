@@ -28,6 +28,10 @@ impl TagUser for PartialTaggedVariant<PartialHead> {
 }
 
 impl Step for Head {
+    // premature termination means we never see most of the reads after the first n
+    fn records_rejected_reads(&self) -> bool {
+        false
+    }
     fn init(
         &mut self,
         _input_info: &InputInfo,
@@ -95,4 +99,23 @@ impl Step for Head {
     fn needs_serial(&self) -> bool {
         true
     }
+
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn checkpoint_state(&self) -> Result<Option<serde_json::Value>> {
+        let so_far = self
+            .so_far
+            .as_ref()
+            .expect("should have been set in init")
+            .lock()
+            .expect("lock poisoned");
+        Ok(Some(serde_json::to_value(&*so_far)?))
+    }
+
+    fn restore_checkpoint_state(&mut self, state: serde_json::Value) -> Result<()> {
+        self.so_far = Some(Arc::new(Mutex::new(serde_json::from_value(state)?)));
+        Ok(())
+    }
 }
@@ -1,15 +1,54 @@
 #![allow(clippy::unnecessary_wraps)]
 //eserde false positives
-use crate::dna::TagValue;
-use crate::io::FastQBlock;
+use crate::dna::{Hit, HitRegion, Hits, TagValue};
+use crate::io::FastQRead;
+use crate::spill::SpillingSlots;
 use crate::transformations::{extend_seed, prelude::*};
 use rand::Rng;
 
-#[derive(Clone, Debug, Default)]
+#[derive(Debug)]
 struct ReservoirBuffer {
-    segments: Vec<FastQBlock>,
+    /// one `encode_molecule` record per sampled molecule
+    slots: SpillingSlots,
+    count: usize,
+    /// the tags stored with each molecule, in record order
+    tag_labels: Vec<TagLabel>,
+}
+
+/// A `ReservoirBuffer` in the checkpoint - records are hex encoded
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ReservoirBufferState {
+    records: Vec<String>,
     count: usize,
-    tags: IndexMap<TagLabel, Vec<TagValue>>,
+    tag_labels: Vec<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ReservoirState {
+    buffers: DemultiplexedData<ReservoirBufferState>,
+    /// `ChaChaRng::get_word_pos` - a u128, which json numbers can not hold
+    rng_word_pos: String,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    use std::fmt::Write;
+    bytes.iter().fold(String::new(), |mut out, byte| {
+        let _ = write!(out, "{byte:02x}");
+        out
+    })
+}
+
+fn from_hex(hex: &str) -> Result<Vec<u8>> {
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        bail!("FilterReservoirSample: corrupt checkpoint record");
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|ii| {
+            u8::from_str_radix(&hex[ii..ii + 2], 16)
+                .context("FilterReservoirSample: corrupt checkpoint record")
+        })
+        .collect()
 }
 
 /// Fairly sample reads (expensive!)
@@ -26,6 +65,11 @@ pub struct ReservoirSample {
     #[tpd(skip, default)]
     #[schemars(skip)]
     rng: Option<Arc<Mutex<Option<rand_chacha::ChaChaRng>>>>,
+
+    /// share of options.memory_limit, per demultiplexed output after init
+    #[tpd(skip, default)]
+    #[schemars(skip)]
+    memory_budget: Option<usize>,
 }
 
 impl VerifyIn<PartialConfig> for PartialReservoirSample {
@@ -64,17 +108,164 @@ impl TagUser for PartialTaggedVariant<PartialReservoirSample> {
     }
 }
 
+fn put_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    put_u64(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+/// Reads back what `put_u64` / `put_bytes` wrote
+struct RecordReader<'a>(&'a [u8]);
+
+impl<'a> RecordReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.0.len() < len {
+            bail!("FilterReservoirSample: corrupt reservoir record");
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        let bytes = self.take(8)?;
+        Ok(u64::from_le_bytes(
+            bytes.try_into().expect("took exactly 8 bytes"),
+        ))
+    }
+
+    fn usize(&mut self) -> Result<usize> {
+        Ok(usize::try_from(self.u64()?)?)
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.usize()?;
+        self.take(len)
+    }
+}
+
+fn encode_tag_value(value: &TagValue, out: &mut Vec<u8>) {
+    match value {
+        TagValue::Missing => out.push(0),
+        TagValue::Location(hits) => {
+            out.push(1);
+            put_u64(out, hits.0.len() as u64);
+            for hit in &hits.0 {
+                put_bytes(out, &hit.sequence);
+                match &hit.location {
+                    None => out.push(0),
+                    Some(location) => {
+                        out.push(1);
+                        put_u64(out, location.start as u64);
+                        put_u64(out, location.len as u64);
+                        put_u64(out, location.segment_index.0 as u64);
+                    }
+                }
+            }
+        }
+        TagValue::String(value) => {
+            out.push(2);
+            put_bytes(out, value);
+        }
+        TagValue::Numeric(value) => {
+            out.push(3);
+            put_u64(out, value.to_bits());
+        }
+        TagValue::Bool(value) => {
+            out.push(4);
+            out.push(u8::from(*value));
+        }
+    }
+}
+
+fn decode_tag_value(reader: &mut RecordReader) -> Result<TagValue> {
+    Ok(match reader.take(1)?[0] {
+        0 => TagValue::Missing,
+        1 => {
+            let count = reader.usize()?;
+            let mut hits = Vec::new();
+            for _ in 0..count {
+                let sequence = reader.bytes()?.into();
+                let location = match reader.take(1)?[0] {
+                    0 => None,
+                    _ => Some(HitRegion {
+                        start: reader.usize()?,
+                        len: reader.usize()?,
+                        segment_index: SegmentIndex(reader.usize()?),
+                    }),
+                };
+                hits.push(Hit { location, sequence });
+            }
+            TagValue::Location(Hits::new_multiple(hits))
+        }
+        2 => TagValue::String(reader.bytes()?.into()),
+        3 => TagValue::Numeric(f64::from_bits(reader.u64()?)),
+        4 => TagValue::Bool(reader.take(1)?[0] != 0),
+        _ => bail!("FilterReservoirSample: corrupt reservoir record"),
+    })
+}
+
+/// One molecule as a `SpillingSlots` record:
+/// name, sequence & quality of every segment, then the values of `tag_labels`
+fn encode_molecule(block: &FastQBlocksCombined, pos: usize, tag_labels: &[TagLabel]) -> Vec<u8> {
+    let mut record = Vec::new();
+    for segment in &block.segments {
+        let read = segment.get(pos);
+        put_bytes(&mut record, read.name());
+        put_bytes(&mut record, read.seq());
+        put_bytes(&mut record, read.qual());
+    }
+    for label in tag_labels {
+        encode_tag_value(&block.tags[label][pos], &mut record);
+    }
+    record
+}
+
+/// Append a molecule `encode_molecule` stored to `output`
+fn decode_molecule(
+    record: &[u8],
+    tag_labels: &[TagLabel],
+    output: &mut FastQBlocksCombined,
+) -> Result<()> {
+    let mut reader = RecordReader(record);
+    for segment in &mut output.segments {
+        let name = segment.append_element(reader.bytes()?);
+        let seq = segment.append_element(reader.bytes()?);
+        let qual = segment.append_element(reader.bytes()?);
+        segment.entries.push(FastQRead::new(name, seq, qual)?);
+    }
+    for label in tag_labels {
+        output
+            .tags
+            .entry(label.clone())
+            .or_default()
+            .push(decode_tag_value(&mut reader)?);
+    }
+    Ok(())
+}
+
 impl Step for ReservoirSample {
+    // reads are held back until the last block, the reservoir is no per-block decision
+    fn records_rejected_reads(&self) -> bool {
+        false
+    }
     fn init(
         &mut self,
         _input_info: &InputInfo,
         _output_prefix: &str,
         _output_directory: &std::path::Path,
         _output_ix_separator: &str,
-        _demultiplex_info: &OptDemultiplex,
+        demultiplex_info: &OptDemultiplex,
         _allow_overwrite: bool,
     ) -> anyhow::Result<Option<DemultiplexBarcodes>> {
         use rand_chacha::rand_core::SeedableRng;
+        // each demultiplexed output keeps its own reservoir
+        self.memory_budget = self
+            .memory_budget
+            .map(|budget| budget / demultiplex_info.len());
         let extended_seed = extend_seed(self.seed);
         self.rng = Some(Arc::new(Mutex::new(Some(
             rand_chacha::ChaChaRng::from_seed(extended_seed),
@@ -83,6 +274,91 @@ impl Step for ReservoirSample {
         Ok(None)
     }
 
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn checkpoint_state(&self) -> Result<Option<serde_json::Value>> {
+        let rng_word_pos = self
+            .rng
+            .as_ref()
+            .expect("rng not set in init")
+            .lock()
+            .expect("rng mutex poisoned")
+            .as_ref()
+            .expect("rng must be initialized before checkpointing")
+            .get_word_pos();
+        let mut data = self
+            .runtime_data
+            .as_ref()
+            .expect("runtime_data not set in init")
+            .lock()
+            .expect("runtime_data mutex poisoned");
+        let mut buffers = DemultiplexedData::new();
+        for (demultiplex_tag, buf) in data.iter_mut() {
+            let mut records = Vec::new();
+            buf.slots.for_each(|record| {
+                records.push(to_hex(record));
+                Ok(())
+            })?;
+            buffers.insert(
+                demultiplex_tag,
+                ReservoirBufferState {
+                    records,
+                    count: buf.count,
+                    tag_labels: buf.tag_labels.iter().map(|label| label.0.clone()).collect(),
+                },
+            );
+        }
+        Ok(Some(serde_json::to_value(ReservoirState {
+            buffers,
+            rng_word_pos: rng_word_pos.to_string(),
+        })?))
+    }
+
+    fn restore_checkpoint_state(&mut self, state: serde_json::Value) -> Result<()> {
+        let state: ReservoirState = serde_json::from_value(state)?;
+        self.rng
+            .as_ref()
+            .expect("rng not set in init")
+            .lock()
+            .expect("rng mutex poisoned")
+            .as_mut()
+            .expect("rng must be initialized before restoring")
+            .set_word_pos(
+                state
+                    .rng_word_pos
+                    .parse()
+                    .context("FilterReservoirSample: corrupt checkpoint rng state")?,
+            );
+        let budget = self.memory_budget.unwrap_or(usize::MAX);
+        let mut data = DemultiplexedData::new();
+        for (demultiplex_tag, buf) in state.buffers {
+            let mut slots = SpillingSlots::new(budget);
+            for record in &buf.records {
+                slots.push(from_hex(record)?)?;
+            }
+            data.insert(
+                demultiplex_tag,
+                ReservoirBuffer {
+                    slots,
+                    count: buf.count,
+                    tag_labels: buf.tag_labels.into_iter().map(TagLabel).collect(),
+                },
+            );
+        }
+        self.runtime_data = Some(Arc::new(Mutex::new(data)));
+        Ok(())
+    }
+
+    fn uses_memory_budget(&self) -> bool {
+        true
+    }
+
+    fn set_memory_budget(&mut self, bytes: usize) {
+        self.memory_budget = Some(bytes);
+    }
+
     fn apply(
         &self,
         block: FastQBlocksCombined,
@@ -104,37 +380,29 @@ impl Step for ReservoirSample {
             .lock();
         let data = data_lock.as_mut().expect("runtime_data mutex poisoned");
 
+        // without a memory_limit, the reservoir never spills
+        let budget = self.memory_budget.unwrap_or(usize::MAX);
         let block_len = block.len();
         for pos in 0..block_len {
             let demultiplex_tag = block.output_tags.as_ref().map_or(0, |tags| tags[pos]);
-            let buf = data.entry(demultiplex_tag).or_default();
+            let buf = data
+                .entry(demultiplex_tag)
+                .or_insert_with(|| ReservoirBuffer {
+                    slots: SpillingSlots::new(budget),
+                    count: 0,
+                    tag_labels: block.tags.keys().cloned().collect(),
+                });
             buf.count += 1;
 
-            if buf.segments.is_empty() || buf.segments[0].len() < self.n {
-                for (segment_no, segment) in block.segments.iter().enumerate() {
-                    if buf.segments.len() <= segment_no {
-                        buf.segments.push(FastQBlock::empty());
-                    }
-                    buf.segments[segment_no].append_read(&segment.get(pos));
-                }
-                for (label, values) in &block.tags {
-                    buf.tags
-                        .entry(label.clone())
-                        .or_default()
-                        .push(values[pos].clone());
-                }
+            if buf.slots.len() < self.n {
+                buf.slots
+                    .push(encode_molecule(&block, pos, &buf.tag_labels))?;
             } else {
                 //algorithm R
                 let j = rng.random_range(1..=buf.count);
                 if j <= self.n {
-                    for (ii, segment) in block.segments.iter().enumerate() {
-                        buf.segments[ii].replace_read(j - 1, &segment.get(pos));
-                    }
-                    for (label, values) in &block.tags {
-                        if let Some(tag_buf) = buf.tags.get_mut(label) {
-                            tag_buf[j - 1] = values[pos].clone();
-                        }
-                    }
+                    buf.slots
+                        .replace(j - 1, encode_molecule(&block, pos, &buf.tag_labels))?;
                 }
             }
         }
@@ -143,25 +411,18 @@ impl Step for ReservoirSample {
             //we gotta copy it all back together, so no easy just hand out our internal
             //storage, I suppose.
             let mut output = block.empty();
+            for label in block.tags.keys() {
+                output.tags.insert(label.clone(), Vec::new());
+            }
             let all_data = data.replace(DemultiplexedData::new());
-            for (demultiplex_tag, buf) in all_data {
+            for (demultiplex_tag, mut buf) in all_data {
                 if let Some(demultiplex_tags) = output.output_tags.as_mut() {
-                    for _ in 0..buf.segments[0].len() {
+                    for _ in 0..buf.slots.len() {
                         demultiplex_tags.push(demultiplex_tag);
                     }
                 }
-                for (segment_no, molecule) in buf.segments.iter().enumerate() {
-                    for read_idx in 0..molecule.entries.len() {
-                        output.segments[segment_no].append_read(&molecule.get(read_idx));
-                    }
-                }
-                for (label, values) in &buf.tags {
-                    output
-                        .tags
-                        .entry(label.clone())
-                        .or_default()
-                        .extend(values.iter().cloned());
-                }
+                buf.slots
+                    .for_each(|record| decode_molecule(record, &buf.tag_labels, &mut output))?;
             }
             Ok((output, true))
         } else {
@@ -180,3 +441,51 @@ impl Step for ReservoirSample {
         true
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tag_value_round_trip() {
+        let values = [
+            TagValue::Missing,
+            TagValue::Location(Hits::new_multiple(vec![
+                Hit {
+                    location: Some(HitRegion {
+                        start: 3,
+                        len: 2,
+                        segment_index: SegmentIndex(1),
+                    }),
+                    sequence: "AC".into(),
+                },
+                Hit {
+                    location: None,
+                    sequence: "GGT".into(),
+                },
+            ])),
+            TagValue::String("label".into()),
+            TagValue::Numeric(-1.5),
+            TagValue::Bool(true),
+        ];
+        let mut record = Vec::new();
+        for value in &values {
+            encode_tag_value(value, &mut record);
+        }
+        let mut reader = RecordReader(&record);
+        for value in &values {
+            assert_eq!(&decode_tag_value(&mut reader).expect("decodes"), value);
+        }
+        assert!(reader.0.is_empty());
+        assert!(decode_tag_value(&mut reader).is_err());
+    }
+
+    #[test]
+    fn test_hex_round_trip() {
+        let record = [0u8, 1, 0x7f, 0x80, 0xff];
+        assert_eq!(to_hex(&record), "00017f80ff");
+        assert_eq!(from_hex(&to_hex(&record)).expect("decodes"), record);
+        assert!(from_hex("0").is_err());
+        assert!(from_hex("zz").is_err());
+    }
+}
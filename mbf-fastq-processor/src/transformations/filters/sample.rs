@@ -70,6 +70,10 @@ impl Step for Sample {
         ))));
         Ok(None)
     }
+    fn supports_checkpoint(&self) -> bool {
+        false // the random number generator state is not serialized
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
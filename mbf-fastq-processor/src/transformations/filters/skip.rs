@@ -77,10 +77,9 @@ impl Step for Skip {
                 .expect("default tag must exist in remaining");
             if *remaining == 0 {
                 Ok((block, true))
-            } else if *remaining >= block.len() {
-                *remaining -= block.len();
-                Ok((block.empty(), true))
             } else {
+                // drain (rather than replace with an empty block) even if the whole block is
+                // skipped, so the skipped reads can make it into the rejected output
                 let here = (*remaining).min(block.len());
                 *remaining -= here;
                 block.drain(0..here);
@@ -110,4 +109,23 @@ impl Step for Skip {
     fn needs_serial(&self) -> bool {
         true
     }
+
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn checkpoint_state(&self) -> Result<Option<serde_json::Value>> {
+        let remaining = self
+            .remaining
+            .as_ref()
+            .expect("SHould have been set in input")
+            .lock()
+            .expect("mutex poisoned");
+        Ok(Some(serde_json::to_value(&*remaining)?))
+    }
+
+    fn restore_checkpoint_state(&mut self, state: serde_json::Value) -> Result<()> {
+        self.remaining = Some(Arc::new(Mutex::new(serde_json::from_value(state)?)));
+        Ok(())
+    }
 }
@@ -1,5 +1,7 @@
 #![allow(clippy::unnecessary_wraps)]
-use indexmap::IndexMap;
+use indexmap::{IndexMap, IndexSet};
+use std::collections::HashMap;
+use std::io::BufRead;
 
 use crate::{
     config::deser::offer_alternatives,
@@ -7,7 +9,11 @@ use crate::{
     transformations::prelude::*,
 };
 
-use crate::dna::{Hits, TagValue};
+use super::reports::common::Q_LOOKUP;
+use crate::dna::{Hit, Hits, TagValue};
+
+/// Error probability we assume for a mismatch when the tag carries no qualities (String tags)
+const DEFAULT_MISMATCH_ERROR_PROBABILITY: f64 = 0.01;
 
 /// Correct a tag (extracted region) to known barcodes
 
@@ -20,18 +26,48 @@ pub struct HammingCorrect {
     /// Output tag to store corrected result
     pub out_label: TagLabel,
     /// Reference to barcodes section
-    pub barcodes: String,
+    pub barcodes: Option<String>,
+    /// File with one barcode per line (optionally followed by a prior count),
+    /// may be gzip/zstd compressed. Alternative to 'barcodes'
+    pub barcodes_file: Option<String>,
     /// Maximum hamming distance for correction
     pub max_hamming_distance: u8,
     /// What to do when no match is found
     pub on_no_match: OnNoMatch,
+    /// How to pick among multiple barcodes within `max_hamming_distance`
+    #[tpd(default)]
+    pub strategy: CorrectionStrategy,
+    /// Minimum posterior probability to accept a correction (strategy = 'posterior')
+    pub min_posterior: f64,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 
     #[tpd(skip)]
     #[schemars(skip)]
     pub resolved_barcodes: IndexMap<BString, String>,
-    #[tpd(skip)]
+    #[tpd(skip, default)]
+    #[schemars(skip)]
+    pub index: Option<Arc<BarcodeIndex>>,
+    /// Barcode abundance (prior counts + exact matches seen so far), strategy = 'posterior' only
+    #[tpd(skip, default)]
     #[schemars(skip)]
-    pub had_iupac: bool,
+    pub counts: Option<Arc<Mutex<HashMap<usize, u64>>>>,
+}
+
+fn default_min_posterior() -> f64 {
+    0.975
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, JsonSchema)]
+#[tpd]
+pub enum CorrectionStrategy {
+    /// The first barcode listed that is within the distance
+    #[default]
+    First,
+    /// The barcode with the smallest distance (first one listed on ties)
+    Closest,
+    /// Weigh candidates by abundance and base qualities (Cell Ranger style)
+    Posterior,
 }
 
 impl VerifyIn<PartialConfig> for PartialHammingCorrect {
@@ -57,12 +93,13 @@ impl VerifyIn<PartialConfig> for PartialHammingCorrect {
                 Ok(())
             }
         });
-        self.barcodes.verify(|v| {
-            if v.is_empty() {
-                Err(ValidationFailure::new("Must not be empty", None))
-            } else {
-                Ok(())
-            }
+        self.barcodes.verify(|v| match v {
+            Some(v) if v.is_empty() => Err(ValidationFailure::new("Must not be empty", None)),
+            _ => Ok(()),
+        });
+        self.barcodes_file.verify(|v| match v {
+            Some(v) if v.is_empty() => Err(ValidationFailure::new("Must not be empty", None)),
+            _ => Ok(()),
         });
         if let Some(out_label) = self.out_label.as_ref()
             && let Some(in_label) = self.in_label.as_ref()
@@ -87,8 +124,37 @@ impl VerifyIn<PartialConfig> for PartialHammingCorrect {
                 Ok(())
             }
         });
+        self.min_posterior.or_with(default_min_posterior);
+        self.min_posterior.verify(|v| {
+            if *v > 0.0 && *v <= 1.0 {
+                Ok(())
+            } else {
+                Err(ValidationFailure::new(
+                    "Must be a probability in (0, 1]",
+                    Some("Cell Ranger uses 0.975"),
+                ))
+            }
+        });
+
+        if let Some(Some(_)) = self.barcodes.as_ref()
+            && let Some(Some(_)) = self.barcodes_file.as_ref()
+        {
+            let spans = vec![
+                (self.barcodes.span(), "Either barcodes".to_string()),
+                (self.barcodes_file.span(), "or barcodes_file".to_string()),
+            ];
+            self.barcodes_file.state = TomlValueState::Custom { spans };
+            self.barcodes_file.help = Some(
+                "Use 'barcodes' for a [barcodes] section in the config, 'barcodes_file' for a (large) whitelist file - not both.".to_string(),
+            );
+            self.resolved_barcodes = Some(IndexMap::new());
+            return Ok(());
+        }
 
-        if let Some(barcodes_to_use) = self.barcodes.as_ref()
+        if let Some(Some(_)) = self.barcodes_file.as_ref() {
+            // read during init - the whitelist may have millions of entries
+            self.resolved_barcodes = Some(IndexMap::new());
+        } else if let Some(Some(barcodes_to_use)) = self.barcodes.as_ref()
             && let Some(barcode_data) = parent.barcodes.as_ref()
             && let Some(barcodes_data) = barcode_data
         {
@@ -110,13 +176,6 @@ impl VerifyIn<PartialConfig> for PartialHammingCorrect {
                         })
                         .collect();
                     // Copy the resolved barcodes
-
-                    // Check if any barcode contains IUPAC ambiguous bases
-                    self.had_iupac = Some(
-                        barcodes_section
-                            .keys()
-                            .any(|x| crate::dna::contains_iupac_ambigous(x)),
-                    );
                     self.resolved_barcodes = Some(barcodes_section);
                 }
                 None => {
@@ -136,7 +195,7 @@ impl VerifyIn<PartialConfig> for PartialHammingCorrect {
             );
         } else {
             return Err(ValidationFailure::new(
-                "HammingCorrect step requires a barcodes section to be defined in the config.",
+                "HammingCorrect step requires either 'barcodes' (a barcodes section defined in the config) or 'barcodes_file'.",
                 Some(&format!("See {}", crate::cli::link_docs("barcodes"))),
             ));
         }
@@ -169,6 +228,7 @@ impl TagUser for PartialTaggedVariant<PartialHammingCorrect> {
                 inner
                     .in_label
                     .to_used_tag(&[TagValueType::String, TagValueType::Location][..]),
+                inner.if_tag.to_used_tag(&[][..]),
             ],
             ..Default::default()
         }
@@ -176,6 +236,61 @@ impl TagUser for PartialTaggedVariant<PartialHammingCorrect> {
 }
 
 impl Step for HammingCorrect {
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
+    fn init(
+        &mut self,
+        _input_info: &InputInfo,
+        _output_prefix: &str,
+        _output_directory: &Path,
+        _output_ix_separator: &str,
+        _demultiplex_info: &OptDemultiplex,
+        _allow_overwrite: bool,
+    ) -> Result<Option<DemultiplexBarcodes>> {
+        let index = match &self.barcodes_file {
+            Some(filename) => BarcodeIndex::from_file(filename)?,
+            None => BarcodeIndex::from_barcodes(self.resolved_barcodes.keys().cloned()),
+        };
+        if self.strategy == CorrectionStrategy::Posterior {
+            if index.had_iupac {
+                bail!(
+                    "HammingCorrect with strategy = 'posterior' does not support IUPAC codes (including N) in the barcodes. Use strategy = 'first' or 'closest'."
+                );
+            }
+            self.counts = Some(Arc::new(Mutex::new(index.prior_counts.clone())));
+        }
+        self.index = Some(Arc::new(index));
+        Ok(None)
+    }
+
+    /// The abundance prior accumulates in input order
+    fn needs_serial(&self) -> bool {
+        self.strategy == CorrectionStrategy::Posterior
+    }
+
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn checkpoint_state(&self) -> Result<Option<serde_json::Value>> {
+        match &self.counts {
+            Some(counts) => {
+                let counts = counts.lock().expect("mutex poisoned");
+                Ok(Some(serde_json::to_value(&*counts)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn restore_checkpoint_state(&mut self, state: serde_json::Value) -> Result<()> {
+        if self.counts.is_some() {
+            self.counts = Some(Arc::new(Mutex::new(serde_json::from_value(state)?)));
+        }
+        Ok(())
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
@@ -183,20 +298,48 @@ impl Step for HammingCorrect {
         _block_no: usize,
         _demultiplex_info: &OptDemultiplex,
     ) -> Result<(FastQBlocksCombined, bool)> {
+        let index = self
+            .index
+            .as_ref()
+            .expect("index must be built during initialization");
+        let mut counts = self
+            .counts
+            .as_ref()
+            .map(|counts| counts.lock().expect("mutex poisoned"));
         let input_tags = block.tags.get(&self.in_label).expect("Input tag not found");
 
-        let barcodes = &self.resolved_barcodes;
+        if let Some(counts) = counts.as_deref_mut() {
+            // exact matches (of this block, too) make up the abundance prior
+            for input_tag in input_tags {
+                match input_tag {
+                    TagValue::Location(hits) => {
+                        for hit in &hits.0 {
+                            index.count_exact(&hit.sequence, counts);
+                        }
+                    }
+                    TagValue::String(sequence) => index.count_exact(sequence, counts),
+                    _ => {}
+                }
+            }
+        }
+        let corrector = Corrector {
+            index,
+            max_hamming_distance: self.max_hamming_distance,
+            on_no_match: self.on_no_match,
+            strategy: self.strategy,
+            posterior: counts.as_deref().map(|counts| (counts, self.min_posterior)),
+        };
+
         let mut output_hits = Vec::new();
 
-        for input_tag in input_tags {
+        for (read_idx, input_tag) in input_tags.iter().enumerate() {
             match input_tag {
                 TagValue::Location(hit_sequences) => {
-                    let corrected_hits = correct_barcodes(
-                        barcodes,
-                        hit_sequences.0.iter().map(|hit| (hit, &hit.sequence)),
-                        self.on_no_match,
-                        self.max_hamming_distance,
-                        self.had_iupac,
+                    let corrected_hits = corrector.correct(
+                        hit_sequences
+                            .0
+                            .iter()
+                            .map(|hit| (hit, &hit.sequence, hit_qualities(&block, hit, read_idx))),
                     );
                     if corrected_hits.is_empty() {
                         match self.on_no_match {
@@ -215,13 +358,8 @@ impl Step for HammingCorrect {
                     }
                 }
                 TagValue::String(hit_string) => {
-                    let mut corrected_hits: Vec<BString> = correct_barcodes(
-                        barcodes,
-                        [hit_string].into_iter().map(|hit| (hit, hit)),
-                        self.on_no_match,
-                        self.max_hamming_distance,
-                        self.had_iupac,
-                    );
+                    let mut corrected_hits: Vec<BString> =
+                        corrector.correct([hit_string].into_iter().map(|hit| (hit, hit, None)));
                     if corrected_hits.is_empty() {
                         match self.on_no_match {
                             OnNoMatch::Remove => {
@@ -257,11 +395,308 @@ impl Step for HammingCorrect {
     }
 }
 
+/// The base qualities under a hit - if it still has a location of matching length.
+fn hit_qualities<'a>(
+    block: &'a FastQBlocksCombined,
+    hit: &Hit,
+    read_idx: usize,
+) -> Option<&'a [u8]> {
+    let location = hit.location.as_ref()?;
+    let segment = &block.segments[location.segment_index.0];
+    let qual = segment.entries[read_idx].qual.get(&segment.block);
+    qual.get(location.start..location.start + location.len)
+        .filter(|qual| qual.len() == hit.sequence.len())
+}
+
+/// The barcodes to correct to, and how to find those close to a sequence.
+///
+/// Small barcode sets are scanned linearly. For large ones (10x whitelists
+/// have millions of entries), we instead look up every sequence
+/// within the hamming distance of the query, which is a few dozen to
+/// a few thousand hash lookups for typical barcode lengths and distances 1/2.
+///
+/// `MapTag` uses it for its keys, too.
+#[derive(Debug)]
+pub struct BarcodeIndex {
+    barcodes: IndexSet<BString>,
+    /// From the optional second column of `barcodes_file`
+    prior_counts: HashMap<usize, u64>,
+    had_iupac: bool,
+    /// Only then can we enumerate the neighbourhood
+    all_acgt: bool,
+}
+
+impl BarcodeIndex {
+    fn from_barcodes(barcodes: impl Iterator<Item = BString>) -> Self {
+        let mut index = Self::from_keys(barcodes);
+        index.had_iupac = index
+            .barcodes
+            .iter()
+            .any(|x| crate::dna::contains_iupac_ambigous(x));
+        index
+    }
+
+    /// Keys that are compared literally - an 'N' is just another letter.
+    pub(crate) fn from_keys(keys: impl Iterator<Item = BString>) -> Self {
+        let barcodes: IndexSet<BString> = keys.collect();
+        let all_acgt = barcodes
+            .iter()
+            .all(|x| x.iter().all(|c| matches!(c, b'A' | b'C' | b'G' | b'T')));
+        BarcodeIndex {
+            barcodes,
+            prior_counts: HashMap::new(),
+            had_iupac: false,
+            all_acgt,
+        }
+    }
+
+    pub(crate) fn index_of(&self, sequence: &[u8]) -> Option<usize> {
+        self.barcodes.get_index_of(BStr::new(sequence))
+    }
+
+    /// One barcode per line, optionally followed by whitespace and a count
+    /// (used as abundance prior). Empty lines and lines starting with '#' are skipped.
+    fn from_file(filename: &str) -> Result<Self> {
+        let file = crate::io::open_file(filename)?;
+        let (reader, _format) = niffler::send::get_reader(Box::new(file))
+            .with_context(|| format!("Could not read barcodes_file {filename}"))?;
+        let reader = std::io::BufReader::new(reader);
+        let mut barcodes = IndexSet::new();
+        let mut prior_counts: HashMap<usize, u64> = HashMap::new();
+        for (line_no, line) in reader.split(b'\n').enumerate() {
+            let line = line.with_context(|| format!("Could not read from {filename}"))?;
+            let mut fields = line
+                .split(u8::is_ascii_whitespace)
+                .filter(|field| !field.is_empty());
+            let Some(barcode) = fields.next() else {
+                continue;
+            };
+            if barcode.starts_with(b"#") {
+                continue;
+            }
+            let (idx, _) = barcodes.insert_full(BString::from(barcode));
+            if let Some(count) = fields.next() {
+                let count: u64 = std::str::from_utf8(count)
+                    .ok()
+                    .and_then(|count| count.parse().ok())
+                    .with_context(|| {
+                        format!(
+                            "{filename}, line {}: could not parse count '{}'",
+                            line_no + 1,
+                            BStr::new(count)
+                        )
+                    })?;
+                *prior_counts.entry(idx).or_default() += count;
+            }
+        }
+        if barcodes.is_empty() {
+            bail!("No barcodes were read from {filename}");
+        }
+        let mut index = Self::from_barcodes(barcodes.into_iter());
+        index.prior_counts = prior_counts;
+        Ok(index)
+    }
+
+    fn count_exact(&self, sequence: &[u8], counts: &mut HashMap<usize, u64>) {
+        if let Some(idx) = self.barcodes.get_index_of(BStr::new(sequence)) {
+            *counts.entry(idx).or_default() += 1;
+        }
+    }
+
+    /// Is looking up every sequence within `max_distance` of a query
+    /// cheaper than comparing it to every barcode?
+    fn use_neighbourhood(&self, query_len: usize, max_distance: u8) -> bool {
+        if !self.all_acgt {
+            return false;
+        }
+        #[allow(clippy::cast_precision_loss)]
+        let (query_len, barcode_count) = (query_len as f64, self.barcodes.len() as f64);
+        let mut neighbourhood = 0.0;
+        let mut positions = 1.0; // query_len choose k
+        let mut substitutions = 1.0; // 3^k
+        for k in 1..=max_distance {
+            let k = f64::from(k);
+            positions = positions * (query_len - k + 1.0).max(0.0) / k;
+            substitutions *= 3.0;
+            neighbourhood += positions * substitutions;
+        }
+        neighbourhood < barcode_count
+    }
+
+    /// Look up all sequences differing from `variant` in exactly `substitutions_left`
+    /// positions at or after `start`.
+    fn collect_neighbours(
+        &self,
+        variant: &mut [u8],
+        start: usize,
+        substitutions_left: u32,
+        distance: u32,
+        found: &mut Vec<(usize, u32)>,
+    ) {
+        if substitutions_left == 0 {
+            if let Some(idx) = self.barcodes.get_index_of(BStr::new(&variant[..])) {
+                found.push((idx, distance));
+            }
+            return;
+        }
+        for pos in start..variant.len() {
+            let original = variant[pos];
+            for base in [b'A', b'C', b'G', b'T'] {
+                if base != original {
+                    variant[pos] = base;
+                    self.collect_neighbours(
+                        variant,
+                        pos + 1,
+                        substitutions_left - 1,
+                        distance,
+                        found,
+                    );
+                }
+            }
+            variant[pos] = original;
+        }
+    }
+
+    /// Barcodes within `max_distance` of `query` (that is not itself a barcode),
+    /// as (index, distance). With `closest_only`, we may stop at the first distance
+    /// that has any.
+    pub(crate) fn candidates(
+        &self,
+        query: &[u8],
+        max_distance: u8,
+        closest_only: bool,
+    ) -> Vec<(usize, u32)> {
+        let mut found = Vec::new();
+        if self.use_neighbourhood(query.len(), max_distance) {
+            let mut variant = query.to_vec();
+            for distance in 1..=u32::from(max_distance) {
+                self.collect_neighbours(&mut variant, 0, distance, distance, &mut found);
+                if closest_only && !found.is_empty() {
+                    break;
+                }
+            }
+        } else {
+            for (idx, barcode) in self.barcodes.iter().enumerate() {
+                if barcode.len() != query.len() {
+                    continue;
+                }
+                let distance = if self.had_iupac {
+                    iupac_hamming_distance(barcode, query)
+                        .try_into()
+                        .unwrap_or(u32::MAX)
+                } else {
+                    hamming(barcode, query)
+                };
+                if distance <= u32::from(max_distance) {
+                    found.push((idx, distance));
+                }
+            }
+        }
+        found
+    }
+
+    /// Cell Ranger style: weigh each candidate by its abundance (+1 pseudocount)
+    /// times the probability of sequencing errors at the differing bases,
+    /// and accept the best one if its share is at least `min_posterior`.
+    fn most_likely(
+        &self,
+        query: &[u8],
+        qualities: Option<&[u8]>,
+        candidates: &[(usize, u32)],
+        counts: &HashMap<usize, u64>,
+        min_posterior: f64,
+    ) -> Option<usize> {
+        let mut total = 0.0;
+        let mut best: Option<(usize, f64)> = None;
+        for &(idx, _distance) in candidates {
+            let mut likelihood = 1.0;
+            for (pos, (a, b)) in self.barcodes[idx].iter().zip(query).enumerate() {
+                if a != b {
+                    likelihood *= qualities.map_or(DEFAULT_MISMATCH_ERROR_PROBABILITY, |qual| {
+                        Q_LOOKUP[qual[pos] as usize]
+                    });
+                }
+            }
+            #[allow(clippy::cast_precision_loss)]
+            let weight = (counts.get(&idx).copied().unwrap_or(0) + 1) as f64 * likelihood;
+            total += weight;
+            if best.is_none_or(|(_, best_weight)| weight > best_weight) {
+                best = Some((idx, weight));
+            }
+        }
+        let (idx, weight) = best?;
+        (weight / total >= min_posterior).then_some(idx)
+    }
+}
+
+struct Corrector<'a> {
+    index: &'a BarcodeIndex,
+    max_hamming_distance: u8,
+    on_no_match: OnNoMatch,
+    strategy: CorrectionStrategy,
+    /// (abundances, `min_posterior`) for strategy = 'posterior'
+    posterior: Option<(&'a HashMap<usize, u64>, f64)>,
+}
+
+impl Corrector<'_> {
+    fn find(&self, sequence: &[u8], qualities: Option<&[u8]>) -> Option<&BString> {
+        let index = self.index;
+        if let Some(idx) = index.barcodes.get_index_of(BStr::new(sequence)) {
+            return index.barcodes.get_index(idx);
+        }
+        let candidates = index.candidates(
+            sequence,
+            self.max_hamming_distance,
+            self.strategy == CorrectionStrategy::Closest,
+        );
+        let idx = match (self.strategy, self.posterior) {
+            (CorrectionStrategy::Posterior, Some((counts, min_posterior))) => {
+                index.most_likely(sequence, qualities, &candidates, counts, min_posterior)?
+            }
+            (CorrectionStrategy::Closest, _) => {
+                candidates
+                    .iter()
+                    .min_by_key(|(idx, distance)| (*distance, *idx))?
+                    .0
+            }
+            _ => candidates.iter().map(|(idx, _)| *idx).min()?,
+        };
+        index.barcodes.get_index(idx)
+    }
+
+    fn correct<'a, T: Clone + WithUpdatedSequence + 'a>(
+        &self,
+        hit_sequences: impl Iterator<Item = (&'a T, &'a BString, Option<&'a [u8]>)>,
+    ) -> Vec<T> {
+        let mut corrected_hits = Vec::new();
+        for (hit_seq, sequence, qualities) in hit_sequences {
+            match self.find(sequence, qualities) {
+                Some(barcode) if barcode == sequence => corrected_hits.push(hit_seq.clone()),
+                Some(barcode) => corrected_hits.push(hit_seq.clone_with_sequence(barcode)),
+                None => match self.on_no_match {
+                    OnNoMatch::Remove => {
+                        // Don't add to corrected_hits - effectively removes the tag
+                    }
+                    OnNoMatch::Empty => {
+                        // Create hit with empty sequence
+                        corrected_hits.push(hit_seq.clone_with_sequence(&BString::new(vec![])));
+                    }
+                    OnNoMatch::Keep => {
+                        // Keep original hit unchanged
+                        corrected_hits.push(hit_seq.clone());
+                    }
+                },
+            }
+        }
+        corrected_hits
+    }
+}
+
 trait WithUpdatedSequence {
     fn clone_with_sequence(&self, sequence: &BString) -> Self;
 }
 
-impl WithUpdatedSequence for crate::dna::Hit {
+impl WithUpdatedSequence for Hit {
     fn clone_with_sequence(&self, sequence: &BString) -> Self {
         let mut new_hit = self.clone();
         new_hit.sequence = sequence.clone();
@@ -275,56 +710,90 @@ impl WithUpdatedSequence for BString {
     }
 }
 
-fn correct_barcodes<'a, T: Clone + WithUpdatedSequence + 'a>(
-    barcodes: &IndexMap<BString, String>,
-    hit_sequences: impl Iterator<Item = (&'a T, &'a BString)>,
-    on_no_match: OnNoMatch,
-    max_hamming_distance: u8,
-    had_iupac: bool,
-) -> Vec<T> {
-    let mut corrected_hits = Vec::new();
-    for (hit_seq, sequence) in hit_sequences {
-        let mut found_match = false;
-
-        // Try exact match first
-        if barcodes.contains_key(sequence) {
-            corrected_hits.push(hit_seq.clone());
-            found_match = true;
-        } else if max_hamming_distance > 0 {
-            // Try hamming distance correction
-            // Use IUPAC hamming distance
-            for barcode in barcodes.keys() {
-                let distance = if had_iupac {
-                    iupac_hamming_distance(barcode, sequence)
-                } else {
-                    hamming(barcode, sequence)
-                        .try_into()
-                        .expect("hamming distance conversion should succeed")
-                };
-                if distance.try_into().unwrap_or(255u8) <= max_hamming_distance {
-                    // Create corrected hit with new sequence
-                    corrected_hits.push(hit_seq.clone_with_sequence(barcode));
-                    found_match = true;
-                    break;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(barcodes: &[&str]) -> BarcodeIndex {
+        BarcodeIndex::from_barcodes(barcodes.iter().map(|x| BString::from(*x)))
+    }
+
+    #[test]
+    fn test_neighbourhood_matches_linear_scan() {
+        // enough barcodes that the neighbourhood lookup is used for distance 1 and 2
+        let mut barcodes = Vec::new();
+        for a in ["A", "C", "G", "T"] {
+            for b in ["A", "C", "G", "T"] {
+                for c in ["A", "C", "G", "T"] {
+                    for d in ["A", "C", "G", "T"] {
+                        barcodes.push(format!("{a}{b}{c}{d}A"));
+                    }
                 }
             }
         }
-
-        if !found_match {
-            match on_no_match {
-                OnNoMatch::Remove => {
-                    // Don't add to corrected_hits - effectively removes the tag
-                }
-                OnNoMatch::Empty => {
-                    // Create hit with empty sequence
-                    corrected_hits.push(hit_seq.clone_with_sequence(&BString::new(vec![])));
-                }
-                OnNoMatch::Keep => {
-                    // Keep original hit unchanged
-                    corrected_hits.push(hit_seq.clone());
+        let refs: Vec<&str> = barcodes.iter().map(String::as_str).collect();
+        let idx = index(&refs);
+        assert!(idx.use_neighbourhood(5, 2));
+        for query in [b"ACGTC", b"TTTTT", b"GACCG", b"NNAAT"] {
+            for max_distance in 1..=2 {
+                let mut by_neighbourhood = idx.candidates(query, max_distance, false);
+                by_neighbourhood.sort_unstable();
+                let mut linear = Vec::new();
+                for (i, barcode) in idx.barcodes.iter().enumerate() {
+                    let distance = hamming(barcode, query);
+                    if distance <= u32::from(max_distance) {
+                        linear.push((i, distance));
+                    }
                 }
+                assert_eq!(by_neighbourhood, linear);
             }
         }
     }
-    corrected_hits
+
+    #[test]
+    fn test_first_and_closest() {
+        let idx = index(&["AAAA", "ACGT"]);
+        let corrector = Corrector {
+            index: &idx,
+            max_hamming_distance: 2,
+            on_no_match: OnNoMatch::Remove,
+            strategy: CorrectionStrategy::First,
+            posterior: None,
+        };
+        // AAAA is 2 away, ACGT only 1
+        assert_eq!(corrector.find(b"ACGA", None), Some(&BString::from("AAAA")));
+        assert_eq!(corrector.find(b"GGGG", None), None);
+        let corrector = Corrector {
+            strategy: CorrectionStrategy::Closest,
+            ..corrector
+        };
+        assert_eq!(corrector.find(b"ACGA", None), Some(&BString::from("ACGT")));
+        assert_eq!(corrector.find(b"GGGG", None), None);
+    }
+
+    #[test]
+    fn test_posterior_uses_abundance_and_quality() {
+        let idx = index(&["AAAA", "AAAC"]);
+        // query AAAG is one away from both
+        let mut counts = HashMap::new();
+        counts.insert(1, 99); // AAAC is abundant
+        let corrector = Corrector {
+            index: &idx,
+            max_hamming_distance: 1,
+            on_no_match: OnNoMatch::Remove,
+            strategy: CorrectionStrategy::Posterior,
+            posterior: Some((&counts, 0.975)),
+        };
+        assert_eq!(
+            corrector.find(b"AAAG", Some(b"IIII")),
+            Some(&BString::from("AAAC"))
+        );
+        // without the abundance difference, it's a coin toss
+        let counts = HashMap::new();
+        let corrector = Corrector {
+            posterior: Some((&counts, 0.975)),
+            ..corrector
+        };
+        assert_eq!(corrector.find(b"AAAG", Some(b"IIII")), None);
+    }
 }
@@ -16,6 +16,10 @@ pub struct _InternalDelay {
 impl TagUser for PartialTaggedVariant<Box<Partial_InternalDelay>> {}
 
 impl Step for Box<_InternalDelay> {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
     fn apply(
         &self,
         block: crate::io::FastQBlocksCombined,
@@ -82,6 +86,19 @@ impl Step for Box<_InternalReadCount> {
         Ok((block, true))
     }
 
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn checkpoint_state(&self) -> Result<Option<serde_json::Value>> {
+        Ok(Some(json!(self.count)))
+    }
+
+    fn restore_checkpoint_state(&mut self, state: serde_json::Value) -> Result<()> {
+        self.count = serde_json::from_value(state)?;
+        Ok(())
+    }
+
     fn finalize(&self, _demultiplex_info: &OptDemultiplex) -> Result<Option<FinalizeReportResult>> {
         let mut contents = serde_json::Map::new();
         contents.insert("_InternalReadCount".to_string(), json!(self.count));
@@ -106,6 +123,10 @@ pub struct _InduceFailure {
 impl TagUser for PartialTaggedVariant<Box<Partial_InduceFailure>> {}
 
 impl Step for Box<_InduceFailure> {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
     fn needs_serial(&self) -> bool {
         true
     }
@@ -119,3 +140,134 @@ impl Step for Box<_InduceFailure> {
         bail!("Induced failure: {}", self.msg);
     }
 }
+
+/// A step supplied in-process by a library user,
+/// see [`crate::api::Pipeline::add_step`]. Never read from the toml.
+#[tpd(no_verify)]
+#[derive(Debug)]
+#[allow(dead_code)]
+pub struct _CustomStep {
+    ignored: Option<u8>, //tpd does not like empty structs
+    #[tpd(skip, default)]
+    pub step: CustomStepBox,
+}
+
+#[derive(Default)]
+pub struct CustomStepBox(Option<Box<dyn Step + Send + Sync>>);
+
+impl std::fmt::Debug for CustomStepBox {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CustomStepBox")
+    }
+}
+
+impl _CustomStep {
+    pub fn new(step: Box<dyn Step + Send + Sync>) -> Self {
+        Self {
+            ignored: None,
+            step: CustomStepBox(Some(step)),
+        }
+    }
+
+    fn inner(&self) -> &(dyn Step + Send + Sync) {
+        self.step
+            .0
+            .as_deref()
+            .expect("_CustomStep is only constructed with a step")
+    }
+}
+
+impl TagUser for PartialTaggedVariant<Box<Partial_CustomStep>> {}
+
+impl Step for Box<_CustomStep> {
+    fn init(
+        &mut self,
+        input_info: &InputInfo,
+        output_prefix: &str,
+        output_directory: &Path,
+        output_ix_separator: &str,
+        demultiplex_info: &OptDemultiplex,
+        allow_overwrite: bool,
+    ) -> Result<Option<DemultiplexBarcodes>> {
+        self.step
+            .0
+            .as_mut()
+            .expect("_CustomStep is only constructed with a step")
+            .init(
+                input_info,
+                output_prefix,
+                output_directory,
+                output_ix_separator,
+                demultiplex_info,
+                allow_overwrite,
+            )
+    }
+
+    fn apply(
+        &self,
+        block: crate::io::FastQBlocksCombined,
+        input_info: &InputInfo,
+        block_no: usize,
+        demultiplex_info: &OptDemultiplex,
+    ) -> anyhow::Result<(crate::io::FastQBlocksCombined, bool)> {
+        self.inner()
+            .apply(block, input_info, block_no, demultiplex_info)
+    }
+
+    fn finalize(&self, demultiplex_info: &OptDemultiplex) -> Result<Option<FinalizeReportResult>> {
+        if self.inner().finalize(demultiplex_info)?.is_some() {
+            bail!(
+                "Custom steps can not contribute to the report - return Ok(None) from finalize and collect your results yourself."
+            );
+        }
+        Ok(None)
+    }
+
+    fn needs_serial(&self) -> bool {
+        self.inner().needs_serial()
+    }
+
+    fn transmits_premature_termination(&self) -> bool {
+        self.inner().transmits_premature_termination()
+    }
+
+    fn records_rejected_reads(&self) -> bool {
+        self.inner().records_rejected_reads()
+    }
+
+    fn supports_checkpoint(&self) -> bool {
+        self.inner().supports_checkpoint()
+    }
+
+    fn checkpoint_state(&self) -> Result<Option<serde_json::Value>> {
+        self.inner().checkpoint_state()
+    }
+
+    fn restore_checkpoint_state(&mut self, state: serde_json::Value) -> Result<()> {
+        self.step
+            .0
+            .as_mut()
+            .expect("_CustomStep is only constructed with a step")
+            .restore_checkpoint_state(state)
+    }
+
+    fn uses_memory_budget(&self) -> bool {
+        self.inner().uses_memory_budget()
+    }
+
+    fn set_memory_budget(&mut self, bytes: usize) {
+        self.step
+            .0
+            .as_mut()
+            .expect("_CustomStep is only constructed with a step")
+            .set_memory_budget(bytes);
+    }
+
+    fn ignores_memory_limit(&self) -> bool {
+        self.inner().ignores_memory_limit()
+    }
+
+    fn modified_segments(&self) -> ModifiedSegments {
+        self.inner().modified_segments()
+    }
+}
@@ -10,6 +10,7 @@ mod report_count;
 mod report_count_oligos;
 mod report_duplicate_count;
 mod report_duplicate_fragment_count;
+mod report_kmer_enrichment;
 mod report_length_distribution;
 mod report_tag_histogram;
 
@@ -29,6 +30,7 @@ pub use report_duplicate_count::{_ReportDuplicateCount, Partial_ReportDuplicateC
 pub use report_duplicate_fragment_count::{
     _ReportDuplicateFragmentCount, Partial_ReportDuplicateFragmentCount,
 };
+pub use report_kmer_enrichment::{_ReportKmerEnrichment, Partial_ReportKmerEnrichment};
 pub use report_length_distribution::{_ReportLengthDistribution, Partial_ReportLengthDistribution};
 pub use report_tag_histogram::{_ReportTagHistogram, Partial_ReportTagHistogram};
 
@@ -329,10 +329,10 @@ pub fn thousands_format(value: f64, digits: u8) -> String {
     }
 }
 
-#[derive(Clone, Debug, JsonSchema)]
+#[derive(Clone, Debug, JsonSchema, serde::Serialize, serde::Deserialize)]
 pub struct PositionCount(pub [usize; 5]);
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
 pub struct PerReadReportData<T> {
     pub segments: Vec<(String, T)>,
 }
@@ -32,6 +32,8 @@ pub struct Inspect {
     pub compression: CompressionFormat,
     #[tpd(default)]
     pub compression_level: Option<u8>,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 
     #[tpd(skip)]
     #[schemars(skip)]
@@ -116,10 +118,31 @@ impl std::fmt::Debug for Inspect {
 }
 
 impl TagUser for PartialTaggedVariant<PartialInspect> {
-    //default is ok, no tags
+    fn get_tag_usage(
+        &mut self,
+        _tags_available: &IndexMap<TagLabel, TagMetadata>,
+        _segment_order: &[String],
+    ) -> TagUsageInfo<'_> {
+        let inner = self
+            .toml_value
+            .as_mut()
+            .expect("get_tag_usage should only be called after successful verification");
+        TagUsageInfo {
+            used_tags: vec![inner.if_tag.to_used_tag(&[][..])],
+            ..Default::default()
+        }
+    }
 }
 
 impl Step for Inspect {
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
+    fn ignores_memory_limit(&self) -> bool {
+        true // the collected reads
+    }
+
     fn needs_serial(&self) -> bool {
         true
     }
@@ -162,6 +185,10 @@ impl Step for Inspect {
         Ok(None)
     }
 
+    fn supports_checkpoint(&self) -> bool {
+        false // writes its own output file
+    }
+
     fn apply(
         &self,
         block: FastQBlocksCombined,
@@ -129,6 +129,26 @@ impl Step for Progress {
         Ok(None)
     }
 
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn checkpoint_state(&self) -> Result<Option<serde_json::Value>> {
+        let total_count = *self
+            .total_count
+            .lock()
+            .expect("total_count lock must not be poisoned");
+        Ok(Some(serde_json::to_value(total_count)?))
+    }
+
+    fn restore_checkpoint_state(&mut self, state: serde_json::Value) -> Result<()> {
+        *self
+            .total_count
+            .lock()
+            .expect("total_count lock must not be poisoned") = serde_json::from_value(state)?;
+        Ok(())
+    }
+
     #[allow(clippy::cast_precision_loss)]
     #[mutants::skip] // we're not testing number values
     fn apply(
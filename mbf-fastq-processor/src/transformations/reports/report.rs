@@ -31,9 +31,21 @@ pub struct Report {
     #[schemars(with = "String")]
     pub count_oligos_segment: SegmentIndexOrAll,
 
+    /// Find k-mers (k = 5..=8) with a strongly position biased distribution
+    pub kmer_enrichment: Option<usize>,
+    #[tpd(adapt_in_verify(String))]
+    #[schemars(with = "String")]
+    pub kmer_enrichment_segment: SegmentIndexOrAll,
+    /// How many k-mers to report
+    pub kmer_enrichment_top_n: usize,
+
     /// Generate histograms for specified tags
     #[tpd(alias = "tag_histogram")]
     pub tag_histograms: Option<Vec<TagLabel>>,
+
+    /// Only count reads where this tag is truthy
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 }
 
 impl Clone for PartialReport {
@@ -48,7 +60,11 @@ impl Clone for PartialReport {
             debug_reproducibility: self.debug_reproducibility.clone(),
             count_oligos: self.count_oligos.clone(),
             count_oligos_segment: self.count_oligos_segment.clone(),
+            kmer_enrichment: self.kmer_enrichment.clone(),
+            kmer_enrichment_segment: self.kmer_enrichment_segment.clone(),
+            kmer_enrichment_top_n: self.kmer_enrichment_top_n.clone(),
             tag_histograms: self.tag_histograms.clone(),
+            if_tag: self.if_tag.clone(),
         }
     }
 }
@@ -72,6 +88,26 @@ impl VerifyIn<PartialConfig> for PartialReport {
         self.count.or(true);
         self.count_oligos_segment.or(SegmentIndexOrAll::All);
         self.count_oligos_segment.validate_segment(parent);
+        self.kmer_enrichment.verify(|k| match k {
+            Some(k) if !(5..=8).contains(k) => Err(ValidationFailure::new(
+                "k must be between 5 and 8",
+                Some("kmer_enrichment is the k-mer length, e.g. kmer_enrichment = 7"),
+            )),
+            _ => Ok(()),
+        });
+        self.kmer_enrichment_segment.or(SegmentIndexOrAll::All);
+        self.kmer_enrichment_segment.validate_segment(parent);
+        self.kmer_enrichment_top_n.or(10);
+        self.kmer_enrichment_top_n.verify(|top_n| {
+            if *top_n == 0 {
+                Err(ValidationFailure::new(
+                    "Must be > 0",
+                    Some("The number of k-mers to report"),
+                ))
+            } else {
+                Ok(())
+            }
+        });
 
         Ok(())
     }
@@ -89,6 +125,9 @@ impl Default for Report {
             debug_reproducibility: false,
             count_oligos: None,
             count_oligos_segment: default_segment_all(),
+            kmer_enrichment: None,
+            kmer_enrichment_segment: default_segment_all(),
+            kmer_enrichment_top_n: 10,
             tag_histograms: None,
         }
     }
@@ -109,6 +148,10 @@ impl TagUser for PartialTaggedVariant<PartialReport> {
 }
 
 impl Step for Report {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
     fn init(
         &mut self,
         _input_info: &InputInfo,
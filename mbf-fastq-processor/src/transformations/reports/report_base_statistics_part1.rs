@@ -3,7 +3,7 @@ use crate::transformations::prelude::*;
 use super::common::{PerReadReportData, Q_LOOKUP, Q20_Q30_LOOKUP};
 use crate::io;
 
-#[derive(Debug, Default, Clone, serde::Serialize)]
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
 pub struct BaseStatisticsPart1 {
     total_bases: usize,
     q20_bases: usize,
@@ -23,21 +23,43 @@ impl Into<serde_json::Value> for BaseStatisticsPart1 {
 #[derive(Debug)]
 pub struct _ReportBaseStatisticsPart1 {
     pub report_no: usize,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
     #[tpd(skip)]
     pub data: Arc<Mutex<DemultiplexedData<PerReadReportData<BaseStatisticsPart1>>>>,
 }
 
 impl Partial_ReportBaseStatisticsPart1 {
-    pub fn new(report_no: usize) -> Self {
+    pub fn new(report_no: usize, if_tag: TomlValue<Option<ConditionalTagLabel>>) -> Self {
         Self {
             report_no: TomlValue::new_ok_unplaced(report_no),
+            if_tag,
             data: Some(Default::default()),
         }
     }
 }
-impl TagUser for PartialTaggedVariant<Box<Partial_ReportBaseStatisticsPart1>> {}
+impl TagUser for PartialTaggedVariant<Box<Partial_ReportBaseStatisticsPart1>> {
+    fn get_tag_usage(
+        &mut self,
+        _tags_available: &IndexMap<TagLabel, TagMetadata>,
+        _segment_order: &[String],
+    ) -> TagUsageInfo<'_> {
+        let inner = self
+            .toml_value
+            .as_mut()
+            .expect("get_tag_usage should only be called after successful verification");
+        TagUsageInfo {
+            used_tags: vec![inner.if_tag.to_used_tag(&[][..])],
+            ..Default::default()
+        }
+    }
+}
 
 impl Step for Box<_ReportBaseStatisticsPart1> {
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
     fn transmits_premature_termination(&self) -> bool {
         false
     }
@@ -64,6 +86,20 @@ impl Step for Box<_ReportBaseStatisticsPart1> {
         Ok(None)
     }
 
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn checkpoint_state(&self) -> Result<Option<serde_json::Value>> {
+        let data = self.data.lock().expect("data poisened");
+        Ok(Some(serde_json::to_value(&*data)?))
+    }
+
+    fn restore_checkpoint_state(&mut self, state: serde_json::Value) -> Result<()> {
+        *self.data.lock().expect("data poisened") = serde_json::from_value(state)?;
+        Ok(())
+    }
+
     fn apply(
         &self,
         block: FastQBlocksCombined,
@@ -4,7 +4,7 @@ use super::common::{BASE_TO_INDEX, PerReadReportData, PositionCount};
 use crate::io;
 use serde_json::json;
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
 pub struct BaseStatisticsPart2 {
     per_position_counts: Vec<PositionCount>,
 }
@@ -43,21 +43,43 @@ impl Into<serde_json::Value> for BaseStatisticsPart2 {
 #[derive(Debug)]
 pub struct _ReportBaseStatisticsPart2 {
     pub report_no: usize,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
     #[tpd(skip)]
     pub data: Arc<Mutex<DemultiplexedData<PerReadReportData<BaseStatisticsPart2>>>>,
 }
 
 impl Partial_ReportBaseStatisticsPart2 {
-    pub fn new(report_no: usize) -> Self {
+    pub fn new(report_no: usize, if_tag: TomlValue<Option<ConditionalTagLabel>>) -> Self {
         Self {
             report_no: TomlValue::new_ok_unplaced(report_no),
+            if_tag,
             data: Some(Default::default()),
         }
     }
 }
-impl TagUser for PartialTaggedVariant<Box<Partial_ReportBaseStatisticsPart2>> {}
+impl TagUser for PartialTaggedVariant<Box<Partial_ReportBaseStatisticsPart2>> {
+    fn get_tag_usage(
+        &mut self,
+        _tags_available: &IndexMap<TagLabel, TagMetadata>,
+        _segment_order: &[String],
+    ) -> TagUsageInfo<'_> {
+        let inner = self
+            .toml_value
+            .as_mut()
+            .expect("get_tag_usage should only be called after successful verification");
+        TagUsageInfo {
+            used_tags: vec![inner.if_tag.to_used_tag(&[][..])],
+            ..Default::default()
+        }
+    }
+}
 
 impl Step for Box<_ReportBaseStatisticsPart2> {
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
     fn transmits_premature_termination(&self) -> bool {
         false
     }
@@ -83,6 +105,20 @@ impl Step for Box<_ReportBaseStatisticsPart2> {
         Ok(None)
     }
 
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn checkpoint_state(&self) -> Result<Option<serde_json::Value>> {
+        let data = self.data.lock().expect("data mutex poisoned");
+        Ok(Some(serde_json::to_value(&*data)?))
+    }
+
+    fn restore_checkpoint_state(&mut self, state: serde_json::Value) -> Result<()> {
+        *self.data.lock().expect("data mutex poisoned") = serde_json::from_value(state)?;
+        Ok(())
+    }
+
     fn apply(
         &self,
         block: FastQBlocksCombined,
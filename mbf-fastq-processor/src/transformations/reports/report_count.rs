@@ -7,6 +7,8 @@ use serde_json::json;
 #[derive(Debug)]
 pub struct _ReportCount {
     pub report_no: usize,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 
     #[tpd(skip)]
     #[schemars(skip)]
@@ -14,17 +16,37 @@ pub struct _ReportCount {
 }
 
 impl Partial_ReportCount {
-    pub fn new(report_no: usize) -> Self {
+    pub fn new(report_no: usize, if_tag: TomlValue<Option<ConditionalTagLabel>>) -> Self {
         Self {
             report_no: TomlValue::new_ok_unplaced(report_no),
+            if_tag,
             data: Some(Default::default()),
         }
     }
 }
 
-impl TagUser for PartialTaggedVariant<Box<Partial_ReportCount>> {}
+impl TagUser for PartialTaggedVariant<Box<Partial_ReportCount>> {
+    fn get_tag_usage(
+        &mut self,
+        _tags_available: &IndexMap<TagLabel, TagMetadata>,
+        _segment_order: &[String],
+    ) -> TagUsageInfo<'_> {
+        let inner = self
+            .toml_value
+            .as_mut()
+            .expect("get_tag_usage should only be called after successful verification");
+        TagUsageInfo {
+            used_tags: vec![inner.if_tag.to_used_tag(&[][..])],
+            ..Default::default()
+        }
+    }
+}
 
 impl Step for Box<_ReportCount> {
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
     fn transmits_premature_termination(&self) -> bool {
         false
     }
@@ -75,6 +97,20 @@ impl Step for Box<_ReportCount> {
         Ok((block, true))
     }
 
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn checkpoint_state(&self) -> Result<Option<serde_json::Value>> {
+        let data = self.data.lock().expect("mutex poisoned");
+        Ok(Some(serde_json::to_value(&*data)?))
+    }
+
+    fn restore_checkpoint_state(&mut self, state: serde_json::Value) -> Result<()> {
+        *self.data.lock().expect("mutex poisoned") = serde_json::from_value(state)?;
+        Ok(())
+    }
+
     fn finalize(&self, demultiplex_info: &OptDemultiplex) -> Result<Option<FinalizeReportResult>> {
         let data = self.data.lock().expect("mutex poisoned");
         let mut contents = serde_json::Map::new();
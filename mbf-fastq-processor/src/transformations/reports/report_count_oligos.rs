@@ -7,6 +7,8 @@ use serde_json::{Map, Value};
 #[derive(Debug)]
 pub struct _ReportCountOligos {
     pub report_no: usize,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
     #[schemars(with = "Vec<String>")]
     #[tpd(skip)]
     pub oligos: Vec<BString>,
@@ -32,16 +34,33 @@ impl VerifyIn<PartialConfig> for Partial_ReportCountOligos {
         Ok(())
     }
 }
-impl TagUser for PartialTaggedVariant<Box<Partial_ReportCountOligos>> {}
+impl TagUser for PartialTaggedVariant<Box<Partial_ReportCountOligos>> {
+    fn get_tag_usage(
+        &mut self,
+        _tags_available: &IndexMap<TagLabel, TagMetadata>,
+        _segment_order: &[String],
+    ) -> TagUsageInfo<'_> {
+        let inner = self
+            .toml_value
+            .as_mut()
+            .expect("get_tag_usage should only be called after successful verification");
+        TagUsageInfo {
+            used_tags: vec![inner.if_tag.to_used_tag(&[][..])],
+            ..Default::default()
+        }
+    }
+}
 
 impl Partial_ReportCountOligos {
     pub fn new(
         report_no: usize,
         oligos: Vec<BString>,
         segment: TomlValue<MustAdapt<String, SegmentIndexOrAll>>,
+        if_tag: TomlValue<Option<ConditionalTagLabel>>,
     ) -> Self {
         Self {
             report_no: TomlValue::new_ok_unplaced(report_no),
+            if_tag,
             oligos: Some(oligos),
             counts: Some(Default::default()),
             segment,
@@ -50,6 +69,10 @@ impl Partial_ReportCountOligos {
 }
 
 impl Step for Box<_ReportCountOligos> {
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
     fn transmits_premature_termination(&self) -> bool {
         false
     }
@@ -125,6 +148,21 @@ impl Step for Box<_ReportCountOligos> {
         }
         Ok((block, true))
     }
+
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn checkpoint_state(&self) -> Result<Option<serde_json::Value>> {
+        let counts = self.counts.lock().expect("counts mutex poisoned");
+        Ok(Some(serde_json::to_value(&*counts)?))
+    }
+
+    fn restore_checkpoint_state(&mut self, state: serde_json::Value) -> Result<()> {
+        *self.counts.lock().expect("counts mutex poisoned") = serde_json::from_value(state)?;
+        Ok(())
+    }
+
     fn finalize(&self, demultiplex_info: &OptDemultiplex) -> Result<Option<FinalizeReportResult>> {
         let mut contents = Map::new();
         let counts = self.counts.lock().expect("counts mutex poisoned");
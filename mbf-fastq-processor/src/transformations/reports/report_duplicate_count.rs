@@ -19,6 +19,8 @@ impl Into<serde_json::Value> for DuplicateCountData {
 #[tpd(no_verify)]
 pub struct _ReportDuplicateCount {
     pub report_no: usize,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
     //that is per read1/read2...
     pub debug_reproducibility: bool,
 
@@ -29,9 +31,14 @@ pub struct _ReportDuplicateCount {
 }
 
 impl Partial_ReportDuplicateCount {
-    pub fn new(report_no: usize, debug_reproducibility: TomlValue<bool>) -> Self {
+    pub fn new(
+        report_no: usize,
+        debug_reproducibility: TomlValue<bool>,
+        if_tag: TomlValue<Option<ConditionalTagLabel>>,
+    ) -> Self {
         Self {
             report_no: TomlValue::new_ok_unplaced(report_no),
+            if_tag,
             debug_reproducibility,
             data_per_segment: Some(Default::default()),
             initial_filter_capacity: Some(Default::default()),
@@ -39,9 +46,32 @@ impl Partial_ReportDuplicateCount {
     }
 }
 
-impl TagUser for PartialTaggedVariant<Box<Partial_ReportDuplicateCount>> {}
+impl TagUser for PartialTaggedVariant<Box<Partial_ReportDuplicateCount>> {
+    fn get_tag_usage(
+        &mut self,
+        _tags_available: &IndexMap<TagLabel, TagMetadata>,
+        _segment_order: &[String],
+    ) -> TagUsageInfo<'_> {
+        let inner = self
+            .toml_value
+            .as_mut()
+            .expect("get_tag_usage should only be called after successful verification");
+        TagUsageInfo {
+            used_tags: vec![inner.if_tag.to_used_tag(&[][..])],
+            ..Default::default()
+        }
+    }
+}
 
 impl Step for Box<_ReportDuplicateCount> {
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
+    fn ignores_memory_limit(&self) -> bool {
+        true
+    }
+
     fn transmits_premature_termination(&self) -> bool {
         false
     }
@@ -82,6 +112,10 @@ impl Step for Box<_ReportDuplicateCount> {
         Ok(None)
     }
 
+    fn supports_checkpoint(&self) -> bool {
+        false // the cuckoo filter can not be serialized
+    }
+
     fn apply(
         &self,
         block: FastQBlocksCombined,
@@ -13,6 +13,8 @@ pub struct DuplicateFragmentCountData {
 #[derive(Debug)]
 pub struct _ReportDuplicateFragmentCount {
     pub report_no: usize,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
     //that is per read1/read2...
     #[tpd(skip)]
     pub data: Arc<Mutex<DemultiplexedData<DuplicateFragmentCountData>>>,
@@ -24,9 +26,14 @@ pub struct _ReportDuplicateFragmentCount {
 }
 
 impl Partial_ReportDuplicateFragmentCount {
-    pub fn new(report_no: usize, debug_reproducibility: TomlValue<bool>) -> Self {
+    pub fn new(
+        report_no: usize,
+        debug_reproducibility: TomlValue<bool>,
+        if_tag: TomlValue<Option<ConditionalTagLabel>>,
+    ) -> Self {
         Self {
             report_no: TomlValue::new_ok_unplaced(report_no),
+            if_tag,
             debug_reproducibility,
             data: Some(Default::default()),
             initial_filter_capacity: Some(Default::default()),
@@ -34,9 +41,32 @@ impl Partial_ReportDuplicateFragmentCount {
         }
     }
 }
-impl TagUser for PartialTaggedVariant<Box<Partial_ReportDuplicateFragmentCount>> {}
+impl TagUser for PartialTaggedVariant<Box<Partial_ReportDuplicateFragmentCount>> {
+    fn get_tag_usage(
+        &mut self,
+        _tags_available: &IndexMap<TagLabel, TagMetadata>,
+        _segment_order: &[String],
+    ) -> TagUsageInfo<'_> {
+        let inner = self
+            .toml_value
+            .as_mut()
+            .expect("get_tag_usage should only be called after successful verification");
+        TagUsageInfo {
+            used_tags: vec![inner.if_tag.to_used_tag(&[][..])],
+            ..Default::default()
+        }
+    }
+}
 
 impl Step for Box<_ReportDuplicateFragmentCount> {
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
+    fn ignores_memory_limit(&self) -> bool {
+        true
+    }
+
     fn transmits_premature_termination(&self) -> bool {
         false
     }
@@ -69,6 +99,10 @@ impl Step for Box<_ReportDuplicateFragmentCount> {
         Ok(None)
     }
 
+    fn supports_checkpoint(&self) -> bool {
+        false // the cuckoo filter can not be serialized
+    }
+
     fn apply(
         &self,
         block: FastQBlocksCombined,
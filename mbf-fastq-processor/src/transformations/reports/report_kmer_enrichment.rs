@@ -0,0 +1,356 @@
+use crate::transformations::prelude::*;
+
+use super::common::BASE_TO_INDEX;
+use serde_json::{Map, Value, json};
+
+/// Positions are collected into at most this many bins,
+/// doubling the bin width whenever a longer read shows up.
+/// Bounds memory to `MAX_POSITION_BINS * 4^k` counters per (demultiplexed) output.
+const MAX_POSITION_BINS: usize = 64;
+
+/// k-mers seen less often are too noisy to judge their positional bias
+const MIN_KMER_COUNT: u64 = 10;
+
+/// k-mer counts per position bin
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct KmerPositionCounts {
+    k: usize,
+    bin_width: usize,
+    /// bin major: `counts[bin * 4^k + kmer]`
+    counts: Vec<u64>,
+}
+
+impl KmerPositionCounts {
+    fn new(k: usize) -> Self {
+        Self {
+            k,
+            bin_width: 1,
+            counts: Vec::new(),
+        }
+    }
+
+    fn kmer_count(&self) -> usize {
+        1 << (2 * self.k)
+    }
+
+    fn bin_count(&self) -> usize {
+        self.counts.len() / self.kmer_count()
+    }
+
+    /// Make sure `position` has a bin - doubling the bin width if necessary
+    fn ensure_position(&mut self, position: usize) {
+        while position / self.bin_width >= MAX_POSITION_BINS {
+            let kmer_count = self.kmer_count();
+            let new_bin_count = self.bin_count().div_ceil(2);
+            let mut new_counts = vec![0; new_bin_count * kmer_count];
+            for (ii, count) in self.counts.iter().enumerate() {
+                let bin = ii / kmer_count;
+                let kmer = ii % kmer_count;
+                new_counts[(bin / 2) * kmer_count + kmer] += count;
+            }
+            self.counts = new_counts;
+            self.bin_width *= 2;
+        }
+        let needed_bins = position / self.bin_width + 1;
+        if self.bin_count() < needed_bins {
+            self.counts.resize(needed_bins * self.kmer_count(), 0);
+        }
+    }
+
+    fn add_read(&mut self, seq: &[u8]) {
+        if seq.len() < self.k {
+            return;
+        }
+        self.ensure_position(seq.len() - self.k);
+        let kmer_count = self.kmer_count();
+        let mask = kmer_count - 1;
+        let mut code = 0;
+        let mut valid = 0;
+        for (ii, base) in seq.iter().enumerate() {
+            let idx = BASE_TO_INDEX[*base as usize] as usize;
+            if idx > 3 {
+                // k-mers containing an N are not counted
+                valid = 0;
+                code = 0;
+                continue;
+            }
+            code = ((code << 2) | idx) & mask;
+            valid += 1;
+            if valid >= self.k {
+                let start = ii + 1 - self.k;
+                self.counts[(start / self.bin_width) * kmer_count + code] += 1;
+            }
+        }
+    }
+
+    fn decode(&self, code: usize) -> String {
+        (0..self.k)
+            .rev()
+            .map(|ii| ['A', 'C', 'G', 'T'][(code >> (2 * ii)) & 3])
+            .collect()
+    }
+
+    /// The `top_n` k-mers with the highest observed/expected ratio at any position.
+    /// Expected is the k-mer's total count, distributed like all k-mers over the positions.
+    #[allow(clippy::cast_precision_loss)]
+    fn to_json(&self, top_n: usize) -> Value {
+        let kmer_count = self.kmer_count();
+        let bin_count = self.bin_count();
+        let mut kmer_totals = vec![0u64; kmer_count];
+        let mut bin_totals = vec![0u64; bin_count];
+        for (ii, count) in self.counts.iter().enumerate() {
+            kmer_totals[ii % kmer_count] += count;
+            bin_totals[ii / kmer_count] += count;
+        }
+        let total: u64 = bin_totals.iter().sum();
+
+        let mut candidates: Vec<(usize, f64, usize, Vec<f64>)> = Vec::new();
+        for (kmer, kmer_total) in kmer_totals.iter().enumerate() {
+            if *kmer_total < MIN_KMER_COUNT {
+                continue;
+            }
+            let enrichment: Vec<f64> = (0..bin_count)
+                .map(|bin| {
+                    if bin_totals[bin] == 0 {
+                        0.0
+                    } else {
+                        let expected = *kmer_total as f64 * bin_totals[bin] as f64 / total as f64;
+                        self.counts[bin * kmer_count + kmer] as f64 / expected
+                    }
+                })
+                .collect();
+            let mut max_bin = 0;
+            for (bin, value) in enrichment.iter().enumerate() {
+                if *value > enrichment[max_bin] {
+                    max_bin = bin;
+                }
+            }
+            candidates.push((kmer, enrichment[max_bin], max_bin, enrichment));
+        }
+        candidates.sort_by(|a, b| {
+            b.1.total_cmp(&a.1)
+                .then_with(|| kmer_totals[b.0].cmp(&kmer_totals[a.0]))
+                .then_with(|| a.0.cmp(&b.0))
+        });
+
+        let kmers: Vec<Value> = candidates
+            .into_iter()
+            .take(top_n)
+            .map(|(kmer, max_enrichment, max_bin, enrichment)| {
+                json!({
+                    "kmer": self.decode(kmer),
+                    "count": kmer_totals[kmer],
+                    "max_enrichment": max_enrichment,
+                    "max_enrichment_position": max_bin * self.bin_width,
+                    "enrichment": enrichment,
+                })
+            })
+            .collect();
+        json!({
+            "k": self.k,
+            "bin_width": self.bin_width,
+            "positions": (0..bin_count).map(|bin| bin * self.bin_width).collect::<Vec<_>>(),
+            "kmers": kmers,
+        })
+    }
+}
+
+#[derive(Clone, JsonSchema)]
+#[tpd]
+#[derive(Debug)]
+pub struct _ReportKmerEnrichment {
+    pub report_no: usize,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
+    pub k: usize,
+    pub top_n: usize,
+
+    #[schemars(with = "String")]
+    #[tpd(adapt_in_verify(String))]
+    pub segment: SegmentIndexOrAll,
+
+    #[tpd(skip)]
+    #[schemars(skip)]
+    pub data: Arc<Mutex<DemultiplexedData<KmerPositionCounts>>>,
+}
+
+impl VerifyIn<PartialConfig> for Partial_ReportKmerEnrichment {
+    fn verify(
+        &mut self,
+        parent: &PartialConfig,
+        _options: &VerifyOptions,
+    ) -> std::result::Result<(), ValidationFailure>
+    where
+        Self: Sized + toml_pretty_deser::Visitor,
+    {
+        self.segment.validate_segment(parent);
+        Ok(())
+    }
+}
+impl TagUser for PartialTaggedVariant<Box<Partial_ReportKmerEnrichment>> {
+    fn get_tag_usage(
+        &mut self,
+        _tags_available: &IndexMap<TagLabel, TagMetadata>,
+        _segment_order: &[String],
+    ) -> TagUsageInfo<'_> {
+        let inner = self
+            .toml_value
+            .as_mut()
+            .expect("get_tag_usage should only be called after successful verification");
+        TagUsageInfo {
+            used_tags: vec![inner.if_tag.to_used_tag(&[][..])],
+            ..Default::default()
+        }
+    }
+}
+
+impl Partial_ReportKmerEnrichment {
+    pub fn new(
+        report_no: usize,
+        k: usize,
+        top_n: usize,
+        segment: TomlValue<MustAdapt<String, SegmentIndexOrAll>>,
+        if_tag: TomlValue<Option<ConditionalTagLabel>>,
+    ) -> Self {
+        Self {
+            report_no: TomlValue::new_ok_unplaced(report_no),
+            if_tag,
+            k: TomlValue::new_ok_unplaced(k),
+            top_n: TomlValue::new_ok_unplaced(top_n),
+            segment,
+            data: Some(Default::default()),
+        }
+    }
+}
+
+impl Step for Box<_ReportKmerEnrichment> {
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
+    fn transmits_premature_termination(&self) -> bool {
+        false
+    }
+    #[mutants::skip] // same result either way, we count straight into the shared (large) tables
+    fn needs_serial(&self) -> bool {
+        true
+    }
+
+    fn init(
+        &mut self,
+        _input_info: &InputInfo,
+        _output_prefix: &str,
+        _output_directory: &Path,
+        _output_ix_separator: &str,
+        demultiplex_info: &OptDemultiplex,
+        _allow_overwrite: bool,
+    ) -> Result<Option<DemultiplexBarcodes>> {
+        let mut data = self.data.lock().expect("data mutex poisoned");
+        for valid_tag in demultiplex_info.iter_tags() {
+            data.insert(valid_tag, KmerPositionCounts::new(self.k));
+        }
+        Ok(None)
+    }
+
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn checkpoint_state(&self) -> Result<Option<serde_json::Value>> {
+        let data = self.data.lock().expect("data mutex poisoned");
+        Ok(Some(serde_json::to_value(&*data)?))
+    }
+
+    fn restore_checkpoint_state(&mut self, state: serde_json::Value) -> Result<()> {
+        *self.data.lock().expect("data mutex poisoned") = serde_json::from_value(state)?;
+        Ok(())
+    }
+
+    fn apply(
+        &self,
+        block: FastQBlocksCombined,
+        _input_info: &InputInfo,
+        _block_no: usize,
+        _demultiplex_info: &OptDemultiplex,
+    ) -> anyhow::Result<(FastQBlocksCombined, bool)> {
+        let mut blocks = Vec::new();
+        match &self.segment {
+            SegmentIndexOrAll::Indexed(idx) => {
+                blocks.push(&block.segments[*idx]);
+            }
+            SegmentIndexOrAll::All => {
+                for segment in &block.segments {
+                    blocks.push(segment);
+                }
+            }
+        }
+        let mut data = self.data.lock().expect("data mutex poisoned");
+        for read_block in blocks {
+            let mut iter = read_block.get_pseudo_iter_including_tag(&block.output_tags);
+            while let Some((read, demultiplex_tag)) = iter.pseudo_next() {
+                data.get_mut(&demultiplex_tag)
+                    .expect("demultiplex tag must exist in data")
+                    .add_read(read.seq());
+            }
+        }
+        Ok((block, true))
+    }
+
+    fn finalize(&self, demultiplex_info: &OptDemultiplex) -> Result<Option<FinalizeReportResult>> {
+        let data = self.data.lock().expect("data mutex poisoned");
+        let mut contents = Map::new();
+        match demultiplex_info {
+            OptDemultiplex::No => {
+                contents.insert(
+                    "kmer_enrichment".to_string(),
+                    data.get(&0)
+                        .expect("default tag 0 must exist in data")
+                        .to_json(self.top_n),
+                );
+            }
+
+            OptDemultiplex::Yes(demultiplex_info) => {
+                for (tag, name) in &demultiplex_info.tag_to_name {
+                    if let Some(name) = name {
+                        let kmer_enrichment = data
+                            .get(tag)
+                            .expect("tag must exist in data")
+                            .to_json(self.top_n);
+                        contents
+                            .insert(name.clone(), json!({ "kmer_enrichment": kmer_enrichment }));
+                    }
+                }
+            }
+        }
+
+        Ok(Some(FinalizeReportResult {
+            report_no: self.report_no,
+            contents: Value::Object(contents),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rebinning_keeps_counts() {
+        let mut counts = KmerPositionCounts::new(5);
+        counts.add_read(b"ACGTAACGTA");
+        assert_eq!(counts.bin_width, 1);
+        assert_eq!(counts.bin_count(), 6);
+        let long_read = vec![b'A'; MAX_POSITION_BINS + 10];
+        counts.add_read(&long_read);
+        assert_eq!(counts.bin_width, 2);
+        assert_eq!(
+            counts.counts.iter().sum::<u64>(),
+            6 + MAX_POSITION_BINS as u64 + 6
+        );
+        // ACGTA at 0 and 5 -> bins 0 and 2
+        let acgta = 0b00_01_10_11_00;
+        assert_eq!(counts.counts[acgta], 1);
+        assert_eq!(counts.counts[2 * counts.kmer_count() + acgta], 1);
+        assert_eq!(counts.decode(acgta), "ACGTA");
+    }
+}
@@ -3,7 +3,7 @@ use crate::transformations::prelude::*;
 use std::collections::BTreeMap;
 
 /// Histogram data structure that can handle both String and Numeric tags
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub enum HistogramData {
     /// String values mapped to their counts
     String(BTreeMap<String, usize>),
@@ -88,6 +88,8 @@ impl From<HistogramData> for serde_json::Value {
 #[derive(Debug)]
 pub struct _ReportTagHistogram {
     pub report_no: usize,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
     pub tag_name: TagLabel,
     #[tpd(skip)]
     pub tag_type: TagValueType,
@@ -96,9 +98,14 @@ pub struct _ReportTagHistogram {
 }
 
 impl Partial_ReportTagHistogram {
-    pub fn new(report_no: usize, tag_name: TomlValue<TagLabel>) -> Self {
+    pub fn new(
+        report_no: usize,
+        tag_name: TomlValue<TagLabel>,
+        if_tag: TomlValue<Option<ConditionalTagLabel>>,
+    ) -> Self {
         Self {
             report_no: TomlValue::new_ok_unplaced(report_no),
+            if_tag,
             tag_name,
             tag_type: None,
             data: Some(Default::default()),
@@ -124,20 +131,27 @@ impl TagUser for PartialTaggedVariant<Box<Partial_ReportTagHistogram>> {
             //if that's happening at all for our dynamically generated one.
         }
         TagUsageInfo {
-            used_tags: vec![inner.tag_name.to_used_tag(
-                &[
-                    TagValueType::String,
-                    TagValueType::Numeric,
-                    TagValueType::Bool,
-                    TagValueType::Location,
-                ][..],
-            )],
+            used_tags: vec![
+                inner.tag_name.to_used_tag(
+                    &[
+                        TagValueType::String,
+                        TagValueType::Numeric,
+                        TagValueType::Bool,
+                        TagValueType::Location,
+                    ][..],
+                ),
+                inner.if_tag.to_used_tag(&[][..]),
+            ],
             ..Default::default()
         }
     }
 }
 
 impl Step for Box<_ReportTagHistogram> {
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
     fn transmits_premature_termination(&self) -> bool {
         false
     }
@@ -177,6 +191,20 @@ impl Step for Box<_ReportTagHistogram> {
         Ok(None)
     }
 
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn checkpoint_state(&self) -> Result<Option<serde_json::Value>> {
+        let data = self.data.lock().expect("Lock poisoned");
+        Ok(Some(serde_json::to_value(&*data)?))
+    }
+
+    fn restore_checkpoint_state(&mut self, state: serde_json::Value) -> Result<()> {
+        *self.data.lock().expect("Lock poisoned") = serde_json::from_value(state)?;
+        Ok(())
+    }
+
     fn apply(
         &self,
         block: FastQBlocksCombined,
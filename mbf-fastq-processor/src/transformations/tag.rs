@@ -1,6 +1,7 @@
 // Common functionality shared by multiple tag transformations
 
 // Individual transformation modules
+pub mod combine_locations;
 pub mod concat_tags;
 pub mod forget_all_tags;
 pub mod forget_tag;
@@ -15,6 +16,7 @@ pub mod store_tags_in_table;
 use anyhow::{Result, bail};
 use bstr::{BStr, BString};
 // Re-exports
+pub use combine_locations::{CombineLocations, PartialCombineLocations};
 pub use concat_tags::{ConcatTags, PartialConcatTags};
 pub use forget_all_tags::{ForgetAllTags, PartialForgetAllTags};
 pub use forget_tag::{ForgetTag, PartialForgetTag};
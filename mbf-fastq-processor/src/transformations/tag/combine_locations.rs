@@ -0,0 +1,549 @@
+#![allow(clippy::unnecessary_wraps)] //eserde false positives
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use crate::dna::{Hit, HitRegion, Hits, TagValue};
+use crate::transformations::prelude::*;
+
+/// The intervals of one read, per segment index
+type Regions = BTreeMap<usize, Vec<Range<usize>>>;
+
+/// How `CombineLocations` combines its input regions
+#[derive(Clone, JsonSchema, PartialEq, Eq, Copy)]
+#[tpd]
+#[derive(Debug)]
+pub enum LocationOperation {
+    /// Everything covered by any input tag
+    Union,
+    /// Everything covered by all input tags
+    Intersect,
+    /// The first tag's regions minus everything covered by the others
+    Subtract,
+    /// Grow every region by `left` / `right` bases (clamped to the read)
+    Extend,
+    /// Everything not covered by the input regions
+    Complement,
+    /// Join regions that are at most `distance` bases apart
+    MergeWithin,
+}
+
+impl LocationOperation {
+    fn name(self) -> &'static str {
+        match self {
+            LocationOperation::Union => "union",
+            LocationOperation::Intersect => "intersect",
+            LocationOperation::Subtract => "subtract",
+            LocationOperation::Extend => "extend",
+            LocationOperation::Complement => "complement",
+            LocationOperation::MergeWithin => "merge_within",
+        }
+    }
+
+    fn min_inputs(self) -> usize {
+        match self {
+            LocationOperation::Intersect | LocationOperation::Subtract => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// Combine location tags with interval algebra, e.g. into one 'mask these regions' tag
+/// for `ReplaceTagWithLetter` or `TrimAtTag`.
+///
+/// Extend, complement and `merge_within` work on the union of all input tags.
+///
+/// ```toml
+/// [[step]]
+/// action = "CombineLocations"
+/// in_labels = ["adapter", "polyA"]
+/// operation = "union"
+/// out_label = "mask"
+/// ```
+#[derive(Clone, JsonSchema)]
+#[tpd]
+#[derive(Debug)]
+pub struct CombineLocations {
+    /// Input location tags
+    in_labels: Vec<TagLabel>,
+    operation: LocationOperation,
+    /// Bases to add before each region (operation = 'extend' only)
+    left: Option<usize>,
+    /// Bases to add after each region (operation = 'extend' only)
+    right: Option<usize>,
+    /// Maximum gap between regions to join (operation = 'merge_within' only)
+    distance: Option<usize>,
+    out_label: TagLabel,
+    #[tpd(default)]
+    if_tag: Option<ConditionalTagLabel>,
+}
+
+impl VerifyIn<PartialConfig> for PartialCombineLocations {
+    fn verify(
+        &mut self,
+        _parent: &PartialConfig,
+        _options: &VerifyOptions,
+    ) -> std::result::Result<(), ValidationFailure>
+    where
+        Self: Sized + toml_pretty_deser::Visitor,
+    {
+        self.in_labels.verify_mut(|v| {
+            // the count depends on the operation and is checked in get_tag_usage,
+            // so we can make suggestions
+            let mut seen = std::collections::HashSet::new();
+            for label in v.iter_mut() {
+                let lv = label.value.as_ref().expect("Parent was ok?");
+                if lv.is_empty() {
+                    label.state = TomlValueState::ValidationFailed {
+                        message: "Must not be empty".to_string(),
+                    };
+                } else if !seen.insert(lv) {
+                    label.state = TomlValueState::ValidationFailed {
+                        message: "Duplicate input label".to_string(),
+                    };
+                }
+            }
+            Ok(())
+        });
+        self.out_label.verify(|v| {
+            if v.is_empty() {
+                Err(ValidationFailure::new(
+                    "Output label must not be empty",
+                    None,
+                ))
+            } else {
+                Ok(())
+            }
+        });
+
+        if let Some(operation) = self.operation.as_ref().copied() {
+            let is_extend = operation == LocationOperation::Extend;
+            let is_merge_within = operation == LocationOperation::MergeWithin;
+            for (field, used) in [
+                (&mut self.left, is_extend),
+                (&mut self.right, is_extend),
+                (&mut self.distance, is_merge_within),
+            ] {
+                if !used && matches!(field.as_ref(), Some(Some(_))) {
+                    field.state = TomlValueState::ValidationFailed {
+                        message: format!("Not used with operation = '{}'", operation.name()),
+                    };
+                    field.help = Some(
+                        "'left' and 'right' are only used by 'extend', 'distance' only by 'merge_within'."
+                            .to_string(),
+                    );
+                }
+            }
+            if is_extend
+                && matches!(self.left.as_ref(), Some(None))
+                && matches!(self.right.as_ref(), Some(None))
+            {
+                return Err(ValidationFailure::new(
+                    "operation = 'extend' requires 'left' and/or 'right'",
+                    Some(
+                        "Set the number of bases to add before ('left') and/or after ('right') each region.",
+                    ),
+                ));
+            }
+            if is_merge_within && matches!(self.distance.as_ref(), Some(None)) {
+                return Err(ValidationFailure::new(
+                    "operation = 'merge_within' requires 'distance'",
+                    Some("Set the maximum gap (in bases) between regions that should be joined."),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TagUser for PartialTaggedVariant<PartialCombineLocations> {
+    fn get_tag_usage(
+        &mut self,
+        tags_available: &IndexMap<TagLabel, TagMetadata>,
+        _segment_order: &[String],
+    ) -> TagUsageInfo<'_> {
+        let inner = self
+            .toml_value
+            .as_mut()
+            .expect("get_tag_usage should only be called after successful verification");
+
+        let operation = *inner.operation.as_ref().expect("Parent was ok?");
+        let in_labels: Vec<TagLabel> = inner
+            .in_labels
+            .as_ref()
+            .expect("Parent was ok?")
+            .iter()
+            .filter_map(|v| v.value.as_ref())
+            .cloned()
+            .collect();
+        if in_labels.len() < operation.min_inputs() {
+            //we do this here so we can make suggestions.
+            let mut available: Vec<String> = tags_available
+                .iter()
+                .filter_map(|(tag_name, tag_meta)| {
+                    if !in_labels.contains(tag_name)
+                        && matches!(tag_meta.tag_type, TagValueType::Location)
+                    {
+                        Some(tag_name.to_string())
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+            available.sort_unstable();
+
+            inner.in_labels.state = TomlValueState::ValidationFailed {
+                message: format!(
+                    "operation = '{}' needs at least {} input label(s)",
+                    operation.name(),
+                    operation.min_inputs()
+                ),
+            };
+            inner.in_labels.help =
+                Some(format!("Available location tags: {}", available.join(", ")));
+            TagUsageInfo::default()
+        } else {
+            let mut used_tags: Vec<_> = inner
+                .in_labels
+                .as_mut()
+                .expect("Parent was ok?")
+                .iter_mut()
+                .map(|x| x.to_used_tag(&[TagValueType::Location][..]))
+                .collect();
+            used_tags.push(inner.if_tag.to_used_tag(&[][..]));
+            TagUsageInfo {
+                used_tags,
+                declared_tag: inner.out_label.to_declared_tag(TagValueType::Location),
+                ..Default::default()
+            }
+        }
+    }
+}
+
+impl CombineLocations {
+    /// Intersect / subtract only make sense within one segment
+    fn verify_same_segment(&self, inputs: &[Regions], segment_order: &[String]) -> Result<()> {
+        let mut segments = inputs.iter().flat_map(BTreeMap::keys);
+        if let Some(first) = segments.next()
+            && let Some(other) = segments.find(|x| *x != first)
+        {
+            bail!(
+                "CombineLocations with operation = '{}' needs all regions of a read on the same segment, \
+                but {:?} have regions on '{}' and '{}'.",
+                self.operation.name(),
+                self.in_labels,
+                segment_order[*first],
+                segment_order[*other],
+            );
+        }
+        Ok(())
+    }
+
+    fn combine(
+        &self,
+        inputs: Vec<Option<Regions>>,
+        read_len: impl Fn(usize) -> usize,
+        segment_order: &[String],
+    ) -> Result<Regions> {
+        let mut result = Regions::new();
+        match self.operation {
+            LocationOperation::Intersect | LocationOperation::Subtract => {
+                let inputs: Vec<Regions> = if self.operation == LocationOperation::Intersect {
+                    match inputs.into_iter().collect::<Option<Vec<_>>>() {
+                        Some(inputs) => inputs,
+                        None => return Ok(result),
+                    }
+                } else {
+                    let mut inputs = inputs.into_iter();
+                    match inputs.next().flatten() {
+                        Some(first) => std::iter::once(first).chain(inputs.flatten()).collect(),
+                        None => return Ok(result),
+                    }
+                };
+                self.verify_same_segment(&inputs, segment_order)?;
+                let mut inputs = inputs.into_iter().map(|regions| {
+                    regions
+                        .into_iter()
+                        .map(|(segment, ranges)| (segment, merge_within(ranges, 0)))
+                        .collect::<Regions>()
+                });
+                result = inputs.next().expect("checked for missing above");
+                if self.operation == LocationOperation::Intersect {
+                    for other in inputs {
+                        for (segment, ranges) in &mut result {
+                            *ranges = intersect(
+                                ranges,
+                                other.get(segment).map_or(&[][..], Vec::as_slice),
+                            );
+                        }
+                    }
+                } else {
+                    let others = union(inputs);
+                    for (segment, ranges) in &mut result {
+                        if let Some(other) = others.get(segment) {
+                            *ranges = subtract(ranges, other);
+                        }
+                    }
+                }
+            }
+            LocationOperation::Union => {
+                result = union(inputs.into_iter().flatten());
+            }
+            LocationOperation::Extend => {
+                let left = self.left.unwrap_or(0);
+                let right = self.right.unwrap_or(0);
+                result = union(inputs.into_iter().flatten());
+                for (segment, ranges) in &mut result {
+                    *ranges = extend(ranges, left, right, read_len(*segment));
+                }
+            }
+            LocationOperation::Complement => {
+                result = union(inputs.into_iter().flatten());
+                for (segment, ranges) in &mut result {
+                    *ranges = complement(ranges, read_len(*segment));
+                }
+            }
+            LocationOperation::MergeWithin => {
+                let distance = self.distance.expect("checked in verify");
+                result = union(inputs.into_iter().flatten());
+                for ranges in result.values_mut() {
+                    *ranges = merge_within(std::mem::take(ranges), distance);
+                }
+            }
+        }
+        result.retain(|_, ranges| !ranges.is_empty());
+        Ok(result)
+    }
+}
+
+impl Step for CombineLocations {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
+    fn apply(
+        &self,
+        mut block: FastQBlocksCombined,
+        input_info: &InputInfo,
+        _block_no: usize,
+        _demultiplex_info: &OptDemultiplex,
+    ) -> anyhow::Result<(FastQBlocksCombined, bool)> {
+        let num_reads = block.segments[0].entries.len();
+        let mut output_tags = Vec::with_capacity(num_reads);
+        {
+            let tag_vectors: Vec<&Vec<TagValue>> = self
+                .in_labels
+                .iter()
+                .map(|label| {
+                    block
+                        .tags
+                        .get(label)
+                        .expect("Input tag not found - should have been caught in validation")
+                })
+                .collect();
+
+            for read_idx in 0..num_reads {
+                let inputs = tag_vectors
+                    .iter()
+                    .zip(&self.in_labels)
+                    .map(|(tags, label)| regions_of(&tags[read_idx], label))
+                    .collect::<Result<Vec<_>>>()?;
+                let combined = self.combine(
+                    inputs,
+                    |segment| block.segments[segment].get(read_idx).len(),
+                    &input_info.segment_order,
+                )?;
+                if combined.is_empty() {
+                    output_tags.push(TagValue::Missing);
+                    continue;
+                }
+                let mut hits = Vec::new();
+                for (segment, ranges) in combined {
+                    let read = block.segments[segment].get(read_idx);
+                    for range in ranges {
+                        hits.push(Hit {
+                            sequence: read.seq()[range.clone()].into(),
+                            location: Some(HitRegion {
+                                start: range.start,
+                                len: range.len(),
+                                segment_index: SegmentIndex(segment),
+                            }),
+                        });
+                    }
+                }
+                output_tags.push(TagValue::Location(Hits::new_multiple(hits)));
+            }
+        }
+        block.tags.insert(self.out_label.clone(), output_tags);
+        Ok((block, true))
+    }
+}
+
+/// The regions of one tag value, None if the tag is missing
+fn regions_of(tag_value: &TagValue, label: &TagLabel) -> Result<Option<Regions>> {
+    match tag_value {
+        TagValue::Missing => Ok(None),
+        TagValue::Location(hits) => {
+            let mut regions = Regions::new();
+            for hit in &hits.0 {
+                let Some(location) = hit.location.as_ref() else {
+                    bail!(
+                        "CombineLocations: tag '{label}' has no location information (any more). \
+                        Combine location tags before steps that invalidate their locations."
+                    );
+                };
+                regions
+                    .entry(location.segment_index.get_index())
+                    .or_default()
+                    .push(location.start..location.start + location.len);
+            }
+            Ok(Some(regions))
+        }
+        _ => unreachable!("CombineLocations only accepts location tags"),
+    }
+}
+
+/// Sort `ranges` and join those at most `distance` apart (0: overlapping or adjacent)
+fn merge_within(mut ranges: Vec<Range<usize>>, distance: usize) -> Vec<Range<usize>> {
+    ranges.retain(|range| !range.is_empty());
+    ranges.sort_by_key(|range| range.start);
+    let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end + distance => {
+                last.end = last.end.max(range.end);
+            }
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+fn union(inputs: impl IntoIterator<Item = Regions>) -> Regions {
+    let mut result = Regions::new();
+    for regions in inputs {
+        for (segment, ranges) in regions {
+            result.entry(segment).or_default().extend(ranges);
+        }
+    }
+    for ranges in result.values_mut() {
+        *ranges = merge_within(std::mem::take(ranges), 0);
+    }
+    result
+}
+
+/// Both inputs must be merged (sorted, non-overlapping)
+fn intersect(a: &[Range<usize>], b: &[Range<usize>]) -> Vec<Range<usize>> {
+    let mut result = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        let start = a[i].start.max(b[j].start);
+        let end = a[i].end.min(b[j].end);
+        if start < end {
+            result.push(start..end);
+        }
+        if a[i].end < b[j].end {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    result
+}
+
+/// Both inputs must be merged (sorted, non-overlapping)
+fn subtract(a: &[Range<usize>], b: &[Range<usize>]) -> Vec<Range<usize>> {
+    let mut result = Vec::new();
+    let mut j = 0;
+    for range in a {
+        while j < b.len() && b[j].end <= range.start {
+            j += 1;
+        }
+        let mut start = range.start;
+        let mut k = j;
+        while k < b.len() && b[k].start < range.end {
+            if b[k].start > start {
+                result.push(start..b[k].start);
+            }
+            start = start.max(b[k].end);
+            k += 1;
+        }
+        if start < range.end {
+            result.push(start..range.end);
+        }
+    }
+    result
+}
+
+/// The input must be merged (sorted, non-overlapping)
+fn complement(ranges: &[Range<usize>], read_len: usize) -> Vec<Range<usize>> {
+    let mut result = Vec::new();
+    let mut start = 0;
+    for range in ranges {
+        if range.start > start {
+            result.push(start..range.start.min(read_len));
+        }
+        start = start.max(range.end);
+    }
+    if start < read_len {
+        result.push(start..read_len);
+    }
+    result
+}
+
+fn extend(
+    ranges: &[Range<usize>],
+    left: usize,
+    right: usize,
+    read_len: usize,
+) -> Vec<Range<usize>> {
+    merge_within(
+        ranges
+            .iter()
+            .map(|range| range.start.saturating_sub(left)..(range.end + right).min(read_len))
+            .collect(),
+        0,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_within() {
+        assert_eq!(merge_within(vec![5..8, 0..2, 1..3], 0), vec![0..3, 5..8]);
+        assert_eq!(merge_within(vec![0..2, 2..4], 0), vec![0..4]);
+        assert_eq!(merge_within(vec![0..2, 4..6, 9..10], 2), vec![0..6, 9..10]);
+        assert_eq!(merge_within(vec![3..3, 0..1], 5), vec![0..1]);
+    }
+
+    #[test]
+    fn test_intersect() {
+        assert_eq!(
+            intersect(&[0..5, 8..12], &[3..9, 11..20]),
+            vec![3..5, 8..9, 11..12]
+        );
+        assert_eq!(intersect(&[0..5], &[5..9]), Vec::<Range<usize>>::new());
+        assert_eq!(intersect(&[0..5], &[]), Vec::<Range<usize>>::new());
+    }
+
+    #[test]
+    fn test_subtract() {
+        assert_eq!(subtract(&[0..10], &[2..4, 6..7]), vec![0..2, 4..6, 7..10]);
+        assert_eq!(subtract(&[0..3, 5..8], &[2..6]), vec![0..2, 6..8]);
+        assert_eq!(subtract(&[0..3], &[0..5]), Vec::<Range<usize>>::new());
+        assert_eq!(subtract(&[0..3], &[]), vec![0..3]);
+    }
+
+    #[test]
+    fn test_complement_and_extend() {
+        assert_eq!(complement(&[2..4, 6..10], 10), vec![0..2, 4..6]);
+        assert_eq!(complement(&[], 5), vec![0..5]);
+        assert_eq!(extend(&[2..4, 8..9], 2, 1, 10), vec![0..5, 6..10]);
+    }
+}
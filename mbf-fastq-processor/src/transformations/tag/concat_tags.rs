@@ -50,6 +50,8 @@ pub struct ConcatTags {
     /// - `merge_present`: Skip missing tags and merge only the present ones
     /// - `set_missing`: Set the output tag to missing if any input tag is missing
     on_missing: OnMissing,
+    #[tpd(default)]
+    if_tag: Option<ConditionalTagLabel>,
 }
 
 impl VerifyIn<PartialConfig> for PartialConcatTags {
@@ -158,10 +160,11 @@ impl TagUser for PartialTaggedVariant<PartialConcatTags> {
             } else {
                 TagValueType::String
             };
-            let used_tags: Vec<_> = tv_in_labels
+            let mut used_tags: Vec<_> = tv_in_labels
                 .iter_mut()
                 .map(|x| x.to_used_tag(&[TagValueType::Location, TagValueType::String]))
                 .collect();
+            used_tags.push(inner.if_tag.to_used_tag(&[][..]));
 
             TagUsageInfo {
                 used_tags,
@@ -174,6 +177,14 @@ impl TagUser for PartialTaggedVariant<PartialConcatTags> {
 }
 
 impl Step for ConcatTags {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
     #[allow(clippy::too_many_lines)]
     fn apply(
         &self,
@@ -25,6 +25,10 @@ impl TagUser for PartialTaggedVariant<PartialForgetAllTags> {
 }
 
 impl Step for ForgetAllTags {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
@@ -31,6 +31,10 @@ impl TagUser for PartialTaggedVariant<PartialForgetTag> {
 }
 
 impl Step for ForgetTag {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
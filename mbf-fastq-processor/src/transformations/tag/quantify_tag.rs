@@ -1,14 +1,13 @@
 #![allow(clippy::unnecessary_wraps)] //eserde false positives
 use crate::{
     config::{CompressionFormat, deser::tpd_adapt_bstring},
+    spill::SpillingCounter,
     transformations::prelude::*,
 };
 
-use std::collections::BTreeMap;
-
 use super::super::tag::default_region_separator;
 
-type QuantifyTagCollector = Arc<Mutex<DemultiplexedData<BTreeMap<Vec<u8>, usize>>>>;
+type QuantifyTagCollector = Arc<Mutex<DemultiplexedData<SpillingCounter>>>;
 
 /// Write a histogram of tag values to a JSON file.
 #[derive(Clone, JsonSchema)]
@@ -21,6 +20,8 @@ pub struct QuantifyTag {
     #[schemars(with = "String")]
     #[tpd(with = "tpd_adapt_bstring")]
     region_separator: BString,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 
     #[tpd(skip, default)] // eserde compatibility https://github.com/mainmatter/eserde/issues/39
     #[schemars(skip)]
@@ -29,6 +30,11 @@ pub struct QuantifyTag {
     #[tpd(skip, default)] // eserde compatibility https://github.com/mainmatter/eserde/issues/39
     #[schemars(skip)]
     pub output_streams: Option<Arc<Mutex<DemultiplexedOutputFiles>>>,
+
+    /// share of options.memory_limit
+    #[tpd(skip, default)]
+    #[schemars(skip)]
+    pub memory_budget: Option<usize>,
 }
 
 impl VerifyIn<PartialConfig> for PartialQuantifyTag {
@@ -56,13 +62,22 @@ impl TagUser for PartialTaggedVariant<PartialQuantifyTag> {
             .as_mut()
             .expect("get_tag_usage should only be called after successful verification");
         TagUsageInfo {
-            used_tags: vec![inner.in_label.to_used_tag(&[TagValueType::Location][..])],
+            used_tags: vec![
+                inner
+                    .in_label
+                    .to_used_tag(&[TagValueType::Location, TagValueType::String][..]),
+                inner.if_tag.to_used_tag(&[][..]),
+            ],
             ..Default::default()
         }
     }
 }
 
 impl Step for QuantifyTag {
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
     fn transmits_premature_termination(&self) -> bool {
         false
     }
@@ -79,9 +94,12 @@ impl Step for QuantifyTag {
         demultiplex_info: &OptDemultiplex,
         allow_overwrite: bool,
     ) -> Result<Option<DemultiplexBarcodes>> {
+        let budget = self
+            .memory_budget
+            .map_or(usize::MAX, |budget| budget / demultiplex_info.len());
         let mut collector = DemultiplexedData::new();
         for tag in demultiplex_info.iter_tags() {
-            collector.insert(tag, BTreeMap::new());
+            collector.insert(tag, SpillingCounter::new(budget));
         }
         self.collector = Some(Arc::new(Mutex::new(collector)));
         self.output_streams = Some(Arc::new(Mutex::new(demultiplex_info.open_output_streams(
@@ -100,6 +118,18 @@ impl Step for QuantifyTag {
         Ok(None)
     }
 
+    fn supports_checkpoint(&self) -> bool {
+        false // the counts are only written at the end
+    }
+
+    fn uses_memory_budget(&self) -> bool {
+        true
+    }
+
+    fn set_memory_budget(&mut self, bytes: usize) {
+        self.memory_budget = Some(bytes);
+    }
+
     fn apply(
         &self,
         block: FastQBlocksCombined,
@@ -119,22 +149,20 @@ impl Step for QuantifyTag {
             .expect("Tag not found. Should have been caught in validation");
         if let Some(demultiplex_tags) = &block.output_tags {
             for (tag_val, demultiplex_tag) in hits.iter().zip(demultiplex_tags) {
-                if let Some(hit) = tag_val.as_sequence() {
-                    *collector
+                if let Some(value) = tag_val.as_str(&self.region_separator) {
+                    collector
                         .get_mut(demultiplex_tag)
                         .expect("value must exist in histogram_values")
-                        .entry(hit.joined_sequence(Some(&self.region_separator)))
-                        .or_insert(0) += 1;
+                        .add(value.into(), 1)?;
                 }
             }
         } else {
             for tag_val in hits {
-                if let Some(hit) = tag_val.as_sequence() {
-                    *collector
+                if let Some(value) = tag_val.as_str(&self.region_separator) {
+                    collector
                         .get_mut(&0)
                         .expect("value must exist in histogram_values")
-                        .entry(hit.joined_sequence(Some(&self.region_separator)))
-                        .or_insert(0) += 1;
+                        .add(value.into(), 1)?;
                 }
             }
         }
@@ -144,7 +172,7 @@ impl Step for QuantifyTag {
 
     fn finalize(&self, _demultiplex_info: &OptDemultiplex) -> Result<Option<FinalizeReportResult>> {
         use std::io::Write;
-        let collector = self
+        let mut collector = self
             .collector
             .as_ref()
             .expect("collector should have been set in init")
@@ -159,9 +187,15 @@ impl Step for QuantifyTag {
             .take();
         for (tag, stream) in output_streams {
             if let Some(mut stream) = stream {
-                let mut str_collector: Vec<(String, usize)> = collector
-                    .get(&tag)
-                    .expect("value must exist in histogram_values")
+                let counter = collector
+                    .get_mut(&tag)
+                    .expect("value must exist in histogram_values");
+                let Some(counts) = counter.in_memory() else {
+                    let counter = std::mem::replace(counter, SpillingCounter::new(0));
+                    write_spilled_counts(counter, &mut stream)?;
+                    continue;
+                };
+                let mut str_collector: Vec<(String, usize)> = counts
                     .iter()
                     .map(|(k, v)| (String::from_utf8_lossy(k).to_string(), *v))
                     .collect();
@@ -180,3 +214,46 @@ impl Step for QuantifyTag {
         Ok(None)
     }
 }
+
+/// Sort key for the spilled counts: count descending, then alphabetically (case insensitive),
+/// followed by the original key and its length
+fn count_sort_key(key: &[u8], count: u64) -> Vec<u8> {
+    let lowercase = String::from_utf8_lossy(key).to_lowercase();
+    let mut sort_key = Vec::with_capacity(8 + lowercase.len() + 1 + key.len() + 4);
+    sort_key.extend_from_slice(&(u64::MAX - count).to_be_bytes());
+    sort_key.extend_from_slice(lowercase.as_bytes());
+    sort_key.push(0);
+    sort_key.extend_from_slice(key);
+    sort_key.extend_from_slice(
+        &u32::try_from(key.len())
+            .expect("tag values are shorter than 4 GB")
+            .to_be_bytes(),
+    );
+    sort_key
+}
+
+/// Same json as the in-memory path, without holding all counts in memory:
+/// the key-sorted counts are sorted by `count_sort_key` in another set of spilled runs
+fn write_spilled_counts(counter: SpillingCounter, stream: &mut impl std::io::Write) -> Result<()> {
+    let budget = counter.budget();
+    let mut by_count = SpillingCounter::new(budget);
+    for record in counter.into_merged()? {
+        let (key, count) = record?;
+        by_count.add(count_sort_key(&key, count), 0)?;
+    }
+    stream.write_all(b"{")?;
+    let mut first = true;
+    for record in by_count.into_merged()? {
+        let (sort_key, _) = record?;
+        let (rest, key_len) = sort_key.split_at(sort_key.len() - 4);
+        let key_len = u32::from_be_bytes(key_len.try_into().expect("4 bytes")) as usize;
+        let key = &rest[rest.len() - key_len..];
+        let count = u64::MAX - u64::from_be_bytes(sort_key[..8].try_into().expect("8 bytes"));
+        stream.write_all(if first { b"\n  ".as_slice() } else { b",\n  " })?;
+        first = false;
+        serde_json::to_writer(&mut *stream, &String::from_utf8_lossy(key))?;
+        write!(stream, ": {count}")?;
+    }
+    stream.write_all(if first { b"}".as_slice() } else { b"\n}" })?;
+    Ok(())
+}
@@ -14,6 +14,8 @@ pub struct ReplaceTagWithLetter {
     #[tpd(with = "tpd_adapt_u8_from_byte_or_char")]
     /// Provide the replacement letter as a single character (e.g., 'N') or its ASCII numeric value (e.g., 78 for 'N').
     pub letter: u8,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 }
 
 impl VerifyIn<PartialConfig> for PartialReplaceTagWithLetter {}
@@ -29,13 +31,28 @@ impl TagUser for PartialTaggedVariant<PartialReplaceTagWithLetter> {
             .as_mut()
             .expect("get_tag_usage should only be called after successful verification");
         TagUsageInfo {
-            used_tags: vec![inner.in_label.to_used_tag(&[TagValueType::Location][..])],
+            used_tags: vec![
+                inner.in_label.to_used_tag(&[TagValueType::Location][..]),
+                inner.if_tag.to_used_tag(&[][..]),
+            ],
             ..Default::default()
         }
     }
 }
 
 impl Step for ReplaceTagWithLetter {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
+    fn modified_segments(&self) -> ModifiedSegments {
+        ModifiedSegments::TagRegions(self.in_label.clone())
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
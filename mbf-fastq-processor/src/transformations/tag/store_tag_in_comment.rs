@@ -50,6 +50,8 @@ pub struct StoreTagInComment {
     #[tpd(with = "tpd_adapt_bstring")]
     #[schemars(with = "String")]
     region_separator: BString,
+    #[tpd(default)]
+    pub if_tag: Option<ConditionalTagLabel>,
 }
 
 impl VerifyIn<PartialConfig> for PartialStoreTagInComment {
@@ -112,14 +114,17 @@ impl TagUser for PartialTaggedVariant<PartialStoreTagInComment> {
             .as_mut()
             .expect("get_tag_usage should only be called after successful verification");
         TagUsageInfo {
-            used_tags: vec![inner.in_label.to_used_tag(
-                &[
-                    TagValueType::Bool,
-                    TagValueType::String,
-                    TagValueType::Location,
-                    TagValueType::Numeric,
-                ][..],
-            )],
+            used_tags: vec![
+                inner.in_label.to_used_tag(
+                    &[
+                        TagValueType::Bool,
+                        TagValueType::String,
+                        TagValueType::Location,
+                        TagValueType::Numeric,
+                    ][..],
+                ),
+                inner.if_tag.to_used_tag(&[][..]),
+            ],
             ..Default::default()
         }
     }
@@ -172,6 +177,18 @@ impl TagUser for PartialTaggedVariant<PartialStoreTagInComment> {
 }
 
 impl Step for StoreTagInComment {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
+    fn modified_segments(&self) -> ModifiedSegments {
+        self.segment.into()
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
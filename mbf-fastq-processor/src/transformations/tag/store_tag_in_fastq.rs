@@ -55,6 +55,9 @@ pub struct StoreTagInFastQ {
     compression_level: Option<u8>,
 
     // Internal state for collecting reads during apply
+    #[tpd(default)]
+    if_tag: Option<ConditionalTagLabel>,
+
     #[tpd(skip, default)]
     #[schemars(skip)]
     output_streams: Option<Arc<Mutex<DemultiplexedOutputFiles>>>,
@@ -180,6 +183,7 @@ impl TagUser for PartialTaggedVariant<PartialStoreTagInFastQ> {
                 used_tags.push(tv_tag.to_used_tag(&[TagValueType::Location][..]));
             }
         }
+        used_tags.push(inner.if_tag.to_used_tag(&[][..]));
         TagUsageInfo {
             used_tags,
             ..Default::default()
@@ -188,6 +192,10 @@ impl TagUser for PartialTaggedVariant<PartialStoreTagInFastQ> {
 }
 
 impl Step for StoreTagInFastQ {
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
     fn needs_serial(&self) -> bool {
         true
     }
@@ -220,6 +228,10 @@ impl Step for StoreTagInFastQ {
     }
 
     #[allow(clippy::too_many_lines)]
+    fn supports_checkpoint(&self) -> bool {
+        false // writes its own output files
+    }
+
     fn apply(
         &self,
         block: FastQBlocksCombined,
@@ -14,6 +14,8 @@ pub struct StoreTagInSequence {
     in_label: TagLabel,
     #[tpd(default)]
     ignore_missing: bool,
+    #[tpd(default)]
+    if_tag: Option<ConditionalTagLabel>,
 }
 
 impl VerifyIn<PartialConfig> for PartialStoreTagInSequence {
@@ -47,13 +49,28 @@ impl TagUser for PartialTaggedVariant<PartialStoreTagInSequence> {
             .as_mut()
             .expect("get_tag_usage should only be called after successful verification");
         TagUsageInfo {
-            used_tags: vec![inner.in_label.to_used_tag(&[TagValueType::Location][..])],
+            used_tags: vec![
+                inner.in_label.to_used_tag(&[TagValueType::Location][..]),
+                inner.if_tag.to_used_tag(&[][..]),
+            ],
             ..Default::default()
         }
     }
 }
 
 impl Step for StoreTagInSequence {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
+    fn modified_segments(&self) -> ModifiedSegments {
+        ModifiedSegments::TagRegions(self.in_label.clone())
+    }
+
     #[allow(clippy::cast_precision_loss)]
     #[allow(clippy::cast_possible_truncation)]
     #[allow(clippy::cast_sign_loss)]
@@ -30,6 +30,8 @@ pub struct StoreTagLocationInComment {
 
     #[tpd(with = "tpd_adapt_u8_from_byte_or_char")]
     comment_insert_char: u8,
+    #[tpd(default)]
+    if_tag: Option<ConditionalTagLabel>,
 }
 
 impl VerifyIn<PartialConfig> for PartialStoreTagLocationInComment {
@@ -65,12 +67,27 @@ impl TagUser for PartialTaggedVariant<PartialStoreTagLocationInComment> {
             .expect("get_tag_usage should only be called after successful verification");
 
         TagUsageInfo {
-            used_tags: vec![inner.in_label.to_used_tag(&[TagValueType::Location][..])],
+            used_tags: vec![
+                inner.in_label.to_used_tag(&[TagValueType::Location][..]),
+                inner.if_tag.to_used_tag(&[][..]),
+            ],
             ..Default::default()
         }
     }
 }
 impl Step for StoreTagLocationInComment {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
+    fn modified_segments(&self) -> ModifiedSegments {
+        self.segment.into()
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
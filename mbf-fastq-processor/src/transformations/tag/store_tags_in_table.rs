@@ -25,6 +25,8 @@ pub struct StoreTagsInTable {
     #[schemars(with = "String")]
     #[tpd(with = "tpd_adapt_bstring")]
     region_separator: BString,
+    #[tpd(default)]
+    if_tag: Option<ConditionalTagLabel>,
 
     #[schemars(skip)]
     #[tpd(skip, default)]
@@ -90,6 +92,7 @@ impl TagUser for PartialTaggedVariant<PartialStoreTagsInTable> {
         }
         let inner = self
             .toml_value
+            .value
             .as_mut()
             .expect("get_tag_usage should only be called after successful verification");
 
@@ -99,6 +102,12 @@ impl TagUser for PartialTaggedVariant<PartialStoreTagsInTable> {
             inner.final_in_labels = Some(final_in_labels);
         }
 
+        // the condition tag is named (and type checked) separately
+        let condition_tag = inner
+            .if_tag
+            .as_ref()
+            .and_then(Option::as_ref)
+            .map(|cond| cond.tag.clone());
         let toml_source = Rc::new(RefCell::new((
             &mut self.toml_value.state,
             &mut self.toml_value.help,
@@ -108,6 +117,7 @@ impl TagUser for PartialTaggedVariant<PartialStoreTagsInTable> {
             // register them as 'used tags'
             used_tags: tags_available //we still need to name them, so they don't appear unused
                 .iter()
+                .filter(|(tag, _metadata)| condition_tag.as_ref() != Some(*tag))
                 .map(|(tag, _metadata)| {
                     Some(UsedTag {
                         name: tag.clone(),
@@ -116,6 +126,7 @@ impl TagUser for PartialTaggedVariant<PartialStoreTagsInTable> {
                         further_help: None,
                     })
                 })
+                .chain(std::iter::once(inner.if_tag.to_used_tag(&[][..])))
                 .collect(),
             ..Default::default()
         }
@@ -123,6 +134,10 @@ impl TagUser for PartialTaggedVariant<PartialStoreTagsInTable> {
 }
 
 impl Step for StoreTagsInTable {
+    fn condition(&self) -> Option<&ConditionalTagLabel> {
+        self.if_tag.as_ref()
+    }
+
     fn init(
         &mut self,
         _input_info: &InputInfo,
@@ -175,6 +190,10 @@ impl Step for StoreTagsInTable {
         false // since we want to dump all the reads even if later on there's a Head
     }
 
+    fn supports_checkpoint(&self) -> bool {
+        false // writes its own output files
+    }
+
     fn apply(
         &self,
         block: FastQBlocksCombined,
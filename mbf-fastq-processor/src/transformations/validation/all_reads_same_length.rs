@@ -81,6 +81,22 @@ impl TagUser for PartialTaggedVariant<PartialValidateAllReadsSameLength> {
 }
 
 impl Step for ValidateAllReadsSameLength {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn checkpoint_state(&self) -> Result<Option<serde_json::Value>> {
+        Ok(Some(serde_json::json!(self.expected_length.get())))
+    }
+
+    fn restore_checkpoint_state(&mut self, state: serde_json::Value) -> Result<()> {
+        let expected_length: Option<usize> = serde_json::from_value(state)?;
+        if let Some(expected_length) = expected_length {
+            self.expected_length = std::sync::OnceLock::from(expected_length);
+        }
+        Ok(())
+    }
+
     fn apply(
         &self,
         block: FastQBlocksCombined,
@@ -55,6 +55,22 @@ impl TagUser for PartialTaggedVariant<PartialValidateName> {
 }
 
 impl Step for ValidateName {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
+    fn checkpoint_state(&self) -> Result<Option<serde_json::Value>> {
+        Ok(Some(serde_json::json!(
+            self.processed_reads.load(Ordering::Relaxed)
+        )))
+    }
+
+    fn restore_checkpoint_state(&mut self, state: serde_json::Value) -> Result<()> {
+        self.processed_reads
+            .store(serde_json::from_value(state)?, Ordering::Relaxed);
+        Ok(())
+    }
+
     fn apply(
         &self,
         block: FastQBlocksCombined,
@@ -32,6 +32,10 @@ impl TagUser for PartialTaggedVariant<PartialValidateQuality> {
 }
 
 impl Step for ValidateQuality {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
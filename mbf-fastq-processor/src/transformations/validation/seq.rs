@@ -36,6 +36,10 @@ impl TagUser for PartialTaggedVariant<PartialValidateSeq> {
 }
 
 impl Step for ValidateSeq {
+    fn supports_checkpoint(&self) -> bool {
+        true
+    }
+
     fn apply(
         &self,
         mut block: FastQBlocksCombined,
@@ -344,6 +344,26 @@ fn test_cases_x_if_tag_x_cut_start_conditional() {
     );
 }
 
+#[test]
+fn test_cases_x_if_tag_x_extract_conditional() {
+    println!("Test case is in: test_cases/if_tag/extract_conditional");
+    run_test(
+        std::path::Path::new("../test_cases/if_tag/extract_conditional"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_if_tag_x_filter_and_report_conditional() {
+    println!("Test case is in: test_cases/if_tag/filter_and_report_conditional");
+    run_test(
+        std::path::Path::new("../test_cases/if_tag/filter_and_report_conditional"),
+        "input.toml",
+        1,
+    );
+}
+
 #[test]
 fn test_cases_x_if_tag_x_if_tag_inverted() {
     println!("Test case is in: test_cases/if_tag/if_tag_inverted");
@@ -882,6 +902,46 @@ fn test_cases_x_integration_x_misc_x_head_with_index() {
     );
 }
 
+#[test]
+fn test_cases_x_integration_x_vars_and_include_x_basic() {
+    println!("Test case is in: test_cases/integration/vars_and_include/basic");
+    run_test(
+        std::path::Path::new("../test_cases/integration/vars_and_include/basic"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_integration_x_vars_and_include_x_error_in_included_file() {
+    println!("Test case is in: test_cases/integration/vars_and_include/error_in_included_file");
+    run_test(
+        std::path::Path::new("../test_cases/integration/vars_and_include/error_in_included_file"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_integration_x_vars_and_include_x_error_unknown_variable() {
+    println!("Test case is in: test_cases/integration/vars_and_include/error_unknown_variable");
+    run_test(
+        std::path::Path::new("../test_cases/integration/vars_and_include/error_unknown_variable"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_integration_tests_x_calc_x_quantify_regions_memory_limit() {
+    println!("Test case is in: test_cases/integration_tests/calc/quantify_regions_memory_limit");
+    run_test(
+        std::path::Path::new("../test_cases/integration_tests/calc/quantify_regions_memory_limit"),
+        "input.toml",
+        1,
+    );
+}
+
 #[test]
 fn test_cases_x_integration_tests_x_calc_x_quantify_regions_multi() {
     println!("Test case is in: test_cases/integration_tests/calc/quantify_regions_multi");
@@ -942,6 +1002,66 @@ fn test_cases_x_output_x_bam_x_interleaved() {
     );
 }
 
+#[test]
+fn test_cases_x_output_x_branches_x_basic() {
+    println!("Test case is in: test_cases/output/branches/basic");
+    run_test(
+        std::path::Path::new("../test_cases/output/branches/basic"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_output_x_branches_x_common_steps_run_once() {
+    println!("Test case is in: test_cases/output/branches/common_steps_run_once");
+    run_test(
+        std::path::Path::new("../test_cases/output/branches/common_steps_run_once"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_output_x_branches_x_error_branch_line_numbers() {
+    println!("Test case is in: test_cases/output/branches/error_branch_line_numbers");
+    run_test(
+        std::path::Path::new("../test_cases/output/branches/error_branch_line_numbers"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_output_x_branches_x_error_top_level_output() {
+    println!("Test case is in: test_cases/output/branches/error_top_level_output");
+    run_test(
+        std::path::Path::new("../test_cases/output/branches/error_top_level_output"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_output_x_checkpoint_x_error_chunksize() {
+    println!("Test case is in: test_cases/output/checkpoint/error_chunksize");
+    run_test(
+        std::path::Path::new("../test_cases/output/checkpoint/error_chunksize"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_output_x_checkpoint_x_error_unsupported_step() {
+    println!("Test case is in: test_cases/output/checkpoint/error_unsupported_step");
+    run_test(
+        std::path::Path::new("../test_cases/output/checkpoint/error_unsupported_step"),
+        "input.toml",
+        1,
+    );
+}
+
 #[test]
 fn test_cases_x_output_x_chunked_x_bam() {
     println!("Test case is in: test_cases/output/chunked/bam");
@@ -1172,6 +1292,106 @@ fn test_cases_x_output_x_progress_x_followed_by_head() {
     );
 }
 
+#[test]
+fn test_cases_x_output_x_qc_thresholds_x_basic() {
+    println!("Test case is in: test_cases/output/qc_thresholds/basic");
+    run_test(
+        std::path::Path::new("../test_cases/output/qc_thresholds/basic"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_output_x_qc_thresholds_x_error_exit_code() {
+    println!("Test case is in: test_cases/output/qc_thresholds/error_exit_code");
+    run_test(
+        std::path::Path::new("../test_cases/output/qc_thresholds/error_exit_code"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_output_x_qc_thresholds_x_error_no_threshold() {
+    println!("Test case is in: test_cases/output/qc_thresholds/error_no_threshold");
+    run_test(
+        std::path::Path::new("../test_cases/output/qc_thresholds/error_no_threshold"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_output_x_qc_thresholds_x_error_unknown_report() {
+    println!("Test case is in: test_cases/output/qc_thresholds/error_unknown_report");
+    run_test(
+        std::path::Path::new("../test_cases/output/qc_thresholds/error_unknown_report"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_output_x_qc_thresholds_x_fail_exit_code() {
+    println!("Test case is in: test_cases/output/qc_thresholds/fail_exit_code");
+    run_test(
+        std::path::Path::new("../test_cases/output/qc_thresholds/fail_exit_code"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_output_x_rejected_output_x_basic() {
+    println!("Test case is in: test_cases/output/rejected_output/basic");
+    run_test(
+        std::path::Path::new("../test_cases/output/rejected_output/basic"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_output_x_rejected_output_x_error_stdout() {
+    println!("Test case is in: test_cases/output/rejected_output/error_stdout");
+    run_test(
+        std::path::Path::new("../test_cases/output/rejected_output/error_stdout"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_output_x_report_funnel_x_basic() {
+    println!("Test case is in: test_cases/output/report_funnel/basic");
+    run_test(
+        std::path::Path::new("../test_cases/output/report_funnel/basic"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_output_x_report_funnel_x_demultiplex() {
+    println!("Test case is in: test_cases/output/report_funnel/demultiplex");
+    run_test(
+        std::path::Path::new("../test_cases/output/report_funnel/demultiplex"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_output_x_report_funnel_x_error_no_report() {
+    println!("Test case is in: test_cases/output/report_funnel/error_no_report");
+    run_test(
+        std::path::Path::new("../test_cases/output/report_funnel/error_no_report"),
+        "input.toml",
+        1,
+    );
+}
+
 #[test]
 fn test_cases_x_output_x_stdout_output_interleaved() {
     println!("Test case is in: test_cases/output/stdout_output_interleaved");
@@ -1286,6 +1506,102 @@ fn test_cases_x_single_step_x_calc_x_kmer_x_phix() {
     );
 }
 
+#[test]
+fn test_cases_x_single_step_x_combine_locations_x_complement() {
+    println!("Test case is in: test_cases/single_step/combine_locations/complement");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/combine_locations/complement"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_combine_locations_x_error_intersect_single_label() {
+    println!(
+        "Test case is in: test_cases/single_step/combine_locations/error_intersect_single_label"
+    );
+    run_test(
+        std::path::Path::new(
+            "../test_cases/single_step/combine_locations/error_intersect_single_label",
+        ),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_combine_locations_x_error_missing_distance() {
+    println!("Test case is in: test_cases/single_step/combine_locations/error_missing_distance");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/combine_locations/error_missing_distance"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_combine_locations_x_error_parameter_not_used() {
+    println!("Test case is in: test_cases/single_step/combine_locations/error_parameter_not_used");
+    run_test(
+        std::path::Path::new(
+            "../test_cases/single_step/combine_locations/error_parameter_not_used",
+        ),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_combine_locations_x_error_segments_differ() {
+    println!("Test case is in: test_cases/single_step/combine_locations/error_segments_differ");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/combine_locations/error_segments_differ"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_combine_locations_x_extend_and_replace() {
+    println!("Test case is in: test_cases/single_step/combine_locations/extend_and_replace");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/combine_locations/extend_and_replace"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_combine_locations_x_intersect_and_subtract() {
+    println!("Test case is in: test_cases/single_step/combine_locations/intersect_and_subtract");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/combine_locations/intersect_and_subtract"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_combine_locations_x_merge_within() {
+    println!("Test case is in: test_cases/single_step/combine_locations/merge_within");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/combine_locations/merge_within"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_combine_locations_x_union() {
+    println!("Test case is in: test_cases/single_step/combine_locations/union");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/combine_locations/union"),
+        "input.toml",
+        1,
+    );
+}
+
 #[test]
 fn test_cases_x_single_step_x_compression_x_zstd_input() {
     println!("Test case is in: test_cases/single_step/compression/zstd_input");
@@ -1440,6 +1756,74 @@ fn test_cases_x_single_step_x_concat_tags_x_with_missing_tag_set_missing() {
     );
 }
 
+#[test]
+fn test_cases_x_single_step_x_convert_x_bin_numeric_tag_x_breakpoints() {
+    println!("Test case is in: test_cases/single_step/convert/bin_numeric_tag/breakpoints");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/convert/bin_numeric_tag/breakpoints"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_convert_x_bin_numeric_tag_x_demultiplex() {
+    println!("Test case is in: test_cases/single_step/convert/bin_numeric_tag/demultiplex");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/convert/bin_numeric_tag/demultiplex"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_convert_x_bin_numeric_tag_x_error_breakpoints_and_quantiles() {
+    println!(
+        "Test case is in: test_cases/single_step/convert/bin_numeric_tag/error_breakpoints_and_quantiles"
+    );
+    run_test(
+        std::path::Path::new(
+            "../test_cases/single_step/convert/bin_numeric_tag/error_breakpoints_and_quantiles",
+        ),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_convert_x_bin_numeric_tag_x_error_label_count() {
+    println!("Test case is in: test_cases/single_step/convert/bin_numeric_tag/error_label_count");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/convert/bin_numeric_tag/error_label_count"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_convert_x_bin_numeric_tag_x_error_no_borders() {
+    println!("Test case is in: test_cases/single_step/convert/bin_numeric_tag/error_no_borders");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/convert/bin_numeric_tag/error_no_borders"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_convert_x_bin_numeric_tag_x_quantiles_with_quantify() {
+    println!(
+        "Test case is in: test_cases/single_step/convert/bin_numeric_tag/quantiles_with_quantify"
+    );
+    run_test(
+        std::path::Path::new(
+            "../test_cases/single_step/convert/bin_numeric_tag/quantiles_with_quantify",
+        ),
+        "input.toml",
+        1,
+    );
+}
+
 #[test]
 fn test_cases_x_single_step_x_convert_x_convert_to_rate_x_all_segments() {
     println!("Test case is in: test_cases/single_step/convert/convert_to_rate/all_segments");
@@ -1450,6 +1834,36 @@ fn test_cases_x_single_step_x_convert_x_convert_to_rate_x_all_segments() {
     );
 }
 
+#[test]
+fn test_cases_x_single_step_x_convert_x_map_tag_x_basic() {
+    println!("Test case is in: test_cases/single_step/convert/map_tag/basic");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/convert/map_tag/basic"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_convert_x_map_tag_x_error_unknown_column() {
+    println!("Test case is in: test_cases/single_step/convert/map_tag/error_unknown_column");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/convert/map_tag/error_unknown_column"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_convert_x_map_tag_x_numeric_csv() {
+    println!("Test case is in: test_cases/single_step/convert/map_tag/numeric_csv");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/convert/map_tag/numeric_csv"),
+        "input.toml",
+        1,
+    );
+}
+
 #[test]
 fn test_cases_x_single_step_x_convert_x_regions_to_length_x_basic() {
     println!("Test case is in: test_cases/single_step/convert/regions_to_length/basic");
@@ -1588,6 +2002,16 @@ fn test_cases_x_single_step_x_duplicates_x_dedup_exact() {
     );
 }
 
+#[test]
+fn test_cases_x_single_step_x_duplicates_x_dedup_exact_memory_limit() {
+    println!("Test case is in: test_cases/single_step/duplicates/dedup_exact_memory_limit");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/duplicates/dedup_exact_memory_limit"),
+        "input.toml",
+        1,
+    );
+}
+
 #[test]
 fn test_cases_x_single_step_x_duplicates_x_dedup_keep_duplicates() {
     println!("Test case is in: test_cases/single_step/duplicates/dedup_keep_duplicates");
@@ -2058,6 +2482,36 @@ fn test_cases_x_single_step_x_edits_x_reverse_complement_conditional() {
     );
 }
 
+#[test]
+fn test_cases_x_single_step_x_edits_x_split_at_tag_x_basic() {
+    println!("Test case is in: test_cases/single_step/edits/split_at_tag/basic");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/edits/split_at_tag/basic"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_edits_x_split_at_tag_x_error_two_segments() {
+    println!("Test case is in: test_cases/single_step/edits/split_at_tag/error_two_segments");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/edits/split_at_tag/error_two_segments"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_edits_x_split_at_tag_x_tags_and_min_length() {
+    println!("Test case is in: test_cases/single_step/edits/split_at_tag/tags_and_min_length");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/edits/split_at_tag/tags_and_min_length"),
+        "input.toml",
+        1,
+    );
+}
+
 #[test]
 fn test_cases_x_single_step_x_edits_x_swap_x_swap_auto_detect_two_segments() {
     println!("Test case is in: test_cases/single_step/edits/swap/swap_auto_detect_two_segments");
@@ -2544,14 +2998,28 @@ fn test_cases_x_single_step_x_error_handling_x_error_messages_x_two_mistakes_ese
 }
 
 #[test]
-fn test_cases_x_single_step_x_error_handling_x_error_messages_x_two_mistakes_post_deserialization()
-{
+fn test_cases_x_single_step_x_error_handling_x_error_messages_x_two_mistakes_post_deserialization()
+{
+    println!(
+        "Test case is in: test_cases/single_step/error_handling/error_messages/two_mistakes_post_deserialization"
+    );
+    run_test(
+        std::path::Path::new(
+            "../test_cases/single_step/error_handling/error_messages/two_mistakes_post_deserialization",
+        ),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_error_handling_x_extraction_x_eval_expr_x_len_from_not_a_len_tag() {
     println!(
-        "Test case is in: test_cases/single_step/error_handling/error_messages/two_mistakes_post_deserialization"
+        "Test case is in: test_cases/single_step/error_handling/extraction/eval_expr/len_from_not_a_len_tag"
     );
     run_test(
         std::path::Path::new(
-            "../test_cases/single_step/error_handling/error_messages/two_mistakes_post_deserialization",
+            "../test_cases/single_step/error_handling/extraction/eval_expr/len_from_not_a_len_tag",
         ),
         "input.toml",
         1,
@@ -2559,13 +3027,13 @@ fn test_cases_x_single_step_x_error_handling_x_error_messages_x_two_mistakes_pos
 }
 
 #[test]
-fn test_cases_x_single_step_x_error_handling_x_extraction_x_eval_expr_x_len_from_not_a_len_tag() {
+fn test_cases_x_single_step_x_error_handling_x_extraction_x_eval_expr_x_string_unknown_function() {
     println!(
-        "Test case is in: test_cases/single_step/error_handling/extraction/eval_expr/len_from_not_a_len_tag"
+        "Test case is in: test_cases/single_step/error_handling/extraction/eval_expr/string_unknown_function"
     );
     run_test(
         std::path::Path::new(
-            "../test_cases/single_step/error_handling/extraction/eval_expr/len_from_not_a_len_tag",
+            "../test_cases/single_step/error_handling/extraction/eval_expr/string_unknown_function",
         ),
         "input.toml",
         1,
@@ -4684,6 +5152,16 @@ fn test_cases_x_single_step_x_eval_x_eval_expr_x_eval_expression_complex() {
     );
 }
 
+#[test]
+fn test_cases_x_single_step_x_eval_x_eval_expr_x_eval_expression_string() {
+    println!("Test case is in: test_cases/single_step/eval/eval_expr/eval_expression_string");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/eval/eval_expr/eval_expression_string"),
+        "input.toml",
+        1,
+    );
+}
+
 #[test]
 fn test_cases_x_single_step_x_eval_x_location() {
     println!("Test case is in: test_cases/single_step/eval/location");
@@ -4744,6 +5222,56 @@ fn test_cases_x_single_step_x_eval_x_threshold() {
     );
 }
 
+#[test]
+fn test_cases_x_single_step_x_external_command_x_error_order_changed() {
+    println!("Test case is in: test_cases/single_step/external_command/error_order_changed");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/external_command/error_order_changed"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_external_command_x_error_program_exits() {
+    println!("Test case is in: test_cases/single_step/external_command/error_program_exits");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/external_command/error_program_exits"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_external_command_x_error_tags_with_fastq() {
+    println!("Test case is in: test_cases/single_step/external_command/error_tags_with_fastq");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/external_command/error_tags_with_fastq"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_external_command_x_fastq_lowercase() {
+    println!("Test case is in: test_cases/single_step/external_command/fastq_lowercase");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/external_command/fastq_lowercase"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_external_command_x_tsv_out_tag() {
+    println!("Test case is in: test_cases/single_step/external_command/tsv_out_tag");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/external_command/tsv_out_tag"),
+        "input.toml",
+        1,
+    );
+}
+
 #[test]
 fn test_cases_x_single_step_x_extraction_x_polytail_x_error_max_mismatch_rate_too_high() {
     println!(
@@ -5166,6 +5694,44 @@ fn test_cases_x_single_step_x_extraction_x_extract_highlight_x_regex() {
     );
 }
 
+#[test]
+fn test_cases_x_single_step_x_extraction_x_extract_iupac_x_error_find_all_anchored() {
+    println!(
+        "Test case is in: test_cases/single_step/extraction/extract_iupac/error_find_all_anchored"
+    );
+    run_test(
+        std::path::Path::new(
+            "../test_cases/single_step/extraction/extract_iupac/error_find_all_anchored",
+        ),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_extraction_x_extract_iupac_x_find_all() {
+    println!("Test case is in: test_cases/single_step/extraction/extract_iupac/find_all");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/extraction/extract_iupac/find_all"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_extraction_x_extract_iupac_x_find_all_multiple_patterns() {
+    println!(
+        "Test case is in: test_cases/single_step/extraction/extract_iupac/find_all_multiple_patterns"
+    );
+    run_test(
+        std::path::Path::new(
+            "../test_cases/single_step/extraction/extract_iupac/find_all_multiple_patterns",
+        ),
+        "input.toml",
+        1,
+    );
+}
+
 #[test]
 fn test_cases_x_single_step_x_extraction_x_extract_iupac_x_multiple() {
     println!("Test case is in: test_cases/single_step/extraction/extract_iupac/multiple");
@@ -5296,6 +5862,20 @@ fn test_cases_x_single_step_x_extraction_x_extract_iupac_x_with_indel_empty_sear
     );
 }
 
+#[test]
+fn test_cases_x_single_step_x_extraction_x_extract_iupac_x_with_indel_find_all() {
+    println!(
+        "Test case is in: test_cases/single_step/extraction/extract_iupac/with_indel_find_all"
+    );
+    run_test(
+        std::path::Path::new(
+            "../test_cases/single_step/extraction/extract_iupac/with_indel_find_all",
+        ),
+        "input.toml",
+        1,
+    );
+}
+
 #[test]
 fn test_cases_x_single_step_x_extraction_x_extract_label_must_not_be_empty() {
     println!("Test case is in: test_cases/single_step/extraction/extract_label_must_not_be_empty");
@@ -5494,6 +6074,20 @@ fn test_cases_x_single_step_x_extraction_x_extract_regex_x_basic() {
     );
 }
 
+#[test]
+fn test_cases_x_single_step_x_extraction_x_extract_regex_x_error_find_all_name() {
+    println!(
+        "Test case is in: test_cases/single_step/extraction/extract_regex/error_find_all_name"
+    );
+    run_test(
+        std::path::Path::new(
+            "../test_cases/single_step/extraction/extract_regex/error_find_all_name",
+        ),
+        "input.toml",
+        1,
+    );
+}
+
 #[test]
 fn test_cases_x_single_step_x_extraction_x_extract_regex_x_extract_regex_from_name() {
     println!(
@@ -5551,6 +6145,16 @@ fn test_cases_x_single_step_x_extraction_x_extract_regex_x_extract_regex_no_repl
     );
 }
 
+#[test]
+fn test_cases_x_single_step_x_extraction_x_extract_regex_x_find_all() {
+    println!("Test case is in: test_cases/single_step/extraction/extract_regex/find_all");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/extraction/extract_regex/find_all"),
+        "input.toml",
+        1,
+    );
+}
+
 #[test]
 fn test_cases_x_single_step_x_extraction_x_extract_regex_x_regex_len_table() {
     println!("Test case is in: test_cases/single_step/extraction/extract_regex/regex_len_table");
@@ -6046,6 +6650,54 @@ fn test_cases_x_single_step_x_extraction_x_store_tags_in_table_x_in_tsv_validate
     );
 }
 
+#[test]
+fn test_cases_x_single_step_x_extraction_x_tag_from_table_x_basic() {
+    println!("Test case is in: test_cases/single_step/extraction/tag_from_table/basic");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/extraction/tag_from_table/basic"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_extraction_x_tag_from_table_x_error_reads_unsorted() {
+    println!(
+        "Test case is in: test_cases/single_step/extraction/tag_from_table/error_reads_unsorted"
+    );
+    run_test(
+        std::path::Path::new(
+            "../test_cases/single_step/extraction/tag_from_table/error_reads_unsorted",
+        ),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_extraction_x_tag_from_table_x_read_name_end_character() {
+    println!(
+        "Test case is in: test_cases/single_step/extraction/tag_from_table/read_name_end_character"
+    );
+    run_test(
+        std::path::Path::new(
+            "../test_cases/single_step/extraction/tag_from_table/read_name_end_character",
+        ),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_extraction_x_tag_from_table_x_sorted_merge() {
+    println!("Test case is in: test_cases/single_step/extraction/tag_from_table/sorted_merge");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/extraction/tag_from_table/sorted_merge"),
+        "input.toml",
+        1,
+    );
+}
+
 #[test]
 fn test_cases_x_single_step_x_extraction_x_umi_extract() {
     println!("Test case is in: test_cases/single_step/extraction/umi_extract");
@@ -6691,6 +7343,16 @@ fn test_cases_x_single_step_x_filter_x_too_many_n_x_segments_vs_all() {
     );
 }
 
+#[test]
+fn test_cases_x_single_step_x_hamming_x_barcodes_file() {
+    println!("Test case is in: test_cases/single_step/hamming/barcodes_file");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/hamming/barcodes_file"),
+        "input.toml",
+        1,
+    );
+}
+
 #[test]
 fn test_cases_x_single_step_x_hamming_x_basic_correction() {
     println!("Test case is in: test_cases/single_step/hamming/basic_correction");
@@ -6721,6 +7383,26 @@ fn test_cases_x_single_step_x_hamming_x_basic_correction_keep() {
     );
 }
 
+#[test]
+fn test_cases_x_single_step_x_hamming_x_error_barcodes_and_file() {
+    println!("Test case is in: test_cases/single_step/hamming/error_barcodes_and_file");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/hamming/error_barcodes_and_file"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_hamming_x_posterior() {
+    println!("Test case is in: test_cases/single_step/hamming/posterior");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/hamming/posterior"),
+        "input.toml",
+        1,
+    );
+}
+
 #[test]
 fn test_cases_x_single_step_x_hamming_x_require_larger_0() {
     println!("Test case is in: test_cases/single_step/hamming/require_larger_0");
@@ -7029,6 +7711,54 @@ fn test_cases_x_single_step_x_performance_x_duplicate_input_allocation() {
     );
 }
 
+#[test]
+fn test_cases_x_single_step_x_rename_x_from_template_x_basic() {
+    println!("Test case is in: test_cases/single_step/rename/from_template/basic");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/rename/from_template/basic"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_rename_x_from_template_x_error_unclosed_placeholder() {
+    println!(
+        "Test case is in: test_cases/single_step/rename/from_template/error_unclosed_placeholder"
+    );
+    run_test(
+        std::path::Path::new(
+            "../test_cases/single_step/rename/from_template/error_unclosed_placeholder",
+        ),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_rename_x_from_template_x_error_unknown_tag() {
+    println!("Test case is in: test_cases/single_step/rename/from_template/error_unknown_tag");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/rename/from_template/error_unknown_tag"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_rename_x_from_template_x_missing_tag_and_name_parts() {
+    println!(
+        "Test case is in: test_cases/single_step/rename/from_template/missing_tag_and_name_parts"
+    );
+    run_test(
+        std::path::Path::new(
+            "../test_cases/single_step/rename/from_template/missing_tag_and_name_parts",
+        ),
+        "input.toml",
+        1,
+    );
+}
+
 #[test]
 fn test_cases_x_single_step_x_rename_x_rename_read_index_placeholder() {
     println!("Test case is in: test_cases/single_step/rename/rename_read_index_placeholder");
@@ -7139,6 +7869,46 @@ fn test_cases_x_single_step_x_reports_x_report_x_basic() {
     );
 }
 
+#[test]
+fn test_cases_x_single_step_x_reports_x_report_x_compare_x_basic() {
+    println!("Test case is in: test_cases/single_step/reports/report/compare/basic");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/reports/report/compare/basic"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_reports_x_report_x_compare_x_error_not_two() {
+    println!("Test case is in: test_cases/single_step/reports/report/compare/error_not_two");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/reports/report/compare/error_not_two"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_reports_x_report_x_compare_x_error_same_report() {
+    println!("Test case is in: test_cases/single_step/reports/report/compare/error_same_report");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/reports/report/compare/error_same_report"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_reports_x_report_x_compare_x_error_unknown_name() {
+    println!("Test case is in: test_cases/single_step/reports/report/compare/error_unknown_name");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/reports/report/compare/error_unknown_name"),
+        "input.toml",
+        1,
+    );
+}
+
 #[test]
 fn test_cases_x_single_step_x_reports_x_report_x_depduplication_per_fragment() {
     println!("Test case is in: test_cases/single_step/reports/report/depduplication_per_fragment");
@@ -7181,6 +7951,30 @@ fn test_cases_x_single_step_x_reports_x_report_x_fasta_more_than_one_block() {
     );
 }
 
+#[test]
+fn test_cases_x_single_step_x_reports_x_report_x_kmer_enrichment_x_basic() {
+    println!("Test case is in: test_cases/single_step/reports/report/kmer_enrichment/basic");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/reports/report/kmer_enrichment/basic"),
+        "input.toml",
+        1,
+    );
+}
+
+#[test]
+fn test_cases_x_single_step_x_reports_x_report_x_kmer_enrichment_x_error_k_too_small() {
+    println!(
+        "Test case is in: test_cases/single_step/reports/report/kmer_enrichment/error_k_too_small"
+    );
+    run_test(
+        std::path::Path::new(
+            "../test_cases/single_step/reports/report/kmer_enrichment/error_k_too_small",
+        ),
+        "input.toml",
+        1,
+    );
+}
+
 #[test]
 fn test_cases_x_single_step_x_reports_x_report_x_no_output() {
     println!("Test case is in: test_cases/single_step/reports/report/no_output");
@@ -7245,6 +8039,16 @@ fn test_cases_x_single_step_x_sampling_x_reservoir_x_error_n_too_low() {
     );
 }
 
+#[test]
+fn test_cases_x_single_step_x_sampling_x_reservoir_x_memory_limit_spill() {
+    println!("Test case is in: test_cases/single_step/sampling/reservoir/memory_limit_spill");
+    run_test(
+        std::path::Path::new("../test_cases/single_step/sampling/reservoir/memory_limit_spill"),
+        "input.toml",
+        1,
+    );
+}
+
 #[test]
 fn test_cases_x_single_step_x_sampling_x_reservoir_x_sample() {
     println!("Test case is in: test_cases/single_step/sampling/reservoir/sample");
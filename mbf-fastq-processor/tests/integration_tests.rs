@@ -861,6 +861,116 @@ fn test_validate_command_no_arguments() {
     assert!(!cmd.status.success(), "Exit code should be non-zero");
 }
 
+#[test]
+fn test_explain_command() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let temp_path = temp_dir.path();
+
+    let config_path = temp_path.join("config.toml");
+    let mut config = fs::File::create(&config_path).unwrap();
+    writeln!(
+        config,
+        r"[input]
+read1 = 'nonexistent.fq'
+
+[[step]]
+action = 'ExtractRegion'
+source = 'read1'
+start = 0
+length = 8
+out_label = 'umi'
+
+[[step]]
+action = 'CalcLength'
+segment = 'read1'
+out_label = 'len'
+
+[[step]]
+action = 'StoreTagInComment'
+in_label = 'umi'
+segment = 'read1'
+
+[[step]]
+action = 'ForgetTag'
+in_label = 'len'
+
+[[step]]
+action = 'CutStart'
+segment = 'read1'
+n = 8
+
+[output]
+prefix = 'output'
+"
+    )
+    .unwrap();
+
+    let cmd = std::process::Command::new(get_bin_path())
+        .arg("explain")
+        .arg(&config_path)
+        .output()
+        .unwrap();
+    let stdout = std::str::from_utf8(&cmd.stdout).unwrap().to_string();
+    let stderr = std::str::from_utf8(&cmd.stderr).unwrap().to_string();
+    assert!(cmd.status.success(), "Exit code should be 0: {stderr}");
+    // columns are padded to align, compare with single spaces
+    let rows: Vec<String> = stdout
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect();
+    assert_eq!(
+        rows,
+        vec![
+            "Step Action Declares Uses Removes Modifies Serial",
+            "1 ExtractRegions umi (Location) - - - no",
+            "2 CalcLength len (Numeric) - - - no",
+            "3 StoreTagInComment - umi - read1 no",
+            "4 ForgetTag - - len - no",
+            "5 CutStart - - - read1 no",
+        ]
+    );
+    assert!(
+        stderr.contains("Tag 'len' (declared in step 2) is never used, only forgotten."),
+        "Expected unused tag warning, got: {stderr}"
+    );
+
+    let cmd = std::process::Command::new(get_bin_path())
+        .arg("explain")
+        .arg(&config_path)
+        .arg("--format")
+        .arg("dot")
+        .output()
+        .unwrap();
+    let stdout = std::str::from_utf8(&cmd.stdout).unwrap().to_string();
+    assert!(cmd.status.success());
+    assert!(stdout.starts_with("digraph pipeline {"), "{stdout}");
+    assert!(stdout.contains("s0 -> s1 [weight=10];"), "{stdout}");
+    assert!(
+        stdout.contains("s0 -> s2 [style=dashed, color=blue, label=\"umi\"];"),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains("s1 -> s3 [style=dotted, color=blue, label=\"len (removed)\"];"),
+        "{stdout}"
+    );
+
+    let cmd = std::process::Command::new(get_bin_path())
+        .arg("explain")
+        .arg(&config_path)
+        .arg("--format")
+        .arg("mermaid")
+        .output()
+        .unwrap();
+    let stdout = std::str::from_utf8(&cmd.stdout).unwrap().to_string();
+    assert!(cmd.status.success());
+    assert!(stdout.starts_with("flowchart TD\n"), "{stdout}");
+    assert!(
+        stdout.contains("s2[\"3: StoreTagInComment<br/>modifies read1\"]"),
+        "{stdout}"
+    );
+    assert!(stdout.contains("s0 -. \"umi\" .-> s2"), "{stdout}");
+}
+
 #[test]
 fn test_verify_command_matching_outputs() {
     // Create temp directory
@@ -2465,3 +2575,473 @@ fn test_output_already_exists() {
     let written_output = std::fs::read_to_string(temp_path.join("output_read1.fq")).unwrap();
     assert!(!written_output.contains("read1_already"));
 }
+
+#[test]
+fn test_report_merge_command() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let temp_path = temp_dir.path();
+
+    for (sample, read_count) in [("sample_a", 2), ("sample_b", 3)] {
+        let sample_dir = temp_path.join(sample);
+        fs::create_dir(&sample_dir).unwrap();
+        let mut fq = fs::File::create(sample_dir.join("input.fq")).unwrap();
+        for ii in 0..read_count {
+            writeln!(fq, "@read{ii}\nACGT\n+\nIIII").unwrap();
+        }
+        let mut config = fs::File::create(sample_dir.join("input.toml")).unwrap();
+        writeln!(
+            config,
+            r"[input]
+read1 = 'input.fq'
+
+[[step]]
+action = 'Report'
+name = 'start'
+count = true
+length_distribution = true
+
+[output]
+prefix = 'output'
+report_json = true
+"
+        )
+        .unwrap();
+        let cmd = std::process::Command::new(get_bin_path())
+            .arg("process")
+            .arg("input.toml")
+            .current_dir(&sample_dir)
+            .output()
+            .unwrap();
+        assert!(cmd.status.success());
+    }
+
+    let cmd = std::process::Command::new(get_bin_path())
+        .arg("report-merge")
+        .arg("sample_a/output.json")
+        .arg("sample_b/output.json")
+        .arg("--output-prefix")
+        .arg("merged")
+        .current_dir(temp_path)
+        .output()
+        .unwrap();
+    let stderr = std::str::from_utf8(&cmd.stderr).unwrap().to_string();
+    assert!(cmd.status.success(), "report-merge failed: {stderr}");
+
+    let tsv = std::fs::read_to_string(temp_path.join("merged.tsv")).unwrap();
+    let lines: Vec<&str> = tsv.lines().collect();
+    assert_eq!(lines[0], "sample\tstart.molecule_count");
+    assert_eq!(lines[1], "sample_a/output\t2");
+    assert_eq!(lines[2], "sample_b/output\t3");
+
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(temp_path.join("merged.json")).unwrap())
+            .unwrap();
+    assert_eq!(
+        json["samples"]["sample_b/output"]["start"]["molecule_count"],
+        3
+    );
+    assert!(temp_path.join("merged.html").exists());
+
+    // refuses to overwrite without --allow-overwrite
+    let cmd = std::process::Command::new(get_bin_path())
+        .arg("report-merge")
+        .arg("sample_a/output.json")
+        .arg("--output-prefix")
+        .arg("merged")
+        .current_dir(temp_path)
+        .output()
+        .unwrap();
+    assert!(!cmd.status.success());
+    let stderr = std::str::from_utf8(&cmd.stderr).unwrap().to_string();
+    assert!(stderr.contains("already exists"));
+}
+
+#[test]
+fn test_process_samples() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let temp_path = temp_dir.path();
+
+    for (sample, read_count) in [("sample_a", 2), ("sample_b", 3)] {
+        let mut fq = fs::File::create(temp_path.join(format!("{sample}.fq"))).unwrap();
+        for ii in 0..read_count {
+            writeln!(fq, "@read{ii}\nACGT\n+\nIIII").unwrap();
+        }
+    }
+    fs::write(
+        temp_path.join("samples.tsv"),
+        "sample\tfastq\nA\tsample_a.fq\nB\tsample_b.fq\nC\tmissing.fq\n",
+    )
+    .unwrap();
+    fs::write(
+        temp_path.join("input.toml"),
+        r"[input]
+read1 = '{fastq}'
+
+[[step]]
+action = 'Report'
+name = 'start'
+count = true
+
+[output]
+prefix = 'out_{sample}'
+report_json = true
+",
+    )
+    .unwrap();
+    let cmd = std::process::Command::new(get_bin_path())
+        .arg("process")
+        .arg("input.toml")
+        .arg("--samples")
+        .arg("samples.tsv")
+        .arg("--jobs")
+        .arg("2")
+        .current_dir(temp_path)
+        .output()
+        .unwrap();
+    let stderr = std::str::from_utf8(&cmd.stderr).unwrap().to_string();
+    // C's input is missing - the others are still processed
+    assert!(!cmd.status.success());
+    assert!(stderr.contains("1 of 3 samples failed: C"), "{stderr}");
+    assert!(temp_path.join("out_A_read1.fq").exists());
+    assert!(temp_path.join("out_B_read1.fq").exists());
+
+    let status = std::fs::read_to_string(temp_path.join("samples_status.tsv")).unwrap();
+    let lines: Vec<&str> = status.lines().collect();
+    assert_eq!(lines[0], "sample\tstatus\tseconds\tmessage");
+    assert!(lines[1].starts_with("A\tok\t"));
+    assert!(lines[2].starts_with("B\tok\t"));
+    assert!(lines[3].starts_with("C\tfailed\t"));
+
+    let tsv = std::fs::read_to_string(temp_path.join("samples_report.tsv")).unwrap();
+    let lines: Vec<&str> = tsv.lines().collect();
+    assert_eq!(lines[0], "sample\tstart.molecule_count");
+    assert_eq!(lines[1], "A\t2");
+    assert_eq!(lines[2], "B\t3");
+
+    // unknown variables are caught before anything runs
+    fs::write(
+        temp_path.join("input.toml"),
+        "[input]\nread1 = '{fastq_r1}'\n[output]\nprefix = 'out_{sample}'\n",
+    )
+    .unwrap();
+    let cmd = std::process::Command::new(get_bin_path())
+        .arg("process")
+        .arg("input.toml")
+        .arg("--samples")
+        .arg("samples.tsv")
+        .arg("--allow-overwrite")
+        .current_dir(temp_path)
+        .output()
+        .unwrap();
+    assert!(!cmd.status.success());
+    let stderr = std::str::from_utf8(&cmd.stderr).unwrap().to_string();
+    assert!(
+        stderr.contains("Unknown sample variable '{fastq_r1}'"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn test_library_api_memory_input_custom_step_and_callbacks() {
+    use mbf_fastq_processor::api::{
+        FastQBlocksCombined, InputInfo, OptDemultiplex, Pipeline, Step,
+    };
+    use std::sync::{Arc, Mutex};
+
+    struct NoLeadingN;
+
+    impl Step for NoLeadingN {
+        fn apply(
+            &self,
+            mut block: FastQBlocksCombined,
+            _input_info: &InputInfo,
+            _block_no: usize,
+            _demultiplex_info: &OptDemultiplex,
+        ) -> anyhow::Result<(FastQBlocksCombined, bool)> {
+            let keep: Vec<bool> = block.segments[0].apply(|read| !read.seq().starts_with(b"N"));
+            block.apply_bool_filter(&keep);
+            Ok((block, true))
+        }
+    }
+
+    let toml = "
+[input]
+    read1 = 'does_not_exist.fq'
+
+[[step]]
+    action = 'Report'
+    name = 'start'
+    count = true
+
+[output]
+    prefix = 'output'
+    format = 'None'
+    report_json = true
+";
+    let td = tempfile::tempdir().unwrap();
+    let names = Arc::new(Mutex::new(Vec::new()));
+    let report = Arc::new(Mutex::new(None));
+    {
+        let names = names.clone();
+        let report = report.clone();
+        Pipeline::from_toml(toml)
+            .unwrap()
+            .with_memory_input(
+                "read1",
+                b"@r1\nACGT\n+\nIIII\n@r2\nNCGT\n+\nIIII\n".to_vec(),
+            )
+            .with_memory_input("read1", b"@r3\nGGGG\n+\nIIII\n".to_vec())
+            .add_step(NoLeadingN)
+            .on_block(move |block| {
+                let mut names = names.lock().unwrap();
+                for read in &block.segments[0].entries {
+                    names.push(
+                        String::from_utf8(read.name.get(&block.segments[0].block).to_vec())
+                            .unwrap(),
+                    );
+                }
+                Ok(())
+            })
+            .on_report(move |value| *report.lock().unwrap() = Some(value))
+            .output_directory(td.path())
+            .run()
+            .unwrap();
+    }
+    assert_eq!(*names.lock().unwrap(), vec!["r1", "r3"]);
+    let report = report.lock().unwrap().take().unwrap();
+    assert_eq!(report["start"]["molecule_count"], 3);
+    // the report went to the callback, not to a file
+    assert!(!td.path().join("output.json").exists());
+
+    // every segment needs a reader
+    let err = Pipeline::from_toml(toml)
+        .unwrap()
+        .with_memory_input("read2", b"@r1\nACGT\n+\nIIII\n".to_vec())
+        .output_directory(td.path())
+        .run()
+        .unwrap_err();
+    assert!(
+        format!("{err:?}").contains("No input reader supplied for segment 'read1'"),
+        "{err:?}"
+    );
+}
+
+/// Interrupt a run with these steps after its first checkpoint, resume it,
+/// and compare output & report with an uninterrupted run.
+fn check_resume_from_checkpoint(steps: &str) {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let temp_path = temp_dir.path();
+    {
+        let mut fq = fs::File::create(temp_path.join("input.fq")).unwrap();
+        for ii in 0..3000 {
+            writeln!(
+                fq,
+                "@read{ii}\n{}\n+\n{}",
+                &"ACGTTGCA"[..1 + ii % 8],
+                &"IIIIIIII"[..1 + ii % 8]
+            )
+            .unwrap();
+        }
+    }
+    let toml = format!(
+        "
+[input]
+    read1 = 'input.fq'
+
+[options]
+    block_size = 10
+
+[[step]]
+    action = '_InternalDelay'
+
+{steps}
+
+[output]
+    prefix = 'output'
+    compression = 'Gzip'
+    report_json = true
+    checkpoint_interval = 0
+"
+    );
+    fs::write(temp_path.join("input.toml"), toml).unwrap();
+    // the reference: one uninterrupted run
+    fs::create_dir(temp_path.join("reference")).unwrap();
+    let cmd = std::process::Command::new(get_bin_path())
+        .arg("process")
+        .arg("../input.toml")
+        .current_dir(temp_path.join("reference"))
+        .output()
+        .unwrap();
+    assert!(cmd.status.success(), "{cmd:?}");
+    assert!(!temp_path.join("reference/output.checkpoint.json").exists());
+
+    // nothing to resume from
+    let cmd = std::process::Command::new(get_bin_path())
+        .arg("process")
+        .arg("input.toml")
+        .arg("--resume")
+        .current_dir(temp_path)
+        .output()
+        .unwrap();
+    assert!(!cmd.status.success());
+    let stderr = std::str::from_utf8(&cmd.stderr).unwrap().to_string();
+    assert!(stderr.contains("No checkpoint to resume from"), "{stderr}");
+
+    // interrupt a run once it has written a checkpoint
+    let mut child = std::process::Command::new(get_bin_path())
+        .arg("process")
+        .arg("input.toml")
+        .current_dir(temp_path)
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    let checkpoint_path = temp_path.join("output.checkpoint.json");
+    while !checkpoint_path.exists() {
+        assert!(
+            child.try_wait().unwrap().is_none(),
+            "run finished before writing a checkpoint"
+        );
+        std::thread::sleep(std::time::Duration::from_millis(5));
+    }
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    child.kill().unwrap();
+    child.wait().unwrap();
+    assert!(checkpoint_path.exists());
+
+    let cmd = std::process::Command::new(get_bin_path())
+        .arg("process")
+        .arg("input.toml")
+        .arg("--resume")
+        .current_dir(temp_path)
+        .output()
+        .unwrap();
+    assert!(cmd.status.success(), "{cmd:?}");
+    assert!(!checkpoint_path.exists());
+
+    assert_eq!(
+        mbf_fastq_processor::decompress_file(&temp_path.join("output_read1.fq.gz")).unwrap(),
+        mbf_fastq_processor::decompress_file(&temp_path.join("reference/output_read1.fq.gz"))
+            .unwrap()
+    );
+    let read_report = |path: &Path| -> serde_json::Value {
+        serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+    };
+    assert_eq!(
+        read_report(&temp_path.join("output.json"))["report"],
+        read_report(&temp_path.join("reference/output.json"))["report"]
+    );
+}
+
+#[test]
+fn test_process_resume_from_checkpoint() {
+    check_resume_from_checkpoint(
+        "
+[[step]]
+    action = 'Rename'
+    search = '^read'
+    replacement = 'molecule_{{READ_INDEX}}_'
+
+[[step]]
+    action = 'Report'
+    name = 'report'
+    count = true
+    length_distribution = true
+",
+    );
+}
+
+#[test]
+fn test_process_resume_from_checkpoint_stateful_steps() {
+    check_resume_from_checkpoint(
+        "
+[[step]]
+    action = 'CalcLength'
+    out_label = 'length'
+    segment = 'read1'
+
+[[step]]
+    action = 'Report'
+    name = 'report'
+    base_statistics = true
+    kmer_enrichment = 5
+    tag_histograms = ['length']
+
+[[step]]
+    action = 'FilterReservoirSample'
+    n = 50
+    seed = 42
+",
+    );
+}
+
+#[test]
+fn test_report_timing() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let temp_path = temp_dir.path();
+    {
+        let mut fq = fs::File::create(temp_path.join("input.fq")).unwrap();
+        for ii in 0..100 {
+            writeln!(fq, "@read{ii}\nACGTTGCA\n+\nIIIIIIII").unwrap();
+        }
+    }
+    let toml = "
+[input]
+    read1 = 'input.fq'
+
+[options]
+    block_size = 10
+
+[[step]]
+    action = 'CutStart'
+    segment = 'read1'
+    n = 2
+
+[[step]]
+    action = 'Report'
+    name = 'report'
+    count = true
+
+[output]
+    prefix = 'output'
+    report_json = true
+    report_html = true
+    report_timing = true
+";
+    fs::write(temp_path.join("input.toml"), toml).unwrap();
+    let cmd = std::process::Command::new(get_bin_path())
+        .arg("process")
+        .arg("input.toml")
+        .current_dir(temp_path)
+        .output()
+        .unwrap();
+    assert!(cmd.status.success(), "{cmd:?}");
+
+    let timing: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(temp_path.join("output.timing.json")).unwrap())
+            .unwrap();
+    assert!(timing["wall_time_s"].as_f64().unwrap() > 0.0);
+    let steps = timing["steps"].as_array().unwrap();
+    let cut_start = steps
+        .iter()
+        .find(|step| step["action"] == "CutStart")
+        .unwrap();
+    assert_eq!(cut_start["step"], 1);
+    // 100 reads in blocks of 10 (plus possibly an empty final block)
+    assert!(cut_start["blocks"].as_u64().unwrap() >= 10);
+    for key in [
+        "wall_time_s",
+        "queue_wait_s",
+        "max_queue_wait_s",
+        "peak_allocation_bytes",
+    ] {
+        assert!(cut_start[key].as_f64().unwrap() >= 0.0, "{key}");
+    }
+    #[cfg(unix)]
+    assert!(cut_start["cpu_time_s"].as_f64().is_some());
+
+    let report: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(temp_path.join("output.json")).unwrap()).unwrap();
+    assert_eq!(report["timing"], timing);
+    let html = fs::read_to_string(temp_path.join("output.html")).unwrap();
+    assert!(html.contains("\"peak_allocation_bytes\""));
+}
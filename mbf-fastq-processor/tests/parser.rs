@@ -2,6 +2,8 @@
 use std::path::{Path, PathBuf};
 
 use bstr::ByteSlice;
+use mbf_fastq_processor::io::input::InputFile;
+use mbf_fastq_processor::io::parsers::{ChainedParser, InputPosition, Parser};
 use mbf_fastq_processor::io::{FastQBlock, parsers::ThreadCount};
 
 #[test]
@@ -69,3 +71,134 @@ fn test_bufsize_variations(input_fastq_filename: &str, bufsize_range: &[usize])
         last = Some(here);
     }
 }
+
+fn parser_test_options() -> mbf_fastq_processor::config::InputOptions {
+    mbf_fastq_processor::config::InputOptions {
+        bam_include_mapped: None,
+        bam_include_unmapped: None,
+        fasta_fake_quality: None,
+        read_comment_character: b' ',
+        build_rapidgzip_index: Some(false),
+        use_rapidgzip: Some(false),
+        threads_per_segment: Some(1),
+    }
+}
+
+fn collect_names(parser: &mut Box<dyn Parser>, names: &mut Vec<Vec<u8>>) -> bool {
+    let pr = parser.parse().unwrap();
+    for read in &pr.fastq_block.entries {
+        names.push(read.name.get(&pr.fastq_block.block).to_vec());
+    }
+    pr.was_final
+}
+
+/// Resuming at any recorded position must yield exactly the reads not yet parsed
+fn test_resume_positions(filename: &Path) {
+    let open = || InputFile::Fastq(ex::fs::File::open(filename).unwrap(), Some(filename.into()));
+    for bufsize in [64, 1000] {
+        let mut parser = open()
+            .get_parser(7, bufsize, ThreadCount(1), &parser_test_options())
+            .unwrap();
+        let mut names = Vec::new();
+        let mut positions = vec![(0, parser.resume_position().unwrap())];
+        loop {
+            let was_final = collect_names(&mut parser, &mut names);
+            positions.push((names.len(), parser.resume_position().unwrap()));
+            if was_final {
+                break;
+            }
+        }
+        assert!(names.len() > 100);
+
+        for (done, (seek_to, skip)) in positions {
+            let position = InputPosition {
+                file: 0,
+                seek_to,
+                skip,
+            };
+            let mut resumed = open().get_parser_at(7, bufsize, &position).unwrap();
+            let mut rest = Vec::new();
+            while !collect_names(&mut resumed, &mut rest) {}
+            assert_eq!(rest, names[done..], "resumed at {position:?}");
+        }
+    }
+}
+
+#[test]
+fn test_resume_position_uncompressed() {
+    test_resume_positions(Path::new(
+        "../test_cases/sample_data/paired_end/input_read1.fq",
+    ));
+}
+
+#[test]
+fn test_resume_position_zstd() {
+    // windows line endings
+    test_resume_positions(Path::new(
+        "../test_cases/sample_data/zstd/input_read1.fq.zst",
+    ));
+}
+
+#[test]
+fn test_resume_position_zstd_frames() {
+    let contents = std::fs::read("../test_cases/sample_data/paired_end/input_read1.fq").unwrap();
+    let mut compressed = Vec::new();
+    for chunk in contents.chunks(1500) {
+        compressed.extend(zstd::stream::encode_all(chunk, 3).unwrap());
+    }
+    let tempdir = tempfile::tempdir().unwrap();
+    let filename = tempdir.path().join("frames.fq.zst");
+    std::fs::write(&filename, compressed).unwrap();
+    test_resume_positions(&filename);
+}
+
+#[test]
+fn test_chained_parser_resume_skips_files() {
+    let filenames = [
+        "../test_cases/sample_data/paired_end/input_read1.fq",
+        "../test_cases/sample_data/misc/input_read1_11.fq",
+    ];
+    let open_all = || {
+        filenames
+            .iter()
+            .map(|filename| {
+                InputFile::Fastq(
+                    ex::fs::File::open(filename).unwrap(),
+                    Some(PathBuf::from(filename)),
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    let parse_rest = |mut parser: ChainedParser| {
+        let mut names = Vec::new();
+        loop {
+            let res = parser.parse().unwrap();
+            for read in &res.fastq_block.entries {
+                names.push(read.name.get(&res.fastq_block.block).to_vec());
+            }
+            if res.was_final {
+                break;
+            }
+        }
+        names
+    };
+    let new_parser =
+        || ChainedParser::new(open_all(), 7, 1000, ThreadCount(1), parser_test_options());
+    let all = parse_rest(new_parser());
+
+    let mut parser = new_parser();
+    let mut done = 0;
+    let mut position = None;
+    // stop somewhere in the second file
+    while done < 510 {
+        let res = parser.parse().unwrap();
+        done += res.fastq_block.entries.len();
+        position = parser.position();
+    }
+    let position = position.unwrap();
+    assert_eq!(position.file, 1);
+
+    let mut resumed = new_parser();
+    resumed.resume_at(&position).unwrap();
+    assert_eq!(parse_rest(resumed), all[done..]);
+}
@@ -189,9 +189,13 @@ const ACTIONS_REQUIRING_GENERIC_TAG: &[&str] = &[
     "ConvertToRate",
     "ConvertRegionsToLength",
     "ConcatTags",
+    "CombineLocations",
+    "RenameFromTemplate",
 ];
 
-const ACTIONS_REQUIRING_TWO_TAGS: &[&str] = &["ConcatTags"];
+const ACTIONS_REQUIRING_TWO_TAGS: &[&str] = &["ConcatTags", "CombineLocations"];
+
+const ACTIONS_REQUIRING_SINGLE_SEGMENT: &[&str] = &["SplitAtTag"];
 
 const TAG_DECLARING_CONVERT_STEPS: &[&str] = &["ConvertToRate", "ConvertRegionsToLength"];
 
@@ -210,11 +214,21 @@ fn prep_config_to_parse(extracted_section: &str) -> String {
         || extracted_section.contains("action = 'Report'");
     let request_report = if has_report_step { "true" } else { "false" };
 
+    let actions = collect_actions(extracted_section);
+    let second_segment = if actions
+        .iter()
+        .any(|a| ACTIONS_REQUIRING_SINGLE_SEGMENT.contains(&a.as_str()))
+    {
+        ""
+    } else {
+        "read2 = \"test_r2.fastq\""
+    };
+
     let mut config = format!(
         r#"
 [input]
 read1 = "test_r1.fastq"
-read2 = "test_r2.fastq"
+{second_segment}
 
 [output]
 prefix = "output"
@@ -227,10 +241,9 @@ report_html = false
     )
     .to_string();
 
-    let actions = collect_actions(extracted_section);
     let needs_numeric_tag = actions
         .iter()
-        .any(|a| a == "FilterByNumericTag" || a == "EvalExpression")
+        .any(|a| a == "FilterByNumericTag" || a == "EvalExpression" || a == "BinNumericTag")
         | has_report_step;
     let if_tag_present =
         extracted_section.contains("if_tag =") && !extracted_section.contains("#if_tag =");
@@ -699,6 +712,8 @@ fn find_struct_file_for_transformation(transformation: &str) -> Option<PathBuf>
                         PathBuf::from("src/transformations/demultiplex.rs")
                     } else if struct_name == "HammingCorrect" {
                         PathBuf::from("src/transformations/hamming_correct.rs")
+                    } else if struct_name == "ExternalCommand" {
+                        PathBuf::from("src/transformations/external_command.rs")
                     } else if struct_name == "Duplicates" {
                         PathBuf::from("src/transformations/extract/tag/duplicates.rs")
                     } else if struct_name == "OtherFile" {